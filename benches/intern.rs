@@ -0,0 +1,90 @@
+#[macro_use]
+extern crate criterion;
+extern crate wasmtextparser;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, Criterion, Throughput};
+use wasmtextparser::{WatParserOptions, WatParserState};
+
+// Tracks the live and peak heap size, and the number of allocations, so
+// the two modes can be compared by memory as well as by time.
+struct PeakAlloc;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCS.fetch_add(1, Ordering::Relaxed);
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc;
+
+// Builds a module whose functions keep referring to the same few ids,
+// like the runtime helpers of compiled C code.
+fn generate_module(funcs: usize, calls: usize) -> String {
+    let mut wat = String::from("(module\n");
+    wat.push_str("  (global $stack_pointer (mut i32) (i32.const 1024))\n");
+    wat.push_str("  (func $memcpy (param $dst i32) (param $src i32) (param $len i32))\n");
+    for i in 0..funcs {
+        writeln!(wat, "  (func $f{} (param $dst i32) (param $src i32)", i).unwrap();
+        for _ in 0..calls {
+            wat.push_str("    (call $memcpy (local.get $dst) (global.get $stack_pointer) (local.get $src))\n");
+        }
+        wat.push_str("  )\n");
+    }
+    wat.push_str(")\n");
+    wat
+}
+
+fn collect(source: &[u8], intern_ids: bool) -> Vec<WatParserState> {
+    let mut parser = WatParserOptions::new().intern_ids(intern_ids).build(source);
+    parser.events().collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+// Returns the peak heap growth and the allocation count of one run.
+fn measure(source: &[u8], intern_ids: bool) -> (usize, usize) {
+    PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+    let base = LIVE.load(Ordering::Relaxed);
+    let allocs = ALLOCS.load(Ordering::Relaxed);
+    drop(collect(source, intern_ids));
+    (PEAK.load(Ordering::Relaxed) - base, ALLOCS.load(Ordering::Relaxed) - allocs)
+}
+
+fn bench_intern(c: &mut Criterion) {
+    let wat = generate_module(1000, 50);
+    let source = wat.as_bytes();
+    for &intern_ids in &[false, true] {
+        let (peak, allocs) = measure(source, intern_ids);
+        println!("intern/{}: peak heap {} KiB, {} allocations",
+                 if intern_ids { "on" } else { "off" },
+                 peak / 1024,
+                 allocs);
+    }
+
+    let mut group = c.benchmark_group("intern");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("off", |b| b.iter(|| collect(black_box(source), false)));
+    group.bench_function("on", |b| b.iter(|| collect(black_box(source), true)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_intern);
+criterion_main!(benches);
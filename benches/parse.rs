@@ -0,0 +1,187 @@
+#[macro_use]
+extern crate criterion;
+extern crate wasmtextparser;
+
+use std::fmt::Write;
+
+use criterion::{black_box, Criterion, Throughput};
+use wasmtextparser::{KnownKeyword, WatLexer, WatParser, WatParserState, WatTokenType};
+
+// Builds a module with `funcs` functions, each holding a folded expression
+// nested `depth` levels deep followed by a run of flat instructions. The
+// output only depends on the arguments.
+fn generate_module(funcs: usize, depth: usize) -> String {
+    let mut wat = String::from("(module\n");
+    wat.push_str("  (memory $mem 1 16)\n");
+    wat.push_str("  (table $tab 16 funcref)\n");
+    wat.push_str("  (global $counter (mut i32) (i32.const 0))\n");
+    for i in 0..funcs {
+        writeln!(wat,
+                 "  (func $f{} (export \"f{}\") (param $a i32) (param $b i64) (result i32)",
+                 i,
+                 i)
+                .unwrap();
+        wat.push_str("    (local $t i32) (local $u i64)\n    ");
+        for level in 0..depth {
+            write!(wat, "(i32.add (i32.const {}) ", (i * 31 + level) % 1000).unwrap();
+        }
+        wat.push_str("(local.get $a)");
+        for _ in 0..depth {
+            wat.push(')');
+        }
+        wat.push('\n');
+        wat.push_str("    local.set $t\n");
+        wat.push_str("    local.get $b\n    i64.const 0x7fffffff\n    i64.and\n    local.set $u\n");
+        wat.push_str("    (i32.store offset=8 (local.get $t) (i32.load offset=4 (i32.const 0)))\n");
+        writeln!(wat, "    (global.set $counter (i32.const {}))", i).unwrap();
+        wat.push_str("    local.get $t)\n");
+        if i % 16 == 0 {
+            writeln!(wat, "  (data (i32.const {}) \"block \\00\\01\\02 {}\")", i * 16, i).unwrap();
+        }
+    }
+    wat.push_str(")\n");
+    wat
+}
+
+fn count_tokens(source: &[u8]) -> u64 {
+    let mut lexer = WatLexer::new(source);
+    let mut count = 0;
+    loop {
+        match lexer.next() {
+            Ok(token) if token.ty == WatTokenType::End => return count,
+            Ok(_) => count += 1,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+// Many small modules, as a service parsing user snippets would see.
+fn generate_snippets(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+                 format!("(module (func $f{} (param i32) (result i32) (i32.add (local.get 0) (i32.const {}))))",
+                         i,
+                         i % 1000)
+             })
+        .collect()
+}
+
+fn count_events(source: &[u8]) -> u64 {
+    drain(&mut WatParser::new(source))
+}
+
+fn drain(parser: &mut WatParser) -> u64 {
+    let mut count = 0;
+    loop {
+        match parser.parse() {
+            WatParserState::End => return count,
+            WatParserState::Error(err) => panic!("{}", err),
+            _ => count += 1,
+        }
+    }
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let wat = generate_module(2000, 32);
+    let source = wat.as_bytes();
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Elements(count_tokens(source)));
+    group.bench_function("tokens", |b| b.iter(|| count_tokens(black_box(source))));
+    group.finish();
+}
+
+fn count_known_keywords(source: &[u8]) -> u64 {
+    let mut lexer = WatLexer::new(source);
+    let mut count = 0;
+    loop {
+        match lexer.next() {
+            Ok(token) if token.ty == WatTokenType::End => return count,
+            Ok(token) => {
+                if token.keyword.is_some() {
+                    count += 1;
+                }
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+fn bench_keywords(c: &mut Criterion) {
+    // The classification has to be right before its cost means anything.
+    let mut lexer = WatLexer::new(b"module func import param result local i32.add");
+    for expected in &[Some(KnownKeyword::Module),
+                      Some(KnownKeyword::Func),
+                      Some(KnownKeyword::Import),
+                      Some(KnownKeyword::Param),
+                      Some(KnownKeyword::Result),
+                      Some(KnownKeyword::Local),
+                      None] {
+        assert_eq!(lexer.next().unwrap().keyword, *expected);
+    }
+
+    let wat = generate_module(2000, 32);
+    let source = wat.as_bytes();
+    let mut group = c.benchmark_group("keywords");
+    group.throughput(Throughput::Elements(count_tokens(source)));
+    group.bench_function("classify", |b| b.iter(|| count_known_keywords(black_box(source))));
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let wat = generate_module(2000, 32);
+    let source = wat.as_bytes();
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Elements(count_events(source)));
+    // Streaming: each event is dropped as soon as it is produced.
+    group.bench_function("streaming", |b| b.iter(|| count_events(black_box(source))));
+    // Tree-like: every event is kept, as a consumer building an AST would.
+    group.bench_function("collect", |b| {
+        b.iter(|| {
+                   let mut parser = WatParser::new(black_box(source));
+                   parser.events().collect::<Result<Vec<_>, _>>().unwrap()
+               })
+    });
+    group.finish();
+}
+
+fn bench_nesting(c: &mut Criterion) {
+    let wat = generate_module(16, 2000);
+    let source = wat.as_bytes();
+    let mut group = c.benchmark_group("nesting");
+    group.throughput(Throughput::Elements(count_events(source)));
+    group.bench_function("deep_folded", |b| b.iter(|| count_events(black_box(source))));
+    group.finish();
+}
+
+fn bench_reuse(c: &mut Criterion) {
+    let snippets = generate_snippets(10_000);
+    let mut group = c.benchmark_group("reuse");
+    group.throughput(Throughput::Elements(snippets.len() as u64));
+    group.bench_function("fresh", |b| {
+        b.iter(|| {
+                   snippets.iter()
+                       .map(|snippet| count_events(black_box(snippet.as_bytes())))
+                       .sum::<u64>()
+               })
+    });
+    group.bench_function("reset", |b| {
+        b.iter(|| {
+                   let mut parser = WatParser::new(b"");
+                   snippets.iter()
+                       .map(|snippet| {
+                                parser.reset(black_box(snippet.as_bytes()));
+                                drain(&mut parser)
+                            })
+                       .sum::<u64>()
+               })
+    });
+    group.finish();
+}
+
+criterion_group!(benches,
+                 bench_lexer,
+                 bench_keywords,
+                 bench_parser,
+                 bench_nesting,
+                 bench_reuse);
+criterion_main!(benches);
@@ -0,0 +1,16 @@
+;; Passive segments and the bulk memory instructions that use them.
+(module
+  (memory 1)
+  (table 2 funcref)
+  (data $greeting "hello")
+  (data "world")
+  (elem $fns func $init)
+  (func $init
+    (memory.init $greeting (i32.const 0) (i32.const 0) (i32.const 5))
+    (data.drop $greeting)
+    (memory.init 1 (i32.const 8) (i32.const 0) (i32.const 5))
+    (memory.copy (i32.const 16) (i32.const 0) (i32.const 5))
+    (memory.fill (i32.const 32) (i32.const 0) (i32.const 4))
+    (table.init $fns (i32.const 0) (i32.const 0) (i32.const 1))
+    (elem.drop $fns)
+    (table.copy (i32.const 1) (i32.const 0) (i32.const 1))))
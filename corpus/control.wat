@@ -0,0 +1,31 @@
+;; Structured control flow, plain and folded.
+(module
+  (func $select (param $x i32) (result i32)
+    block $out (result i32)
+      loop $again
+        local.get $x
+        br_if $again
+      end
+      i32.const 7
+    end)
+  (func $branch (param i32) (result i32)
+    (block $a
+      (block $b
+        (br_table $a $b $a (local.get 0))))
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 1))
+      (else (i32.const 0))))
+  (func $unfolded (param i32) (result i32)
+    local.get 0
+    if $l (result i32)
+      i32.const 1
+    else $l
+      i32.const 2
+    end $l)
+  (func $calls (result i32)
+    (call $branch (call $select (i32.const 3)))
+    (return)
+    unreachable)
+  (func $calls_void
+    nop
+    (drop (call $calls))))
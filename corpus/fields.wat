@@ -0,0 +1,33 @@
+(module $m
+  (type $t (func (param i32 i64 f32) (param $named f64) (result i32)))
+  (type $long (func (param $aaaaaaaa i32) (param $bbbbbbbbb i64) (param $ccccccc f32) (param $dddddd f64) (result i32 i64)))
+  (import "env" "mem" (memory 1))
+  (import "env" "f" (func $imp (param i32)))
+  (global $g (mut i32) (i32.const 5))
+  (global (export "k") i64 i64.const -3)
+  (table $tb 2 funcref)
+  (elem (i32.const 0) $f $imp)
+  (data (i32.const 8) "hi\00\ff\"there\n")
+  (func $f (export "f") (param $x i32) (param $yyyyyyyyyyyyyyy i64) (param $zzzzzzzzzzzzzz f32) (result i32) (local $t i32) (local i64)
+    block $out
+      loop $l
+        local.get $x
+        if (result i32)
+          i32.const 1
+        else
+          i32.const 2
+        end
+        br_if $out
+        br $l
+      end
+    end
+    (if (result i32) (local.get $x)
+      (then (i32.add (i32.const 1) (i32.const 2)))
+      (else (block (result i32) i32.const 3)))
+    f32.const 0x1.8p3
+    drop
+    (i32.load offset=4 align=2 (i32.const 0))
+    i32.add)
+  (func (type $t) i32.const 0)
+  (@custom "producers" "\01\08language\01\04Rust\00")
+)
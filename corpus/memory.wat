@@ -0,0 +1,13 @@
+;; Memories, data segments and memory instructions.
+(module
+  (memory $m 1 2)
+  (data (i32.const 0) "\00\01\02abc" "\u{263a}")
+  (data $d "passive")
+  (data (memory $m) (offset (i32.const 16)) "more")
+  (func (export "copy") (param $dst i32) (param $src i32) (param $len i32)
+    (memory.copy (local.get $dst) (local.get $src) (local.get $len))
+    (memory.init $d (i32.const 0) (i32.const 0) (i32.const 4))
+    (data.drop $d)
+    (i64.store8 offset=3 (i32.const 0) (i64.const 255))
+    (drop (f64.load align=4 (i32.const 8)))
+    (drop (memory.grow (memory.size)))))
@@ -0,0 +1,12 @@
+;; Integer and float literals.
+(module
+  (global i32 (i32.const -2147483648))
+  (global i32 (i32.const 0xffffffff))
+  (global i64 (i64.const 9223372036854775807))
+  (global f32 (f32.const -0x1.fffffep127))
+  (global f32 (f32.const nan:0x200000))
+  (global f64 (f64.const -inf))
+  (global f64 (f64.const 1e-300))
+  (global $mut (mut f64) (f64.const 0.5))
+  (func (result f64)
+    (f64.add (global.get $mut) (f64.const 1000.0001))))
@@ -0,0 +1,28 @@
+(module
+  (memory 1)
+  (memory $scratch 1)
+
+  ;; Adds 7 to each of the four lanes at address 16 and stores them at 0.
+  (func $add7 (export "add7")
+    (v128.store (i32.const 0)
+      (i32x4.add
+        (v128.load offset=16 (i32.const 0))
+        (i32x4.splat (i32.const 7)))))
+
+  (func $lanes (param $v v128) (result i32)
+    (i8x16.extract_lane_s 3
+      (i8x16.shuffle 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+        (local.get $v)
+        (v128.const i32x4 1 2 3 -4))))
+
+  ;; A bare number is the lane; with two, the first is the memory.
+  (func $load_lanes (param $v v128) (result v128)
+    (v128.store32_lane 1 offset=4 2 (i32.const 0) (local.get $v))
+    (drop (v128.load64_splat (i32.const 0)))
+    (v128.load16_lane $scratch 1 (i32.const 0)
+      (v128.load8_lane 1 (i32.const 0) (local.get $v))))
+
+  (func $floats (result v128)
+    (f64x2.add
+      (v128.const f64x2 1.5 -inf)
+      (v128.const i8x16 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 -128))))
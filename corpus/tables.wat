@@ -0,0 +1,17 @@
+;; Tables, element segments and indirect calls.
+(module
+  (type $v (func))
+  (table $t 4 funcref)
+  (table $inline funcref (elem $a $b))
+  (table $refs 1 10 externref)
+  (elem (table $t) (i32.const 1) func $a $b)
+  (elem $passive func $a)
+  (elem declare func $b)
+  (elem (i32.const 3) funcref (ref.func $a) (ref.null func))
+  (func $a)
+  (func $b (param $i i32)
+    (call_indirect $t (type $v) (local.get $i))
+    (table.init $t $passive (i32.const 0) (i32.const 0) (i32.const 1))
+    (elem.drop $passive)
+    (drop (table.get $refs (i32.const 0)))
+    (drop (table.size $t))))
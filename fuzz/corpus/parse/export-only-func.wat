@@ -0,0 +1 @@
+(module (func (export "f") i32.const 1))
\ No newline at end of file
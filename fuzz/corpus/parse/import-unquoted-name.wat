@@ -0,0 +1 @@
+(module (import foo "bar" (memory 1)))
\ No newline at end of file
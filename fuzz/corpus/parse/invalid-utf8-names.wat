@@ -0,0 +1 @@
+(module (import "\ff\fe" "\u{d800}" (memory 1)))
\ No newline at end of file
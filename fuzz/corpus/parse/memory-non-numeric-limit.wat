@@ -0,0 +1 @@
+(module (import "a" "b" (memory abc)))
\ No newline at end of file
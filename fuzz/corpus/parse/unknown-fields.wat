@@ -0,0 +1,2 @@
+(module (import "a" "b" (event)))
+(module (memory 1))
\ No newline at end of file
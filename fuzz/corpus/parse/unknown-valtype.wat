@@ -0,0 +1 @@
+(module (global v256 (i32.const 0)))
\ No newline at end of file
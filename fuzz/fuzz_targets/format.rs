@@ -0,0 +1,20 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+extern crate wasmtextparser;
+
+use wasmtextparser::{format, WatParser};
+
+// Whatever parses formats to text that parses again and formats to
+// itself.
+fuzz_target!(|data: &[u8]| {
+    let text = match format(WatParser::new(data).events()) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    match format(WatParser::new(text.as_bytes()).events()) {
+        Ok(again) => assert_eq!(again, text),
+        Err(err) => panic!("{}\n{}", err, text),
+    }
+});
@@ -0,0 +1,31 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+extern crate wasmtextparser;
+
+use wasmtextparser::{WatParser, WatParserState};
+
+fn drive(data: &[u8], recover: bool) {
+    let mut parser = WatParser::new(data);
+    parser.set_error_recovery(recover);
+    let mut last_error = None;
+    loop {
+        match parser.parse() {
+            WatParserState::End => return,
+            WatParserState::Error(ref err) => {
+                // Errors that recovery cannot get past are sticky.
+                if !recover || last_error.as_ref() == Some(err) {
+                    return;
+                }
+                last_error = Some(err.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    drive(data, false);
+    drive(data, true);
+});
@@ -0,0 +1,57 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+extern crate wasmtextparser;
+
+use std::str;
+use wasmtextparser::{PushParser, WatParserOptions, WatParserState};
+
+// The events of the whole input, up to End or the error that stops
+// parsing.
+fn parse_whole(data: &[u8], recover: bool) -> Vec<WatParserState> {
+    let mut parser = WatParserOptions::new().error_recovery(recover).build(data);
+    let mut events = Vec::new();
+    let mut last_error = None;
+    loop {
+        match parser.parse() {
+            WatParserState::End => {
+                events.push(WatParserState::End);
+                return events;
+            }
+            WatParserState::Error(err) => {
+                // Errors that recovery cannot get past are sticky.
+                if last_error.as_ref() == Some(&err) {
+                    return events;
+                }
+                events.push(WatParserState::Error(err.clone()));
+                if !recover {
+                    return events;
+                }
+                last_error = Some(err);
+            }
+            event => events.push(event),
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    // Text that is not UTF-8 is reported where the push parser finds it.
+    if data.is_empty() || str::from_utf8(&data[1..]).is_err() {
+        return;
+    }
+    // The first byte picks the chunk size.
+    let size = data[0] as usize + 1;
+    let data = &data[1..];
+    for &recover in &[false, true] {
+        let mut events = Vec::new();
+        {
+            let options = WatParserOptions::new().error_recovery(recover);
+            let mut parser = PushParser::with_options(|event| events.push(event), options);
+            if data.chunks(size).all(|chunk| parser.feed(chunk).is_ok()) {
+                let _ = parser.finish();
+            }
+        }
+        assert_eq!(events, parse_whole(data, recover));
+    }
+});
@@ -0,0 +1,675 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::io;
+use std::io::prelude::*;
+use lexer::WatPosition;
+use wat::{ConstExpr, ErrorKind, Name, Result, WatDataKind, WatElemKind, WatElemList, WatImport,
+          WatInstructionArg, WatLocal, WatParser, WatParserError, WatParserState, WatTypeuse,
+          START_POSITION};
+
+const DEFAULT_MAX_WIDTH: usize = 80;
+
+/// Writes a stream of parser events back out as WAT text.
+///
+/// Every module field starts on its own line, instructions are written
+/// one per line and folded instructions keep their parentheses. Code is
+/// indented once per open group or `block`/`loop`/`if`, and the params,
+/// results and locals of a header too long for one line go on lines of
+/// their own.
+pub struct WatWriter<W: Write> {
+    out: W,
+    indent: String,
+    max_width: usize,
+    depth: usize,
+    // Depth of the instructions at the top of the current body.
+    body_depth: usize,
+    at_start: bool,
+}
+
+impl<W: Write> WatWriter<W> {
+    pub fn new(out: W) -> WatWriter<W> {
+        WatWriter {
+            out,
+            indent: String::from("  "),
+            max_width: DEFAULT_MAX_WIDTH,
+            depth: 0,
+            body_depth: 0,
+            at_start: true,
+        }
+    }
+
+    /// Sets the text written once per nesting level, two spaces by default.
+    pub fn set_indent(&mut self, indent: &str) {
+        self.indent = String::from(indent);
+    }
+
+    /// Sets the width past which headers are wrapped, 80 by default.
+    pub fn set_max_width(&mut self, max_width: usize) {
+        self.max_width = max_width;
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn start_line(&mut self) -> io::Result<()> {
+        if !self.at_start {
+            self.out.write_all(b"\n")?;
+        }
+        self.at_start = false;
+        for _ in 0..self.depth {
+            self.out.write_all(self.indent.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.out.write_all(s.as_bytes())
+    }
+
+    fn write_id(&mut self, id: &Option<Vec<u8>>) -> io::Result<()> {
+        if let Some(ref id) = *id {
+            self.out.write_all(b" ")?;
+            self.out.write_all(id)?;
+        }
+        Ok(())
+    }
+
+    fn write_name(&mut self, name: &str) -> io::Result<()> {
+        self.write_str(&quote_name(name))
+    }
+
+    // Writes `head`, then `clauses` and `tail` on the same line if they
+    // fit in `max_width`, and each clause on a line of its own if not.
+    fn write_wrapped(&mut self, head: &str, clauses: &[String], tail: &str) -> io::Result<()> {
+        let width = self.indent.chars().count() * self.depth + head.chars().count() +
+                    clauses.iter().map(|clause| clause.chars().count() + 1).sum::<usize>() +
+                    tail.len();
+        self.write_str(head)?;
+        if width <= self.max_width {
+            for clause in clauses {
+                self.write_str(" ")?;
+                self.write_str(clause)?;
+            }
+        } else {
+            self.depth += 1;
+            for clause in clauses {
+                self.start_line()?;
+                self.write_str(clause)?;
+            }
+            self.depth -= 1;
+        }
+        self.write_str(tail)
+    }
+
+    // Data strings hold raw bytes, so anything outside printable ASCII is
+    // written as a \hh escape.
+    fn write_data_string(&mut self, data: &[u8]) -> io::Result<()> {
+        self.out.write_all(b"\"")?;
+        for byte in data {
+            match *byte {
+                b'"' | b'\\' => write!(self.out, "\\{}", *byte as char)?,
+                b'\t' => self.write_str("\\t")?,
+                b'\n' => self.write_str("\\n")?,
+                b'\r' => self.write_str("\\r")?,
+                0x20..=0x7E => self.out.write_all(&[*byte])?,
+                _ => write!(self.out, "\\{:02x}", byte)?,
+            }
+        }
+        self.out.write_all(b"\"")
+    }
+
+    fn write_data(&mut self, kind: &WatDataKind, data: &[u8]) -> io::Result<()> {
+        if let WatDataKind::Active { ref memory, ref offset } = *kind {
+            write!(self.out, " (memory {}) (offset ", memory)?;
+            write_const_expr(&mut self.out, offset)?;
+            self.write_str(")")?;
+        }
+        self.write_str(" ")?;
+        self.write_data_string(data)
+    }
+
+    fn write_exports(&mut self, names: &[Name]) -> io::Result<()> {
+        for name in names {
+            self.write_str(" (export ")?;
+            self.write_name(name)?;
+            self.write_str(")")?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.depth = self.depth.saturating_sub(1);
+        self.write_str(")")
+    }
+
+    /// Writes the text for one event. `Initial` writes nothing and `End`
+    /// finishes the last line; an `Error` event is returned as an
+    /// `InvalidInput` error.
+    pub fn write(&mut self, state: &WatParserState) -> io::Result<()> {
+        match *state {
+            WatParserState::Initial => Ok(()),
+            WatParserState::End => self.write_str("\n"),
+            WatParserState::Error(ref err) => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, err.clone()))
+            }
+            WatParserState::StartModule { ref id, .. } => {
+                self.start_line()?;
+                self.write_str("(module")?;
+                self.write_id(id)?;
+                self.depth += 1;
+                Ok(())
+            }
+            WatParserState::Type { ref id, ref functype, .. } => {
+                self.start_line()?;
+                let head = format!("(type{} (func", id_text(id));
+                self.write_wrapped(&head, &typeuse_clauses(functype), "))")
+            }
+            WatParserState::Import {
+                ref modname,
+                ref fieldname,
+                ref import,
+                ref export_names,
+                ..
+            } if !export_names.is_empty() => {
+                // Only the inline form can export an import.
+                let (kind, id, rest) = match *import {
+                    WatImport::Func { ref id, ref typeuse } => ("func", id, typeuse.to_string()),
+                    WatImport::Table { ref id, ref tabletype } => {
+                        ("table", id, tabletype.to_string())
+                    }
+                    WatImport::Memory { ref id, ref memtype } => {
+                        ("memory", id, memtype.to_string())
+                    }
+                    WatImport::Global { ref id, ref globaltype } => {
+                        ("global", id, globaltype.to_string())
+                    }
+                };
+                self.start_line()?;
+                write!(self.out, "({}", kind)?;
+                self.write_id(id)?;
+                self.write_exports(export_names)?;
+                self.write_str(" (import ")?;
+                self.write_name(modname)?;
+                self.write_str(" ")?;
+                self.write_name(fieldname)?;
+                self.write_str(")")?;
+                if !rest.is_empty() {
+                    write!(self.out, " {}", rest)?;
+                }
+                self.write_str(")")
+            }
+            WatParserState::Import {
+                ref modname,
+                ref fieldname,
+                ref import,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(import ")?;
+                self.write_name(modname)?;
+                self.write_str(" ")?;
+                self.write_name(fieldname)?;
+                write!(self.out, " {})", import)
+            }
+            WatParserState::StartFunc {
+                ref id,
+                ref export_names,
+                ref typeuse,
+                ref locals,
+                ..
+            } => {
+                self.start_line()?;
+                let mut head = format!("(func{}", id_text(id));
+                for name in export_names {
+                    head.push_str(&format!(" (export {})", quote_name(name)));
+                }
+                let mut clauses = typeuse_clauses(typeuse);
+                clauses.extend(locals.iter().map(local_clause));
+                self.write_wrapped(&head, &clauses, "")?;
+                self.depth += 1;
+                self.body_depth = self.depth;
+                Ok(())
+            }
+            WatParserState::StartGlobal {
+                ref id,
+                ref export_names,
+                ref globaltype,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(global")?;
+                self.write_id(id)?;
+                self.write_exports(export_names)?;
+                write!(self.out, " {}", globaltype)?;
+                self.depth += 1;
+                self.body_depth = self.depth;
+                Ok(())
+            }
+            WatParserState::Table {
+                ref id,
+                ref export_names,
+                ref tabletype,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(table")?;
+                self.write_id(id)?;
+                self.write_exports(export_names)?;
+                write!(self.out, " {})", tabletype)
+            }
+            WatParserState::Memory {
+                ref id,
+                ref export_names,
+                ref memtype,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(memory")?;
+                self.write_id(id)?;
+                self.write_exports(export_names)?;
+                write!(self.out, " {})", memtype)
+            }
+            WatParserState::Export {
+                ref name,
+                kind,
+                ref index,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(export ")?;
+                self.write_name(name)?;
+                write!(self.out, " ({} {}))", kind, index)
+            }
+            WatParserState::Start { ref func, .. } => {
+                self.start_line()?;
+                write!(self.out, "(start {})", func)
+            }
+            WatParserState::Elem {
+                ref id,
+                ref kind,
+                ref elems,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(elem")?;
+                self.write_id(id)?;
+                write_elem(&mut self.out, kind, elems)?;
+                self.write_str(")")
+            }
+            WatParserState::Data {
+                ref id,
+                ref kind,
+                ref data,
+                ..
+            } => {
+                self.start_line()?;
+                self.write_str("(data")?;
+                self.write_id(id)?;
+                self.write_data(kind, data)?;
+                self.write_str(")")
+            }
+            WatParserState::CustomSection { ref name, ref data, .. } => {
+                self.start_line()?;
+                self.write_str("(@custom ")?;
+                self.write_name(name)?;
+                self.write_str(" ")?;
+                self.write_data_string(data)?;
+                self.write_str(")")
+            }
+            WatParserState::CodeOperator {
+                ref instruction,
+                ref args,
+                group,
+                depth,
+                ..
+            } => {
+                self.depth = self.body_depth + depth as usize;
+                self.start_line()?;
+                if group {
+                    self.write_str("(")?;
+                }
+                self.out.write_all(instruction)?;
+                write_args(&mut self.out, args)
+            }
+            WatParserState::CodeOperatorEnd { depth } => {
+                self.depth = self.body_depth + depth as usize;
+                self.write_str(")")
+            }
+            WatParserState::EndFunc { .. } | WatParserState::EndGlobal { .. } => {
+                self.depth = self.body_depth;
+                self.close()
+            }
+            WatParserState::EndModule => self.close(),
+        }
+    }
+}
+
+/// Formats the module described by `events` as WAT text, with
+/// `WatWriter`'s default settings. The first error among the events is
+/// returned.
+///
+/// Formatting is idempotent, and the text parses back to the same events
+/// but for their positions.
+///
+/// ```
+/// use wasmtextparser::{format, WatParser};
+///
+/// let source = b"(module (func $f (param i32) (result i32) (block local.get 0 br 0)))";
+/// let text = format(WatParser::new(source).events()).unwrap();
+/// assert_eq!(text,
+///            "(module\n  (func $f (param i32) (result i32)\n    (block\n      local.get 0\n      \
+///             br 0)))\n");
+/// assert_eq!(format(WatParser::new(text.as_bytes()).events()).unwrap(), text);
+/// ```
+pub fn format<I>(events: I) -> Result<String>
+    where I: IntoIterator<Item = Result<WatParserState>>
+{
+    let mut writer = WatWriter::new(Vec::new());
+    for event in events {
+        writer.write(&event?).map_err(format_error)?;
+    }
+    writer.write(&WatParserState::End).map_err(format_error)?;
+    String::from_utf8(writer.into_inner()).map_err(|_| {
+        WatParserError {
+            kind: ErrorKind::InvalidLiteral,
+            message: "formatted text is not valid UTF-8".into(),
+            start: START_POSITION,
+            end: START_POSITION,
+        }
+    })
+}
+
+// Writing to a Vec only fails on an `Error` event passed as `Ok`, which
+// gives back that error.
+fn format_error(err: io::Error) -> WatParserError {
+    let message = err.to_string();
+    match err.into_inner().map(|inner| inner.downcast::<WatParserError>()) {
+        Some(Ok(err)) => *err,
+        _ => {
+            WatParserError {
+                kind: ErrorKind::Unsupported,
+                message: message.into_boxed_str(),
+                start: START_POSITION,
+                end: START_POSITION,
+            }
+        }
+    }
+}
+
+/// The first events that differ between a module and its formatted
+/// text, as found by `roundtrip`.
+#[derive(Debug,Clone)]
+pub struct WatMismatch {
+    /// The event parsed from the input, and where it starts there.
+    pub original: WatParserState,
+    pub original_start: WatPosition,
+    /// The event parsed from the formatted text, an `Error` if the text
+    /// does not parse, and where it starts there.
+    pub formatted: WatParserState,
+    pub formatted_start: WatPosition,
+    /// The formatted text.
+    pub text: String,
+}
+
+/// Parses `source`, formats it, parses the formatted text and compares
+/// the two event streams, ignoring positions. Returns the first pair of
+/// events that differ, if any, or the error parsing `source` stopped at.
+///
+/// ```
+/// use wasmtextparser::roundtrip;
+///
+/// let source = b"(module (func $f (param i32) (result i32) (block local.get 0 br 0)))";
+/// assert!(roundtrip(source).unwrap().is_none());
+/// assert!(roundtrip(b"(module (func (nop))").is_err());
+/// ```
+pub fn roundtrip(source: &[u8]) -> Result<Option<WatMismatch>> {
+    let original = parse_events(source);
+    if let Some(&(WatParserState::Error(ref err), _)) = original.last() {
+        return Err(err.clone());
+    }
+    let text = format(original.iter().map(|(event, _)| Ok(event.clone())))?;
+    let formatted = parse_events(text.as_bytes());
+    for (&(ref a, a_start), &(ref b, b_start)) in original.iter().zip(&formatted) {
+        if without_position(a) != without_position(b) {
+            return Ok(Some(WatMismatch {
+                original: a.clone(),
+                original_start: a_start,
+                formatted: b.clone(),
+                formatted_start: b_start,
+                text,
+            }));
+        }
+    }
+    // Both streams end with their only `End` or `Error`, so they can only
+    // agree this far when they are the same length.
+    Ok(None)
+}
+
+// Every event up to and including `End` or the first `Error`, with where
+// it starts.
+fn parse_events(source: &[u8]) -> Vec<(WatParserState, WatPosition)> {
+    let mut parser = WatParser::new(source);
+    let mut events = Vec::new();
+    loop {
+        let event = parser.parse();
+        let (start, done) = match event {
+            WatParserState::End => (parser.event_start(), true),
+            WatParserState::Error(ref err) => (err.start, true),
+            _ => (parser.event_start(), false),
+        };
+        events.push((event, start));
+        if done {
+            return events;
+        }
+    }
+}
+
+fn without_position(event: &WatParserState) -> WatParserState {
+    let mut event = event.clone();
+    match event {
+        WatParserState::StartModule { ref mut position, .. } |
+        WatParserState::Type { ref mut position, .. } |
+        WatParserState::Import { ref mut position, .. } |
+        WatParserState::StartFunc { ref mut position, .. } |
+        WatParserState::EndFunc { ref mut position } |
+        WatParserState::StartGlobal { ref mut position, .. } |
+        WatParserState::EndGlobal { ref mut position } |
+        WatParserState::Table { ref mut position, .. } |
+        WatParserState::Memory { ref mut position, .. } |
+        WatParserState::Elem { ref mut position, .. } |
+        WatParserState::Data { ref mut position, .. } |
+        WatParserState::CustomSection { ref mut position, .. } |
+        WatParserState::Export { ref mut position, .. } |
+        WatParserState::Start { ref mut position, .. } |
+        WatParserState::CodeOperator { ref mut position, .. } => *position = START_POSITION,
+        WatParserState::Initial |
+        WatParserState::End |
+        WatParserState::Error(_) |
+        WatParserState::EndModule |
+        WatParserState::CodeOperatorEnd { .. } => {}
+    }
+    event
+}
+
+pub(crate) fn write_args<W: Write>(out: &mut W, args: &[WatInstructionArg]) -> io::Result<()> {
+    for arg in args {
+        out.write_all(b" ")?;
+        match *arg {
+            WatInstructionArg::ID(ref id) |
+            WatInstructionArg::Flags(ref id, _) |
+            WatInstructionArg::Shape(ref id) => out.write_all(id)?,
+            // Integers keep the author's spelling.
+            WatInstructionArg::Unsigned { ref raw, .. } |
+            WatInstructionArg::Signed { ref raw, .. } => out.write_all(raw)?,
+            WatInstructionArg::Float(ref float) => write!(out, "{}", float)?,
+            WatInstructionArg::Symbol(symbol) => write!(out, "{}", symbol)?,
+            WatInstructionArg::Memory(ref index) |
+            WatInstructionArg::Table(ref index) |
+            WatInstructionArg::Function(ref index) |
+            WatInstructionArg::Data(ref index) |
+            WatInstructionArg::Elem(ref index) => write!(out, "{}", index)?,
+            WatInstructionArg::HeapType(ref reftype) => write!(out, "{}", reftype.heaptype())?,
+            WatInstructionArg::Result(ref valtype) => write!(out, "(result {})", valtype)?,
+            WatInstructionArg::Type(ref typeuse) => write!(out, "{}", typeuse)?,
+        }
+    }
+    Ok(())
+}
+
+// Const exprs are stored flat, so they are written unfolded.
+pub(crate) fn write_const_expr<W: Write>(out: &mut W, expr: &ConstExpr) -> io::Result<()> {
+    for (i, instruction) in expr.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b" ")?;
+        }
+        out.write_all(&instruction.instruction)?;
+        write_args(out, &instruction.args)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_elem<W: Write>(out: &mut W,
+                                   kind: &WatElemKind,
+                                   elems: &WatElemList)
+                                   -> io::Result<()> {
+    match *kind {
+        WatElemKind::Active { ref table, ref offset } => {
+            if let Some(ref table) = *table {
+                write!(out, " (table {})", table)?;
+            }
+            out.write_all(b" (offset ")?;
+            write_const_expr(out, offset)?;
+            out.write_all(b")")?;
+        }
+        WatElemKind::Passive => {}
+        WatElemKind::Declarative => out.write_all(b" declare")?,
+    }
+    match *elems {
+        WatElemList::Functions(ref funcs) => {
+            out.write_all(b" func")?;
+            for func in funcs {
+                write!(out, " {}", func)?;
+            }
+        }
+        WatElemList::Expressions(ref reftype, ref exprs) => {
+            write!(out, " {}", reftype)?;
+            for expr in exprs {
+                out.write_all(b" (item ")?;
+                write_const_expr(out, expr)?;
+                out.write_all(b")")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn id_text(id: &Option<Vec<u8>>) -> String {
+    match *id {
+        Some(ref id) => format!(" {}", String::from_utf8_lossy(id)),
+        None => String::new(),
+    }
+}
+
+pub(crate) fn quote_name(name: &str) -> String {
+    let mut text = String::from("\"");
+    for ch in name.chars() {
+        match ch {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\t' => text.push_str("\\t"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            _ if (ch as u32) < 0x20 || ch == '\u{7F}' => {
+                text.push_str(&format!("\\{:02x}", ch as u32))
+            }
+            _ => text.push(ch),
+        }
+    }
+    text.push('"');
+    text
+}
+
+// Splits a typeuse the way its `Display` writes it: the `(type ...)`,
+// one `(param ...)` per named param or run of unnamed ones, and the
+// `(result ...)`.
+pub(crate) fn typeuse_clauses(typeuse: &WatTypeuse) -> Vec<String> {
+    let mut clauses = vec![];
+    if let Some(ref id) = typeuse.id {
+        clauses.push(format!("(type {})", String::from_utf8_lossy(id)));
+    } else if let Some(index) = typeuse.index {
+        clauses.push(format!("(type {})", index));
+    }
+    let mut unnamed: Option<String> = None;
+    for param in &typeuse.params {
+        match param.id {
+            Some(ref id) => {
+                clauses.extend(unnamed.take().map(|clause| clause + ")"));
+                clauses.push(format!("(param {} {})", String::from_utf8_lossy(id), param.valtype));
+            }
+            None => {
+                let clause = unnamed.get_or_insert_with(|| String::from("(param"));
+                clause.push_str(&format!(" {}", param.valtype));
+            }
+        }
+    }
+    clauses.extend(unnamed.map(|clause| clause + ")"));
+    if !typeuse.results.is_empty() {
+        let mut clause = String::from("(result");
+        for result in &typeuse.results {
+            clause.push_str(&format!(" {}", result.valtype));
+        }
+        clauses.push(clause + ")");
+    }
+    clauses
+}
+
+pub(crate) fn local_clause(local: &WatLocal) -> String {
+    format!("(local{} {})", id_text(&local.id), local.valtype)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use super::*;
+
+    const SOURCE: &[u8] = b"(module $m (type $t (func (param i32) (result i32)))
+        (import \"env\" \"log\" (func $log (param i32))) (memory (export \"mem\") 1)
+        (global $g (mut i32) (i32.const 0))
+        (func $f (export \"f\") (type $t) (local $x i64)
+          (if (result i32) (local.get 0) (then (i32.const 1)) (else (i32.const 2))))
+        (data (i32.const 8) \"hi\\n\") (export \"g\" (global $g)) (start $f))";
+
+    #[test]
+    fn formats_a_module() {
+        assert_eq!(format(WatParser::new(SOURCE).events()).unwrap(),
+                   "(module $m\n\
+                   \x20 (type $t (func (param i32) (result i32)))\n\
+                   \x20 (import \"env\" \"log\" (func $log (param i32)))\n\
+                   \x20 (memory (export \"mem\") 1)\n\
+                   \x20 (global $g (mut i32)\n\
+                   \x20   (i32.const 0))\n\
+                   \x20 (func $f (export \"f\") (type $t) (local $x i64)\n\
+                   \x20   (if (result i32)\n\
+                   \x20     (local.get 0)\n\
+                   \x20     (then\n\
+                   \x20       (i32.const 1))\n\
+                   \x20     (else\n\
+                   \x20       (i32.const 2))))\n\
+                   \x20 (data (memory 0) (offset i32.const 8) \"hi\\n\")\n\
+                   \x20 (export \"g\" (global $g))\n\
+                   \x20 (start $f))\n");
+    }
+
+    #[test]
+    fn formatted_text_parses_to_the_same_events() {
+        let text = format(WatParser::new(SOURCE).events()).unwrap();
+        let events = |source: &[u8]| -> Vec<WatParserState> {
+            parse_events(source).iter().map(|event| without_position(&event.0)).collect()
+        };
+        let original = events(SOURCE);
+        let formatted = events(text.as_bytes());
+        assert_eq!(original.last(), Some(&WatParserState::End));
+        assert_eq!(original, formatted);
+    }
+}
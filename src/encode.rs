@@ -0,0 +1,1784 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use lexer::WatPosition;
+use opcode::WatOpcode;
+use resolve::{IndexResolver, IndexSpace};
+use wat::{data_to_u64, ConstExpr, ErrorKind, ID, OptionalID, Result, WatDataKind, WatElemKind,
+          WatElemList, WatExportKind, WatFloat, WatImport, WatIndex, WatInstructionArg, WatLimits,
+          WatParserError, WatParserState, WatRefType, WatSign, WatTypeuse, WatValType};
+
+/// Assembles the module described by `events` in the binary format.
+///
+/// Writes the type, import, function, table, memory, global, export,
+/// element, data count, code and data sections. `$id`s are resolved to
+/// indices, and the types of typeuses without a matching `(type ...)`
+/// are added after the declared ones, in order of first use. The data
+/// count section is only written when code uses `memory.init` or
+/// `data.drop`.
+///
+/// The first error is returned: one from the events, an unknown `$id`, an
+/// out of range float, or an `Unsupported` one for an instruction with
+/// no binary encoding yet or an id interned by
+/// `WatParserOptions::intern_ids`.
+///
+/// ```
+/// use wasmtextparser::{encode_module, WatParser};
+///
+/// let source = b"(module (func (export \"answer\") (result i32) i32.const 42))";
+/// let wasm = encode_module(WatParser::new(source).events()).unwrap();
+/// assert_eq!(wasm, b"\0asm\x01\0\0\0\
+///                    \x01\x05\x01\x60\x00\x01\x7f\
+///                    \x03\x02\x01\x00\
+///                    \x07\x0a\x01\x06answer\x00\x00\
+///                    \x0a\x06\x01\x04\x00\x41\x2a\x0b");
+/// ```
+pub fn encode_module<I>(events: I) -> Result<Vec<u8>>
+    where I: IntoIterator<Item = Result<WatParserState>>
+{
+    let events = events.into_iter().collect::<Result<Vec<_>>>()?;
+    let mut encoder = Encoder::new(&events);
+    for event in &events {
+        encoder.event(event)?;
+    }
+    Ok(encoder.finish())
+}
+
+// The immediates that follow an opcode.
+#[derive(Clone,Copy)]
+enum Immediates {
+    None,
+    BlockType,
+    Label,
+    LabelTable,
+    Func,
+    CallIndirect,
+    Local,
+    Global,
+    Table,
+    Memarg,
+    Memory,
+    I32,
+    I64,
+    F32,
+    F64,
+    MemoryInit,
+    MemoryCopy,
+    Data,
+    TableInit,
+    TableCopy,
+    Elem,
+    HeapType,
+    Select,
+    Zero,
+    V128Const,
+    Shuffle,
+    Lane,
+    MemargLane,
+}
+
+// The prefix byte, or 0 for none, the opcode and its immediates.
+fn encoding(opcode: &WatOpcode) -> Option<(u8, u32, Immediates)> {
+    Some(match *opcode {
+        WatOpcode::AtomicFence => (0xFE, 0x03, Immediates::Zero),
+        WatOpcode::Block => (0x00, 0x02, Immediates::BlockType),
+        WatOpcode::Br => (0x00, 0x0C, Immediates::Label),
+        WatOpcode::BrIf => (0x00, 0x0D, Immediates::Label),
+        WatOpcode::BrTable => (0x00, 0x0E, Immediates::LabelTable),
+        WatOpcode::Call => (0x00, 0x10, Immediates::Func),
+        WatOpcode::CallIndirect => (0x00, 0x11, Immediates::CallIndirect),
+        WatOpcode::DataDrop => (0xFC, 0x09, Immediates::Data),
+        WatOpcode::Drop => (0x00, 0x1A, Immediates::None),
+        WatOpcode::ElemDrop => (0xFC, 0x0D, Immediates::Elem),
+        WatOpcode::Else => (0x00, 0x05, Immediates::None),
+        WatOpcode::End => (0x00, 0x0B, Immediates::None),
+        WatOpcode::F32Abs => (0x00, 0x8B, Immediates::None),
+        WatOpcode::F32Add => (0x00, 0x92, Immediates::None),
+        WatOpcode::F32Ceil => (0x00, 0x8D, Immediates::None),
+        WatOpcode::F32Const => (0x00, 0x43, Immediates::F32),
+        WatOpcode::F32ConvertI32S => (0x00, 0xB2, Immediates::None),
+        WatOpcode::F32ConvertI32U => (0x00, 0xB3, Immediates::None),
+        WatOpcode::F32ConvertI64S => (0x00, 0xB4, Immediates::None),
+        WatOpcode::F32ConvertI64U => (0x00, 0xB5, Immediates::None),
+        WatOpcode::F32Copysign => (0x00, 0x98, Immediates::None),
+        WatOpcode::F32DemoteF64 => (0x00, 0xB6, Immediates::None),
+        WatOpcode::F32Div => (0x00, 0x95, Immediates::None),
+        WatOpcode::F32Eq => (0x00, 0x5B, Immediates::None),
+        WatOpcode::F32Floor => (0x00, 0x8E, Immediates::None),
+        WatOpcode::F32Ge => (0x00, 0x60, Immediates::None),
+        WatOpcode::F32Gt => (0x00, 0x5E, Immediates::None),
+        WatOpcode::F32Le => (0x00, 0x5F, Immediates::None),
+        WatOpcode::F32Load => (0x00, 0x2A, Immediates::Memarg),
+        WatOpcode::F32Lt => (0x00, 0x5D, Immediates::None),
+        WatOpcode::F32Max => (0x00, 0x97, Immediates::None),
+        WatOpcode::F32Min => (0x00, 0x96, Immediates::None),
+        WatOpcode::F32Mul => (0x00, 0x94, Immediates::None),
+        WatOpcode::F32Ne => (0x00, 0x5C, Immediates::None),
+        WatOpcode::F32Nearest => (0x00, 0x90, Immediates::None),
+        WatOpcode::F32Neg => (0x00, 0x8C, Immediates::None),
+        WatOpcode::F32ReinterpretI32 => (0x00, 0xBE, Immediates::None),
+        WatOpcode::F32Sqrt => (0x00, 0x91, Immediates::None),
+        WatOpcode::F32Store => (0x00, 0x38, Immediates::Memarg),
+        WatOpcode::F32Sub => (0x00, 0x93, Immediates::None),
+        WatOpcode::F32Trunc => (0x00, 0x8F, Immediates::None),
+        WatOpcode::F32x4Abs => (0xFD, 0xE0, Immediates::None),
+        WatOpcode::F32x4Add => (0xFD, 0xE4, Immediates::None),
+        WatOpcode::F32x4Ceil => (0xFD, 0x67, Immediates::None),
+        WatOpcode::F32x4ConvertI32x4S => (0xFD, 0xFA, Immediates::None),
+        WatOpcode::F32x4ConvertI32x4U => (0xFD, 0xFB, Immediates::None),
+        WatOpcode::F32x4DemoteF64x2Zero => (0xFD, 0x5E, Immediates::None),
+        WatOpcode::F32x4Div => (0xFD, 0xE7, Immediates::None),
+        WatOpcode::F32x4Eq => (0xFD, 0x41, Immediates::None),
+        WatOpcode::F32x4ExtractLane => (0xFD, 0x1F, Immediates::Lane),
+        WatOpcode::F32x4Floor => (0xFD, 0x68, Immediates::None),
+        WatOpcode::F32x4Ge => (0xFD, 0x46, Immediates::None),
+        WatOpcode::F32x4Gt => (0xFD, 0x44, Immediates::None),
+        WatOpcode::F32x4Le => (0xFD, 0x45, Immediates::None),
+        WatOpcode::F32x4Lt => (0xFD, 0x43, Immediates::None),
+        WatOpcode::F32x4Max => (0xFD, 0xE9, Immediates::None),
+        WatOpcode::F32x4Min => (0xFD, 0xE8, Immediates::None),
+        WatOpcode::F32x4Mul => (0xFD, 0xE6, Immediates::None),
+        WatOpcode::F32x4Ne => (0xFD, 0x42, Immediates::None),
+        WatOpcode::F32x4Nearest => (0xFD, 0x6A, Immediates::None),
+        WatOpcode::F32x4Neg => (0xFD, 0xE1, Immediates::None),
+        WatOpcode::F32x4Pmax => (0xFD, 0xEB, Immediates::None),
+        WatOpcode::F32x4Pmin => (0xFD, 0xEA, Immediates::None),
+        WatOpcode::F32x4ReplaceLane => (0xFD, 0x20, Immediates::Lane),
+        WatOpcode::F32x4Splat => (0xFD, 0x13, Immediates::None),
+        WatOpcode::F32x4Sqrt => (0xFD, 0xE3, Immediates::None),
+        WatOpcode::F32x4Sub => (0xFD, 0xE5, Immediates::None),
+        WatOpcode::F32x4Trunc => (0xFD, 0x69, Immediates::None),
+        WatOpcode::F64Abs => (0x00, 0x99, Immediates::None),
+        WatOpcode::F64Add => (0x00, 0xA0, Immediates::None),
+        WatOpcode::F64Ceil => (0x00, 0x9B, Immediates::None),
+        WatOpcode::F64Const => (0x00, 0x44, Immediates::F64),
+        WatOpcode::F64ConvertI32S => (0x00, 0xB7, Immediates::None),
+        WatOpcode::F64ConvertI32U => (0x00, 0xB8, Immediates::None),
+        WatOpcode::F64ConvertI64S => (0x00, 0xB9, Immediates::None),
+        WatOpcode::F64ConvertI64U => (0x00, 0xBA, Immediates::None),
+        WatOpcode::F64Copysign => (0x00, 0xA6, Immediates::None),
+        WatOpcode::F64Div => (0x00, 0xA3, Immediates::None),
+        WatOpcode::F64Eq => (0x00, 0x61, Immediates::None),
+        WatOpcode::F64Floor => (0x00, 0x9C, Immediates::None),
+        WatOpcode::F64Ge => (0x00, 0x66, Immediates::None),
+        WatOpcode::F64Gt => (0x00, 0x64, Immediates::None),
+        WatOpcode::F64Le => (0x00, 0x65, Immediates::None),
+        WatOpcode::F64Load => (0x00, 0x2B, Immediates::Memarg),
+        WatOpcode::F64Lt => (0x00, 0x63, Immediates::None),
+        WatOpcode::F64Max => (0x00, 0xA5, Immediates::None),
+        WatOpcode::F64Min => (0x00, 0xA4, Immediates::None),
+        WatOpcode::F64Mul => (0x00, 0xA2, Immediates::None),
+        WatOpcode::F64Ne => (0x00, 0x62, Immediates::None),
+        WatOpcode::F64Nearest => (0x00, 0x9E, Immediates::None),
+        WatOpcode::F64Neg => (0x00, 0x9A, Immediates::None),
+        WatOpcode::F64PromoteF32 => (0x00, 0xBB, Immediates::None),
+        WatOpcode::F64ReinterpretI64 => (0x00, 0xBF, Immediates::None),
+        WatOpcode::F64Sqrt => (0x00, 0x9F, Immediates::None),
+        WatOpcode::F64Store => (0x00, 0x39, Immediates::Memarg),
+        WatOpcode::F64Sub => (0x00, 0xA1, Immediates::None),
+        WatOpcode::F64Trunc => (0x00, 0x9D, Immediates::None),
+        WatOpcode::F64x2Abs => (0xFD, 0xEC, Immediates::None),
+        WatOpcode::F64x2Add => (0xFD, 0xF0, Immediates::None),
+        WatOpcode::F64x2Ceil => (0xFD, 0x74, Immediates::None),
+        WatOpcode::F64x2ConvertLowI32x4S => (0xFD, 0xFE, Immediates::None),
+        WatOpcode::F64x2ConvertLowI32x4U => (0xFD, 0xFF, Immediates::None),
+        WatOpcode::F64x2Div => (0xFD, 0xF3, Immediates::None),
+        WatOpcode::F64x2Eq => (0xFD, 0x47, Immediates::None),
+        WatOpcode::F64x2ExtractLane => (0xFD, 0x21, Immediates::Lane),
+        WatOpcode::F64x2Floor => (0xFD, 0x75, Immediates::None),
+        WatOpcode::F64x2Ge => (0xFD, 0x4C, Immediates::None),
+        WatOpcode::F64x2Gt => (0xFD, 0x4A, Immediates::None),
+        WatOpcode::F64x2Le => (0xFD, 0x4B, Immediates::None),
+        WatOpcode::F64x2Lt => (0xFD, 0x49, Immediates::None),
+        WatOpcode::F64x2Max => (0xFD, 0xF5, Immediates::None),
+        WatOpcode::F64x2Min => (0xFD, 0xF4, Immediates::None),
+        WatOpcode::F64x2Mul => (0xFD, 0xF2, Immediates::None),
+        WatOpcode::F64x2Ne => (0xFD, 0x48, Immediates::None),
+        WatOpcode::F64x2Nearest => (0xFD, 0x94, Immediates::None),
+        WatOpcode::F64x2Neg => (0xFD, 0xED, Immediates::None),
+        WatOpcode::F64x2Pmax => (0xFD, 0xF7, Immediates::None),
+        WatOpcode::F64x2Pmin => (0xFD, 0xF6, Immediates::None),
+        WatOpcode::F64x2PromoteLowF32x4 => (0xFD, 0x5F, Immediates::None),
+        WatOpcode::F64x2ReplaceLane => (0xFD, 0x22, Immediates::Lane),
+        WatOpcode::F64x2Splat => (0xFD, 0x14, Immediates::None),
+        WatOpcode::F64x2Sqrt => (0xFD, 0xEF, Immediates::None),
+        WatOpcode::F64x2Sub => (0xFD, 0xF1, Immediates::None),
+        WatOpcode::F64x2Trunc => (0xFD, 0x7A, Immediates::None),
+        WatOpcode::GlobalGet => (0x00, 0x23, Immediates::Global),
+        WatOpcode::GlobalSet => (0x00, 0x24, Immediates::Global),
+        WatOpcode::I16x8Abs => (0xFD, 0x80, Immediates::None),
+        WatOpcode::I16x8Add => (0xFD, 0x8E, Immediates::None),
+        WatOpcode::I16x8AddSatS => (0xFD, 0x8F, Immediates::None),
+        WatOpcode::I16x8AddSatU => (0xFD, 0x90, Immediates::None),
+        WatOpcode::I16x8AllTrue => (0xFD, 0x83, Immediates::None),
+        WatOpcode::I16x8AvgrU => (0xFD, 0x9B, Immediates::None),
+        WatOpcode::I16x8Bitmask => (0xFD, 0x84, Immediates::None),
+        WatOpcode::I16x8Eq => (0xFD, 0x2D, Immediates::None),
+        WatOpcode::I16x8ExtaddPairwiseI8x16S => (0xFD, 0x7C, Immediates::None),
+        WatOpcode::I16x8ExtaddPairwiseI8x16U => (0xFD, 0x7D, Immediates::None),
+        WatOpcode::I16x8ExtendHighI8x16S => (0xFD, 0x88, Immediates::None),
+        WatOpcode::I16x8ExtendHighI8x16U => (0xFD, 0x8A, Immediates::None),
+        WatOpcode::I16x8ExtendLowI8x16S => (0xFD, 0x87, Immediates::None),
+        WatOpcode::I16x8ExtendLowI8x16U => (0xFD, 0x89, Immediates::None),
+        WatOpcode::I16x8ExtmulHighI8x16S => (0xFD, 0x9D, Immediates::None),
+        WatOpcode::I16x8ExtmulHighI8x16U => (0xFD, 0x9F, Immediates::None),
+        WatOpcode::I16x8ExtmulLowI8x16S => (0xFD, 0x9C, Immediates::None),
+        WatOpcode::I16x8ExtmulLowI8x16U => (0xFD, 0x9E, Immediates::None),
+        WatOpcode::I16x8ExtractLaneS => (0xFD, 0x18, Immediates::Lane),
+        WatOpcode::I16x8ExtractLaneU => (0xFD, 0x19, Immediates::Lane),
+        WatOpcode::I16x8GeS => (0xFD, 0x35, Immediates::None),
+        WatOpcode::I16x8GeU => (0xFD, 0x36, Immediates::None),
+        WatOpcode::I16x8GtS => (0xFD, 0x31, Immediates::None),
+        WatOpcode::I16x8GtU => (0xFD, 0x32, Immediates::None),
+        WatOpcode::I16x8LeS => (0xFD, 0x33, Immediates::None),
+        WatOpcode::I16x8LeU => (0xFD, 0x34, Immediates::None),
+        WatOpcode::I16x8LtS => (0xFD, 0x2F, Immediates::None),
+        WatOpcode::I16x8LtU => (0xFD, 0x30, Immediates::None),
+        WatOpcode::I16x8MaxS => (0xFD, 0x98, Immediates::None),
+        WatOpcode::I16x8MaxU => (0xFD, 0x99, Immediates::None),
+        WatOpcode::I16x8MinS => (0xFD, 0x96, Immediates::None),
+        WatOpcode::I16x8MinU => (0xFD, 0x97, Immediates::None),
+        WatOpcode::I16x8Mul => (0xFD, 0x95, Immediates::None),
+        WatOpcode::I16x8NarrowI32x4S => (0xFD, 0x85, Immediates::None),
+        WatOpcode::I16x8NarrowI32x4U => (0xFD, 0x86, Immediates::None),
+        WatOpcode::I16x8Ne => (0xFD, 0x2E, Immediates::None),
+        WatOpcode::I16x8Neg => (0xFD, 0x81, Immediates::None),
+        WatOpcode::I16x8Q15mulrSatS => (0xFD, 0x82, Immediates::None),
+        WatOpcode::I16x8ReplaceLane => (0xFD, 0x1A, Immediates::Lane),
+        WatOpcode::I16x8Shl => (0xFD, 0x8B, Immediates::None),
+        WatOpcode::I16x8ShrS => (0xFD, 0x8C, Immediates::None),
+        WatOpcode::I16x8ShrU => (0xFD, 0x8D, Immediates::None),
+        WatOpcode::I16x8Splat => (0xFD, 0x10, Immediates::None),
+        WatOpcode::I16x8Sub => (0xFD, 0x91, Immediates::None),
+        WatOpcode::I16x8SubSatS => (0xFD, 0x92, Immediates::None),
+        WatOpcode::I16x8SubSatU => (0xFD, 0x93, Immediates::None),
+        WatOpcode::I32Add => (0x00, 0x6A, Immediates::None),
+        WatOpcode::I32And => (0x00, 0x71, Immediates::None),
+        WatOpcode::I32AtomicLoad => (0xFE, 0x10, Immediates::Memarg),
+        WatOpcode::I32AtomicLoad16U => (0xFE, 0x13, Immediates::Memarg),
+        WatOpcode::I32AtomicLoad8U => (0xFE, 0x12, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwAdd => (0xFE, 0x1E, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwAnd => (0xFE, 0x2C, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwCmpxchg => (0xFE, 0x48, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwOr => (0xFE, 0x33, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwSub => (0xFE, 0x25, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwXchg => (0xFE, 0x41, Immediates::Memarg),
+        WatOpcode::I32AtomicRmwXor => (0xFE, 0x3A, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16AddU => (0xFE, 0x21, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16AndU => (0xFE, 0x2F, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16CmpxchgU => (0xFE, 0x4B, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16OrU => (0xFE, 0x36, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16SubU => (0xFE, 0x28, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16XchgU => (0xFE, 0x44, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw16XorU => (0xFE, 0x3D, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8AddU => (0xFE, 0x20, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8AndU => (0xFE, 0x2E, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8CmpxchgU => (0xFE, 0x4A, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8OrU => (0xFE, 0x35, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8SubU => (0xFE, 0x27, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8XchgU => (0xFE, 0x43, Immediates::Memarg),
+        WatOpcode::I32AtomicRmw8XorU => (0xFE, 0x3C, Immediates::Memarg),
+        WatOpcode::I32AtomicStore => (0xFE, 0x17, Immediates::Memarg),
+        WatOpcode::I32AtomicStore16 => (0xFE, 0x1A, Immediates::Memarg),
+        WatOpcode::I32AtomicStore8 => (0xFE, 0x19, Immediates::Memarg),
+        WatOpcode::I32Clz => (0x00, 0x67, Immediates::None),
+        WatOpcode::I32Const => (0x00, 0x41, Immediates::I32),
+        WatOpcode::I32Ctz => (0x00, 0x68, Immediates::None),
+        WatOpcode::I32DivS => (0x00, 0x6D, Immediates::None),
+        WatOpcode::I32DivU => (0x00, 0x6E, Immediates::None),
+        WatOpcode::I32Eq => (0x00, 0x46, Immediates::None),
+        WatOpcode::I32Eqz => (0x00, 0x45, Immediates::None),
+        WatOpcode::I32Extend16S => (0x00, 0xC1, Immediates::None),
+        WatOpcode::I32Extend8S => (0x00, 0xC0, Immediates::None),
+        WatOpcode::I32GeS => (0x00, 0x4E, Immediates::None),
+        WatOpcode::I32GeU => (0x00, 0x4F, Immediates::None),
+        WatOpcode::I32GtS => (0x00, 0x4A, Immediates::None),
+        WatOpcode::I32GtU => (0x00, 0x4B, Immediates::None),
+        WatOpcode::I32LeS => (0x00, 0x4C, Immediates::None),
+        WatOpcode::I32LeU => (0x00, 0x4D, Immediates::None),
+        WatOpcode::I32Load => (0x00, 0x28, Immediates::Memarg),
+        WatOpcode::I32Load16S => (0x00, 0x2E, Immediates::Memarg),
+        WatOpcode::I32Load16U => (0x00, 0x2F, Immediates::Memarg),
+        WatOpcode::I32Load8S => (0x00, 0x2C, Immediates::Memarg),
+        WatOpcode::I32Load8U => (0x00, 0x2D, Immediates::Memarg),
+        WatOpcode::I32LtS => (0x00, 0x48, Immediates::None),
+        WatOpcode::I32LtU => (0x00, 0x49, Immediates::None),
+        WatOpcode::I32Mul => (0x00, 0x6C, Immediates::None),
+        WatOpcode::I32Ne => (0x00, 0x47, Immediates::None),
+        WatOpcode::I32Or => (0x00, 0x72, Immediates::None),
+        WatOpcode::I32Popcnt => (0x00, 0x69, Immediates::None),
+        WatOpcode::I32ReinterpretF32 => (0x00, 0xBC, Immediates::None),
+        WatOpcode::I32RemS => (0x00, 0x6F, Immediates::None),
+        WatOpcode::I32RemU => (0x00, 0x70, Immediates::None),
+        WatOpcode::I32Rotl => (0x00, 0x77, Immediates::None),
+        WatOpcode::I32Rotr => (0x00, 0x78, Immediates::None),
+        WatOpcode::I32Shl => (0x00, 0x74, Immediates::None),
+        WatOpcode::I32ShrS => (0x00, 0x75, Immediates::None),
+        WatOpcode::I32ShrU => (0x00, 0x76, Immediates::None),
+        WatOpcode::I32Store => (0x00, 0x36, Immediates::Memarg),
+        WatOpcode::I32Store16 => (0x00, 0x3B, Immediates::Memarg),
+        WatOpcode::I32Store8 => (0x00, 0x3A, Immediates::Memarg),
+        WatOpcode::I32Sub => (0x00, 0x6B, Immediates::None),
+        WatOpcode::I32TruncF32S => (0x00, 0xA8, Immediates::None),
+        WatOpcode::I32TruncF32U => (0x00, 0xA9, Immediates::None),
+        WatOpcode::I32TruncF64S => (0x00, 0xAA, Immediates::None),
+        WatOpcode::I32TruncF64U => (0x00, 0xAB, Immediates::None),
+        WatOpcode::I32TruncSatF32S => (0xFC, 0x00, Immediates::None),
+        WatOpcode::I32TruncSatF32U => (0xFC, 0x01, Immediates::None),
+        WatOpcode::I32TruncSatF64S => (0xFC, 0x02, Immediates::None),
+        WatOpcode::I32TruncSatF64U => (0xFC, 0x03, Immediates::None),
+        WatOpcode::I32WrapI64 => (0x00, 0xA7, Immediates::None),
+        WatOpcode::I32Xor => (0x00, 0x73, Immediates::None),
+        WatOpcode::I32x4Abs => (0xFD, 0xA0, Immediates::None),
+        WatOpcode::I32x4Add => (0xFD, 0xAE, Immediates::None),
+        WatOpcode::I32x4AllTrue => (0xFD, 0xA3, Immediates::None),
+        WatOpcode::I32x4Bitmask => (0xFD, 0xA4, Immediates::None),
+        WatOpcode::I32x4DotI16x8S => (0xFD, 0xBA, Immediates::None),
+        WatOpcode::I32x4Eq => (0xFD, 0x37, Immediates::None),
+        WatOpcode::I32x4ExtaddPairwiseI16x8S => (0xFD, 0x7E, Immediates::None),
+        WatOpcode::I32x4ExtaddPairwiseI16x8U => (0xFD, 0x7F, Immediates::None),
+        WatOpcode::I32x4ExtendHighI16x8S => (0xFD, 0xA8, Immediates::None),
+        WatOpcode::I32x4ExtendHighI16x8U => (0xFD, 0xAA, Immediates::None),
+        WatOpcode::I32x4ExtendLowI16x8S => (0xFD, 0xA7, Immediates::None),
+        WatOpcode::I32x4ExtendLowI16x8U => (0xFD, 0xA9, Immediates::None),
+        WatOpcode::I32x4ExtmulHighI16x8S => (0xFD, 0xBD, Immediates::None),
+        WatOpcode::I32x4ExtmulHighI16x8U => (0xFD, 0xBF, Immediates::None),
+        WatOpcode::I32x4ExtmulLowI16x8S => (0xFD, 0xBC, Immediates::None),
+        WatOpcode::I32x4ExtmulLowI16x8U => (0xFD, 0xBE, Immediates::None),
+        WatOpcode::I32x4ExtractLane => (0xFD, 0x1B, Immediates::Lane),
+        WatOpcode::I32x4GeS => (0xFD, 0x3F, Immediates::None),
+        WatOpcode::I32x4GeU => (0xFD, 0x40, Immediates::None),
+        WatOpcode::I32x4GtS => (0xFD, 0x3B, Immediates::None),
+        WatOpcode::I32x4GtU => (0xFD, 0x3C, Immediates::None),
+        WatOpcode::I32x4LeS => (0xFD, 0x3D, Immediates::None),
+        WatOpcode::I32x4LeU => (0xFD, 0x3E, Immediates::None),
+        WatOpcode::I32x4LtS => (0xFD, 0x39, Immediates::None),
+        WatOpcode::I32x4LtU => (0xFD, 0x3A, Immediates::None),
+        WatOpcode::I32x4MaxS => (0xFD, 0xB8, Immediates::None),
+        WatOpcode::I32x4MaxU => (0xFD, 0xB9, Immediates::None),
+        WatOpcode::I32x4MinS => (0xFD, 0xB6, Immediates::None),
+        WatOpcode::I32x4MinU => (0xFD, 0xB7, Immediates::None),
+        WatOpcode::I32x4Mul => (0xFD, 0xB5, Immediates::None),
+        WatOpcode::I32x4Ne => (0xFD, 0x38, Immediates::None),
+        WatOpcode::I32x4Neg => (0xFD, 0xA1, Immediates::None),
+        WatOpcode::I32x4ReplaceLane => (0xFD, 0x1C, Immediates::Lane),
+        WatOpcode::I32x4Shl => (0xFD, 0xAB, Immediates::None),
+        WatOpcode::I32x4ShrS => (0xFD, 0xAC, Immediates::None),
+        WatOpcode::I32x4ShrU => (0xFD, 0xAD, Immediates::None),
+        WatOpcode::I32x4Splat => (0xFD, 0x11, Immediates::None),
+        WatOpcode::I32x4Sub => (0xFD, 0xB1, Immediates::None),
+        WatOpcode::I32x4TruncSatF32x4S => (0xFD, 0xF8, Immediates::None),
+        WatOpcode::I32x4TruncSatF32x4U => (0xFD, 0xF9, Immediates::None),
+        WatOpcode::I32x4TruncSatF64x2SZero => (0xFD, 0xFC, Immediates::None),
+        WatOpcode::I32x4TruncSatF64x2UZero => (0xFD, 0xFD, Immediates::None),
+        WatOpcode::I64Add => (0x00, 0x7C, Immediates::None),
+        WatOpcode::I64And => (0x00, 0x83, Immediates::None),
+        WatOpcode::I64AtomicLoad => (0xFE, 0x11, Immediates::Memarg),
+        WatOpcode::I64AtomicLoad16U => (0xFE, 0x15, Immediates::Memarg),
+        WatOpcode::I64AtomicLoad32U => (0xFE, 0x16, Immediates::Memarg),
+        WatOpcode::I64AtomicLoad8U => (0xFE, 0x14, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwAdd => (0xFE, 0x1F, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwAnd => (0xFE, 0x2D, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwCmpxchg => (0xFE, 0x49, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwOr => (0xFE, 0x34, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwSub => (0xFE, 0x26, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwXchg => (0xFE, 0x42, Immediates::Memarg),
+        WatOpcode::I64AtomicRmwXor => (0xFE, 0x3B, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16AddU => (0xFE, 0x23, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16AndU => (0xFE, 0x31, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16CmpxchgU => (0xFE, 0x4D, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16OrU => (0xFE, 0x38, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16SubU => (0xFE, 0x2A, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16XchgU => (0xFE, 0x46, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw16XorU => (0xFE, 0x3F, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32AddU => (0xFE, 0x24, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32AndU => (0xFE, 0x32, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32CmpxchgU => (0xFE, 0x4E, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32OrU => (0xFE, 0x39, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32SubU => (0xFE, 0x2B, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32XchgU => (0xFE, 0x47, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw32XorU => (0xFE, 0x40, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8AddU => (0xFE, 0x22, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8AndU => (0xFE, 0x30, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8CmpxchgU => (0xFE, 0x4C, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8OrU => (0xFE, 0x37, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8SubU => (0xFE, 0x29, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8XchgU => (0xFE, 0x45, Immediates::Memarg),
+        WatOpcode::I64AtomicRmw8XorU => (0xFE, 0x3E, Immediates::Memarg),
+        WatOpcode::I64AtomicStore => (0xFE, 0x18, Immediates::Memarg),
+        WatOpcode::I64AtomicStore16 => (0xFE, 0x1C, Immediates::Memarg),
+        WatOpcode::I64AtomicStore32 => (0xFE, 0x1D, Immediates::Memarg),
+        WatOpcode::I64AtomicStore8 => (0xFE, 0x1B, Immediates::Memarg),
+        WatOpcode::I64Clz => (0x00, 0x79, Immediates::None),
+        WatOpcode::I64Const => (0x00, 0x42, Immediates::I64),
+        WatOpcode::I64Ctz => (0x00, 0x7A, Immediates::None),
+        WatOpcode::I64DivS => (0x00, 0x7F, Immediates::None),
+        WatOpcode::I64DivU => (0x00, 0x80, Immediates::None),
+        WatOpcode::I64Eq => (0x00, 0x51, Immediates::None),
+        WatOpcode::I64Eqz => (0x00, 0x50, Immediates::None),
+        WatOpcode::I64Extend16S => (0x00, 0xC3, Immediates::None),
+        WatOpcode::I64Extend32S => (0x00, 0xC4, Immediates::None),
+        WatOpcode::I64Extend8S => (0x00, 0xC2, Immediates::None),
+        WatOpcode::I64ExtendI32S => (0x00, 0xAC, Immediates::None),
+        WatOpcode::I64ExtendI32U => (0x00, 0xAD, Immediates::None),
+        WatOpcode::I64GeS => (0x00, 0x59, Immediates::None),
+        WatOpcode::I64GeU => (0x00, 0x5A, Immediates::None),
+        WatOpcode::I64GtS => (0x00, 0x55, Immediates::None),
+        WatOpcode::I64GtU => (0x00, 0x56, Immediates::None),
+        WatOpcode::I64LeS => (0x00, 0x57, Immediates::None),
+        WatOpcode::I64LeU => (0x00, 0x58, Immediates::None),
+        WatOpcode::I64Load => (0x00, 0x29, Immediates::Memarg),
+        WatOpcode::I64Load16S => (0x00, 0x32, Immediates::Memarg),
+        WatOpcode::I64Load16U => (0x00, 0x33, Immediates::Memarg),
+        WatOpcode::I64Load32S => (0x00, 0x34, Immediates::Memarg),
+        WatOpcode::I64Load32U => (0x00, 0x35, Immediates::Memarg),
+        WatOpcode::I64Load8S => (0x00, 0x30, Immediates::Memarg),
+        WatOpcode::I64Load8U => (0x00, 0x31, Immediates::Memarg),
+        WatOpcode::I64LtS => (0x00, 0x53, Immediates::None),
+        WatOpcode::I64LtU => (0x00, 0x54, Immediates::None),
+        WatOpcode::I64Mul => (0x00, 0x7E, Immediates::None),
+        WatOpcode::I64Ne => (0x00, 0x52, Immediates::None),
+        WatOpcode::I64Or => (0x00, 0x84, Immediates::None),
+        WatOpcode::I64Popcnt => (0x00, 0x7B, Immediates::None),
+        WatOpcode::I64ReinterpretF64 => (0x00, 0xBD, Immediates::None),
+        WatOpcode::I64RemS => (0x00, 0x81, Immediates::None),
+        WatOpcode::I64RemU => (0x00, 0x82, Immediates::None),
+        WatOpcode::I64Rotl => (0x00, 0x89, Immediates::None),
+        WatOpcode::I64Rotr => (0x00, 0x8A, Immediates::None),
+        WatOpcode::I64Shl => (0x00, 0x86, Immediates::None),
+        WatOpcode::I64ShrS => (0x00, 0x87, Immediates::None),
+        WatOpcode::I64ShrU => (0x00, 0x88, Immediates::None),
+        WatOpcode::I64Store => (0x00, 0x37, Immediates::Memarg),
+        WatOpcode::I64Store16 => (0x00, 0x3D, Immediates::Memarg),
+        WatOpcode::I64Store32 => (0x00, 0x3E, Immediates::Memarg),
+        WatOpcode::I64Store8 => (0x00, 0x3C, Immediates::Memarg),
+        WatOpcode::I64Sub => (0x00, 0x7D, Immediates::None),
+        WatOpcode::I64TruncF32S => (0x00, 0xAE, Immediates::None),
+        WatOpcode::I64TruncF32U => (0x00, 0xAF, Immediates::None),
+        WatOpcode::I64TruncF64S => (0x00, 0xB0, Immediates::None),
+        WatOpcode::I64TruncF64U => (0x00, 0xB1, Immediates::None),
+        WatOpcode::I64TruncSatF32S => (0xFC, 0x04, Immediates::None),
+        WatOpcode::I64TruncSatF32U => (0xFC, 0x05, Immediates::None),
+        WatOpcode::I64TruncSatF64S => (0xFC, 0x06, Immediates::None),
+        WatOpcode::I64TruncSatF64U => (0xFC, 0x07, Immediates::None),
+        WatOpcode::I64Xor => (0x00, 0x85, Immediates::None),
+        WatOpcode::I64x2Abs => (0xFD, 0xC0, Immediates::None),
+        WatOpcode::I64x2Add => (0xFD, 0xCE, Immediates::None),
+        WatOpcode::I64x2AllTrue => (0xFD, 0xC3, Immediates::None),
+        WatOpcode::I64x2Bitmask => (0xFD, 0xC4, Immediates::None),
+        WatOpcode::I64x2Eq => (0xFD, 0xD6, Immediates::None),
+        WatOpcode::I64x2ExtendHighI32x4S => (0xFD, 0xC8, Immediates::None),
+        WatOpcode::I64x2ExtendHighI32x4U => (0xFD, 0xCA, Immediates::None),
+        WatOpcode::I64x2ExtendLowI32x4S => (0xFD, 0xC7, Immediates::None),
+        WatOpcode::I64x2ExtendLowI32x4U => (0xFD, 0xC9, Immediates::None),
+        WatOpcode::I64x2ExtmulHighI32x4S => (0xFD, 0xDD, Immediates::None),
+        WatOpcode::I64x2ExtmulHighI32x4U => (0xFD, 0xDF, Immediates::None),
+        WatOpcode::I64x2ExtmulLowI32x4S => (0xFD, 0xDC, Immediates::None),
+        WatOpcode::I64x2ExtmulLowI32x4U => (0xFD, 0xDE, Immediates::None),
+        WatOpcode::I64x2ExtractLane => (0xFD, 0x1D, Immediates::Lane),
+        WatOpcode::I64x2GeS => (0xFD, 0xDB, Immediates::None),
+        WatOpcode::I64x2GtS => (0xFD, 0xD9, Immediates::None),
+        WatOpcode::I64x2LeS => (0xFD, 0xDA, Immediates::None),
+        WatOpcode::I64x2LtS => (0xFD, 0xD8, Immediates::None),
+        WatOpcode::I64x2Mul => (0xFD, 0xD5, Immediates::None),
+        WatOpcode::I64x2Ne => (0xFD, 0xD7, Immediates::None),
+        WatOpcode::I64x2Neg => (0xFD, 0xC1, Immediates::None),
+        WatOpcode::I64x2ReplaceLane => (0xFD, 0x1E, Immediates::Lane),
+        WatOpcode::I64x2Shl => (0xFD, 0xCB, Immediates::None),
+        WatOpcode::I64x2ShrS => (0xFD, 0xCC, Immediates::None),
+        WatOpcode::I64x2ShrU => (0xFD, 0xCD, Immediates::None),
+        WatOpcode::I64x2Splat => (0xFD, 0x12, Immediates::None),
+        WatOpcode::I64x2Sub => (0xFD, 0xD1, Immediates::None),
+        WatOpcode::I8x16Abs => (0xFD, 0x60, Immediates::None),
+        WatOpcode::I8x16Add => (0xFD, 0x6E, Immediates::None),
+        WatOpcode::I8x16AddSatS => (0xFD, 0x6F, Immediates::None),
+        WatOpcode::I8x16AddSatU => (0xFD, 0x70, Immediates::None),
+        WatOpcode::I8x16AllTrue => (0xFD, 0x63, Immediates::None),
+        WatOpcode::I8x16AvgrU => (0xFD, 0x7B, Immediates::None),
+        WatOpcode::I8x16Bitmask => (0xFD, 0x64, Immediates::None),
+        WatOpcode::I8x16Eq => (0xFD, 0x23, Immediates::None),
+        WatOpcode::I8x16ExtractLaneS => (0xFD, 0x15, Immediates::Lane),
+        WatOpcode::I8x16ExtractLaneU => (0xFD, 0x16, Immediates::Lane),
+        WatOpcode::I8x16GeS => (0xFD, 0x2B, Immediates::None),
+        WatOpcode::I8x16GeU => (0xFD, 0x2C, Immediates::None),
+        WatOpcode::I8x16GtS => (0xFD, 0x27, Immediates::None),
+        WatOpcode::I8x16GtU => (0xFD, 0x28, Immediates::None),
+        WatOpcode::I8x16LeS => (0xFD, 0x29, Immediates::None),
+        WatOpcode::I8x16LeU => (0xFD, 0x2A, Immediates::None),
+        WatOpcode::I8x16LtS => (0xFD, 0x25, Immediates::None),
+        WatOpcode::I8x16LtU => (0xFD, 0x26, Immediates::None),
+        WatOpcode::I8x16MaxS => (0xFD, 0x78, Immediates::None),
+        WatOpcode::I8x16MaxU => (0xFD, 0x79, Immediates::None),
+        WatOpcode::I8x16MinS => (0xFD, 0x76, Immediates::None),
+        WatOpcode::I8x16MinU => (0xFD, 0x77, Immediates::None),
+        WatOpcode::I8x16NarrowI16x8S => (0xFD, 0x65, Immediates::None),
+        WatOpcode::I8x16NarrowI16x8U => (0xFD, 0x66, Immediates::None),
+        WatOpcode::I8x16Ne => (0xFD, 0x24, Immediates::None),
+        WatOpcode::I8x16Neg => (0xFD, 0x61, Immediates::None),
+        WatOpcode::I8x16Popcnt => (0xFD, 0x62, Immediates::None),
+        WatOpcode::I8x16ReplaceLane => (0xFD, 0x17, Immediates::Lane),
+        WatOpcode::I8x16Shl => (0xFD, 0x6B, Immediates::None),
+        WatOpcode::I8x16ShrS => (0xFD, 0x6C, Immediates::None),
+        WatOpcode::I8x16ShrU => (0xFD, 0x6D, Immediates::None),
+        WatOpcode::I8x16Shuffle => (0xFD, 0x0D, Immediates::Shuffle),
+        WatOpcode::I8x16Splat => (0xFD, 0x0F, Immediates::None),
+        WatOpcode::I8x16Sub => (0xFD, 0x71, Immediates::None),
+        WatOpcode::I8x16SubSatS => (0xFD, 0x72, Immediates::None),
+        WatOpcode::I8x16SubSatU => (0xFD, 0x73, Immediates::None),
+        WatOpcode::I8x16Swizzle => (0xFD, 0x0E, Immediates::None),
+        WatOpcode::If => (0x00, 0x04, Immediates::BlockType),
+        WatOpcode::LocalGet => (0x00, 0x20, Immediates::Local),
+        WatOpcode::LocalSet => (0x00, 0x21, Immediates::Local),
+        WatOpcode::LocalTee => (0x00, 0x22, Immediates::Local),
+        WatOpcode::Loop => (0x00, 0x03, Immediates::BlockType),
+        WatOpcode::MemoryAtomicNotify => (0xFE, 0x00, Immediates::Memarg),
+        WatOpcode::MemoryAtomicWait32 => (0xFE, 0x01, Immediates::Memarg),
+        WatOpcode::MemoryAtomicWait64 => (0xFE, 0x02, Immediates::Memarg),
+        WatOpcode::MemoryCopy => (0xFC, 0x0A, Immediates::MemoryCopy),
+        WatOpcode::MemoryFill => (0xFC, 0x0B, Immediates::Memory),
+        WatOpcode::MemoryGrow => (0x00, 0x40, Immediates::Memory),
+        WatOpcode::MemoryInit => (0xFC, 0x08, Immediates::MemoryInit),
+        WatOpcode::MemorySize => (0x00, 0x3F, Immediates::Memory),
+        WatOpcode::Nop => (0x00, 0x01, Immediates::None),
+        WatOpcode::RefFunc => (0x00, 0xD2, Immediates::Func),
+        WatOpcode::RefIsNull => (0x00, 0xD1, Immediates::None),
+        WatOpcode::RefNull => (0x00, 0xD0, Immediates::HeapType),
+        WatOpcode::Return => (0x00, 0x0F, Immediates::None),
+        WatOpcode::ReturnCall => (0x00, 0x12, Immediates::Func),
+        WatOpcode::ReturnCallIndirect => (0x00, 0x13, Immediates::CallIndirect),
+        WatOpcode::Select => (0x00, 0x1B, Immediates::Select),
+        WatOpcode::TableCopy => (0xFC, 0x0E, Immediates::TableCopy),
+        WatOpcode::TableFill => (0xFC, 0x11, Immediates::Table),
+        WatOpcode::TableGet => (0x00, 0x25, Immediates::Table),
+        WatOpcode::TableGrow => (0xFC, 0x0F, Immediates::Table),
+        WatOpcode::TableInit => (0xFC, 0x0C, Immediates::TableInit),
+        WatOpcode::TableSet => (0x00, 0x26, Immediates::Table),
+        WatOpcode::TableSize => (0xFC, 0x10, Immediates::Table),
+        WatOpcode::Unreachable => (0x00, 0x00, Immediates::None),
+        WatOpcode::V128And => (0xFD, 0x4E, Immediates::None),
+        WatOpcode::V128Andnot => (0xFD, 0x4F, Immediates::None),
+        WatOpcode::V128AnyTrue => (0xFD, 0x53, Immediates::None),
+        WatOpcode::V128Bitselect => (0xFD, 0x52, Immediates::None),
+        WatOpcode::V128Const => (0xFD, 0x0C, Immediates::V128Const),
+        WatOpcode::V128Load => (0xFD, 0x00, Immediates::Memarg),
+        WatOpcode::V128Load16Lane => (0xFD, 0x55, Immediates::MemargLane),
+        WatOpcode::V128Load16Splat => (0xFD, 0x08, Immediates::Memarg),
+        WatOpcode::V128Load16x4S => (0xFD, 0x03, Immediates::Memarg),
+        WatOpcode::V128Load16x4U => (0xFD, 0x04, Immediates::Memarg),
+        WatOpcode::V128Load32Lane => (0xFD, 0x56, Immediates::MemargLane),
+        WatOpcode::V128Load32Splat => (0xFD, 0x09, Immediates::Memarg),
+        WatOpcode::V128Load32Zero => (0xFD, 0x5C, Immediates::Memarg),
+        WatOpcode::V128Load32x2S => (0xFD, 0x05, Immediates::Memarg),
+        WatOpcode::V128Load32x2U => (0xFD, 0x06, Immediates::Memarg),
+        WatOpcode::V128Load64Lane => (0xFD, 0x57, Immediates::MemargLane),
+        WatOpcode::V128Load64Splat => (0xFD, 0x0A, Immediates::Memarg),
+        WatOpcode::V128Load64Zero => (0xFD, 0x5D, Immediates::Memarg),
+        WatOpcode::V128Load8Lane => (0xFD, 0x54, Immediates::MemargLane),
+        WatOpcode::V128Load8Splat => (0xFD, 0x07, Immediates::Memarg),
+        WatOpcode::V128Load8x8S => (0xFD, 0x01, Immediates::Memarg),
+        WatOpcode::V128Load8x8U => (0xFD, 0x02, Immediates::Memarg),
+        WatOpcode::V128Not => (0xFD, 0x4D, Immediates::None),
+        WatOpcode::V128Or => (0xFD, 0x50, Immediates::None),
+        WatOpcode::V128Store => (0xFD, 0x0B, Immediates::Memarg),
+        WatOpcode::V128Store16Lane => (0xFD, 0x59, Immediates::MemargLane),
+        WatOpcode::V128Store32Lane => (0xFD, 0x5A, Immediates::MemargLane),
+        WatOpcode::V128Store64Lane => (0xFD, 0x5B, Immediates::MemargLane),
+        WatOpcode::V128Store8Lane => (0xFD, 0x58, Immediates::MemargLane),
+        WatOpcode::V128Xor => (0xFD, 0x51, Immediates::None),
+        WatOpcode::Unknown(_) => return None,
+    })
+}
+
+#[derive(Clone,PartialEq,Eq)]
+struct FuncType {
+    params: Vec<WatValType>,
+    results: Vec<WatValType>,
+}
+
+impl FuncType {
+    fn of(typeuse: &WatTypeuse) -> FuncType {
+        FuncType {
+            params: typeuse.params.iter().map(|param| param.valtype.clone()).collect(),
+            results: typeuse.results.iter().map(|result| result.valtype.clone()).collect(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Section {
+    count: u32,
+    bytes: Vec<u8>,
+}
+
+// A folded instruction waiting for its closing ')'.
+enum Frame {
+    // A plain instruction, written after its operands.
+    Deferred(Vec<u8>),
+    Block,
+    // The header is written when `(then` opens, after the condition.
+    If { header: Option<Vec<u8>>, label: OptionalID },
+    // `(then ...)` or `(else ...)`.
+    Clause,
+}
+
+// The function body or global initializer being encoded.
+#[derive(Default)]
+struct Body {
+    locals: BTreeMap<ID, u32>,
+    local_decls: Vec<u8>,
+    labels: Vec<OptionalID>,
+    frames: Vec<Frame>,
+    code: Vec<u8>,
+}
+
+struct Encoder {
+    resolver: IndexResolver,
+    types: Vec<FuncType>,
+    imports: Section,
+    funcs: Section,
+    tables: Section,
+    memories: Section,
+    globals: Section,
+    exports: Section,
+    elems: Section,
+    codes: Section,
+    datas: Section,
+    // Custom sections, each with its id and size, to go after the others.
+    customs: Vec<u8>,
+    func_count: u32,
+    table_count: u32,
+    memory_count: u32,
+    global_count: u32,
+    // The function index of the `(start ...)` field.
+    start: Option<u32>,
+    defined: bool,
+    uses_data_count: bool,
+    body: Option<Body>,
+}
+
+fn encode_error(kind: ErrorKind, message: String, position: WatPosition) -> WatParserError {
+    WatParserError {
+        kind,
+        message: message.into_boxed_str(),
+        start: position,
+        end: position,
+    }
+}
+
+fn interned_error(position: WatPosition) -> WatParserError {
+    encode_error(ErrorKind::Unsupported,
+                 String::from("interned ids cannot be encoded"),
+                 position)
+}
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_i64(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 && byte & 0x40 == 0 || value == -1 && byte & 0x40 != 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, section: &Section) {
+    if section.count == 0 {
+        return;
+    }
+    let mut count = Vec::new();
+    write_u32(&mut count, section.count);
+    out.push(id);
+    write_u32(out, (count.len() + section.bytes.len()) as u32);
+    out.extend_from_slice(&count);
+    out.extend_from_slice(&section.bytes);
+}
+
+fn valtype_byte(valtype: &WatValType) -> u8 {
+    match *valtype {
+        WatValType::I32 => 0x7F,
+        WatValType::I64 => 0x7E,
+        WatValType::F32 => 0x7D,
+        WatValType::F64 => 0x7C,
+        WatValType::V128 => 0x7B,
+        WatValType::Ref(ref reftype) => reftype_byte(reftype),
+    }
+}
+
+// `(ref ...)` types without a shorthand take more than a byte, and are
+// turned away by `typed_ref_position` before anything is encoded.
+fn reftype_byte(reftype: &WatRefType) -> u8 {
+    match *reftype {
+        WatRefType::FuncRef => 0x70,
+        WatRefType::ExternRef => 0x6F,
+        WatRefType::AnyRef => 0x6E,
+        WatRefType::EqRef => 0x6D,
+        WatRefType::I31Ref => 0x6C,
+        WatRefType::StructRef => 0x6B,
+        WatRefType::ArrayRef => 0x6A,
+        WatRefType::NullRef => 0x71,
+        WatRefType::Ref { .. } => unreachable!(),
+    }
+}
+
+// Where `event` uses a `(ref ...)` type that has no shorthand, if it
+// does.
+fn typed_ref_position(event: &WatParserState) -> Option<WatPosition> {
+    let reftype = |reftype: &WatRefType| matches!(*reftype, WatRefType::Ref { .. });
+    let valtype = |valtype: &WatValType| matches!(*valtype, WatValType::Ref(ref r) if reftype(r));
+    let typeuse = |typeuse: &WatTypeuse| {
+        typeuse.params.iter().any(|param| valtype(&param.valtype)) ||
+        typeuse.results.iter().any(|result| valtype(&result.valtype))
+    };
+    let (typed, position) = match *event {
+        WatParserState::Type { ref functype, position, .. } => (typeuse(functype), position),
+        WatParserState::Import { ref import, position, .. } => {
+            let typed = match *import {
+                WatImport::Func { typeuse: ref t, .. } => typeuse(t),
+                WatImport::Table { ref tabletype, .. } => reftype(&tabletype.reftype),
+                WatImport::Global { ref globaltype, .. } => valtype(&globaltype.valtype),
+                WatImport::Memory { .. } => false,
+            };
+            (typed, position)
+        }
+        WatParserState::StartFunc { typeuse: ref t, ref locals, position, .. } => {
+            (typeuse(t) || locals.iter().any(|local| valtype(&local.valtype)), position)
+        }
+        WatParserState::StartGlobal { ref globaltype, position, .. } => {
+            (valtype(&globaltype.valtype), position)
+        }
+        WatParserState::Table { ref tabletype, position, .. } => (reftype(&tabletype.reftype), position),
+        WatParserState::Elem { elems: WatElemList::Expressions(ref r, _), position, .. } => {
+            (reftype(r), position)
+        }
+        WatParserState::CodeOperator { ref args, position, .. } => {
+            let typed = args.iter().any(|arg| match *arg {
+                WatInstructionArg::HeapType(ref r) => reftype(r),
+                WatInstructionArg::Result(ref v) => valtype(v),
+                WatInstructionArg::Type(ref t) => typeuse(t),
+                _ => false,
+            });
+            (typed, position)
+        }
+        _ => return None,
+    };
+    if typed { Some(position) } else { None }
+}
+
+fn write_limits(out: &mut Vec<u8>, limits: &WatLimits, shared: bool) {
+    out.push(limits.max.is_some() as u8 | (shared as u8) << 1);
+    write_u32(out, limits.min);
+    if let Some(max) = limits.max {
+        write_u32(out, max);
+    }
+}
+
+// Runs of locals of the same type are declared together.
+fn write_local_decls(out: &mut Vec<u8>, valtypes: &[WatValType]) {
+    let mut runs: Vec<(u32, &WatValType)> = Vec::new();
+    for valtype in valtypes {
+        match runs.last_mut() {
+            Some(&mut (ref mut count, last)) if last == valtype => *count += 1,
+            _ => runs.push((1, valtype)),
+        }
+    }
+    write_u32(out, runs.len() as u32);
+    for (count, valtype) in runs {
+        write_u32(out, count);
+        out.push(valtype_byte(valtype));
+    }
+}
+
+// The log2 of the bytes accessed, from the name: `i64.load8_s` reads
+// one byte, `i64.atomic.rmw32.add_u` four and `i64.load` eight.
+fn natural_alignment(name: &[u8]) -> u32 {
+    if name.starts_with(b"v128.") {
+        return v128_alignment(&name[5..]);
+    }
+    let width: Vec<u8> = name.iter()
+        .skip(3)
+        .skip_while(|ch| !ch.is_ascii_digit())
+        .take_while(|ch| ch.is_ascii_digit())
+        .cloned()
+        .collect();
+    let bits = match &width[..] {
+        b"8" => 8,
+        b"16" => 16,
+        b"32" => 32,
+        b"64" => 64,
+        _ if name.starts_with(b"i64") || name.starts_with(b"f64") => 64,
+        _ => 32,
+    };
+    (bits / 8u32).trailing_zeros()
+}
+
+// `load8x8_s` reads eight bytes, `load16_splat` and `store16_lane` two,
+// `load` and `store` sixteen.
+fn v128_alignment(access: &[u8]) -> u32 {
+    let numbers: Vec<u32> = access.split(|ch| !ch.is_ascii_digit())
+        .filter(|digits| !digits.is_empty())
+        .map(|digits| digits.iter().fold(0, |n, digit| n * 10 + u32::from(digit - b'0')))
+        .collect();
+    let bits = match numbers[..] {
+        [] => 128,
+        [bits] => bits,
+        [bits, count, ..] => bits * count,
+    };
+    (bits / 8).trailing_zeros()
+}
+
+// Instructions name items by a bare `$id` or number.
+fn arg_index(arg: &WatInstructionArg) -> Option<WatIndex> {
+    match *arg {
+        WatInstructionArg::ID(ref id) => Some(WatIndex::Id(id.clone())),
+        WatInstructionArg::Symbol(symbol) => Some(WatIndex::Symbol(symbol)),
+        WatInstructionArg::Unsigned { ref value, .. } if value.len() <= 4 => {
+            Some(WatIndex::Num(data_to_u64(value) as u32))
+        }
+        WatInstructionArg::Function(ref index) => Some(index.clone()),
+        _ => None,
+    }
+}
+
+// The lane indices of `i8x16.shuffle` or of a `*_lane` instruction, which
+// come after any memory arguments.
+fn write_lanes(out: &mut Vec<u8>,
+               args: &[WatInstructionArg],
+               count: usize,
+               position: WatPosition)
+               -> Result<()> {
+    let lanes: Vec<u8> = args.iter().filter_map(int_value).map(|lane| lane as u8).collect();
+    if lanes.len() != count {
+        let message = format!("expected {} lane {}",
+                              count,
+                              if count == 1 { "index" } else { "indices" });
+        return Err(encode_error(ErrorKind::UnexpectedToken, message, position));
+    }
+    out.extend_from_slice(&lanes);
+    Ok(())
+}
+
+// The lanes of a v128 shape: 4 for `i32x4`.
+fn lane_count(shape: &[u8]) -> usize {
+    match shape {
+        b"i8x16" => 16,
+        b"i16x8" => 8,
+        b"i32x4" | b"f32x4" => 4,
+        _ => 2,
+    }
+}
+
+fn space_name(space: IndexSpace) -> &'static str {
+    match space {
+        IndexSpace::Type => "type",
+        IndexSpace::Func => "function",
+        IndexSpace::Table => "table",
+        IndexSpace::Memory => "memory",
+        IndexSpace::Global => "global",
+        IndexSpace::Elem => "element segment",
+        IndexSpace::Data => "data segment",
+    }
+}
+
+fn int_value(arg: &WatInstructionArg) -> Option<i64> {
+    match *arg {
+        WatInstructionArg::Unsigned { ref value, .. } => Some(data_to_u64(value) as i64),
+        WatInstructionArg::Signed { sign: WatSign::Negative, ref value, .. } => {
+            Some((data_to_u64(value) as i64).wrapping_neg())
+        }
+        WatInstructionArg::Signed { ref value, .. } => Some(data_to_u64(value) as i64),
+        _ => None,
+    }
+}
+
+// The bits of a float literal, or `None` when a number rounds to
+// infinity. NaN payloads must fit the significand.
+fn float_bits(float: &WatFloat, double: bool) -> Option<u64> {
+    let (sign_bit, exponent_bits, quiet_bit): (u64, u64, u64) = if double {
+        (1 << 63, 0x7FF << 52, 1 << 51)
+    } else {
+        (1 << 31, 0xFF << 23, 1 << 22)
+    };
+    let sign = |sign| if sign == WatSign::Negative { sign_bit } else { 0 };
+    match *float {
+        WatFloat::Number(..) => {
+            let text = float.to_string();
+            let bits = if double {
+                text.parse::<f64>().ok().filter(|value| value.is_finite())?.to_bits()
+            } else {
+                u64::from(text.parse::<f32>().ok().filter(|value| value.is_finite())?.to_bits())
+            };
+            Some(bits)
+        }
+        WatFloat::Inf(s) => Some(sign(s) | exponent_bits),
+        WatFloat::NaN(s, None) => Some(sign(s) | exponent_bits | quiet_bit),
+        WatFloat::NaN(s, Some(ref payload)) => {
+            let payload = data_to_u64(payload);
+            if payload == 0 || payload >= quiet_bit << 1 {
+                return None;
+            }
+            Some(sign(s) | exponent_bits | payload)
+        }
+    }
+}
+
+impl Encoder {
+    // Declared types take the first indices, so they are collected
+    // before anything refers to a type.
+    fn new(events: &[WatParserState]) -> Encoder {
+        let mut resolver = IndexResolver::new();
+        let mut types = Vec::new();
+        for event in events {
+            resolver.add_event(event);
+            if let WatParserState::Type { ref functype, .. } = *event {
+                types.push(FuncType::of(functype));
+            }
+        }
+        Encoder {
+            resolver,
+            types,
+            imports: Section::default(),
+            funcs: Section::default(),
+            tables: Section::default(),
+            memories: Section::default(),
+            globals: Section::default(),
+            exports: Section::default(),
+            elems: Section::default(),
+            codes: Section::default(),
+            datas: Section::default(),
+            customs: Vec::new(),
+            func_count: 0,
+            table_count: 0,
+            memory_count: 0,
+            global_count: 0,
+            start: None,
+            defined: false,
+            uses_data_count: false,
+            body: None,
+        }
+    }
+
+    fn index(&self, space: IndexSpace, index: &WatIndex, position: WatPosition) -> Result<u32> {
+        match *index {
+            WatIndex::Num(num) => Ok(num),
+            WatIndex::Id(ref id) => {
+                self.resolver.lookup(space, id).ok_or_else(|| {
+                    let message = format!("unknown {} {}",
+                                          space_name(space),
+                                          String::from_utf8_lossy(id));
+                    encode_error(ErrorKind::Validation, message, position)
+                })
+            }
+            WatIndex::Symbol(_) => Err(interned_error(position)),
+        }
+    }
+
+    // The index of the first argument that names an item of `space`.
+    fn item_index(&self,
+                  space: IndexSpace,
+                  args: &[WatInstructionArg],
+                  position: WatPosition)
+                  -> Result<u32> {
+        match args.first().and_then(arg_index) {
+            Some(index) => self.index(space, &index, position),
+            None => {
+                let message = format!("expected a {} index", space_name(space));
+                Err(encode_error(ErrorKind::UnexpectedToken, message, position))
+            }
+        }
+    }
+
+    // A typeuse with just params and results refers to the first type
+    // with the same signature, added if there is none.
+    fn type_index(&mut self, typeuse: &WatTypeuse, position: WatPosition) -> Result<u32> {
+        if let Some(index) = typeuse.index {
+            return Ok(index);
+        }
+        if let Some(ref id) = typeuse.id {
+            return self.index(IndexSpace::Type, &WatIndex::Id(id.clone()), position);
+        }
+        let functype = FuncType::of(typeuse);
+        let index = match self.types.iter().position(|t| *t == functype) {
+            Some(index) => index,
+            None => {
+                self.types.push(functype);
+                self.types.len() - 1
+            }
+        };
+        Ok(index as u32)
+    }
+
+    fn export(&mut self, name: &str, kind: u8, index: u32) {
+        write_bytes(&mut self.exports.bytes, name.as_bytes());
+        self.exports.bytes.push(kind);
+        write_u32(&mut self.exports.bytes, index);
+        self.exports.count += 1;
+    }
+
+    fn event(&mut self, event: &WatParserState) -> Result<()> {
+        if let Some(position) = typed_ref_position(event) {
+            let message = String::from("(ref ...) types cannot be encoded yet");
+            return Err(encode_error(ErrorKind::Unsupported, message, position));
+        }
+        match *event {
+            WatParserState::Error(ref err) => return Err(err.clone()),
+            WatParserState::Type { .. } => {}
+            WatParserState::Import {
+                ref modname,
+                ref fieldname,
+                ref import,
+                ref export_names,
+                position,
+            } => {
+                if self.defined {
+                    let message = String::from("imports must come before definitions");
+                    return Err(encode_error(ErrorKind::Validation, message, position));
+                }
+                let mut out = Vec::new();
+                write_bytes(&mut out, modname.as_bytes());
+                write_bytes(&mut out, fieldname.as_bytes());
+                let (kind, index) = match *import {
+                    WatImport::Func { ref typeuse, .. } => {
+                        out.push(0x00);
+                        write_u32(&mut out, self.type_index(typeuse, position)?);
+                        self.func_count += 1;
+                        (0x00, self.func_count - 1)
+                    }
+                    WatImport::Table { ref tabletype, .. } => {
+                        out.push(0x01);
+                        out.push(reftype_byte(&tabletype.reftype));
+                        write_limits(&mut out, &tabletype.limits, false);
+                        self.table_count += 1;
+                        (0x01, self.table_count - 1)
+                    }
+                    WatImport::Memory { ref memtype, .. } => {
+                        out.push(0x02);
+                        write_limits(&mut out, &memtype.limits, memtype.shared);
+                        self.memory_count += 1;
+                        (0x02, self.memory_count - 1)
+                    }
+                    WatImport::Global { ref globaltype, .. } => {
+                        out.push(0x03);
+                        out.push(valtype_byte(&globaltype.valtype));
+                        out.push(globaltype.mutable as u8);
+                        self.global_count += 1;
+                        (0x03, self.global_count - 1)
+                    }
+                };
+                for name in export_names {
+                    self.export(name, kind, index);
+                }
+                self.imports.bytes.append(&mut out);
+                self.imports.count += 1;
+            }
+            WatParserState::StartFunc {
+                ref export_names,
+                ref typeuse,
+                ref locals,
+                position,
+                ..
+            } => {
+                self.defined = true;
+                let type_index = self.type_index(typeuse, position)?;
+                write_u32(&mut self.funcs.bytes, type_index);
+                self.funcs.count += 1;
+                let index = self.func_count;
+                for name in export_names {
+                    self.export(name, 0x00, index);
+                }
+                self.func_count += 1;
+                let mut body = Body::default();
+                // With only `(type $t)`, the params come from the type
+                // and have no ids.
+                let by_type = typeuse.id.is_some() || typeuse.index.is_some();
+                let param_count = if by_type && typeuse.params.is_empty() {
+                    self.types.get(type_index as usize).map_or(0, |t| t.params.len())
+                } else {
+                    typeuse.params.len()
+                };
+                for (i, param) in typeuse.params.iter().enumerate() {
+                    if let Some(ref id) = param.id {
+                        body.locals.entry(id.clone()).or_insert(i as u32);
+                    }
+                }
+                for (i, local) in locals.iter().enumerate() {
+                    if let Some(ref id) = local.id {
+                        body.locals.entry(id.clone()).or_insert((param_count + i) as u32);
+                    }
+                }
+                let valtypes: Vec<WatValType> =
+                    locals.iter().map(|local| local.valtype.clone()).collect();
+                write_local_decls(&mut body.local_decls, &valtypes);
+                self.body = Some(body);
+            }
+            WatParserState::EndFunc { .. } => {
+                let mut body = self.body.take().unwrap_or_default();
+                let mut entry = body.local_decls;
+                entry.append(&mut body.code);
+                entry.push(0x0B);
+                write_bytes(&mut self.codes.bytes, &entry);
+                self.codes.count += 1;
+            }
+            WatParserState::StartGlobal {
+                ref export_names,
+                ref globaltype,
+                ..
+            } => {
+                self.defined = true;
+                self.globals.bytes.push(valtype_byte(&globaltype.valtype));
+                self.globals.bytes.push(globaltype.mutable as u8);
+                let index = self.global_count;
+                for name in export_names {
+                    self.export(name, 0x03, index);
+                }
+                self.global_count += 1;
+                self.body = Some(Body::default());
+            }
+            WatParserState::EndGlobal { .. } => {
+                let mut body = self.body.take().unwrap_or_default();
+                self.globals.bytes.append(&mut body.code);
+                self.globals.bytes.push(0x0B);
+                self.globals.count += 1;
+            }
+            WatParserState::Table { ref export_names, ref tabletype, .. } => {
+                self.defined = true;
+                self.tables.bytes.push(reftype_byte(&tabletype.reftype));
+                write_limits(&mut self.tables.bytes, &tabletype.limits, false);
+                self.tables.count += 1;
+                let index = self.table_count;
+                for name in export_names {
+                    self.export(name, 0x01, index);
+                }
+                self.table_count += 1;
+            }
+            WatParserState::Memory { ref export_names, ref memtype, .. } => {
+                self.defined = true;
+                write_limits(&mut self.memories.bytes, &memtype.limits, memtype.shared);
+                self.memories.count += 1;
+                let index = self.memory_count;
+                for name in export_names {
+                    self.export(name, 0x02, index);
+                }
+                self.memory_count += 1;
+            }
+            WatParserState::Export {
+                ref name,
+                kind,
+                ref index,
+                position,
+            } => {
+                let (space, byte) = match kind {
+                    WatExportKind::Func => (IndexSpace::Func, 0x00),
+                    WatExportKind::Table => (IndexSpace::Table, 0x01),
+                    WatExportKind::Memory => (IndexSpace::Memory, 0x02),
+                    WatExportKind::Global => (IndexSpace::Global, 0x03),
+                };
+                let index = self.index(space, index, position)?;
+                self.export(name, byte, index);
+            }
+            WatParserState::Start { ref func, position } => {
+                self.start = Some(self.index(IndexSpace::Func, func, position)?);
+            }
+            WatParserState::Elem {
+                ref kind,
+                ref elems,
+                position,
+                ..
+            } => {
+                let mut out = Vec::new();
+                self.elem(&mut out, kind, elems, position)?;
+                self.elems.bytes.append(&mut out);
+                self.elems.count += 1;
+            }
+            WatParserState::Data {
+                ref kind,
+                ref data,
+                position,
+                ..
+            } => {
+                let mut out = Vec::new();
+                if let WatDataKind::Active { ref memory, ref offset } = *kind {
+                    match self.index(IndexSpace::Memory, memory, position)? {
+                        0 => out.push(0x00),
+                        memory => {
+                            out.push(0x02);
+                            write_u32(&mut out, memory);
+                        }
+                    }
+                    self.const_expr(&mut out, offset, position)?;
+                } else {
+                    out.push(0x01);
+                }
+                write_bytes(&mut out, data);
+                self.datas.bytes.append(&mut out);
+                self.datas.count += 1;
+            }
+            WatParserState::CodeOperator {
+                ref instruction,
+                ref opcode,
+                ref args,
+                group,
+                position,
+                ..
+            } => {
+                let mut body = self.body.take().unwrap_or_default();
+                let result = self.code_operator(&mut body, instruction, opcode, args, group, position);
+                self.body = Some(body);
+                result?;
+            }
+            WatParserState::CodeOperatorEnd { .. } => {
+                if let Some(ref mut body) = self.body {
+                    match body.frames.pop() {
+                        Some(Frame::Deferred(mut bytes)) => body.code.append(&mut bytes),
+                        Some(Frame::Block) => {
+                            body.code.push(0x0B);
+                            body.labels.pop();
+                        }
+                        // An `if` without `(then ...)` is empty.
+                        Some(Frame::If { header: Some(mut header), .. }) => {
+                            body.code.append(&mut header);
+                            body.code.push(0x0B);
+                        }
+                        Some(Frame::If { header: None, .. }) => {
+                            body.code.push(0x0B);
+                            body.labels.pop();
+                        }
+                        Some(Frame::Clause) | None => {}
+                    }
+                }
+            }
+            WatParserState::CustomSection { ref name, ref data, .. } => {
+                let mut section = Vec::new();
+                write_bytes(&mut section, name.as_bytes());
+                section.extend_from_slice(data);
+                self.customs.push(0);
+                write_bytes(&mut self.customs, &section);
+            }
+            WatParserState::Initial |
+            WatParserState::End |
+            WatParserState::StartModule { .. } |
+            WatParserState::EndModule => {}
+        }
+        Ok(())
+    }
+
+    // Folded instructions are written in stack order: the operands,
+    // then the instruction; blocks are written as they open.
+    fn code_operator(&mut self,
+                     body: &mut Body,
+                     instruction: &[u8],
+                     opcode: &WatOpcode,
+                     args: &[WatInstructionArg],
+                     group: bool,
+                     position: WatPosition)
+                     -> Result<()> {
+        let label = args.first().and_then(|arg| match *arg {
+                                               WatInstructionArg::ID(ref id) => Some(id.clone()),
+                                               _ => None,
+                                           });
+        match *opcode {
+            WatOpcode::If if group => {
+                let header = self.instruction(body, opcode, args, position)?;
+                body.frames.push(Frame::If {
+                                     header: Some(header),
+                                     label,
+                                 });
+            }
+            WatOpcode::Block | WatOpcode::Loop | WatOpcode::If => {
+                let mut header = self.instruction(body, opcode, args, position)?;
+                body.code.append(&mut header);
+                body.labels.push(label);
+                if group {
+                    body.frames.push(Frame::Block);
+                }
+            }
+            WatOpcode::Else if group => {
+                match body.frames.last() {
+                    Some(&Frame::If { header: None, .. }) => {}
+                    _ => return Err(self.clause_error(instruction, position)),
+                }
+                body.code.push(0x05);
+                body.frames.push(Frame::Clause);
+            }
+            WatOpcode::Else => body.code.push(0x05),
+            WatOpcode::End => {
+                body.code.push(0x0B);
+                body.labels.pop();
+            }
+            WatOpcode::Unknown(_) if group && instruction == b"then" => {
+                match body.frames.last_mut() {
+                    Some(&mut Frame::If { ref mut header, ref mut label }) if header.is_some() => {
+                        body.code.append(header.as_mut().unwrap());
+                        *header = None;
+                        body.labels.push(label.take());
+                    }
+                    _ => return Err(self.clause_error(instruction, position)),
+                }
+                body.frames.push(Frame::Clause);
+            }
+            _ => {
+                let mut bytes = self.instruction(body, opcode, args, position)?;
+                if group {
+                    body.frames.push(Frame::Deferred(bytes));
+                } else {
+                    body.code.append(&mut bytes);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn clause_error(&self, instruction: &[u8], position: WatPosition) -> WatParserError {
+        let message = format!("unexpected '{}' outside of a folded 'if'",
+                              String::from_utf8_lossy(instruction));
+        encode_error(ErrorKind::UnexpectedToken, message, position)
+    }
+
+    fn const_expr(&mut self,
+                  out: &mut Vec<u8>,
+                  expr: &ConstExpr,
+                  position: WatPosition)
+                  -> Result<()> {
+        let body = Body::default();
+        for instruction in expr {
+            let opcode = WatOpcode::from_bytes(&instruction.instruction);
+            let mut bytes = self.instruction(&body, &opcode, &instruction.args, position)?;
+            out.append(&mut bytes);
+        }
+        out.push(0x0B);
+        Ok(())
+    }
+
+    // Picks the most compact of the eight segment layouts.
+    fn elem(&mut self,
+            out: &mut Vec<u8>,
+            kind: &WatElemKind,
+            elems: &WatElemList,
+            position: WatPosition)
+            -> Result<()> {
+        let (expressions, elem_type) = match *elems {
+            WatElemList::Functions(_) => (0, 0x00),
+            WatElemList::Expressions(ref reftype, _) => (4, reftype_byte(reftype)),
+        };
+        match *kind {
+            WatElemKind::Active { ref table, ref offset } => {
+                let table = match *table {
+                    Some(ref table) => self.index(IndexSpace::Table, table, position)?,
+                    None => 0,
+                };
+                if table == 0 && elem_type != 0x6F {
+                    out.push(expressions);
+                    self.const_expr(out, offset, position)?;
+                } else {
+                    out.push(expressions | 2);
+                    write_u32(out, table);
+                    self.const_expr(out, offset, position)?;
+                    out.push(elem_type);
+                }
+            }
+            WatElemKind::Passive => {
+                out.push(expressions | 1);
+                out.push(elem_type);
+            }
+            WatElemKind::Declarative => {
+                out.push(expressions | 3);
+                out.push(elem_type);
+            }
+        }
+        match *elems {
+            WatElemList::Functions(ref funcs) => {
+                write_u32(out, funcs.len() as u32);
+                for func in funcs {
+                    write_u32(out, self.index(IndexSpace::Func, func, position)?);
+                }
+            }
+            WatElemList::Expressions(_, ref exprs) => {
+                write_u32(out, exprs.len() as u32);
+                for expr in exprs {
+                    self.const_expr(out, expr, position)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // A label is a `$id` of an enclosing block or a relative depth.
+    fn label(&self, body: &Body, arg: &WatInstructionArg, position: WatPosition) -> Result<u32> {
+        match arg_index(arg) {
+            Some(WatIndex::Num(depth)) => Ok(depth),
+            Some(WatIndex::Id(ref id)) => {
+                match body.labels.iter().rev().position(|label| label.as_ref() == Some(id)) {
+                    Some(depth) => Ok(depth as u32),
+                    None => {
+                        let message = format!("unknown label {}", String::from_utf8_lossy(id));
+                        Err(encode_error(ErrorKind::Validation, message, position))
+                    }
+                }
+            }
+            Some(WatIndex::Symbol(_)) => Err(interned_error(position)),
+            None => {
+                let message = String::from("expected a label");
+                Err(encode_error(ErrorKind::UnexpectedToken, message, position))
+            }
+        }
+    }
+
+    fn local(&self, body: &Body, args: &[WatInstructionArg], position: WatPosition) -> Result<u32> {
+        match args.first().and_then(arg_index) {
+            Some(WatIndex::Num(index)) => Ok(index),
+            Some(WatIndex::Id(ref id)) => {
+                body.locals.get(id).cloned().ok_or_else(|| {
+                    let message = format!("unknown local {}", String::from_utf8_lossy(id));
+                    encode_error(ErrorKind::Validation, message, position)
+                })
+            }
+            Some(WatIndex::Symbol(_)) => Err(interned_error(position)),
+            None => {
+                let message = String::from("expected a local index");
+                Err(encode_error(ErrorKind::UnexpectedToken, message, position))
+            }
+        }
+    }
+
+    // The table or memory indices among `args`, in order.
+    fn indices(&self,
+               space: IndexSpace,
+               args: &[WatInstructionArg],
+               position: WatPosition)
+               -> Result<Vec<u32>> {
+        let mut indices = Vec::new();
+        for arg in args {
+            match (space, arg) {
+                (IndexSpace::Table, &WatInstructionArg::Table(ref index)) |
+                (IndexSpace::Memory, &WatInstructionArg::Memory(ref index)) |
+                (IndexSpace::Data, &WatInstructionArg::Data(ref index)) |
+                (IndexSpace::Elem, &WatInstructionArg::Elem(ref index)) => {
+                    indices.push(self.index(space, index, position)?)
+                }
+                _ => {}
+            }
+        }
+        Ok(indices)
+    }
+
+    fn write_blocktype(&mut self,
+                       out: &mut Vec<u8>,
+                       args: &[WatInstructionArg],
+                       position: WatPosition)
+                       -> Result<()> {
+        let typeuse = args.iter()
+            .filter_map(|arg| match *arg {
+                            WatInstructionArg::Type(ref typeuse) => Some(typeuse),
+                            _ => None,
+                        })
+            .next();
+        match typeuse {
+            None => out.push(0x40),
+            Some(typeuse) if typeuse.id.is_none() && typeuse.index.is_none() &&
+                             typeuse.params.is_empty() &&
+                             typeuse.results.len() <= 1 => {
+                out.push(typeuse.results.first().map_or(0x40, |result| valtype_byte(&result.valtype)))
+            }
+            Some(typeuse) => {
+                let index = self.type_index(typeuse, position)?;
+                write_i64(out, i64::from(index));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_memarg(&self,
+                    out: &mut Vec<u8>,
+                    opcode: &WatOpcode,
+                    args: &[WatInstructionArg],
+                    position: WatPosition)
+                    -> Result<()> {
+        let mut align = natural_alignment(opcode.name());
+        let mut offset = 0;
+        for arg in args {
+            match *arg {
+                WatInstructionArg::Flags(ref keyword, value) if keyword.starts_with(b"offset=") => {
+                    offset = value
+                }
+                WatInstructionArg::Flags(ref keyword, value) if keyword.starts_with(b"align=") => {
+                    if !value.is_power_of_two() {
+                        let message = format!("alignment {} is not a power of two", value);
+                        return Err(encode_error(ErrorKind::Validation, message, position));
+                    }
+                    align = value.trailing_zeros();
+                }
+                WatInstructionArg::Flags(_, value) => align = value,
+                _ => {}
+            }
+        }
+        // Other memories than 0 are flagged in the alignment field.
+        match self.indices(IndexSpace::Memory, args, position)?.first() {
+            Some(&memory) if memory != 0 => {
+                write_u32(out, align | 0x40);
+                write_u32(out, memory);
+            }
+            _ => write_u32(out, align),
+        }
+        write_u32(out, offset);
+        Ok(())
+    }
+
+    fn write_float(&self,
+                   out: &mut Vec<u8>,
+                   args: &[WatInstructionArg],
+                   double: bool,
+                   position: WatPosition)
+                   -> Result<()> {
+        let float = args.iter()
+            .filter_map(|arg| match *arg {
+                            WatInstructionArg::Float(ref float) => Some(float),
+                            _ => None,
+                        })
+            .next();
+        let float = match float {
+            Some(float) => float,
+            None => {
+                let message = String::from("expected a float");
+                return Err(encode_error(ErrorKind::UnexpectedToken, message, position));
+            }
+        };
+        let bits = match float_bits(float, double) {
+            Some(bits) => bits,
+            None => {
+                let message = format!("constant out of range for {}",
+                                      if double { "f64" } else { "f32" });
+                return Err(encode_error(ErrorKind::OutOfRange, message, position));
+            }
+        };
+        if double {
+            out.extend_from_slice(&bits.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(bits as u32).to_le_bytes());
+        }
+        Ok(())
+    }
+
+    // The sixteen bytes of a `v128.const`, from its shape and lanes.
+    fn write_v128(&self, out: &mut Vec<u8>, args: &[WatInstructionArg], position: WatPosition) -> Result<()> {
+        let lane_bytes = match args.first() {
+            Some(WatInstructionArg::Shape(shape)) => 16 / lane_count(shape),
+            _ => {
+                let message = String::from("expected a v128 shape");
+                return Err(encode_error(ErrorKind::UnexpectedToken, message, position));
+            }
+        };
+        for arg in &args[1..] {
+            let bits = match *arg {
+                WatInstructionArg::Float(ref float) => {
+                    match float_bits(float, lane_bytes == 8) {
+                        Some(bits) => bits,
+                        None => {
+                            let message = format!("constant out of range for {}",
+                                                  if lane_bytes == 8 { "f64" } else { "f32" });
+                            return Err(encode_error(ErrorKind::OutOfRange, message, position));
+                        }
+                    }
+                }
+                _ => int_value(arg).unwrap_or(0) as u64,
+            };
+            out.extend_from_slice(&bits.to_le_bytes()[..lane_bytes]);
+        }
+        Ok(())
+    }
+
+    // Encodes one instruction with its immediates. Blocks get their
+    // header only.
+    fn instruction(&mut self,
+                   body: &Body,
+                   opcode: &WatOpcode,
+                   args: &[WatInstructionArg],
+                   position: WatPosition)
+                   -> Result<Vec<u8>> {
+        let (prefix, code, immediates) = match encoding(opcode) {
+            Some(encoding) => encoding,
+            None => {
+                let message = format!("cannot encode instruction '{}'",
+                                      String::from_utf8_lossy(opcode.name()));
+                return Err(encode_error(ErrorKind::Unsupported, message, position));
+            }
+        };
+        let mut out = Vec::new();
+        for arg in args {
+            if let WatInstructionArg::Result(ref valtype) = *arg {
+                // A typed `select`.
+                out.extend_from_slice(&[0x1C, 0x01, valtype_byte(valtype)]);
+                return Ok(out);
+            }
+        }
+        if prefix != 0 {
+            out.push(prefix);
+            write_u32(&mut out, code);
+        } else {
+            out.push(code as u8);
+        }
+        match immediates {
+            Immediates::None | Immediates::Select => {}
+            Immediates::BlockType => self.write_blocktype(&mut out, args, position)?,
+            Immediates::Label | Immediates::LabelTable => {
+                if args.is_empty() {
+                    let message = String::from("expected a label");
+                    return Err(encode_error(ErrorKind::UnexpectedToken, message, position));
+                }
+                // `br_table` lists its targets before the default.
+                if let Immediates::LabelTable = immediates {
+                    write_u32(&mut out, (args.len() - 1) as u32);
+                }
+                for arg in args {
+                    write_u32(&mut out, self.label(body, arg, position)?);
+                }
+            }
+            Immediates::Func => {
+                write_u32(&mut out, self.item_index(IndexSpace::Func, args, position)?)
+            }
+            Immediates::CallIndirect => {
+                let typeuse = args.iter()
+                    .filter_map(|arg| match *arg {
+                                    WatInstructionArg::Type(ref typeuse) => Some(typeuse.clone()),
+                                    _ => None,
+                                })
+                    .next()
+                    .unwrap_or_default();
+                write_u32(&mut out, self.type_index(&typeuse, position)?);
+                let tables = self.indices(IndexSpace::Table, args, position)?;
+                write_u32(&mut out, tables.first().cloned().unwrap_or(0));
+            }
+            Immediates::Local => write_u32(&mut out, self.local(body, args, position)?),
+            Immediates::Global => {
+                write_u32(&mut out, self.item_index(IndexSpace::Global, args, position)?)
+            }
+            Immediates::Table | Immediates::Memory => {
+                let space = match immediates {
+                    Immediates::Table => IndexSpace::Table,
+                    _ => IndexSpace::Memory,
+                };
+                let indices = self.indices(space, args, position)?;
+                write_u32(&mut out, indices.first().cloned().unwrap_or(0));
+            }
+            Immediates::Memarg => self.write_memarg(&mut out, opcode, args, position)?,
+            Immediates::I32 | Immediates::I64 => {
+                let value = match args.first().and_then(int_value) {
+                    Some(value) => value,
+                    None => {
+                        let message = String::from("expected an integer");
+                        return Err(encode_error(ErrorKind::UnexpectedToken, message, position));
+                    }
+                };
+                match immediates {
+                    Immediates::I32 => write_i64(&mut out, i64::from(value as i32)),
+                    _ => write_i64(&mut out, value),
+                }
+            }
+            Immediates::F32 => self.write_float(&mut out, args, false, position)?,
+            Immediates::F64 => self.write_float(&mut out, args, true, position)?,
+            Immediates::MemoryInit | Immediates::TableInit => {
+                let (segment, space) = match immediates {
+                    Immediates::MemoryInit => (IndexSpace::Data, IndexSpace::Memory),
+                    _ => (IndexSpace::Elem, IndexSpace::Table),
+                };
+                let segments = self.indices(segment, args, position)?;
+                write_u32(&mut out, segments.first().cloned().unwrap_or(0));
+                let indices = self.indices(space, args, position)?;
+                write_u32(&mut out, indices.first().cloned().unwrap_or(0));
+                if let Immediates::MemoryInit = immediates {
+                    self.uses_data_count = true;
+                }
+            }
+            Immediates::MemoryCopy | Immediates::TableCopy => {
+                let space = match immediates {
+                    Immediates::MemoryCopy => IndexSpace::Memory,
+                    _ => IndexSpace::Table,
+                };
+                let indices = self.indices(space, args, position)?;
+                write_u32(&mut out, indices.first().cloned().unwrap_or(0));
+                write_u32(&mut out, indices.get(1).cloned().unwrap_or(0));
+            }
+            Immediates::Data | Immediates::Elem => {
+                let space = match immediates {
+                    Immediates::Data => IndexSpace::Data,
+                    _ => IndexSpace::Elem,
+                };
+                let indices = self.indices(space, args, position)?;
+                write_u32(&mut out, indices.first().cloned().unwrap_or(0));
+                if let Immediates::Data = immediates {
+                    self.uses_data_count = true;
+                }
+            }
+            Immediates::HeapType => {
+                for arg in args {
+                    if let WatInstructionArg::HeapType(ref reftype) = *arg {
+                        out.push(reftype_byte(reftype));
+                    }
+                }
+            }
+            Immediates::Zero => out.push(0x00),
+            Immediates::V128Const => self.write_v128(&mut out, args, position)?,
+            Immediates::Shuffle => write_lanes(&mut out, args, 16, position)?,
+            Immediates::Lane => write_lanes(&mut out, args, 1, position)?,
+            Immediates::MemargLane => {
+                self.write_memarg(&mut out, opcode, args, position)?;
+                write_lanes(&mut out, args, 1, position)?;
+            }
+        }
+        Ok(out)
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut types = Section::default();
+        for functype in &self.types {
+            types.bytes.push(0x60);
+            write_u32(&mut types.bytes, functype.params.len() as u32);
+            types.bytes.extend(functype.params.iter().map(valtype_byte));
+            write_u32(&mut types.bytes, functype.results.len() as u32);
+            types.bytes.extend(functype.results.iter().map(valtype_byte));
+            types.count += 1;
+        }
+        let mut out = b"\0asm\x01\0\0\0".to_vec();
+        write_section(&mut out, 1, &types);
+        write_section(&mut out, 2, &self.imports);
+        write_section(&mut out, 3, &self.funcs);
+        write_section(&mut out, 4, &self.tables);
+        write_section(&mut out, 5, &self.memories);
+        write_section(&mut out, 6, &self.globals);
+        write_section(&mut out, 7, &self.exports);
+        if let Some(start) = self.start {
+            out.push(8);
+            let mut index = Vec::new();
+            write_u32(&mut index, start);
+            write_bytes(&mut out, &index);
+        }
+        write_section(&mut out, 9, &self.elems);
+        if self.uses_data_count {
+            out.push(12);
+            let mut count = Vec::new();
+            write_u32(&mut count, self.datas.count);
+            write_bytes(&mut out, &count);
+        }
+        write_section(&mut out, 10, &self.codes);
+        write_section(&mut out, 11, &self.datas);
+        out.extend_from_slice(&self.customs);
+        out
+    }
+}
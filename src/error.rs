@@ -0,0 +1,321 @@
+use core::fmt;
+use core::result;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::error::Error;
+use lexer::WatPosition;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Lexical,
+    UnexpectedToken,
+    InvalidLiteral,
+    OutOfRange,
+    Unsupported,
+    /// Well formed text that breaks a rule of the module structure, such
+    /// as an unfolded `else` with no `if`, a `block` left without its
+    /// `end` or an `end $label` naming another block.
+    ///
+    /// ```
+    /// use wasmtextparser::{ErrorKind, WatParser};
+    ///
+    /// fn first_error(source: &[u8]) -> Option<String> {
+    ///     let err = WatParser::new(source).events().find_map(|event| event.err())?;
+    ///     assert_eq!(err.kind, ErrorKind::Validation);
+    ///     Some(format!("{}", err))
+    /// }
+    ///
+    /// assert_eq!(first_error(b"(module (func nop else))").unwrap(), "1:19: else without if");
+    /// assert_eq!(first_error(b"(module (func i32.const 1 if nop))").unwrap(),
+    ///            "1:27: unclosed block at end of function");
+    /// assert_eq!(first_error(b"(module (func (result i32)\n\
+    ///                             i32.const 1 if (result i32) i32.const 2 else i32.const 3 end))"),
+    ///            None);
+    ///
+    /// assert_eq!(first_error(b"(module (func block $a nop end $a))"), None);
+    /// assert_eq!(first_error(b"(module (func block $a nop end))"), None);
+    /// assert_eq!(first_error(b"(module (func block $a nop end $b))").unwrap(),
+    ///            "1:32: mismatched block label '$b'");
+    /// ```
+    Validation,
+}
+
+/// Error reported by both the lexer and the parser.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatParserError {
+    pub kind: ErrorKind,
+    pub message: Box<str>,
+    pub start: WatPosition,
+    pub end: WatPosition,
+}
+
+impl fmt::Display for WatParserError {
+    // Columns are stored 0-based but reported 1-based, like most editors.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}:{}: {}",
+               self.start.line,
+               self.start.column + 1,
+               self.message)
+    }
+}
+
+// ANSI escapes used by `diagnostic` with color on.
+const STYLE_ERROR: &str = "\x1b[1;31m";
+const STYLE_MESSAGE: &str = "\x1b[1m";
+const STYLE_GUTTER: &str = "\x1b[1;34m";
+const STYLE_RESET: &str = "\x1b[0m";
+
+// Excerpt lines longer than this many characters are clipped to the
+// marker and `EXCERPT_CONTEXT` characters either side of it, with a
+// marker of at most `MAX_MARKER_WIDTH`.
+const MAX_EXCERPT_WIDTH: usize = 120;
+const EXCERPT_CONTEXT: usize = 40;
+const MAX_MARKER_WIDTH: usize = 40;
+
+impl WatParserError {
+    /// Formats the error with the offending source line and a caret
+    /// (followed by tildes for multi-character spans) under the token.
+    pub fn display_with_source(&self, source: &[u8]) -> String {
+        self.render("", source, false)
+    }
+
+    /// Formats the error for a terminal, like rustc does: the message,
+    /// `path:line:column`, and the excerpt from `source_excerpt`. With
+    /// `color`, ANSI escapes pick out the message, gutter and marker.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParser;
+    ///
+    /// let source = b"(module\n  (func $f\n    nop";
+    /// let err = WatParser::new(source).events().last().unwrap().unwrap_err();
+    /// assert_eq!(err.diagnostic("f.wat", source, false),
+    ///            "error: expected ')' while parsing instruction, found end of input\n \
+    ///             --> f.wat:2:3\n  |\n\
+    ///             2 |   (func $f\n  |   ^~~~~~~~\n\
+    ///             3 |     nop\n  |     ~~^");
+    /// ```
+    pub fn diagnostic(&self, path: &str, source: &[u8], color: bool) -> String {
+        self.render(&format!("{}:", path), source, color)
+    }
+
+    fn render(&self, location: &str, source: &[u8], color: bool) -> String {
+        format!("{}{}\n{}{} {}{}:{}\n{}",
+                style("error", STYLE_ERROR, color),
+                style(&format!(": {}", self.message), STYLE_MESSAGE, color),
+                " ".repeat(self.gutter_width()),
+                style("-->", STYLE_GUTTER, color),
+                location,
+                self.start.line,
+                self.start.column + 1,
+                self.excerpt(source, color))
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.end.line > self.start.line
+    }
+
+    fn gutter_width(&self) -> usize {
+        let last = if self.is_multiline() { self.end.line } else { self.start.line };
+        last.to_string().len()
+    }
+
+    /// The offending source line with the span marked under it, without
+    /// the message. A span over several lines also shows the line it
+    /// ends on, marked up to its end. A line over 120 characters is cut
+    /// down to the marked part and 40 characters either side, with
+    /// `...` where it was cut.
+    pub fn source_excerpt(&self, source: &[u8]) -> String {
+        self.excerpt(source, false)
+    }
+
+    fn excerpt(&self, source: &[u8], color: bool) -> String {
+        let gutter = " ".repeat(self.gutter_width());
+        let bar = style("|", STYLE_GUTTER, color);
+        let (line_start, line_end) = line_bounds(source, self.start);
+        let start = self.start.position.min(line_end).max(line_start);
+        let end = if self.is_multiline() {
+            line_end
+        } else {
+            self.end.position.min(line_end).max(start)
+        };
+        let width = String::from_utf8_lossy(&source[start..end]).chars().count();
+        let mut marker = String::from("^");
+        for _ in 1..width {
+            marker.push('~');
+        }
+        let mut excerpt = format!("{} {}\n{}",
+                                  gutter,
+                                  bar,
+                                  self.excerpt_line(source,
+                                                    self.start.line,
+                                                    (line_start, line_end),
+                                                    start,
+                                                    &marker,
+                                                    color));
+        if !self.is_multiline() {
+            return excerpt;
+        }
+        if self.end.line > self.start.line + 1 {
+            excerpt.push_str("\n...");
+        }
+        // The last line is marked from its first non-blank character, or
+        // just at the end when that is where it starts.
+        let (line_start, line_end) = line_bounds(source, self.end);
+        let end = self.end.position.min(line_end).max(line_start);
+        let first = source[line_start..end]
+            .iter()
+            .position(|&ch| ch != b' ' && ch != b'\t')
+            .map_or(end, |offset| line_start + offset);
+        let width = String::from_utf8_lossy(&source[first..end]).chars().count();
+        let mut marker = String::new();
+        for _ in 1..width {
+            marker.push('~');
+        }
+        marker.push('^');
+        excerpt.push('\n');
+        excerpt.push_str(&self.excerpt_line(source,
+                                            self.end.line,
+                                            (line_start, line_end),
+                                            first,
+                                            &marker,
+                                            color));
+        excerpt
+    }
+
+    // A numbered source line and the marker under it, starting at `at`.
+    fn excerpt_line(&self,
+                    source: &[u8],
+                    number: usize,
+                    (line_start, line_end): (usize, usize),
+                    at: usize,
+                    marker: &str,
+                    color: bool)
+                    -> String {
+        let line = String::from_utf8_lossy(&source[line_start..line_end]);
+        let line_width = line.chars().count();
+        let mut column = String::from_utf8_lossy(&source[line_start..at]).chars().count();
+        let mut marker = marker;
+        // A long line is cut down to the marker and some context on
+        // either side, and a long marker to the end that has the caret.
+        let (from, to) = if line_width > MAX_EXCERPT_WIDTH {
+            let marker_width = marker.chars().count();
+            if marker_width > MAX_MARKER_WIDTH {
+                if marker.starts_with('^') {
+                    marker = &marker[..MAX_MARKER_WIDTH];
+                } else {
+                    marker = &marker[marker_width - MAX_MARKER_WIDTH..];
+                    column += marker_width - MAX_MARKER_WIDTH;
+                }
+            }
+            let from = column.saturating_sub(EXCERPT_CONTEXT);
+            (from, (column + marker.len() + EXCERPT_CONTEXT).min(line_width))
+        } else {
+            (0, line_width)
+        };
+        let mut clipped = String::new();
+        if from > 0 {
+            clipped.push_str("...");
+        }
+        clipped.extend(line.chars().skip(from).take(to - from));
+        if to < line_width {
+            clipped.push_str("...");
+        }
+        // Pad with the line's own tabs so the caret lines up in any
+        // editor, and count characters rather than bytes.
+        let mut padding = String::from(if from > 0 { "   " } else { "" });
+        padding.extend(line.chars()
+                           .skip(from)
+                           .take(column - from)
+                           .map(|ch| if ch == '\t' { '\t' } else { ' ' }));
+        let width = self.gutter_width();
+        let bar = style("|", STYLE_GUTTER, color);
+        format!("{} {} {}\n{} {} {}{}",
+                style(&format!("{:>1$}", number, width), STYLE_GUTTER, color),
+                bar,
+                clipped,
+                " ".repeat(width),
+                bar,
+                padding,
+                style(marker, STYLE_ERROR, color))
+    }
+}
+
+// The start and end of the line `position` is on, without its line
+// break, clamped to `source`.
+fn line_bounds(source: &[u8], position: WatPosition) -> (usize, usize) {
+    let line_start = position.position.saturating_sub(position.column).min(source.len());
+    let line_end = source[line_start..]
+        .iter()
+        .position(|&ch| ch == b'\n')
+        .map_or(source.len(), |len| line_start + len);
+    let line_end = if line_end > line_start && source[line_end - 1] == b'\r' {
+        line_end - 1
+    } else {
+        line_end
+    };
+    (line_start, line_end)
+}
+
+fn style(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("{}{}{}", style, text, STYLE_RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for WatParserError {}
+
+pub type Result<T> = result::Result<T, WatParserError>;
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use wat::WatParser;
+
+    fn first_error(source: &[u8]) -> super::WatParserError {
+        WatParser::new(source).events().find_map(|event| event.err()).unwrap()
+    }
+
+    #[test]
+    fn long_lines_are_clipped() {
+        // A narrow span deep in a long line keeps 40 characters either side.
+        let mut source = Vec::from(&b"(module"[..]);
+        source.resize(100_000, b' ');
+        source.extend_from_slice(b"(memory x)");
+        source.resize(200_000, b' ');
+        source.push(b')');
+        let err = first_error(&source);
+        assert_eq!(err.source_excerpt(&source),
+                   format!("  |\n1 | ...{}(memory x){}...\n  | {}^",
+                           " ".repeat(32),
+                           " ".repeat(39),
+                           " ".repeat(43)));
+
+        // A long span keeps the end with its caret, on each line.
+        let mut source = Vec::from(&b"(module (func"[..]);
+        source.resize(200_000, b' ');
+        source.extend_from_slice(b"\nnop");
+        source.resize(400_000, b' ');
+        source.extend_from_slice(b"nop");
+        let err = first_error(&source);
+        assert_eq!(err.source_excerpt(&source),
+                   format!("  |\n\
+                            1 | (module (func{}...\n  |         ^{}\n\
+                            2 | ...{}nop\n  | {}{}^",
+                           " ".repeat(75),
+                           "~".repeat(39),
+                           " ".repeat(77),
+                           " ".repeat(43),
+                           "~".repeat(39)));
+        assert!(err.diagnostic("f.wat", &source, true).len() < 1000);
+    }
+}
@@ -0,0 +1,54 @@
+/// A post-MVP proposal that some syntax depends on.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub(crate) enum Proposal {
+    Threads,
+    Simd,
+    ReferenceTypes,
+    BulkMemory,
+    SignExtension,
+    SaturatingFloatToInt,
+    TailCall,
+    Gc,
+}
+
+impl Proposal {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Proposal::Threads => "threads",
+            Proposal::Simd => "simd",
+            Proposal::ReferenceTypes => "reference types",
+            Proposal::BulkMemory => "bulk memory",
+            Proposal::SignExtension => "sign extension",
+            Proposal::SaturatingFloatToInt => "saturating float-to-int",
+            Proposal::TailCall => "tail call",
+            Proposal::Gc => "gc",
+        }
+    }
+}
+
+const SIMD_PREFIXES: &[&[u8]] = &[b"v128.", b"i8x16.", b"i16x8.", b"i32x4.", b"i64x2.", b"f32x4.", b"f64x2."];
+
+/// The proposal an instruction belongs to, or `None` for MVP instructions.
+pub(crate) fn instruction_proposal(name: &[u8]) -> Option<Proposal> {
+    match name {
+        // Ahead of the patterns below, which `i32x4.trunc_sat_f32x4_s`
+        // would match too.
+        _ if SIMD_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) => Some(Proposal::Simd),
+        b"ref.null" | b"ref.is_null" | b"ref.func" | b"table.get" | b"table.set" |
+        b"table.size" | b"table.grow" | b"table.fill" => Some(Proposal::ReferenceTypes),
+        b"memory.copy" | b"memory.fill" | b"memory.init" | b"data.drop" | b"table.copy" |
+        b"table.init" | b"elem.drop" => Some(Proposal::BulkMemory),
+        b"i32.extend8_s" | b"i32.extend16_s" | b"i64.extend8_s" | b"i64.extend16_s" |
+        b"i64.extend32_s" => Some(Proposal::SignExtension),
+        b"return_call" | b"return_call_indirect" => Some(Proposal::TailCall),
+        b"wake" | b"i32.wait" | b"i64.wait" => Some(Proposal::Threads),
+        // Covers `*.atomic.*`, `memory.atomic.*` and `atomic.fence`.
+        _ if contains(name, b"atomic.") => Some(Proposal::Threads),
+        _ if contains(name, b".trunc_sat_") => Some(Proposal::SaturatingFloatToInt),
+        _ => None,
+    }
+}
+
+fn contains(name: &[u8], part: &[u8]) -> bool {
+    name.windows(part.len()).any(|window| window == part)
+}
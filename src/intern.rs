@@ -0,0 +1,74 @@
+use core::fmt;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use wat::ID;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// A `$id` reference interned by a `WatInterner`. Displayed as `$#n`,
+/// the interner holds the original text.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Symbol(pub u32);
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$#{}", self.0)
+    }
+}
+
+/// Maps `$id`s to `Symbol`s, numbered in order of first use.
+///
+/// ```
+/// use wasmtextparser::WatInterner;
+///
+/// let mut interner = WatInterner::new();
+/// let memcpy = interner.intern(b"$memcpy");
+/// assert_eq!(interner.intern(b"$sp").0, 1);
+/// assert_eq!(interner.intern(b"$memcpy"), memcpy);
+/// assert_eq!(interner.resolve(memcpy), Some(&b"$memcpy"[..]));
+/// ```
+#[derive(Debug,Clone,Default)]
+pub struct WatInterner {
+    symbols: BTreeMap<ID, Symbol>,
+    ids: Vec<ID>,
+}
+
+impl WatInterner {
+    pub fn new() -> WatInterner {
+        WatInterner::default()
+    }
+
+    pub fn intern(&mut self, id: &[u8]) -> Symbol {
+        if let Some(symbol) = self.symbols.get(id) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.ids.len() as u32);
+        self.symbols.insert(id.to_vec(), symbol);
+        self.ids.push(id.to_vec());
+        symbol
+    }
+
+    /// The symbol for `id`, if it has been interned.
+    pub fn get(&self, id: &[u8]) -> Option<Symbol> {
+        self.symbols.get(id).cloned()
+    }
+
+    /// The `$id` text of `symbol`, including its `$`.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&[u8]> {
+        self.ids.get(symbol.0 as usize).map(|id| &id[..])
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.symbols.clear();
+        self.ids.clear();
+    }
+}
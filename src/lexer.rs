@@ -1,25 +1,72 @@
-use std::mem;
-use std::result;
+//! The WAT tokenizer, usable on its own, e.g. for syntax highlighting.
+//!
+//! ```
+//! use wasmtextparser::{WatLexer, WatTokenType};
+//!
+//! let source = b"(func $f (i32.const -1)) ;; done";
+//! let mut lexer = WatLexer::new(source);
+//! let mut tokens = Vec::new();
+//! loop {
+//!     let token = lexer.next().unwrap().clone();
+//!     if token.ty == WatTokenType::End {
+//!         break;
+//!     }
+//!     tokens.push((token.ty, token.content(source)));
+//! }
+//! assert_eq!(tokens,
+//!            [(WatTokenType::OpenParen, &b"("[..]),
+//!             (WatTokenType::Keyword, b"func"),
+//!             (WatTokenType::ID, b"$f"),
+//!             (WatTokenType::OpenParen, b"("),
+//!             (WatTokenType::Keyword, b"i32.const"),
+//!             (WatTokenType::Signed, b"-1"),
+//!             (WatTokenType::CloseParen, b")"),
+//!             (WatTokenType::CloseParen, b")")]);
+//! ```
 
-#[derive(Debug,Copy,Clone)]
-pub struct WatLexerError {
-    pub message: &'static str,
-    pub line: usize,
-    pub column: usize,
-}
+use core::mem;
+use core::str;
+use error::{ErrorKind, Result, WatParserError};
+#[cfg(feature = "serde")]
+use serde::Serialize;
 
-pub type Result<T> = result::Result<T, WatLexerError>;
+/// Lexer errors are `WatParserError`s of kind `ErrorKind::Lexical`.
+pub type WatLexerError = WatParserError;
 
-#[derive(Debug,Clone,Copy)]
+/// A source location: 1-based line, 0-based column and byte offset.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatPosition {
     pub line: usize,
     pub column: usize,
     pub position: usize,
 }
 
-#[derive(Debug,PartialEq,Eq)]
+/// Token classes of the WAT lexical grammar. Whitespace and comments
+/// produce no tokens.
+///
+/// ```
+/// use wasmtextparser::{WatLexer, WatTokenType};
+///
+/// for &(text, ty) in &[(&b"i32.add"[..], WatTokenType::Keyword),
+///                      (b"0x2A", WatTokenType::Unsigned),
+///                      (b"-0x1", WatTokenType::Signed),
+///                      (b"+inf", WatTokenType::Float),
+///                      (b"\"a\\n\"", WatTokenType::String),
+///                      (b"$x", WatTokenType::ID),
+///                      (b"1a", WatTokenType::Reserved),
+///                      (b"", WatTokenType::End)] {
+///     assert_eq!(WatLexer::new(text).next().unwrap().ty, ty, "{:?}", text);
+/// }
+/// ```
+/// ```
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[non_exhaustive]
 pub enum WatTokenType {
+    /// The end of the input; returned again by every later `next()`.
     End,
+    /// Starts with a lowercase letter: instruction names, field
+    /// keywords, value types and `offset=`/`align=` immediates.
     Keyword,
     Unsigned,
     Signed,
@@ -28,16 +75,181 @@ pub enum WatTokenType {
     ID,
     OpenParen,
     CloseParen,
+    /// Any other run of identifier characters, such as `1a` or a lone
+    /// `$`. Valid WAT never contains one. A `+` or `-` without a number
+    /// after it is one as well, and the parser says so:
+    ///
+    /// ```
+    /// use wasmtextparser::WatParser;
+    ///
+    /// let err = WatParser::new(b"(module (func i32.const 1 - drop))")
+    ///     .events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(&*err.message,
+    ///            "unexpected '-' (a sign without a number) while parsing instruction");
+    /// ```
     Reserved,
 }
 
-#[derive(Debug)]
+/// Keywords with a fixed meaning in the module structure, recognized
+/// by the lexer so the parser can compare them without looking at bytes.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[non_exhaustive]
+pub enum KnownKeyword {
+    Module,
+    Type,
+    Import,
+    Export,
+    Func,
+    Global,
+    Table,
+    Memory,
+    Elem,
+    Data,
+    Start,
+    Param,
+    Result,
+    Local,
+    Mut,
+    Shared,
+    Offset,
+    Item,
+    Declare,
+    Funcref,
+    Externref,
+    Ref,
+    Null,
+}
+
+impl KnownKeyword {
+    /// Classifies the text of a keyword token.
+    ///
+    /// ```
+    /// use wasmtextparser::lexer::KnownKeyword;
+    ///
+    /// assert_eq!(KnownKeyword::from_bytes(b"module"), Some(KnownKeyword::Module));
+    /// assert_eq!(KnownKeyword::from_bytes(b"param"), Some(KnownKeyword::Param));
+    /// assert_eq!(KnownKeyword::from_bytes(b"i32.add"), None);
+    /// ```
+    pub fn from_bytes(keyword: &[u8]) -> Option<KnownKeyword> {
+        Some(match keyword {
+                 b"module" => KnownKeyword::Module,
+                 b"type" => KnownKeyword::Type,
+                 b"import" => KnownKeyword::Import,
+                 b"export" => KnownKeyword::Export,
+                 b"func" => KnownKeyword::Func,
+                 b"global" => KnownKeyword::Global,
+                 b"table" => KnownKeyword::Table,
+                 b"memory" => KnownKeyword::Memory,
+                 b"elem" => KnownKeyword::Elem,
+                 b"data" => KnownKeyword::Data,
+                 b"start" => KnownKeyword::Start,
+                 b"param" => KnownKeyword::Param,
+                 b"result" => KnownKeyword::Result,
+                 b"local" => KnownKeyword::Local,
+                 b"mut" => KnownKeyword::Mut,
+                 b"shared" => KnownKeyword::Shared,
+                 b"offset" => KnownKeyword::Offset,
+                 b"item" => KnownKeyword::Item,
+                 b"declare" => KnownKeyword::Declare,
+                 b"funcref" => KnownKeyword::Funcref,
+                 b"externref" => KnownKeyword::Externref,
+                 b"ref" => KnownKeyword::Ref,
+                 b"null" => KnownKeyword::Null,
+                 _ => return None,
+             })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KnownKeyword::Module => "module",
+            KnownKeyword::Type => "type",
+            KnownKeyword::Import => "import",
+            KnownKeyword::Export => "export",
+            KnownKeyword::Func => "func",
+            KnownKeyword::Global => "global",
+            KnownKeyword::Table => "table",
+            KnownKeyword::Memory => "memory",
+            KnownKeyword::Elem => "elem",
+            KnownKeyword::Data => "data",
+            KnownKeyword::Start => "start",
+            KnownKeyword::Param => "param",
+            KnownKeyword::Result => "result",
+            KnownKeyword::Local => "local",
+            KnownKeyword::Mut => "mut",
+            KnownKeyword::Shared => "shared",
+            KnownKeyword::Offset => "offset",
+            KnownKeyword::Item => "item",
+            KnownKeyword::Declare => "declare",
+            KnownKeyword::Funcref => "funcref",
+            KnownKeyword::Externref => "externref",
+            KnownKeyword::Ref => "ref",
+            KnownKeyword::Null => "null",
+        }
+    }
+}
+
+/// A token and the source span it covers.
+#[derive(Debug,Clone,PartialEq,Eq)]
 pub struct WatToken {
     pub ty: WatTokenType,
     pub start: WatPosition,
     pub end: WatPosition,
+    /// Set for `Keyword` tokens that are a `KnownKeyword`.
+    pub keyword: Option<KnownKeyword>,
+}
+
+impl WatToken {
+    /// The token's text in `source`, the input it was scanned from.
+    /// Unlike `WatLexer::current_token_content`, this works for any
+    /// token kept around, not just the current one.
+    ///
+    /// ```
+    /// use wasmtextparser::WatLexer;
+    ///
+    /// let source = b"(i32.const 42)";
+    /// let mut lexer = WatLexer::new(source);
+    /// let mut tokens = Vec::new();
+    /// for _ in 0..3 {
+    ///     let token = lexer.next().unwrap().clone();
+    ///     assert_eq!(token.content(source), lexer.current_token_content());
+    ///     tokens.push(token);
+    /// }
+    /// assert_eq!(tokens[1].content(source), b"i32.const");
+    /// assert_eq!(tokens[1].len(), 9);
+    /// ```
+    pub fn content<'s>(&self, source: &'s [u8]) -> &'s [u8] {
+        &source[self.start.position..self.end.position]
+    }
+
+    /// Length of the token's text in bytes; 0 for `End`.
+    pub fn len(&self) -> usize {
+        self.end.position - self.start.position
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
+/// Splits WAT source into tokens, skipping whitespace and comments.
+///
+/// A line comment may run to the end of the input:
+///
+/// ```
+/// use wasmtextparser::{WatLexer, WatParser, WatParserState, WatTokenType};
+///
+/// let source = b"(module) ;; trailing comment";
+/// let mut lexer = WatLexer::new(source);
+/// for _ in 0..3 {
+///     lexer.next().unwrap();
+/// }
+/// let end = lexer.next().unwrap();
+/// assert_eq!(end.ty, WatTokenType::End);
+/// assert_eq!(end.start.position, source.len());
+///
+/// let events = WatParser::new(source).events().collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(events.last(), Some(&WatParserState::EndModule));
+/// ```
 pub struct WatLexer<'a> {
     source: &'a [u8],
     token: Option<WatToken>,
@@ -48,7 +260,7 @@ pub struct WatLexer<'a> {
 }
 
 impl<'a> WatLexer<'a> {
-    pub fn new(source: &[u8]) -> WatLexer {
+    pub fn new(source: &[u8]) -> WatLexer<'_> {
         return WatLexer {
                    source,
                    token: None,
@@ -59,11 +271,90 @@ impl<'a> WatLexer<'a> {
                };
     }
 
+    // A lexer that starts scanning `source` at `start`, as if everything
+    // before it had been scanned already.
+    pub(crate) fn starting_at(source: &[u8], start: WatPosition) -> WatLexer<'_> {
+        return WatLexer {
+                   source,
+                   token: None,
+                   past_token: None,
+                   position: start.position,
+                   line: start.line,
+                   line_start: start.position - start.column,
+               };
+    }
+
+    // Moves the lexer and its tokens over to `source`, which holds the
+    // same text up to the scan position, or nothing at all while the
+    // lexer is set aside.
+    pub(crate) fn rebind(self, source: &[u8]) -> WatLexer<'_> {
+        return WatLexer {
+                   source,
+                   token: self.token,
+                   past_token: self.past_token,
+                   position: self.position,
+                   line: self.line,
+                   line_start: self.line_start,
+               };
+    }
+
+    // Scans the current token again, such as an `End` token after the
+    // source has grown.
+    pub(crate) fn rescan(&mut self) -> Result<()> {
+        if let Some(ref token) = self.token {
+            let start = token.start;
+            self.position = start.position;
+            self.line = start.line;
+            self.line_start = start.position - start.column;
+        }
+        let token = self.scan_next_token()?;
+        self.token = Some(token);
+        Ok(())
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &str) -> WatLexer<'_> {
+        WatLexer::new(source.as_bytes())
+    }
+
+    /// Checks that the whole source is valid UTF-8, as WAT text must be,
+    /// and reports the start of the first invalid sequence otherwise.
+    pub fn validate_utf8(&self) -> Result<()> {
+        self.validate_utf8_from(0)
+    }
+
+    // Checks the source from `start` on, which must be at a char
+    // boundary.
+    pub(crate) fn validate_utf8_from(&self, start: usize) -> Result<()> {
+        let offset = match str::from_utf8(&self.source[start..]) {
+            Ok(_) => return Ok(()),
+            Err(err) => start + err.valid_up_to(),
+        };
+        let before = &self.source[..offset];
+        let line_start = before.iter().rposition(|&ch| ch == b'\n').map_or(0, |i| i + 1);
+        let position = WatPosition {
+            line: 1 + before.iter().filter(|&&ch| ch == b'\n').count(),
+            column: offset - line_start,
+            position: offset,
+        };
+        Err(WatParserError {
+                kind: ErrorKind::Lexical,
+                message: "Invalid UTF-8 sequence".into(),
+                start: position,
+                end: position,
+            })
+    }
+
     fn current_char(&self) -> u8 {
         self.source[self.position]
     }
 
-    fn current_position(&self) -> WatPosition {
+    pub(crate) fn source(&self) -> &'a [u8] {
+        self.source
+    }
+
+    /// Position of the next character to be scanned.
+    pub fn current_position(&self) -> WatPosition {
         WatPosition {
             line: self.line,
             column: self.position - self.line_start,
@@ -136,11 +427,12 @@ impl<'a> WatLexer<'a> {
                               ty: WatTokenType::String,
                               start,
                               end: self.current_position(),
+                              keyword: None,
                           });
             }
             if ch == b'\\' {
                 if !self.next_char() {
-                    return return Err(self.unexpected_eos());
+                    return Err(self.unexpected_eos());
                 }
                 // escapes?
                 match self.current_char() {
@@ -157,6 +449,7 @@ impl<'a> WatLexer<'a> {
                         if !self.is_hexdigit() {
                             return Err(self.unexpected_char());
                         }
+                        let digits = self.current_position();
                         self.skip_hexnum();
                         if self.eos() {
                             return Err(self.unexpected_eos());
@@ -164,10 +457,17 @@ impl<'a> WatLexer<'a> {
                         if self.current_char() != b'}' {
                             return Err(self.unexpected_char());
                         }
+                        let code = &self.source[digits.position..self.position];
+                        if unicode_escape(code).is_none() {
+                            return Err(WatParserError {
+                                           kind: ErrorKind::Lexical,
+                                           message: "Invalid unicode escape".into(),
+                                           start: digits,
+                                           end: self.current_position(),
+                                       });
+                        }
                     }
-                    b't' | b'n' | b'r' | b'"' | b'\'' | b'\\' => {
-                        self.next_char();
-                    }
+                    b't' | b'n' | b'r' | b'"' | b'\'' | b'\\' => {}
                     _ => {
                         if !self.is_hexdigit() {
                             return Err(self.unexpected_char());
@@ -182,9 +482,7 @@ impl<'a> WatLexer<'a> {
                 }
             } else if ch >= 0x80 {
                 // UTF-8 stuff
-                if (ch & 0xC0) == 0x80 {
-                    return Err(self.unexpected_char());
-                } else if (ch & 0xF8) == 0xF8 {
+                if (ch & 0xC0) == 0x80 || (ch & 0xF8) == 0xF8 {
                     return Err(self.unexpected_char());
                 }
                 // byte 2
@@ -216,7 +514,7 @@ impl<'a> WatLexer<'a> {
                 return Err(self.unexpected_char());
             }
         }
-        return return Err(self.unexpected_eos());
+        return Err(self.unexpected_eos());
     }
 
     fn is_digit_char(ch: u8) -> bool {
@@ -224,7 +522,7 @@ impl<'a> WatLexer<'a> {
     }
 
     fn is_hexdigit_char(ch: u8) -> bool {
-        return ch >= b'0' && ch <= b'9' || ch >= b'A' && ch <= b'Z' || ch >= b'a' && ch <= b'z';
+        return ch >= b'0' && ch <= b'9' || ch >= b'A' && ch <= b'F' || ch >= b'a' && ch <= b'f';
     }
 
     fn is_num(str: &[u8]) -> bool {
@@ -283,7 +581,7 @@ impl<'a> WatLexer<'a> {
                 return false;
             }
         }
-        if i < str.len() && str[i] != b'P' && str[i] != b'p' {
+        if i < str.len() && (str[i] == b'P' || str[i] == b'p') {
             i += 1;
             if i < str.len() && (str[i] == b'-' || str[i] == b'+') {
                 i += 1;
@@ -309,10 +607,11 @@ impl<'a> WatLexer<'a> {
             return WatLexer::is_hexfloat(&str[i + 2..]);
         }
 
+        let start = i;
         while i < str.len() && str[i] != b'.' && str[i] != b'E' && str[i] != b'e' {
             i += 1;
         }
-        if !WatLexer::is_num(&str[0..i]) {
+        if !WatLexer::is_num(&str[start..i]) {
             return false;
         }
         if i < str.len() && str[i] == b'.' {
@@ -325,7 +624,7 @@ impl<'a> WatLexer<'a> {
                 return false;
             }
         }
-        if i < str.len() && str[i] != b'E' && str[i] != b'e' {
+        if i < str.len() && (str[i] == b'E' || str[i] == b'e') {
             i += 1;
             if i < str.len() && (str[i] == b'-' || str[i] == b'+') {
                 i += 1;
@@ -341,11 +640,13 @@ impl<'a> WatLexer<'a> {
         while self.next_char() && self.is_idchar() {}
         let end = self.current_position();
         let end_position = end.position;
-        if self.source[start_position] == b'$' {
+        // An id needs at least one character after the `$`.
+        if self.source[start_position] == b'$' && end_position - start_position > 1 {
             return WatToken {
                        ty: WatTokenType::ID,
                        start,
                        end,
+                       keyword: None,
                    };
         }
         if (self.source[start_position] == b'+' || self.source[start_position] == b'-') &&
@@ -354,6 +655,7 @@ impl<'a> WatLexer<'a> {
                        ty: WatTokenType::Signed,
                        start,
                        end,
+                       keyword: None,
                    };
         }
         if WatLexer::is_number(&self.source[start_position..end_position]) {
@@ -361,6 +663,7 @@ impl<'a> WatLexer<'a> {
                        ty: WatTokenType::Unsigned,
                        start,
                        end,
+                       keyword: None,
                    };
         }
         if WatLexer::is_float(&self.source[start_position..end_position]) {
@@ -368,6 +671,7 @@ impl<'a> WatLexer<'a> {
                        ty: WatTokenType::Float,
                        start,
                        end,
+                       keyword: None,
                    };
         }
         if self.source[start_position] >= b'a' && self.source[start_position] <= b'z' {
@@ -376,20 +680,24 @@ impl<'a> WatLexer<'a> {
                        ty: WatTokenType::Keyword,
                        start,
                        end,
+                       keyword: KnownKeyword::from_bytes(&self.source[start_position..end_position]),
                    };
         }
         return WatToken {
                    ty: WatTokenType::Reserved,
                    start,
                    end,
+                   keyword: None,
                };
     }
 
     fn create_error(&self, message: &'static str) -> WatLexerError {
-        WatLexerError {
-            message,
-            line: self.line,
-            column: self.position - self.line_start,
+        let position = self.current_position();
+        WatParserError {
+            kind: ErrorKind::Lexical,
+            message: message.into(),
+            start: position,
+            end: position,
         }
     }
 
@@ -457,6 +765,7 @@ impl<'a> WatLexer<'a> {
                           ty: WatTokenType::End,
                           start: self.current_position(),
                           end: self.current_position(),
+                          keyword: None,
                       });
         }
         let ch = self.current_char();
@@ -469,6 +778,7 @@ impl<'a> WatLexer<'a> {
                               ty: WatTokenType::OpenParen,
                               start,
                               end: self.current_position(),
+                              keyword: None,
                           }
                       }
                       b')' => {
@@ -478,6 +788,7 @@ impl<'a> WatLexer<'a> {
                               ty: WatTokenType::CloseParen,
                               start,
                               end: self.current_position(),
+                              keyword: None,
                           }
                       }
                       _ => {
@@ -490,28 +801,89 @@ impl<'a> WatLexer<'a> {
                   });
     }
 
+    /// Scans the next token and makes it the current one.
+    ///
+    /// A malformed string escape is reported at its first bad character,
+    /// and a `\u{...}` that is not a Unicode scalar value at its digits:
+    ///
+    /// ```
+    /// use wasmtextparser::WatLexer;
+    ///
+    /// for &(source, column) in &[(&b"\"\\u{ZZ}\""[..], 4), (b"\"\\u{\"", 4), (b"\"\\uZ\"", 3),
+    ///                            (b"\"\\xG\"", 2), (b"\"\\aG\"", 3)] {
+    ///     let err = WatLexer::new(source).next().unwrap_err();
+    ///     assert_eq!((err.start.column, err.end.column), (column, column));
+    /// }
+    /// for &source in &[&b"\"\\u{D800}\""[..], b"\"\\u{11_0000}\"", b"\"\\u{100000000}\""] {
+    ///     let err = WatLexer::new(source).next().unwrap_err();
+    ///     assert_eq!((err.start.column, err.end.column), (4, source.len() - 2));
+    /// }
+    /// ```
+    ///
+    /// Control characters and DEL can only be written as escapes:
+    ///
+    /// ```
+    /// use wasmtextparser::WatLexer;
+    ///
+    /// for ch in (0..0x20).chain(Some(0x7F)) {
+    ///     let literal = [b'"', ch, b'"'];
+    ///     assert_eq!(WatLexer::new(&literal).next().unwrap_err().start.column, 1);
+    ///     let escaped = format!("\"\\{:02x}\\u{{{:x}}}\"", ch, ch);
+    ///     assert!(WatLexer::new(escaped.as_bytes()).next().is_ok());
+    /// }
+    /// ```
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<&WatToken> {
         let token = self.scan_next_token()?;
-        mem::swap(&mut self.token, &mut self.past_token);
-        self.token = Some(token);
-        Ok(self.current_token())
+        self.past_token = self.token.take();
+        Ok(self.token.insert(token))
+    }
+
+    /// Steps over the character at which scanning last failed, so lexing
+    /// can resume after an error.
+    pub fn skip_char(&mut self) {
+        if self.eos() {
+            return;
+        }
+        if self.current_char() == 0x0A {
+            self.line += 1;
+            self.line_start = self.position + 1;
+        }
+        self.position += 1;
+    }
+
+    /// The token returned by the last `next()` call, or `None` before
+    /// the first one.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatLexer, WatTokenType};
+    ///
+    /// let mut lexer = WatLexer::new(b"");
+    /// assert!(lexer.current_token().is_none());
+    /// lexer.next().unwrap();
+    /// assert_eq!(lexer.current_token().map(|token| token.ty), Some(WatTokenType::End));
+    /// ```
+    pub fn current_token(&self) -> Option<&WatToken> {
+        self.token.as_ref()
     }
 
-    pub fn current_token(&self) -> &WatToken {
-        self.token.as_ref().unwrap()
+    // The current token, for the parser, which reads one before asking.
+    pub(crate) fn token(&self) -> &WatToken {
+        self.token.as_ref().expect("no token has been read")
     }
 
+    /// Source bytes of the current token, empty before the first
+    /// `next()` call.
     pub fn current_token_content(&self) -> &[u8] {
-        let token = self.token.as_ref().unwrap();
-        &self.source[token.start.position..token.end.position]
+        self.token.as_ref().map_or(&[], |token| token.content(self.source))
     }
 
-    pub fn rewind(&mut self) {
+    pub(crate) fn rewind(&mut self) {
         if self.past_token.is_none() {
             panic!("Cannot rewind more than once or at the stream start");
         }
         {
-            let ref last_position = self.token.as_ref().unwrap().start;
+            let last_position = &self.token.as_ref().unwrap().start;
             self.position = last_position.position;
             self.line = last_position.line;
             self.line_start = last_position.position - last_position.column;
@@ -520,3 +892,295 @@ impl<'a> WatLexer<'a> {
         self.past_token = None;
     }
 }
+
+// The char of a `\u{...}` escape, from its digits, if it is a Unicode
+// scalar value. Underscores are taken to be between digits.
+pub(crate) fn unicode_escape(digits: &[u8]) -> Option<char> {
+    if digits.is_empty() {
+        return None;
+    }
+    let mut code: u32 = 0;
+    for &ch in digits.iter().filter(|&&ch| ch != b'_') {
+        let digit = (ch as char).to_digit(16)?;
+        code = code.checked_mul(16)?.checked_add(digit)?;
+    }
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use super::*;
+    use wat::{WatInstructionArg, WatParser, WatParserState};
+
+    // The type and text of each token, up to `End` or the first error.
+    fn tokens(lexer: &mut WatLexer) -> Result<Vec<(WatTokenType, Vec<u8>)>> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next()?;
+            if token.ty == WatTokenType::End {
+                return Ok(tokens);
+            }
+            tokens.push((token.ty, lexer.current_token_content().to_vec()));
+        }
+    }
+
+    #[test]
+    fn token_classification() {
+        use self::WatTokenType::*;
+
+        let table: &[(&str, &[(WatTokenType, &str)])] =
+            &[("i32.add", &[(Keyword, "i32.add")]),
+              ("offset=8 align=4", &[(Keyword, "offset=8"), (Keyword, "align=4")]),
+              ("42", &[(Unsigned, "42")]),
+              ("0x2A", &[(Unsigned, "0x2A")]),
+              ("1_000", &[(Unsigned, "1_000")]),
+              ("0xFF_FF", &[(Unsigned, "0xFF_FF")]),
+              ("+1", &[(Signed, "+1")]),
+              ("+5", &[(Signed, "+5")]),
+              ("-0x1", &[(Signed, "-0x1")]),
+              ("1.5", &[(Float, "1.5")]),
+              ("-0x1p-3", &[(Float, "-0x1p-3")]),
+              ("inf", &[(Float, "inf")]),
+              ("+inf", &[(Float, "+inf")]),
+              ("-nan", &[(Float, "-nan")]),
+              ("nan:0x7f", &[(Float, "nan:0x7f")]),
+              ("\"a\\n\"", &[(String, "\"a\\n\"")]),
+              ("$x", &[(ID, "$x")]),
+              ("$", &[(Reserved, "$")]),
+              ("1a", &[(Reserved, "1a")]),
+              ("1__0", &[(Reserved, "1__0")]),
+              ("_1", &[(Reserved, "_1")]),
+              ("1_", &[(Reserved, "1_")]),
+              ("0x_1", &[(Reserved, "0x_1")]),
+              ("Foo", &[(Reserved, "Foo")]),
+              ("+", &[(Reserved, "+")]),
+              ("-", &[(Reserved, "-")]),
+              ("+x", &[(Reserved, "+x")]),
+              ("", &[]),
+              ("(module $m)",
+               &[(OpenParen, "("), (Keyword, "module"), (ID, "$m"), (CloseParen, ")")]),
+              ("(i32.const -1)(nop)",
+               &[(OpenParen, "("),
+                 (Keyword, "i32.const"),
+                 (Signed, "-1"),
+                 (CloseParen, ")"),
+                 (OpenParen, "("),
+                 (Keyword, "nop"),
+                 (CloseParen, ")")]),
+              ("nop ;; comment\n(; block (; nested ;) ;) nop",
+               &[(Keyword, "nop"), (Keyword, "nop")]),
+              ("\"a\"\"b\"$c", &[(String, "\"a\""), (String, "\"b\""), (ID, "$c")])];
+        for &(source, expected) in table {
+            let expected: Vec<_> =
+                expected.iter().map(|&(ty, text)| (ty, text.as_bytes().to_vec())).collect();
+            assert_eq!(tokens(&mut WatLexer::new(source.as_bytes())).unwrap(),
+                       expected,
+                       "{}",
+                       source);
+        }
+    }
+
+    #[test]
+    fn plus_signs() {
+        for &(source, ty) in &[("+5", WatTokenType::Signed),
+                               ("+0x5", WatTokenType::Signed),
+                               ("+5.0", WatTokenType::Float),
+                               ("+inf", WatTokenType::Float),
+                               ("+nan:0x1", WatTokenType::Float),
+                               ("+", WatTokenType::Reserved),
+                               ("+_5", WatTokenType::Reserved)] {
+            assert_eq!(tokens(&mut WatLexer::new(source.as_bytes())).unwrap(),
+                       [(ty, source.as_bytes().to_vec())],
+                       "{}",
+                       source);
+        }
+        // A sign and a number apart are two tokens.
+        assert_eq!(tokens(&mut WatLexer::new(b"+ 5")).unwrap(),
+                   [(WatTokenType::Reserved, b"+".to_vec()),
+                    (WatTokenType::Unsigned, b"5".to_vec())]);
+    }
+
+    #[test]
+    fn underscores_between_digits_only() {
+        for &(source, ty) in &[("1_000_000", WatTokenType::Unsigned),
+                               ("0xFF_FF", WatTokenType::Unsigned),
+                               ("-1_0", WatTokenType::Signed),
+                               ("1_0.0_1e1_0", WatTokenType::Float),
+                               ("1__000", WatTokenType::Reserved),
+                               ("0xFF__FF", WatTokenType::Reserved),
+                               ("1.0__1", WatTokenType::Reserved),
+                               ("1_.0", WatTokenType::Reserved),
+                               ("0x_1", WatTokenType::Reserved)] {
+            assert_eq!(tokens(&mut WatLexer::new(source.as_bytes())).unwrap(),
+                       [(ty, source.as_bytes().to_vec())],
+                       "{}",
+                       source);
+        }
+    }
+
+    #[test]
+    fn string_escape_error_columns() {
+        // Each error is at the first bad character, not at the quote.
+        for &(source, column, message) in
+            &[(&b"\"\\u{"[..], 4, "Unexpected eos"),
+              (b"\"\\u{\"", 4, "Unexpected character"),
+              (b"\"\\u{ZZ}\"", 4, "Unexpected character"),
+              (b"\"\\uZ\"", 3, "Unexpected character"),
+              (b"\"\\xG\"", 2, "Unexpected character"),
+              (b"\"\\aG\"", 3, "Unexpected character"),
+              (b"\"\\u{41\"", 6, "Unexpected character"),
+              (b"\"\\n\\t\\41\\u{42}\\uZ\"", 16, "Unexpected character")] {
+            let err = WatLexer::new(source).next().unwrap_err();
+            assert_eq!((err.kind, &*err.message), (ErrorKind::Lexical, message));
+            assert_eq!((err.start.line, err.start.column), (1, column));
+        }
+        let err = tokens(&mut WatLexer::new(b"nop\n  \"\\uZ\"")).unwrap_err();
+        assert_eq!((err.start.line, err.start.column, err.start.position), (2, 5, 9));
+    }
+
+    #[test]
+    fn current_token_before_next() {
+        let mut lexer = WatLexer::new(b"");
+        assert_eq!(lexer.current_token(), None);
+        assert_eq!(lexer.current_token_content(), b"");
+        assert_eq!(lexer.next().unwrap().ty, WatTokenType::End);
+        assert_eq!(lexer.current_token().map(|token| token.ty), Some(WatTokenType::End));
+    }
+
+    #[test]
+    fn from_str_lexes_the_str() {
+        let source = "(module \"caf\u{e9}\")";
+        let mut lexer = WatLexer::from_str(source);
+        assert_eq!(lexer.validate_utf8(), Ok(()));
+        assert_eq!(tokens(&mut lexer).unwrap(),
+                   [(WatTokenType::OpenParen, b"(".to_vec()),
+                    (WatTokenType::Keyword, b"module".to_vec()),
+                    (WatTokenType::String, "\"caf\u{e9}\"".as_bytes().to_vec()),
+                    (WatTokenType::CloseParen, b")".to_vec())]);
+        assert_eq!(WatParser::from_str("(module)").parse(),
+                   WatParser::new(b"(module)").parse());
+    }
+
+    #[test]
+    fn invalid_utf8_outside_a_string() {
+        let source = b"(module)\n  \xff (func)";
+        let err = WatLexer::new(source).validate_utf8().unwrap_err();
+        assert_eq!((err.kind, &*err.message), (ErrorKind::Lexical, "Invalid UTF-8 sequence"));
+        assert_eq!((err.start.line, err.start.column, err.start.position), (2, 2, 11));
+        match WatParser::new(source).parse() {
+            WatParserState::Error(parser_err) => assert_eq!(parser_err, err),
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn hex_numbers_take_hex_digits_only() {
+        for &(source, ty) in &[("0xABCDEF", WatTokenType::Unsigned),
+                               ("0xabc_def", WatTokenType::Unsigned),
+                               ("-0xAb", WatTokenType::Signed),
+                               ("0xA.Fp1", WatTokenType::Float),
+                               ("0xG", WatTokenType::Reserved),
+                               ("0xZ", WatTokenType::Reserved),
+                               ("0xfg", WatTokenType::Reserved),
+                               ("0x1.g", WatTokenType::Reserved)] {
+            let tokens = tokens(&mut WatLexer::new(source.as_bytes())).unwrap();
+            assert_eq!(tokens, [(ty, source.as_bytes().to_vec())], "{}", source);
+        }
+        for source in &[&b"i32.const 0xG"[..], b"i32.const 0xfg"] {
+            match WatParser::func_body(source).parse() {
+                WatParserState::Error(err) => assert_eq!(err.start.column, 10),
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    // A position as (line, column, position).
+    type Place = (usize, usize, usize);
+
+    // The start and end of each token, up to and including `End`.
+    fn spans(source: &[u8]) -> Vec<(Place, Place)> {
+        let mut lexer = WatLexer::new(source);
+        let mut spans = Vec::new();
+        loop {
+            let token = lexer.next().unwrap();
+            spans.push(((token.start.line, token.start.column, token.start.position),
+                        (token.end.line, token.end.column, token.end.position)));
+            if token.ty == WatTokenType::End {
+                return spans;
+            }
+        }
+    }
+
+    #[test]
+    fn spans_at_end_of_input_without_newline() {
+        assert_eq!(spans(b"(module)"),
+                   [((1, 0, 0), (1, 1, 1)),
+                    ((1, 1, 1), (1, 7, 7)),
+                    ((1, 7, 7), (1, 8, 8)),
+                    ((1, 8, 8), (1, 8, 8))]);
+        assert_eq!(spans(b"nop\ni32.const 5"),
+                   [((1, 0, 0), (1, 3, 3)),
+                    ((2, 0, 4), (2, 9, 13)),
+                    ((2, 10, 14), (2, 11, 15)),
+                    ((2, 11, 15), (2, 11, 15))]);
+        // The last token ends one past the last byte, whatever its kind.
+        for source in &["$id", "\"text\"", "0x1p4", "-7", "a.b=c"] {
+            let end = spans(source.as_bytes())[0].1;
+            assert_eq!(end, (1, source.len(), source.len()), "{}", source);
+        }
+
+        let mut parser = WatParser::new(b"(module)");
+        while parser.parse() != WatParserState::End {}
+        assert_eq!(parser.stats().end.map(|end| end.position), Some(8));
+        let mut parser = WatParser::func_body(b"nop\ni32.const 5");
+        parser.parse();
+        match parser.parse() {
+            WatParserState::CodeOperator { ref args, .. } => {
+                assert_eq!(*args,
+                           [WatInstructionArg::Unsigned {
+                                value: vec![5],
+                                raw: b"5".to_vec(),
+                            }]);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(parser.source_text(parser.event_start(), parser.event_end()), b"i32.const 5");
+        assert_eq!(parser.parse(), WatParserState::End);
+    }
+
+    #[test]
+    fn line_comment_at_end_of_input_without_newline() {
+        assert_eq!(spans(b"(module) ;; done"),
+                   [((1, 0, 0), (1, 1, 1)),
+                    ((1, 1, 1), (1, 7, 7)),
+                    ((1, 7, 7), (1, 8, 8)),
+                    ((1, 16, 16), (1, 16, 16))]);
+        assert_eq!(spans(b";;"), [((1, 2, 2), (1, 2, 2))]);
+        assert_eq!(spans(b"nop\n;; last"), [((1, 0, 0), (1, 3, 3)), ((2, 7, 11), (2, 7, 11))]);
+
+        let mut parser = WatParser::new(b"(module) ;; done");
+        assert!(matches!(parser.parse(), WatParserState::StartModule { .. }));
+        assert_eq!(parser.parse(), WatParserState::EndModule);
+        assert_eq!(parser.parse(), WatParserState::End);
+    }
+
+    #[test]
+    fn control_characters_in_strings() {
+        // A control character or DEL must be escaped; written as itself,
+        // it is an error at that byte.
+        for ch in (0..0x20).chain(Some(0x7F)) {
+            let escaped = format!("\"a\\{:02x}b\"", ch);
+            let mut lexer = WatLexer::new(escaped.as_bytes());
+            assert_eq!(lexer.next().unwrap().ty, WatTokenType::String, "{:#x}", ch);
+            assert_eq!(lexer.next().unwrap().ty, WatTokenType::End);
+
+            let literal = [b'"', b'a', ch, b'b', b'"'];
+            let err = WatLexer::new(&literal).next().unwrap_err();
+            assert_eq!((&*err.message, err.start.column), ("Unexpected character", 2), "{:#x}", ch);
+        }
+        for &escape in &[&b"\"\\t\""[..], b"\"\\n\"", b"\"\\r\"", b"\"\\u{7f}\""] {
+            assert_eq!(WatLexer::new(escape).next().unwrap().ty, WatTokenType::String);
+        }
+    }
+}
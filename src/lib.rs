@@ -0,0 +1,121 @@
+//! Streaming parser for the WebAssembly text format (WAT).
+//!
+//! `WatParser` reads a module one event at a time; each call to `parse()`
+//! returns the next `WatParserState` until `End` or `Error`.
+//! `WastParser` reads `.wast` spec test scripts, whose modules it hands to
+//! a `WatParser`.
+//!
+//! ```
+//! use wasmtextparser::{WatParser, WatParserState};
+//!
+//! let source = b"(module (func (export \"answer\") (result i32) i32.const 42))";
+//! let mut parser = WatParser::new(source);
+//! let mut funcs = 0;
+//! loop {
+//!     match parser.parse() {
+//!         WatParserState::StartFunc { .. } => funcs += 1,
+//!         WatParserState::End => break,
+//!         WatParserState::Error(ref err) => panic!("{}", err),
+//!         _ => {}
+//!     }
+//! }
+//! assert_eq!(funcs, 1);
+//! ```
+//!
+//! Only `core` and `alloc` are needed by the parser. The default `std`
+//! feature adds `WatWriter`, `format`, `print_events` and the
+//! `std::error::Error` impl; build with `--no-default-features` for
+//! `no_std` targets.
+
+#![no_std]
+#![allow(clippy::needless_return, clippy::manual_range_contains)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(feature = "std")]
+pub mod emit;
+pub mod encode;
+pub mod error;
+pub mod intern;
+mod instructions;
+pub mod lexer;
+pub mod module;
+pub mod opcode;
+pub mod push;
+pub mod resolve;
+#[cfg(feature = "std")]
+pub mod trace;
+pub mod visit;
+pub mod wast;
+pub mod wat;
+
+#[cfg(feature = "std")]
+pub use emit::{format, roundtrip, WatMismatch, WatWriter};
+pub use encode::encode_module;
+pub use error::{ErrorKind, Result, WatParserError};
+pub use intern::{Symbol, WatInterner};
+pub use lexer::{KnownKeyword, WatLexer, WatPosition, WatToken, WatTokenType};
+pub use module::{parse_module, WatExport, WatModule};
+pub use opcode::WatOpcode;
+pub use push::PushParser;
+pub use resolve::{IndexResolver, IndexSpace};
+#[cfg(feature = "std")]
+pub use trace::{print_events, WatTracer};
+pub use visit::{InstructionCounter, WatVisitor};
+pub use wast::{WastAction, WastModule, WastParser, WastParserState, WastValue};
+pub use wat::{WatEvents, WatExportKind, WatFeatures, WatParseStats, WatParser, WatParserOptions,
+              WatParserState};
+
+// Runs with and without the std feature; `cargo test --no-default-features`
+// checks that the parser side needs nothing beyond core and alloc.
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use super::*;
+
+    const SOURCE: &[u8] = b"(module (memory 1) (func $f (export \"f\") (result i32) i32.const 7))";
+
+    #[test]
+    fn parser_surface() {
+        let events = WatParser::new(SOURCE).events().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(events.len(), 6);
+        assert!(WatLexer::new(SOURCE).validate_utf8().is_ok());
+
+        let module = parse_module(SOURCE).unwrap();
+        assert_eq!((module.memories.len(), module.funcs.len()), (1, 1));
+
+        let mut resolver = IndexResolver::new();
+        for event in &events {
+            resolver.add_event(event);
+        }
+        assert_eq!(resolver.lookup(IndexSpace::Func, b"$f"), Some(0));
+
+        let wasm = encode_module(WatParser::new(SOURCE).events()).unwrap();
+        assert_eq!(&wasm[..4], b"\0asm");
+
+        let mut pushed = Vec::new();
+        PushParser::new(|event| pushed.push(event)).feed(SOURCE).unwrap().finish().unwrap();
+        assert_eq!(pushed.last(), Some(&WatParserState::End));
+        assert_eq!(pushed[..pushed.len() - 1], events[..]);
+    }
+
+    #[test]
+    fn errors_display_without_std() {
+        let err = WatParser::new(b"(module (func)").events().find_map(|event| event.err()).unwrap();
+        assert_eq!(err.to_string(), "1:1: expected ')', found end of input");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn errors_are_std_errors() {
+        let err = WatParser::new(b"(module (func)").events().find_map(|event| event.err()).unwrap();
+        let err: &dyn std::error::Error = &err;
+        assert!(err.source().is_none());
+    }
+}
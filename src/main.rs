@@ -1,32 +1,497 @@
+extern crate wasmtextparser;
+#[cfg(feature = "json")]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+use std::env;
 use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use std::fs::File;
-use std::str;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+use wasmtextparser::{format, roundtrip, WatTracer};
+use wasmtextparser::{encode_module, WastModule, WastParser, WastParserState, WatLexer,
+                     WatParser, WatParserError, WatParserState, WatPosition, WatTokenType};
+
+// Exit statuses; with several files, the worst one is returned.
+const EXIT_OK: i32 = 0;
+const EXIT_PARSE_ERROR: i32 = 1;
+const EXIT_IO_ERROR: i32 = 2;
+
+// SPEC_SKIPS, the spec test scripts that --check skips.
+include!("spec_skips.rs");
+
+struct Args {
+    json: bool,
+    fmt: bool,
+    tokens: bool,
+    time: bool,
+    roundtrip: bool,
+    iterations: usize,
+    wasm: Option<String>,
+    check: bool,
+    max_errors: Option<usize>,
+    paths: Vec<String>,
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("usage: {} [--json | --fmt | --tokens | --time [--iterations N] | \
+               --roundtrip] [--wasm <out.wasm>] [--check [--max-errors N]] \
+               <file.wat>...\n\
+               Parses each file in turn, and standard input for '-'. By default,\n\
+               the events are printed one per line, indented by nesting. With\n\
+               --json, each is written as one line of JSON, errors included. With\n\
+               --fmt, the module is written back out as formatted WAT. With\n\
+               --tokens, only the lexer runs, and each token is printed with its\n\
+               span and text. With --time, the file is parsed N times, once by\n\
+               default, without printing events, and the fastest run is reported\n\
+               with the input size and throughput. With --roundtrip, the module\n\
+               is formatted and the text parsed again, and the first event that\n\
+               differs, positions aside, is reported. With --wasm, the module is\n\
+               written to out.wasm in the binary format instead; it takes a single\n\
+               file. With --check, nothing is printed but the errors, all of them\n\
+               or the first N, and the exit status tells whether there were any.\n\
+               With several files, each is reported as ok or failed, then the\n\
+               number that failed. Files named *.wast are read as spec test\n\
+               scripts, and only printed, as events or tokens, or checked:\n\
+               their modules must parse, and the quoted ones of assert_malformed\n\
+               must not. Scripts for proposals not supported yet are then skipped.",
+              program);
+    process::exit(EXIT_IO_ERROR);
+}
+
+fn parse_args() -> Args {
+    let mut args = env::args();
+    let program = args.next().unwrap_or_else(|| String::from("wasmtextparser"));
+    let mut json = false;
+    let mut fmt = false;
+    let mut tokens = false;
+    let mut time = false;
+    let mut roundtrip = false;
+    let mut iterations = None;
+    let mut wasm = None;
+    let mut check = false;
+    let mut max_errors = None;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--fmt" => fmt = true,
+            "--tokens" => tokens = true,
+            "--time" => time = true,
+            "--roundtrip" => roundtrip = true,
+            "--iterations" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => iterations = Some(n),
+                    _ => usage(&program),
+                }
+            }
+            "--wasm" => {
+                match args.next() {
+                    Some(out) => wasm = Some(out),
+                    None => usage(&program),
+                }
+            }
+            "--check" => check = true,
+            "--max-errors" => {
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => max_errors = Some(n),
+                    _ => usage(&program),
+                }
+            }
+            "-" => paths.push(arg),
+            _ if arg.starts_with('-') => usage(&program),
+            _ => paths.push(arg),
+        }
+    }
+    let modes = json as u8 + fmt as u8 + tokens as u8 + time as u8 + roundtrip as u8;
+    if paths.is_empty() || modes > 1 || wasm.is_some() && paths.len() > 1 ||
+       iterations.is_some() && !time {
+        usage(&program);
+    }
+    Args {
+        json,
+        fmt,
+        tokens,
+        time,
+        roundtrip,
+        iterations: iterations.unwrap_or(1),
+        wasm,
+        check,
+        max_errors,
+        paths,
+    }
+}
+
+#[cfg(feature = "json")]
+fn print_json<T: serde::Serialize>(event: &T) {
+    println!("{}", serde_json::to_string(event).unwrap());
+}
+
+#[cfg(not(feature = "json"))]
+fn print_json<T>(_: &T) {
+    eprintln!("error: --json needs the binary built with the json feature");
+    process::exit(EXIT_IO_ERROR);
+}
+
+// Returns the printer of the default output, a readable trace of the
+// events; without the std feature, their Debug form.
+#[cfg(feature = "std")]
+fn trace_events() -> impl FnMut(&WatParserState) -> io::Result<()> {
+    let mut tracer = WatTracer::new(io::stdout());
+    move |event| tracer.write(event)
+}
+
+#[cfg(not(feature = "std"))]
+fn trace_events() -> impl FnMut(&WatParserState) -> io::Result<()> {
+    |event| writeln!(io::stdout(), "{:?}", event)
+}
+
+#[cfg(feature = "std")]
+fn print_formatted(mut parser: WatParser, wat: &[u8], name: &str) -> i32 {
+    match format(parser.events()) {
+        Ok(text) => {
+            print!("{}", text);
+            EXIT_OK
+        }
+        Err(err) => {
+            report(&err, name, wat);
+            EXIT_PARSE_ERROR
+        }
+    }
+}
 
-use lexer::{WatLexer, WatTokenType};
-use wat::{WatParser, WatParserState};
+#[cfg(not(feature = "std"))]
+fn print_formatted(_: WatParser, _: &[u8], _: &str) -> i32 {
+    eprintln!("error: --fmt needs the binary built with the std feature");
+    process::exit(EXIT_IO_ERROR);
+}
+
+// Checks that the formatted text of `wat` parses to the same events, and
+// reports the first pair that differs.
+#[cfg(feature = "std")]
+fn check_roundtrip(wat: &[u8], name: &str) -> i32 {
+    match roundtrip(wat) {
+        Ok(None) => EXIT_OK,
+        Ok(Some(mismatch)) => {
+            eprintln!("error: {}: the formatted text parses differently\n  \
+                       input {}: {:?}\n  formatted {}: {:?}",
+                      name,
+                      span(mismatch.original_start),
+                      mismatch.original,
+                      span(mismatch.formatted_start),
+                      mismatch.formatted);
+            EXIT_PARSE_ERROR
+        }
+        Err(err) => {
+            report(&err, name, wat);
+            EXIT_PARSE_ERROR
+        }
+    }
+}
 
-mod lexer;
-mod wat;
+#[cfg(not(feature = "std"))]
+fn check_roundtrip(_: &[u8], _: &str) -> i32 {
+    eprintln!("error: --roundtrip needs the binary built with the std feature");
+    process::exit(EXIT_IO_ERROR);
+}
 
 fn main() {
-    let wat = &_read_wat().unwrap();
-    let mut parser = WatParser::new(wat);
+    let args = parse_args();
+    let mut status = EXIT_OK;
+    let mut failed = 0;
+    let mut skipped = 0;
+    for path in &args.paths {
+        let name = if path == "-" { "<stdin>" } else { path };
+        if let Some(reason) = spec_skip(&args, path) {
+            eprintln!("{}: skipped, {}", name, reason);
+            skipped += 1;
+            continue;
+        }
+        let file_status = run(&args, path, name);
+        if args.paths.len() > 1 {
+            let result = if file_status == EXIT_OK { "ok" } else { "failed" };
+            eprintln!("{}: {}", name, result);
+        }
+        if file_status != EXIT_OK {
+            failed += 1;
+        }
+        status = status.max(file_status);
+    }
+    if args.paths.len() > 1 {
+        if skipped > 0 {
+            eprintln!("{} files, {} failed, {} skipped", args.paths.len(), failed, skipped);
+        } else {
+            eprintln!("{} files, {} failed", args.paths.len(), failed);
+        }
+    }
+    process::exit(status);
+}
+
+// Why a script is on the skip list, when checking.
+fn spec_skip(args: &Args, path: &str) -> Option<&'static str> {
+    if !args.check {
+        return None;
+    }
+    let file_name = Path::new(path).file_name()?;
+    SPEC_SKIPS.iter()
+        .find(|skip| file_name == skip.0)
+        .map(|skip| skip.1)
+}
+
+// Processes one file in the mode `args` asks for, and returns its exit
+// status.
+fn run(args: &Args, path: &str, name: &str) -> i32 {
+    let wat = match read_wat(path) {
+        Ok(wat) => wat,
+        Err(err) => {
+            eprintln!("error: {}: {}", name, err);
+            return EXIT_IO_ERROR;
+        }
+    };
+    if args.tokens {
+        return print_tokens(&wat);
+    }
+    if path.ends_with(".wast") {
+        if args.fmt || args.time || args.roundtrip || args.wasm.is_some() {
+            eprintln!("error: {}: scripts can only be printed or checked", name);
+            return EXIT_IO_ERROR;
+        }
+        if args.check {
+            return check_script(&wat, name, args.max_errors);
+        }
+        return print_script(&wat, name, args.json);
+    }
+    if args.time {
+        return time(&wat, name, args.iterations);
+    }
+    if args.roundtrip {
+        return check_roundtrip(&wat, name);
+    }
+    if args.check {
+        return check(&wat, name, args.max_errors);
+    }
+    let mut parser = WatParser::new(&wat);
+    if let Some(ref out) = args.wasm {
+        let wasm = match encode_module(parser.events()) {
+            Ok(wasm) => wasm,
+            Err(err) => {
+                report(&err, name, &wat);
+                return EXIT_PARSE_ERROR;
+            }
+        };
+        if let Err(err) = File::create(out).and_then(|mut f| f.write_all(&wasm)) {
+            eprintln!("error: {}: {}", out, err);
+            return EXIT_IO_ERROR;
+        }
+        return EXIT_OK;
+    }
+    if args.fmt {
+        return print_formatted(parser, &wat, name);
+    }
+    let mut trace = trace_events();
+    loop {
+        let s = parser.parse();
+        if args.json {
+            print_json(&s);
+        } else if let Err(err) = trace(&s) {
+            eprintln!("error: {}", err);
+            return EXIT_IO_ERROR;
+        }
+        match s {
+            WatParserState::End => {
+                let stats = parser.stats();
+                eprintln!("{}: {} functions, {} imports, {} instructions, {} bytes",
+                          name,
+                          stats.funcs,
+                          stats.imports,
+                          stats.instructions,
+                          stats.end.map_or(0, |end| end.position));
+                return EXIT_OK;
+            }
+            WatParserState::Error(ref err) => {
+                report(err, name, &wat);
+                return EXIT_PARSE_ERROR;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Prints the events of a .wast script, and checks that its quoted
+// modules parse. Errors in one are shown in the quoted text.
+fn print_script(wast: &[u8], name: &str, json: bool) -> i32 {
+    let mut parser = WastParser::new(wast);
     loop {
         let s = parser.parse();
-        println!("{:?}", s);
-        if let WatParserState::End = *s {
+        if json {
+            print_json(&s);
+        } else {
+            println!("{:?}", s);
+        }
+        match s {
+            WastParserState::End => return EXIT_OK,
+            WastParserState::Error(ref err) => {
+                report(err, name, wast);
+                return EXIT_PARSE_ERROR;
+            }
+            WastParserState::QuotedModule { ref source, position, .. } => {
+                if let Err(err) = parse(source) {
+                    report(&err, "<quoted module>", source);
+                    eprintln!("note: the module is quoted at {}:{}", name, span(position));
+                    return EXIT_PARSE_ERROR;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Checks that the modules of a script parse, and that the quoted modules
+// of its assert_malformed commands don't. Nothing is validated, so the
+// modules of other assertions are left alone.
+fn check_script(wast: &[u8], name: &str, max_errors: Option<usize>) -> i32 {
+    let mut parser = WastParser::new(wast);
+    let mut reported = 0;
+    loop {
+        match parser.parse() {
+            WastParserState::End => break,
+            WastParserState::Error(ref err) => {
+                report(err, name, wast);
+                reported += 1;
+                break;
+            }
+            WastParserState::QuotedModule { ref source, position, .. } => {
+                if let Err(err) = parse(source) {
+                    report(&err, "<quoted module>", source);
+                    eprintln!("note: the module is quoted at {}:{}", name, span(position));
+                    reported += 1;
+                }
+            }
+            WastParserState::AssertMalformed { module: WastModule::Quote(ref source),
+                                               ref message,
+                                               position } if parse(source).is_ok() => {
+                eprintln!("error: the quoted module parses, but is expected to be malformed: \
+                           {:?}\n --> {}:{}",
+                          message,
+                          name,
+                          span(position));
+                reported += 1;
+            }
+            _ => {}
+        }
+        if Some(reported) == max_errors {
             break;
         }
-        if let WatParserState::Error(err) = *s {
-            panic!("parse failed: {}", err.message);
+    }
+    if reported == 0 { EXIT_OK } else { EXIT_PARSE_ERROR }
+}
+
+// Parses all of `wat`, and returns the first error.
+fn parse(wat: &[u8]) -> Result<(), WatParserError> {
+    let mut parser = WatParser::new(wat);
+    loop {
+        match parser.parse() {
+            WatParserState::End => return Ok(()),
+            WatParserState::Error(err) => return Err(err),
+            _ => {}
+        }
+    }
+}
+
+// Parses `wat` `iterations` times and prints the fastest run.
+fn time(wat: &[u8], name: &str, iterations: usize) -> i32 {
+    let mut best = Duration::MAX;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let result = parse(wat);
+        best = best.min(start.elapsed());
+        if let Err(err) = result {
+            report(&err, name, wat);
+            return EXIT_PARSE_ERROR;
+        }
+    }
+    let seconds = best.as_secs_f64();
+    println!("{}: {:.3} ms, {} bytes, {:.1} MB/s, best of {}",
+             name,
+             seconds * 1e3,
+             wat.len(),
+             wat.len() as f64 / seconds / 1e6,
+             iterations);
+    EXIT_OK
+}
+
+// Reports every error parsing recovers from, up to `max_errors`.
+fn check(wat: &[u8], name: &str, max_errors: Option<usize>) -> i32 {
+    let mut parser = WatParser::new(wat);
+    parser.set_error_recovery(true);
+    let mut reported = 0;
+    loop {
+        match parser.parse() {
+            WatParserState::End => break,
+            WatParserState::Error(ref err) => {
+                // An error parsing cannot get past is returned again.
+                if parser.errors().len() == reported {
+                    break;
+                }
+                reported += 1;
+                report(err, name, wat);
+                if Some(reported) == max_errors {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    if reported == 0 { EXIT_OK } else { EXIT_PARSE_ERROR }
+}
+
+// Prints one line per token: its type, span and text, and an `Error`
+// line for a lexer error.
+fn print_tokens(wat: &[u8]) -> i32 {
+    let mut lexer = WatLexer::new(wat);
+    loop {
+        match lexer.next() {
+            Ok(token) => {
+                let text = String::from_utf8_lossy(token.content(wat));
+                println!("{:?} {}-{} {:?}", token.ty, span(token.start), span(token.end), text);
+                if token.ty == WatTokenType::End {
+                    return EXIT_OK;
+                }
+            }
+            Err(err) => {
+                println!("Error {}-{} {:?}", span(err.start), span(err.end), err.message);
+                return EXIT_PARSE_ERROR;
+            }
         }
     }
 }
 
-fn _read_wat() -> io::Result<Vec<u8>> {
+// Columns are printed 1-based, as in error messages.
+fn span(position: WatPosition) -> String {
+    format!("{}:{}", position.line, position.column + 1)
+}
+
+// Prints `err` with the source line it points at, in color when
+// standard error is a terminal and NO_COLOR is not set.
+fn report(err: &WatParserError, name: &str, wat: &[u8]) {
+    let color = env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) &&
+                io::stderr().is_terminal();
+    eprintln!("{}", err.diagnostic(name, wat, color));
+}
+
+fn read_wat(path: &str) -> io::Result<Vec<u8>> {
     let mut data = Vec::new();
-    let mut f = File::open("t.wat")?;
-    f.read_to_end(&mut data)?;
+    if path == "-" {
+        io::stdin().read_to_end(&mut data)?;
+    } else {
+        let mut f = File::open(path)?;
+        f.read_to_end(&mut data)?;
+    }
     Ok(data)
 }
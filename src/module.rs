@@ -0,0 +1,452 @@
+use core::mem;
+use alloc::vec::Vec;
+use lexer::WatPosition;
+use opcode::WatOpcode;
+use wat::{Data, Keyword, Name, OptionalID, Result, WatDataKind, WatElemKind, WatElemList,
+          WatExportKind, WatGlobalType, WatImport, WatIndex, WatInstructionArg, WatLocal,
+          WatMemoryType, WatParser, WatParserState, WatTableType, WatTypeuse};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use wat::{serialize_hex, serialize_optional_text, serialize_text};
+
+/// An instruction of a function body or global initializer.
+///
+/// Bodies are small trees: a parenthesized instruction has `folded` set
+/// to its nested instructions, in source order, and a plain one has
+/// `None`. `(i32.add (local.get 0) (i32.const 1))` is one operator
+/// holding two.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatOperator {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))]
+    pub instruction: Keyword,
+    pub opcode: WatOpcode,
+    pub args: Vec<WatInstructionArg>,
+    pub folded: Option<Vec<WatOperator>>,
+    pub position: WatPosition,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatType {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub functype: WatTypeuse,
+    pub position: WatPosition,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatImportField {
+    pub modname: Name,
+    pub fieldname: Name,
+    pub import: WatImport,
+    pub export_names: Vec<Name>,
+    pub position: WatPosition,
+}
+
+/// A function and its body.
+///
+/// ```
+/// use wasmtextparser::parse_module;
+///
+/// let module = parse_module(b"(module (func (export \"f\") (export \"g\") (nop)))").unwrap();
+/// let func = &module.funcs[0];
+/// assert_eq!(func.export_names, ["f", "g"]);
+/// assert_eq!(func.body.len(), 1);
+/// ```
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatFunc {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub export_names: Vec<Name>,
+    pub typeuse: WatTypeuse,
+    pub locals: Vec<WatLocal>,
+    pub body: Vec<WatOperator>,
+    pub position: WatPosition,
+}
+
+/// A global and its initializer.
+///
+/// ```
+/// use wasmtextparser::parse_module;
+///
+/// let module = parse_module(b"(module (global (export \"g\") (export \"h\") i32 (i32.const 7)))").unwrap();
+/// let global = &module.globals[0];
+/// assert_eq!(global.export_names, ["g", "h"]);
+/// assert_eq!(global.init.len(), 1);
+/// assert_eq!(global.init[0].instruction, b"i32.const");
+/// ```
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatGlobal {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub export_names: Vec<Name>,
+    pub globaltype: WatGlobalType,
+    pub init: Vec<WatOperator>,
+    pub position: WatPosition,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatTable {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub export_names: Vec<Name>,
+    pub tabletype: WatTableType,
+    pub position: WatPosition,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatMemory {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub export_names: Vec<Name>,
+    pub memtype: WatMemoryType,
+    pub position: WatPosition,
+}
+
+/// An `(export ...)` field; inline exports stay with their item.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatExport {
+    pub name: Name,
+    pub kind: WatExportKind,
+    pub index: WatIndex,
+    pub position: WatPosition,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatElem {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub kind: WatElemKind,
+    pub elems: WatElemList,
+    pub position: WatPosition,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatData {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub kind: WatDataKind,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+    pub data: Data,
+    pub position: WatPosition,
+}
+
+/// A whole module, as built by `parse_module`. Fields are grouped by
+/// kind, each in source order; `position` is that of `(module`.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatModule {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+    pub id: OptionalID,
+    pub types: Vec<WatType>,
+    pub imports: Vec<WatImportField>,
+    pub funcs: Vec<WatFunc>,
+    pub globals: Vec<WatGlobal>,
+    pub tables: Vec<WatTable>,
+    pub memories: Vec<WatMemory>,
+    pub exports: Vec<WatExport>,
+    /// The function of the `(start ...)` field, if there is one.
+    pub start: Option<WatIndex>,
+    pub elems: Vec<WatElem>,
+    pub datas: Vec<WatData>,
+    pub position: WatPosition,
+}
+
+// Collects CodeOperator events into a tree; `open` holds the folded
+// instructions whose ')' has not been seen yet.
+#[derive(Default)]
+struct CodeBuilder {
+    code: Vec<WatOperator>,
+    open: Vec<WatOperator>,
+}
+
+impl CodeBuilder {
+    fn push(&mut self, operator: WatOperator) {
+        match self.open.last_mut() {
+            Some(parent) => parent.folded.as_mut().unwrap().push(operator),
+            None => self.code.push(operator),
+        }
+    }
+
+    fn add_event(&mut self, event: WatParserState) {
+        match event {
+            WatParserState::CodeOperator {
+                instruction,
+                opcode,
+                args,
+                group,
+                position,
+                ..
+            } => {
+                let operator = WatOperator {
+                    instruction,
+                    opcode,
+                    args,
+                    folded: if group { Some(vec![]) } else { None },
+                    position,
+                };
+                if group {
+                    self.open.push(operator);
+                } else {
+                    self.push(operator);
+                }
+            }
+            WatParserState::CodeOperatorEnd { .. } => {
+                let operator = self.open.pop().unwrap();
+                self.push(operator);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) -> Vec<WatOperator> {
+        mem::take(&mut self.code)
+    }
+}
+
+/// Parses a complete module into a `WatModule`, returning the first
+/// error if there is one. This is built on `WatParser` events; use the
+/// parser directly to avoid holding the whole module in memory.
+///
+/// ```
+/// let module = wasmtextparser::parse_module(b"(module (func $f (i32.add (i32.const 1) (i32.const 2)) drop))")
+///     .unwrap();
+/// assert_eq!(module.funcs[0].id, Some(b"$f".to_vec()));
+/// assert_eq!(module.funcs[0].body.len(), 2);
+/// assert_eq!(module.funcs[0].body[0].folded.as_ref().unwrap().len(), 2);
+/// ```
+pub fn parse_module(source: &[u8]) -> Result<WatModule> {
+    let mut parser = WatParser::new(source);
+    let mut module = WatModule {
+        id: None,
+        types: vec![],
+        imports: vec![],
+        funcs: vec![],
+        globals: vec![],
+        tables: vec![],
+        memories: vec![],
+        exports: vec![],
+        start: None,
+        elems: vec![],
+        datas: vec![],
+        // Replaced by StartModule, which always comes first.
+        position: WatPosition {
+            line: 1,
+            column: 0,
+            position: 0,
+        },
+    };
+    let mut code = CodeBuilder::default();
+    for event in parser.events() {
+        match event? {
+            WatParserState::StartModule { id, position } => {
+                module.id = id;
+                module.position = position;
+            }
+            WatParserState::Type {
+                id,
+                functype,
+                position,
+            } => {
+                module.types.push(WatType {
+                                      id,
+                                      functype,
+                                      position,
+                                  })
+            }
+            WatParserState::Import {
+                modname,
+                fieldname,
+                import,
+                export_names,
+                position,
+            } => {
+                module.imports.push(WatImportField {
+                                        modname,
+                                        fieldname,
+                                        import,
+                                        export_names,
+                                        position,
+                                    })
+            }
+            WatParserState::StartFunc {
+                id,
+                export_names,
+                typeuse,
+                locals,
+                position,
+            } => {
+                module.funcs.push(WatFunc {
+                                      id,
+                                      export_names,
+                                      typeuse,
+                                      locals,
+                                      body: vec![],
+                                      position,
+                                  })
+            }
+            WatParserState::EndFunc { .. } => {
+                module.funcs.last_mut().unwrap().body = code.finish();
+            }
+            WatParserState::StartGlobal {
+                id,
+                export_names,
+                globaltype,
+                position,
+            } => {
+                module.globals.push(WatGlobal {
+                                        id,
+                                        export_names,
+                                        globaltype,
+                                        init: vec![],
+                                        position,
+                                    })
+            }
+            WatParserState::EndGlobal { .. } => {
+                module.globals.last_mut().unwrap().init = code.finish();
+            }
+            WatParserState::Table {
+                id,
+                export_names,
+                tabletype,
+                position,
+            } => {
+                module.tables.push(WatTable {
+                                       id,
+                                       export_names,
+                                       tabletype,
+                                       position,
+                                   })
+            }
+            WatParserState::Memory {
+                id,
+                export_names,
+                memtype,
+                position,
+            } => {
+                module.memories.push(WatMemory {
+                                         id,
+                                         export_names,
+                                         memtype,
+                                         position,
+                                     })
+            }
+            WatParserState::Export {
+                name,
+                kind,
+                index,
+                position,
+            } => {
+                module.exports.push(WatExport {
+                                        name,
+                                        kind,
+                                        index,
+                                        position,
+                                    })
+            }
+            WatParserState::Start { func, .. } => module.start = Some(func),
+            WatParserState::Elem {
+                id,
+                kind,
+                elems,
+                position,
+            } => {
+                module.elems.push(WatElem {
+                                      id,
+                                      kind,
+                                      elems,
+                                      position,
+                                  })
+            }
+            WatParserState::Data {
+                id,
+                kind,
+                data,
+                position,
+            } => {
+                module.datas.push(WatData {
+                                      id,
+                                      kind,
+                                      data,
+                                      position,
+                                  })
+            }
+            event => code.add_event(event),
+        }
+    }
+    Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use super::*;
+
+    fn id_text(id: &OptionalID) -> Option<&[u8]> {
+        id.as_ref().map(|id| &id[..])
+    }
+
+    fn instructions(operators: &[WatOperator]) -> Vec<String> {
+        operators.iter().map(|op| String::from_utf8_lossy(&op.instruction).into_owned()).collect()
+    }
+
+    #[test]
+    fn fields_corpus() {
+        let module = parse_module(include_bytes!("../corpus/fields.wat")).unwrap();
+        assert_eq!(id_text(&module.id), Some(&b"$m"[..]));
+
+        let types: Vec<_> = module.types.iter().map(|t| id_text(&t.id)).collect();
+        assert_eq!(types, [Some(&b"$t"[..]), Some(&b"$long"[..])]);
+        assert_eq!(module.types[0].functype.params.len(), 4);
+        assert_eq!(module.types[1].functype.results.len(), 2);
+
+        let imports: Vec<_> = module.imports
+            .iter()
+            .map(|import| (&import.modname[..], &import.fieldname[..]))
+            .collect();
+        assert_eq!(imports, [("env", "mem"), ("env", "f")]);
+        assert!(matches!(module.imports[1].import,
+                         WatImport::Func { id: Some(ref id), .. } if id == b"$imp"));
+
+        let globals: Vec<_> =
+            module.globals.iter().map(|g| (id_text(&g.id), g.export_names.clone())).collect();
+        assert_eq!(globals, [(Some(&b"$g"[..]), vec![]), (None, vec![Name::from("k")])]);
+        assert_eq!(instructions(&module.globals[1].init), ["i64.const"]);
+
+        assert_eq!(module.tables.len(), 1);
+        assert_eq!(id_text(&module.tables[0].id), Some(&b"$tb"[..]));
+        assert!(module.memories.is_empty());
+        assert!(module.exports.is_empty());
+        assert_eq!(module.start, None);
+        assert_eq!(module.elems.len(), 1);
+        assert_eq!(module.datas.len(), 1);
+        assert_eq!(module.datas[0].data, b"hi\0\xff\"there\n");
+
+        assert_eq!(module.funcs.len(), 2);
+        let f = &module.funcs[0];
+        assert_eq!(id_text(&f.id), Some(&b"$f"[..]));
+        assert_eq!(f.export_names, ["f"]);
+        assert_eq!((f.typeuse.params.len(), f.locals.len()), (3, 2));
+        assert_eq!(instructions(&f.body),
+                   ["block", "loop", "local.get", "if", "i32.const", "else", "i32.const", "end",
+                    "br_if", "br", "end", "end", "if", "f32.const", "drop", "i32.load",
+                    "i32.add"]);
+        let folded_if = f.body[12].folded.as_ref().unwrap();
+        assert_eq!(instructions(folded_if), ["local.get", "then", "else"]);
+        assert_eq!(instructions(folded_if[1].folded.as_ref().unwrap()), ["i32.add"]);
+        let g = &module.funcs[1];
+        assert_eq!(id_text(&g.typeuse.id), Some(&b"$t"[..]));
+        assert_eq!(instructions(&g.body), ["i32.const"]);
+    }
+}
@@ -0,0 +1,1588 @@
+// Covers MVP, sign extension, saturating truncation, bulk memory,
+// reference types, tail calls, threads and SIMD, plus the pre-standard
+// spellings. The variants, `lookup` and `name` were generated together;
+// keep them in step when adding instructions. `lookup` also decides
+// which names `WatParserOptions::reject_unknown_instructions` accepts.
+
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use wat::serialize_text;
+
+/// An instruction name, decoded. Pre-standard spellings such as
+/// `get_local` or `i32.wrap/i64` map to the same opcode as the standard
+/// ones; names the parser does not know are kept in `Unknown`.
+///
+/// ```
+/// use wasmtextparser::WatOpcode;
+///
+/// assert_eq!(WatOpcode::from_bytes(b"i32.add"), WatOpcode::I32Add);
+/// assert_eq!(WatOpcode::from_bytes(b"get_local"), WatOpcode::LocalGet);
+/// assert_eq!(WatOpcode::LocalGet.name(), b"local.get");
+/// assert_eq!(WatOpcode::from_bytes(b"i32.cnst"), WatOpcode::Unknown(b"i32.cnst".to_vec()));
+/// ```
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatOpcode {
+    AtomicFence,
+    Block,
+    Br,
+    BrIf,
+    BrTable,
+    Call,
+    CallIndirect,
+    DataDrop,
+    Drop,
+    ElemDrop,
+    Else,
+    End,
+    F32Abs,
+    F32Add,
+    F32Ceil,
+    F32Const,
+    F32ConvertI32S,
+    F32ConvertI32U,
+    F32ConvertI64S,
+    F32ConvertI64U,
+    F32Copysign,
+    F32DemoteF64,
+    F32Div,
+    F32Eq,
+    F32Floor,
+    F32Ge,
+    F32Gt,
+    F32Le,
+    F32Load,
+    F32Lt,
+    F32Max,
+    F32Min,
+    F32Mul,
+    F32Ne,
+    F32Nearest,
+    F32Neg,
+    F32ReinterpretI32,
+    F32Sqrt,
+    F32Store,
+    F32Sub,
+    F32Trunc,
+    F32x4Abs,
+    F32x4Add,
+    F32x4Ceil,
+    F32x4ConvertI32x4S,
+    F32x4ConvertI32x4U,
+    F32x4DemoteF64x2Zero,
+    F32x4Div,
+    F32x4Eq,
+    F32x4ExtractLane,
+    F32x4Floor,
+    F32x4Ge,
+    F32x4Gt,
+    F32x4Le,
+    F32x4Lt,
+    F32x4Max,
+    F32x4Min,
+    F32x4Mul,
+    F32x4Ne,
+    F32x4Nearest,
+    F32x4Neg,
+    F32x4Pmax,
+    F32x4Pmin,
+    F32x4ReplaceLane,
+    F32x4Splat,
+    F32x4Sqrt,
+    F32x4Sub,
+    F32x4Trunc,
+    F64Abs,
+    F64Add,
+    F64Ceil,
+    F64Const,
+    F64ConvertI32S,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI64U,
+    F64Copysign,
+    F64Div,
+    F64Eq,
+    F64Floor,
+    F64Ge,
+    F64Gt,
+    F64Le,
+    F64Load,
+    F64Lt,
+    F64Max,
+    F64Min,
+    F64Mul,
+    F64Ne,
+    F64Nearest,
+    F64Neg,
+    F64PromoteF32,
+    F64ReinterpretI64,
+    F64Sqrt,
+    F64Store,
+    F64Sub,
+    F64Trunc,
+    F64x2Abs,
+    F64x2Add,
+    F64x2Ceil,
+    F64x2ConvertLowI32x4S,
+    F64x2ConvertLowI32x4U,
+    F64x2Div,
+    F64x2Eq,
+    F64x2ExtractLane,
+    F64x2Floor,
+    F64x2Ge,
+    F64x2Gt,
+    F64x2Le,
+    F64x2Lt,
+    F64x2Max,
+    F64x2Min,
+    F64x2Mul,
+    F64x2Ne,
+    F64x2Nearest,
+    F64x2Neg,
+    F64x2Pmax,
+    F64x2Pmin,
+    F64x2PromoteLowF32x4,
+    F64x2ReplaceLane,
+    F64x2Splat,
+    F64x2Sqrt,
+    F64x2Sub,
+    F64x2Trunc,
+    GlobalGet,
+    GlobalSet,
+    I16x8Abs,
+    I16x8Add,
+    I16x8AddSatS,
+    I16x8AddSatU,
+    I16x8AllTrue,
+    I16x8AvgrU,
+    I16x8Bitmask,
+    I16x8Eq,
+    I16x8ExtaddPairwiseI8x16S,
+    I16x8ExtaddPairwiseI8x16U,
+    I16x8ExtendHighI8x16S,
+    I16x8ExtendHighI8x16U,
+    I16x8ExtendLowI8x16S,
+    I16x8ExtendLowI8x16U,
+    I16x8ExtmulHighI8x16S,
+    I16x8ExtmulHighI8x16U,
+    I16x8ExtmulLowI8x16S,
+    I16x8ExtmulLowI8x16U,
+    I16x8ExtractLaneS,
+    I16x8ExtractLaneU,
+    I16x8GeS,
+    I16x8GeU,
+    I16x8GtS,
+    I16x8GtU,
+    I16x8LeS,
+    I16x8LeU,
+    I16x8LtS,
+    I16x8LtU,
+    I16x8MaxS,
+    I16x8MaxU,
+    I16x8MinS,
+    I16x8MinU,
+    I16x8Mul,
+    I16x8NarrowI32x4S,
+    I16x8NarrowI32x4U,
+    I16x8Ne,
+    I16x8Neg,
+    I16x8Q15mulrSatS,
+    I16x8ReplaceLane,
+    I16x8Shl,
+    I16x8ShrS,
+    I16x8ShrU,
+    I16x8Splat,
+    I16x8Sub,
+    I16x8SubSatS,
+    I16x8SubSatU,
+    I32Add,
+    I32And,
+    I32AtomicLoad,
+    I32AtomicLoad16U,
+    I32AtomicLoad8U,
+    I32AtomicRmwAdd,
+    I32AtomicRmwAnd,
+    I32AtomicRmwCmpxchg,
+    I32AtomicRmwOr,
+    I32AtomicRmwSub,
+    I32AtomicRmwXchg,
+    I32AtomicRmwXor,
+    I32AtomicRmw16AddU,
+    I32AtomicRmw16AndU,
+    I32AtomicRmw16CmpxchgU,
+    I32AtomicRmw16OrU,
+    I32AtomicRmw16SubU,
+    I32AtomicRmw16XchgU,
+    I32AtomicRmw16XorU,
+    I32AtomicRmw8AddU,
+    I32AtomicRmw8AndU,
+    I32AtomicRmw8CmpxchgU,
+    I32AtomicRmw8OrU,
+    I32AtomicRmw8SubU,
+    I32AtomicRmw8XchgU,
+    I32AtomicRmw8XorU,
+    I32AtomicStore,
+    I32AtomicStore16,
+    I32AtomicStore8,
+    I32Clz,
+    I32Const,
+    I32Ctz,
+    I32DivS,
+    I32DivU,
+    I32Eq,
+    I32Eqz,
+    I32Extend16S,
+    I32Extend8S,
+    I32GeS,
+    I32GeU,
+    I32GtS,
+    I32GtU,
+    I32LeS,
+    I32LeU,
+    I32Load,
+    I32Load16S,
+    I32Load16U,
+    I32Load8S,
+    I32Load8U,
+    I32LtS,
+    I32LtU,
+    I32Mul,
+    I32Ne,
+    I32Or,
+    I32Popcnt,
+    I32ReinterpretF32,
+    I32RemS,
+    I32RemU,
+    I32Rotl,
+    I32Rotr,
+    I32Shl,
+    I32ShrS,
+    I32ShrU,
+    I32Store,
+    I32Store16,
+    I32Store8,
+    I32Sub,
+    I32TruncF32S,
+    I32TruncF32U,
+    I32TruncF64S,
+    I32TruncF64U,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I32WrapI64,
+    I32Xor,
+    I32x4Abs,
+    I32x4Add,
+    I32x4AllTrue,
+    I32x4Bitmask,
+    I32x4DotI16x8S,
+    I32x4Eq,
+    I32x4ExtaddPairwiseI16x8S,
+    I32x4ExtaddPairwiseI16x8U,
+    I32x4ExtendHighI16x8S,
+    I32x4ExtendHighI16x8U,
+    I32x4ExtendLowI16x8S,
+    I32x4ExtendLowI16x8U,
+    I32x4ExtmulHighI16x8S,
+    I32x4ExtmulHighI16x8U,
+    I32x4ExtmulLowI16x8S,
+    I32x4ExtmulLowI16x8U,
+    I32x4ExtractLane,
+    I32x4GeS,
+    I32x4GeU,
+    I32x4GtS,
+    I32x4GtU,
+    I32x4LeS,
+    I32x4LeU,
+    I32x4LtS,
+    I32x4LtU,
+    I32x4MaxS,
+    I32x4MaxU,
+    I32x4MinS,
+    I32x4MinU,
+    I32x4Mul,
+    I32x4Ne,
+    I32x4Neg,
+    I32x4ReplaceLane,
+    I32x4Shl,
+    I32x4ShrS,
+    I32x4ShrU,
+    I32x4Splat,
+    I32x4Sub,
+    I32x4TruncSatF32x4S,
+    I32x4TruncSatF32x4U,
+    I32x4TruncSatF64x2SZero,
+    I32x4TruncSatF64x2UZero,
+    I64Add,
+    I64And,
+    I64AtomicLoad,
+    I64AtomicLoad16U,
+    I64AtomicLoad32U,
+    I64AtomicLoad8U,
+    I64AtomicRmwAdd,
+    I64AtomicRmwAnd,
+    I64AtomicRmwCmpxchg,
+    I64AtomicRmwOr,
+    I64AtomicRmwSub,
+    I64AtomicRmwXchg,
+    I64AtomicRmwXor,
+    I64AtomicRmw16AddU,
+    I64AtomicRmw16AndU,
+    I64AtomicRmw16CmpxchgU,
+    I64AtomicRmw16OrU,
+    I64AtomicRmw16SubU,
+    I64AtomicRmw16XchgU,
+    I64AtomicRmw16XorU,
+    I64AtomicRmw32AddU,
+    I64AtomicRmw32AndU,
+    I64AtomicRmw32CmpxchgU,
+    I64AtomicRmw32OrU,
+    I64AtomicRmw32SubU,
+    I64AtomicRmw32XchgU,
+    I64AtomicRmw32XorU,
+    I64AtomicRmw8AddU,
+    I64AtomicRmw8AndU,
+    I64AtomicRmw8CmpxchgU,
+    I64AtomicRmw8OrU,
+    I64AtomicRmw8SubU,
+    I64AtomicRmw8XchgU,
+    I64AtomicRmw8XorU,
+    I64AtomicStore,
+    I64AtomicStore16,
+    I64AtomicStore32,
+    I64AtomicStore8,
+    I64Clz,
+    I64Const,
+    I64Ctz,
+    I64DivS,
+    I64DivU,
+    I64Eq,
+    I64Eqz,
+    I64Extend16S,
+    I64Extend32S,
+    I64Extend8S,
+    I64ExtendI32S,
+    I64ExtendI32U,
+    I64GeS,
+    I64GeU,
+    I64GtS,
+    I64GtU,
+    I64LeS,
+    I64LeU,
+    I64Load,
+    I64Load16S,
+    I64Load16U,
+    I64Load32S,
+    I64Load32U,
+    I64Load8S,
+    I64Load8U,
+    I64LtS,
+    I64LtU,
+    I64Mul,
+    I64Ne,
+    I64Or,
+    I64Popcnt,
+    I64ReinterpretF64,
+    I64RemS,
+    I64RemU,
+    I64Rotl,
+    I64Rotr,
+    I64Shl,
+    I64ShrS,
+    I64ShrU,
+    I64Store,
+    I64Store16,
+    I64Store32,
+    I64Store8,
+    I64Sub,
+    I64TruncF32S,
+    I64TruncF32U,
+    I64TruncF64S,
+    I64TruncF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+    I64Xor,
+    I64x2Abs,
+    I64x2Add,
+    I64x2AllTrue,
+    I64x2Bitmask,
+    I64x2Eq,
+    I64x2ExtendHighI32x4S,
+    I64x2ExtendHighI32x4U,
+    I64x2ExtendLowI32x4S,
+    I64x2ExtendLowI32x4U,
+    I64x2ExtmulHighI32x4S,
+    I64x2ExtmulHighI32x4U,
+    I64x2ExtmulLowI32x4S,
+    I64x2ExtmulLowI32x4U,
+    I64x2ExtractLane,
+    I64x2GeS,
+    I64x2GtS,
+    I64x2LeS,
+    I64x2LtS,
+    I64x2Mul,
+    I64x2Ne,
+    I64x2Neg,
+    I64x2ReplaceLane,
+    I64x2Shl,
+    I64x2ShrS,
+    I64x2ShrU,
+    I64x2Splat,
+    I64x2Sub,
+    I8x16Abs,
+    I8x16Add,
+    I8x16AddSatS,
+    I8x16AddSatU,
+    I8x16AllTrue,
+    I8x16AvgrU,
+    I8x16Bitmask,
+    I8x16Eq,
+    I8x16ExtractLaneS,
+    I8x16ExtractLaneU,
+    I8x16GeS,
+    I8x16GeU,
+    I8x16GtS,
+    I8x16GtU,
+    I8x16LeS,
+    I8x16LeU,
+    I8x16LtS,
+    I8x16LtU,
+    I8x16MaxS,
+    I8x16MaxU,
+    I8x16MinS,
+    I8x16MinU,
+    I8x16NarrowI16x8S,
+    I8x16NarrowI16x8U,
+    I8x16Ne,
+    I8x16Neg,
+    I8x16Popcnt,
+    I8x16ReplaceLane,
+    I8x16Shl,
+    I8x16ShrS,
+    I8x16ShrU,
+    I8x16Shuffle,
+    I8x16Splat,
+    I8x16Sub,
+    I8x16SubSatS,
+    I8x16SubSatU,
+    I8x16Swizzle,
+    If,
+    LocalGet,
+    LocalSet,
+    LocalTee,
+    Loop,
+    MemoryAtomicNotify,
+    MemoryAtomicWait32,
+    MemoryAtomicWait64,
+    MemoryCopy,
+    MemoryFill,
+    MemoryGrow,
+    MemoryInit,
+    MemorySize,
+    Nop,
+    RefFunc,
+    RefIsNull,
+    RefNull,
+    Return,
+    ReturnCall,
+    ReturnCallIndirect,
+    Select,
+    TableCopy,
+    TableFill,
+    TableGet,
+    TableGrow,
+    TableInit,
+    TableSet,
+    TableSize,
+    Unreachable,
+    V128And,
+    V128Andnot,
+    V128AnyTrue,
+    V128Bitselect,
+    V128Const,
+    V128Load,
+    V128Load16Lane,
+    V128Load16Splat,
+    V128Load16x4S,
+    V128Load16x4U,
+    V128Load32Lane,
+    V128Load32Splat,
+    V128Load32Zero,
+    V128Load32x2S,
+    V128Load32x2U,
+    V128Load64Lane,
+    V128Load64Splat,
+    V128Load64Zero,
+    V128Load8Lane,
+    V128Load8Splat,
+    V128Load8x8S,
+    V128Load8x8U,
+    V128Not,
+    V128Or,
+    V128Store,
+    V128Store16Lane,
+    V128Store32Lane,
+    V128Store64Lane,
+    V128Store8Lane,
+    V128Xor,
+    Unknown(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] Vec<u8>),
+}
+
+impl WatOpcode {
+    pub fn from_bytes(name: &[u8]) -> WatOpcode {
+        match WatOpcode::lookup(name) {
+            Some(opcode) => opcode,
+            None => WatOpcode::Unknown(name.to_vec()),
+        }
+    }
+
+    /// Like `from_bytes`, without allocating for unknown names.
+    pub fn lookup(name: &[u8]) -> Option<WatOpcode> {
+        Some(match name {
+                 b"atomic.fence" => WatOpcode::AtomicFence,
+                 b"block" => WatOpcode::Block,
+                 b"br" => WatOpcode::Br,
+                 b"br_if" => WatOpcode::BrIf,
+                 b"br_table" => WatOpcode::BrTable,
+                 b"call" => WatOpcode::Call,
+                 b"call_indirect" => WatOpcode::CallIndirect,
+                 b"data.drop" => WatOpcode::DataDrop,
+                 b"drop" => WatOpcode::Drop,
+                 b"elem.drop" => WatOpcode::ElemDrop,
+                 b"else" => WatOpcode::Else,
+                 b"end" => WatOpcode::End,
+                 b"f32.abs" => WatOpcode::F32Abs,
+                 b"f32.add" => WatOpcode::F32Add,
+                 b"f32.ceil" => WatOpcode::F32Ceil,
+                 b"f32.const" => WatOpcode::F32Const,
+                 b"f32.convert_i32_s" | b"f32.convert_s/i32" => WatOpcode::F32ConvertI32S,
+                 b"f32.convert_i32_u" | b"f32.convert_u/i32" => WatOpcode::F32ConvertI32U,
+                 b"f32.convert_i64_s" | b"f32.convert_s/i64" => WatOpcode::F32ConvertI64S,
+                 b"f32.convert_i64_u" | b"f32.convert_u/i64" => WatOpcode::F32ConvertI64U,
+                 b"f32.copysign" => WatOpcode::F32Copysign,
+                 b"f32.demote_f64" | b"f32.demote/f64" => WatOpcode::F32DemoteF64,
+                 b"f32.div" => WatOpcode::F32Div,
+                 b"f32.eq" => WatOpcode::F32Eq,
+                 b"f32.floor" => WatOpcode::F32Floor,
+                 b"f32.ge" => WatOpcode::F32Ge,
+                 b"f32.gt" => WatOpcode::F32Gt,
+                 b"f32.le" => WatOpcode::F32Le,
+                 b"f32.load" => WatOpcode::F32Load,
+                 b"f32.lt" => WatOpcode::F32Lt,
+                 b"f32.max" => WatOpcode::F32Max,
+                 b"f32.min" => WatOpcode::F32Min,
+                 b"f32.mul" => WatOpcode::F32Mul,
+                 b"f32.ne" => WatOpcode::F32Ne,
+                 b"f32.nearest" => WatOpcode::F32Nearest,
+                 b"f32.neg" => WatOpcode::F32Neg,
+                 b"f32.reinterpret_i32" | b"f32.reinterpret/i32" => WatOpcode::F32ReinterpretI32,
+                 b"f32.sqrt" => WatOpcode::F32Sqrt,
+                 b"f32.store" => WatOpcode::F32Store,
+                 b"f32.sub" => WatOpcode::F32Sub,
+                 b"f32.trunc" => WatOpcode::F32Trunc,
+                 b"f32x4.abs" => WatOpcode::F32x4Abs,
+                 b"f32x4.add" => WatOpcode::F32x4Add,
+                 b"f32x4.ceil" => WatOpcode::F32x4Ceil,
+                 b"f32x4.convert_i32x4_s" => WatOpcode::F32x4ConvertI32x4S,
+                 b"f32x4.convert_i32x4_u" => WatOpcode::F32x4ConvertI32x4U,
+                 b"f32x4.demote_f64x2_zero" => WatOpcode::F32x4DemoteF64x2Zero,
+                 b"f32x4.div" => WatOpcode::F32x4Div,
+                 b"f32x4.eq" => WatOpcode::F32x4Eq,
+                 b"f32x4.extract_lane" => WatOpcode::F32x4ExtractLane,
+                 b"f32x4.floor" => WatOpcode::F32x4Floor,
+                 b"f32x4.ge" => WatOpcode::F32x4Ge,
+                 b"f32x4.gt" => WatOpcode::F32x4Gt,
+                 b"f32x4.le" => WatOpcode::F32x4Le,
+                 b"f32x4.lt" => WatOpcode::F32x4Lt,
+                 b"f32x4.max" => WatOpcode::F32x4Max,
+                 b"f32x4.min" => WatOpcode::F32x4Min,
+                 b"f32x4.mul" => WatOpcode::F32x4Mul,
+                 b"f32x4.ne" => WatOpcode::F32x4Ne,
+                 b"f32x4.nearest" => WatOpcode::F32x4Nearest,
+                 b"f32x4.neg" => WatOpcode::F32x4Neg,
+                 b"f32x4.pmax" => WatOpcode::F32x4Pmax,
+                 b"f32x4.pmin" => WatOpcode::F32x4Pmin,
+                 b"f32x4.replace_lane" => WatOpcode::F32x4ReplaceLane,
+                 b"f32x4.splat" => WatOpcode::F32x4Splat,
+                 b"f32x4.sqrt" => WatOpcode::F32x4Sqrt,
+                 b"f32x4.sub" => WatOpcode::F32x4Sub,
+                 b"f32x4.trunc" => WatOpcode::F32x4Trunc,
+                 b"f64.abs" => WatOpcode::F64Abs,
+                 b"f64.add" => WatOpcode::F64Add,
+                 b"f64.ceil" => WatOpcode::F64Ceil,
+                 b"f64.const" => WatOpcode::F64Const,
+                 b"f64.convert_i32_s" | b"f64.convert_s/i32" => WatOpcode::F64ConvertI32S,
+                 b"f64.convert_i32_u" | b"f64.convert_u/i32" => WatOpcode::F64ConvertI32U,
+                 b"f64.convert_i64_s" | b"f64.convert_s/i64" => WatOpcode::F64ConvertI64S,
+                 b"f64.convert_i64_u" | b"f64.convert_u/i64" => WatOpcode::F64ConvertI64U,
+                 b"f64.copysign" => WatOpcode::F64Copysign,
+                 b"f64.div" => WatOpcode::F64Div,
+                 b"f64.eq" => WatOpcode::F64Eq,
+                 b"f64.floor" => WatOpcode::F64Floor,
+                 b"f64.ge" => WatOpcode::F64Ge,
+                 b"f64.gt" => WatOpcode::F64Gt,
+                 b"f64.le" => WatOpcode::F64Le,
+                 b"f64.load" => WatOpcode::F64Load,
+                 b"f64.lt" => WatOpcode::F64Lt,
+                 b"f64.max" => WatOpcode::F64Max,
+                 b"f64.min" => WatOpcode::F64Min,
+                 b"f64.mul" => WatOpcode::F64Mul,
+                 b"f64.ne" => WatOpcode::F64Ne,
+                 b"f64.nearest" => WatOpcode::F64Nearest,
+                 b"f64.neg" => WatOpcode::F64Neg,
+                 b"f64.promote_f32" | b"f64.promote/f32" => WatOpcode::F64PromoteF32,
+                 b"f64.reinterpret_i64" | b"f64.reinterpret/i64" => WatOpcode::F64ReinterpretI64,
+                 b"f64.sqrt" => WatOpcode::F64Sqrt,
+                 b"f64.store" => WatOpcode::F64Store,
+                 b"f64.sub" => WatOpcode::F64Sub,
+                 b"f64.trunc" => WatOpcode::F64Trunc,
+                 b"f64x2.abs" => WatOpcode::F64x2Abs,
+                 b"f64x2.add" => WatOpcode::F64x2Add,
+                 b"f64x2.ceil" => WatOpcode::F64x2Ceil,
+                 b"f64x2.convert_low_i32x4_s" => WatOpcode::F64x2ConvertLowI32x4S,
+                 b"f64x2.convert_low_i32x4_u" => WatOpcode::F64x2ConvertLowI32x4U,
+                 b"f64x2.div" => WatOpcode::F64x2Div,
+                 b"f64x2.eq" => WatOpcode::F64x2Eq,
+                 b"f64x2.extract_lane" => WatOpcode::F64x2ExtractLane,
+                 b"f64x2.floor" => WatOpcode::F64x2Floor,
+                 b"f64x2.ge" => WatOpcode::F64x2Ge,
+                 b"f64x2.gt" => WatOpcode::F64x2Gt,
+                 b"f64x2.le" => WatOpcode::F64x2Le,
+                 b"f64x2.lt" => WatOpcode::F64x2Lt,
+                 b"f64x2.max" => WatOpcode::F64x2Max,
+                 b"f64x2.min" => WatOpcode::F64x2Min,
+                 b"f64x2.mul" => WatOpcode::F64x2Mul,
+                 b"f64x2.ne" => WatOpcode::F64x2Ne,
+                 b"f64x2.nearest" => WatOpcode::F64x2Nearest,
+                 b"f64x2.neg" => WatOpcode::F64x2Neg,
+                 b"f64x2.pmax" => WatOpcode::F64x2Pmax,
+                 b"f64x2.pmin" => WatOpcode::F64x2Pmin,
+                 b"f64x2.promote_low_f32x4" => WatOpcode::F64x2PromoteLowF32x4,
+                 b"f64x2.replace_lane" => WatOpcode::F64x2ReplaceLane,
+                 b"f64x2.splat" => WatOpcode::F64x2Splat,
+                 b"f64x2.sqrt" => WatOpcode::F64x2Sqrt,
+                 b"f64x2.sub" => WatOpcode::F64x2Sub,
+                 b"f64x2.trunc" => WatOpcode::F64x2Trunc,
+                 b"global.get" | b"get_global" => WatOpcode::GlobalGet,
+                 b"global.set" | b"set_global" => WatOpcode::GlobalSet,
+                 b"i16x8.abs" => WatOpcode::I16x8Abs,
+                 b"i16x8.add" => WatOpcode::I16x8Add,
+                 b"i16x8.add_sat_s" => WatOpcode::I16x8AddSatS,
+                 b"i16x8.add_sat_u" => WatOpcode::I16x8AddSatU,
+                 b"i16x8.all_true" => WatOpcode::I16x8AllTrue,
+                 b"i16x8.avgr_u" => WatOpcode::I16x8AvgrU,
+                 b"i16x8.bitmask" => WatOpcode::I16x8Bitmask,
+                 b"i16x8.eq" => WatOpcode::I16x8Eq,
+                 b"i16x8.extadd_pairwise_i8x16_s" => WatOpcode::I16x8ExtaddPairwiseI8x16S,
+                 b"i16x8.extadd_pairwise_i8x16_u" => WatOpcode::I16x8ExtaddPairwiseI8x16U,
+                 b"i16x8.extend_high_i8x16_s" => WatOpcode::I16x8ExtendHighI8x16S,
+                 b"i16x8.extend_high_i8x16_u" => WatOpcode::I16x8ExtendHighI8x16U,
+                 b"i16x8.extend_low_i8x16_s" => WatOpcode::I16x8ExtendLowI8x16S,
+                 b"i16x8.extend_low_i8x16_u" => WatOpcode::I16x8ExtendLowI8x16U,
+                 b"i16x8.extmul_high_i8x16_s" => WatOpcode::I16x8ExtmulHighI8x16S,
+                 b"i16x8.extmul_high_i8x16_u" => WatOpcode::I16x8ExtmulHighI8x16U,
+                 b"i16x8.extmul_low_i8x16_s" => WatOpcode::I16x8ExtmulLowI8x16S,
+                 b"i16x8.extmul_low_i8x16_u" => WatOpcode::I16x8ExtmulLowI8x16U,
+                 b"i16x8.extract_lane_s" => WatOpcode::I16x8ExtractLaneS,
+                 b"i16x8.extract_lane_u" => WatOpcode::I16x8ExtractLaneU,
+                 b"i16x8.ge_s" => WatOpcode::I16x8GeS,
+                 b"i16x8.ge_u" => WatOpcode::I16x8GeU,
+                 b"i16x8.gt_s" => WatOpcode::I16x8GtS,
+                 b"i16x8.gt_u" => WatOpcode::I16x8GtU,
+                 b"i16x8.le_s" => WatOpcode::I16x8LeS,
+                 b"i16x8.le_u" => WatOpcode::I16x8LeU,
+                 b"i16x8.lt_s" => WatOpcode::I16x8LtS,
+                 b"i16x8.lt_u" => WatOpcode::I16x8LtU,
+                 b"i16x8.max_s" => WatOpcode::I16x8MaxS,
+                 b"i16x8.max_u" => WatOpcode::I16x8MaxU,
+                 b"i16x8.min_s" => WatOpcode::I16x8MinS,
+                 b"i16x8.min_u" => WatOpcode::I16x8MinU,
+                 b"i16x8.mul" => WatOpcode::I16x8Mul,
+                 b"i16x8.narrow_i32x4_s" => WatOpcode::I16x8NarrowI32x4S,
+                 b"i16x8.narrow_i32x4_u" => WatOpcode::I16x8NarrowI32x4U,
+                 b"i16x8.ne" => WatOpcode::I16x8Ne,
+                 b"i16x8.neg" => WatOpcode::I16x8Neg,
+                 b"i16x8.q15mulr_sat_s" => WatOpcode::I16x8Q15mulrSatS,
+                 b"i16x8.replace_lane" => WatOpcode::I16x8ReplaceLane,
+                 b"i16x8.shl" => WatOpcode::I16x8Shl,
+                 b"i16x8.shr_s" => WatOpcode::I16x8ShrS,
+                 b"i16x8.shr_u" => WatOpcode::I16x8ShrU,
+                 b"i16x8.splat" => WatOpcode::I16x8Splat,
+                 b"i16x8.sub" => WatOpcode::I16x8Sub,
+                 b"i16x8.sub_sat_s" => WatOpcode::I16x8SubSatS,
+                 b"i16x8.sub_sat_u" => WatOpcode::I16x8SubSatU,
+                 b"i32.add" => WatOpcode::I32Add,
+                 b"i32.and" => WatOpcode::I32And,
+                 b"i32.atomic.load" => WatOpcode::I32AtomicLoad,
+                 b"i32.atomic.load16_u" => WatOpcode::I32AtomicLoad16U,
+                 b"i32.atomic.load8_u" => WatOpcode::I32AtomicLoad8U,
+                 b"i32.atomic.rmw.add" => WatOpcode::I32AtomicRmwAdd,
+                 b"i32.atomic.rmw.and" => WatOpcode::I32AtomicRmwAnd,
+                 b"i32.atomic.rmw.cmpxchg" => WatOpcode::I32AtomicRmwCmpxchg,
+                 b"i32.atomic.rmw.or" => WatOpcode::I32AtomicRmwOr,
+                 b"i32.atomic.rmw.sub" => WatOpcode::I32AtomicRmwSub,
+                 b"i32.atomic.rmw.xchg" => WatOpcode::I32AtomicRmwXchg,
+                 b"i32.atomic.rmw.xor" => WatOpcode::I32AtomicRmwXor,
+                 b"i32.atomic.rmw16.add_u" => WatOpcode::I32AtomicRmw16AddU,
+                 b"i32.atomic.rmw16.and_u" => WatOpcode::I32AtomicRmw16AndU,
+                 b"i32.atomic.rmw16.cmpxchg_u" => WatOpcode::I32AtomicRmw16CmpxchgU,
+                 b"i32.atomic.rmw16.or_u" => WatOpcode::I32AtomicRmw16OrU,
+                 b"i32.atomic.rmw16.sub_u" => WatOpcode::I32AtomicRmw16SubU,
+                 b"i32.atomic.rmw16.xchg_u" => WatOpcode::I32AtomicRmw16XchgU,
+                 b"i32.atomic.rmw16.xor_u" => WatOpcode::I32AtomicRmw16XorU,
+                 b"i32.atomic.rmw8.add_u" => WatOpcode::I32AtomicRmw8AddU,
+                 b"i32.atomic.rmw8.and_u" => WatOpcode::I32AtomicRmw8AndU,
+                 b"i32.atomic.rmw8.cmpxchg_u" => WatOpcode::I32AtomicRmw8CmpxchgU,
+                 b"i32.atomic.rmw8.or_u" => WatOpcode::I32AtomicRmw8OrU,
+                 b"i32.atomic.rmw8.sub_u" => WatOpcode::I32AtomicRmw8SubU,
+                 b"i32.atomic.rmw8.xchg_u" => WatOpcode::I32AtomicRmw8XchgU,
+                 b"i32.atomic.rmw8.xor_u" => WatOpcode::I32AtomicRmw8XorU,
+                 b"i32.atomic.store" => WatOpcode::I32AtomicStore,
+                 b"i32.atomic.store16" => WatOpcode::I32AtomicStore16,
+                 b"i32.atomic.store8" => WatOpcode::I32AtomicStore8,
+                 b"i32.clz" => WatOpcode::I32Clz,
+                 b"i32.const" => WatOpcode::I32Const,
+                 b"i32.ctz" => WatOpcode::I32Ctz,
+                 b"i32.div_s" => WatOpcode::I32DivS,
+                 b"i32.div_u" => WatOpcode::I32DivU,
+                 b"i32.eq" => WatOpcode::I32Eq,
+                 b"i32.eqz" => WatOpcode::I32Eqz,
+                 b"i32.extend16_s" => WatOpcode::I32Extend16S,
+                 b"i32.extend8_s" => WatOpcode::I32Extend8S,
+                 b"i32.ge_s" => WatOpcode::I32GeS,
+                 b"i32.ge_u" => WatOpcode::I32GeU,
+                 b"i32.gt_s" => WatOpcode::I32GtS,
+                 b"i32.gt_u" => WatOpcode::I32GtU,
+                 b"i32.le_s" => WatOpcode::I32LeS,
+                 b"i32.le_u" => WatOpcode::I32LeU,
+                 b"i32.load" => WatOpcode::I32Load,
+                 b"i32.load16_s" => WatOpcode::I32Load16S,
+                 b"i32.load16_u" => WatOpcode::I32Load16U,
+                 b"i32.load8_s" => WatOpcode::I32Load8S,
+                 b"i32.load8_u" => WatOpcode::I32Load8U,
+                 b"i32.lt_s" => WatOpcode::I32LtS,
+                 b"i32.lt_u" => WatOpcode::I32LtU,
+                 b"i32.mul" => WatOpcode::I32Mul,
+                 b"i32.ne" => WatOpcode::I32Ne,
+                 b"i32.or" => WatOpcode::I32Or,
+                 b"i32.popcnt" => WatOpcode::I32Popcnt,
+                 b"i32.reinterpret_f32" | b"i32.reinterpret/f32" => WatOpcode::I32ReinterpretF32,
+                 b"i32.rem_s" => WatOpcode::I32RemS,
+                 b"i32.rem_u" => WatOpcode::I32RemU,
+                 b"i32.rotl" => WatOpcode::I32Rotl,
+                 b"i32.rotr" => WatOpcode::I32Rotr,
+                 b"i32.shl" => WatOpcode::I32Shl,
+                 b"i32.shr_s" => WatOpcode::I32ShrS,
+                 b"i32.shr_u" => WatOpcode::I32ShrU,
+                 b"i32.store" => WatOpcode::I32Store,
+                 b"i32.store16" => WatOpcode::I32Store16,
+                 b"i32.store8" => WatOpcode::I32Store8,
+                 b"i32.sub" => WatOpcode::I32Sub,
+                 b"i32.trunc_f32_s" | b"i32.trunc_s/f32" => WatOpcode::I32TruncF32S,
+                 b"i32.trunc_f32_u" | b"i32.trunc_u/f32" => WatOpcode::I32TruncF32U,
+                 b"i32.trunc_f64_s" | b"i32.trunc_s/f64" => WatOpcode::I32TruncF64S,
+                 b"i32.trunc_f64_u" | b"i32.trunc_u/f64" => WatOpcode::I32TruncF64U,
+                 b"i32.trunc_sat_f32_s" => WatOpcode::I32TruncSatF32S,
+                 b"i32.trunc_sat_f32_u" => WatOpcode::I32TruncSatF32U,
+                 b"i32.trunc_sat_f64_s" => WatOpcode::I32TruncSatF64S,
+                 b"i32.trunc_sat_f64_u" => WatOpcode::I32TruncSatF64U,
+                 b"i32.wrap_i64" | b"i32.wrap/i64" => WatOpcode::I32WrapI64,
+                 b"i32.xor" => WatOpcode::I32Xor,
+                 b"i32x4.abs" => WatOpcode::I32x4Abs,
+                 b"i32x4.add" => WatOpcode::I32x4Add,
+                 b"i32x4.all_true" => WatOpcode::I32x4AllTrue,
+                 b"i32x4.bitmask" => WatOpcode::I32x4Bitmask,
+                 b"i32x4.dot_i16x8_s" => WatOpcode::I32x4DotI16x8S,
+                 b"i32x4.eq" => WatOpcode::I32x4Eq,
+                 b"i32x4.extadd_pairwise_i16x8_s" => WatOpcode::I32x4ExtaddPairwiseI16x8S,
+                 b"i32x4.extadd_pairwise_i16x8_u" => WatOpcode::I32x4ExtaddPairwiseI16x8U,
+                 b"i32x4.extend_high_i16x8_s" => WatOpcode::I32x4ExtendHighI16x8S,
+                 b"i32x4.extend_high_i16x8_u" => WatOpcode::I32x4ExtendHighI16x8U,
+                 b"i32x4.extend_low_i16x8_s" => WatOpcode::I32x4ExtendLowI16x8S,
+                 b"i32x4.extend_low_i16x8_u" => WatOpcode::I32x4ExtendLowI16x8U,
+                 b"i32x4.extmul_high_i16x8_s" => WatOpcode::I32x4ExtmulHighI16x8S,
+                 b"i32x4.extmul_high_i16x8_u" => WatOpcode::I32x4ExtmulHighI16x8U,
+                 b"i32x4.extmul_low_i16x8_s" => WatOpcode::I32x4ExtmulLowI16x8S,
+                 b"i32x4.extmul_low_i16x8_u" => WatOpcode::I32x4ExtmulLowI16x8U,
+                 b"i32x4.extract_lane" => WatOpcode::I32x4ExtractLane,
+                 b"i32x4.ge_s" => WatOpcode::I32x4GeS,
+                 b"i32x4.ge_u" => WatOpcode::I32x4GeU,
+                 b"i32x4.gt_s" => WatOpcode::I32x4GtS,
+                 b"i32x4.gt_u" => WatOpcode::I32x4GtU,
+                 b"i32x4.le_s" => WatOpcode::I32x4LeS,
+                 b"i32x4.le_u" => WatOpcode::I32x4LeU,
+                 b"i32x4.lt_s" => WatOpcode::I32x4LtS,
+                 b"i32x4.lt_u" => WatOpcode::I32x4LtU,
+                 b"i32x4.max_s" => WatOpcode::I32x4MaxS,
+                 b"i32x4.max_u" => WatOpcode::I32x4MaxU,
+                 b"i32x4.min_s" => WatOpcode::I32x4MinS,
+                 b"i32x4.min_u" => WatOpcode::I32x4MinU,
+                 b"i32x4.mul" => WatOpcode::I32x4Mul,
+                 b"i32x4.ne" => WatOpcode::I32x4Ne,
+                 b"i32x4.neg" => WatOpcode::I32x4Neg,
+                 b"i32x4.replace_lane" => WatOpcode::I32x4ReplaceLane,
+                 b"i32x4.shl" => WatOpcode::I32x4Shl,
+                 b"i32x4.shr_s" => WatOpcode::I32x4ShrS,
+                 b"i32x4.shr_u" => WatOpcode::I32x4ShrU,
+                 b"i32x4.splat" => WatOpcode::I32x4Splat,
+                 b"i32x4.sub" => WatOpcode::I32x4Sub,
+                 b"i32x4.trunc_sat_f32x4_s" => WatOpcode::I32x4TruncSatF32x4S,
+                 b"i32x4.trunc_sat_f32x4_u" => WatOpcode::I32x4TruncSatF32x4U,
+                 b"i32x4.trunc_sat_f64x2_s_zero" => WatOpcode::I32x4TruncSatF64x2SZero,
+                 b"i32x4.trunc_sat_f64x2_u_zero" => WatOpcode::I32x4TruncSatF64x2UZero,
+                 b"i64.add" => WatOpcode::I64Add,
+                 b"i64.and" => WatOpcode::I64And,
+                 b"i64.atomic.load" => WatOpcode::I64AtomicLoad,
+                 b"i64.atomic.load16_u" => WatOpcode::I64AtomicLoad16U,
+                 b"i64.atomic.load32_u" => WatOpcode::I64AtomicLoad32U,
+                 b"i64.atomic.load8_u" => WatOpcode::I64AtomicLoad8U,
+                 b"i64.atomic.rmw.add" => WatOpcode::I64AtomicRmwAdd,
+                 b"i64.atomic.rmw.and" => WatOpcode::I64AtomicRmwAnd,
+                 b"i64.atomic.rmw.cmpxchg" => WatOpcode::I64AtomicRmwCmpxchg,
+                 b"i64.atomic.rmw.or" => WatOpcode::I64AtomicRmwOr,
+                 b"i64.atomic.rmw.sub" => WatOpcode::I64AtomicRmwSub,
+                 b"i64.atomic.rmw.xchg" => WatOpcode::I64AtomicRmwXchg,
+                 b"i64.atomic.rmw.xor" => WatOpcode::I64AtomicRmwXor,
+                 b"i64.atomic.rmw16.add_u" => WatOpcode::I64AtomicRmw16AddU,
+                 b"i64.atomic.rmw16.and_u" => WatOpcode::I64AtomicRmw16AndU,
+                 b"i64.atomic.rmw16.cmpxchg_u" => WatOpcode::I64AtomicRmw16CmpxchgU,
+                 b"i64.atomic.rmw16.or_u" => WatOpcode::I64AtomicRmw16OrU,
+                 b"i64.atomic.rmw16.sub_u" => WatOpcode::I64AtomicRmw16SubU,
+                 b"i64.atomic.rmw16.xchg_u" => WatOpcode::I64AtomicRmw16XchgU,
+                 b"i64.atomic.rmw16.xor_u" => WatOpcode::I64AtomicRmw16XorU,
+                 b"i64.atomic.rmw32.add_u" => WatOpcode::I64AtomicRmw32AddU,
+                 b"i64.atomic.rmw32.and_u" => WatOpcode::I64AtomicRmw32AndU,
+                 b"i64.atomic.rmw32.cmpxchg_u" => WatOpcode::I64AtomicRmw32CmpxchgU,
+                 b"i64.atomic.rmw32.or_u" => WatOpcode::I64AtomicRmw32OrU,
+                 b"i64.atomic.rmw32.sub_u" => WatOpcode::I64AtomicRmw32SubU,
+                 b"i64.atomic.rmw32.xchg_u" => WatOpcode::I64AtomicRmw32XchgU,
+                 b"i64.atomic.rmw32.xor_u" => WatOpcode::I64AtomicRmw32XorU,
+                 b"i64.atomic.rmw8.add_u" => WatOpcode::I64AtomicRmw8AddU,
+                 b"i64.atomic.rmw8.and_u" => WatOpcode::I64AtomicRmw8AndU,
+                 b"i64.atomic.rmw8.cmpxchg_u" => WatOpcode::I64AtomicRmw8CmpxchgU,
+                 b"i64.atomic.rmw8.or_u" => WatOpcode::I64AtomicRmw8OrU,
+                 b"i64.atomic.rmw8.sub_u" => WatOpcode::I64AtomicRmw8SubU,
+                 b"i64.atomic.rmw8.xchg_u" => WatOpcode::I64AtomicRmw8XchgU,
+                 b"i64.atomic.rmw8.xor_u" => WatOpcode::I64AtomicRmw8XorU,
+                 b"i64.atomic.store" => WatOpcode::I64AtomicStore,
+                 b"i64.atomic.store16" => WatOpcode::I64AtomicStore16,
+                 b"i64.atomic.store32" => WatOpcode::I64AtomicStore32,
+                 b"i64.atomic.store8" => WatOpcode::I64AtomicStore8,
+                 b"i64.clz" => WatOpcode::I64Clz,
+                 b"i64.const" => WatOpcode::I64Const,
+                 b"i64.ctz" => WatOpcode::I64Ctz,
+                 b"i64.div_s" => WatOpcode::I64DivS,
+                 b"i64.div_u" => WatOpcode::I64DivU,
+                 b"i64.eq" => WatOpcode::I64Eq,
+                 b"i64.eqz" => WatOpcode::I64Eqz,
+                 b"i64.extend16_s" => WatOpcode::I64Extend16S,
+                 b"i64.extend32_s" => WatOpcode::I64Extend32S,
+                 b"i64.extend8_s" => WatOpcode::I64Extend8S,
+                 b"i64.extend_i32_s" | b"i64.extend_s/i32" => WatOpcode::I64ExtendI32S,
+                 b"i64.extend_i32_u" | b"i64.extend_u/i32" => WatOpcode::I64ExtendI32U,
+                 b"i64.ge_s" => WatOpcode::I64GeS,
+                 b"i64.ge_u" => WatOpcode::I64GeU,
+                 b"i64.gt_s" => WatOpcode::I64GtS,
+                 b"i64.gt_u" => WatOpcode::I64GtU,
+                 b"i64.le_s" => WatOpcode::I64LeS,
+                 b"i64.le_u" => WatOpcode::I64LeU,
+                 b"i64.load" => WatOpcode::I64Load,
+                 b"i64.load16_s" => WatOpcode::I64Load16S,
+                 b"i64.load16_u" => WatOpcode::I64Load16U,
+                 b"i64.load32_s" => WatOpcode::I64Load32S,
+                 b"i64.load32_u" => WatOpcode::I64Load32U,
+                 b"i64.load8_s" => WatOpcode::I64Load8S,
+                 b"i64.load8_u" => WatOpcode::I64Load8U,
+                 b"i64.lt_s" => WatOpcode::I64LtS,
+                 b"i64.lt_u" => WatOpcode::I64LtU,
+                 b"i64.mul" => WatOpcode::I64Mul,
+                 b"i64.ne" => WatOpcode::I64Ne,
+                 b"i64.or" => WatOpcode::I64Or,
+                 b"i64.popcnt" => WatOpcode::I64Popcnt,
+                 b"i64.reinterpret_f64" | b"i64.reinterpret/f64" => WatOpcode::I64ReinterpretF64,
+                 b"i64.rem_s" => WatOpcode::I64RemS,
+                 b"i64.rem_u" => WatOpcode::I64RemU,
+                 b"i64.rotl" => WatOpcode::I64Rotl,
+                 b"i64.rotr" => WatOpcode::I64Rotr,
+                 b"i64.shl" => WatOpcode::I64Shl,
+                 b"i64.shr_s" => WatOpcode::I64ShrS,
+                 b"i64.shr_u" => WatOpcode::I64ShrU,
+                 b"i64.store" => WatOpcode::I64Store,
+                 b"i64.store16" => WatOpcode::I64Store16,
+                 b"i64.store32" => WatOpcode::I64Store32,
+                 b"i64.store8" => WatOpcode::I64Store8,
+                 b"i64.sub" => WatOpcode::I64Sub,
+                 b"i64.trunc_f32_s" | b"i64.trunc_s/f32" => WatOpcode::I64TruncF32S,
+                 b"i64.trunc_f32_u" | b"i64.trunc_u/f32" => WatOpcode::I64TruncF32U,
+                 b"i64.trunc_f64_s" | b"i64.trunc_s/f64" => WatOpcode::I64TruncF64S,
+                 b"i64.trunc_f64_u" | b"i64.trunc_u/f64" => WatOpcode::I64TruncF64U,
+                 b"i64.trunc_sat_f32_s" => WatOpcode::I64TruncSatF32S,
+                 b"i64.trunc_sat_f32_u" => WatOpcode::I64TruncSatF32U,
+                 b"i64.trunc_sat_f64_s" => WatOpcode::I64TruncSatF64S,
+                 b"i64.trunc_sat_f64_u" => WatOpcode::I64TruncSatF64U,
+                 b"i64.xor" => WatOpcode::I64Xor,
+                 b"i64x2.abs" => WatOpcode::I64x2Abs,
+                 b"i64x2.add" => WatOpcode::I64x2Add,
+                 b"i64x2.all_true" => WatOpcode::I64x2AllTrue,
+                 b"i64x2.bitmask" => WatOpcode::I64x2Bitmask,
+                 b"i64x2.eq" => WatOpcode::I64x2Eq,
+                 b"i64x2.extend_high_i32x4_s" => WatOpcode::I64x2ExtendHighI32x4S,
+                 b"i64x2.extend_high_i32x4_u" => WatOpcode::I64x2ExtendHighI32x4U,
+                 b"i64x2.extend_low_i32x4_s" => WatOpcode::I64x2ExtendLowI32x4S,
+                 b"i64x2.extend_low_i32x4_u" => WatOpcode::I64x2ExtendLowI32x4U,
+                 b"i64x2.extmul_high_i32x4_s" => WatOpcode::I64x2ExtmulHighI32x4S,
+                 b"i64x2.extmul_high_i32x4_u" => WatOpcode::I64x2ExtmulHighI32x4U,
+                 b"i64x2.extmul_low_i32x4_s" => WatOpcode::I64x2ExtmulLowI32x4S,
+                 b"i64x2.extmul_low_i32x4_u" => WatOpcode::I64x2ExtmulLowI32x4U,
+                 b"i64x2.extract_lane" => WatOpcode::I64x2ExtractLane,
+                 b"i64x2.ge_s" => WatOpcode::I64x2GeS,
+                 b"i64x2.gt_s" => WatOpcode::I64x2GtS,
+                 b"i64x2.le_s" => WatOpcode::I64x2LeS,
+                 b"i64x2.lt_s" => WatOpcode::I64x2LtS,
+                 b"i64x2.mul" => WatOpcode::I64x2Mul,
+                 b"i64x2.ne" => WatOpcode::I64x2Ne,
+                 b"i64x2.neg" => WatOpcode::I64x2Neg,
+                 b"i64x2.replace_lane" => WatOpcode::I64x2ReplaceLane,
+                 b"i64x2.shl" => WatOpcode::I64x2Shl,
+                 b"i64x2.shr_s" => WatOpcode::I64x2ShrS,
+                 b"i64x2.shr_u" => WatOpcode::I64x2ShrU,
+                 b"i64x2.splat" => WatOpcode::I64x2Splat,
+                 b"i64x2.sub" => WatOpcode::I64x2Sub,
+                 b"i8x16.abs" => WatOpcode::I8x16Abs,
+                 b"i8x16.add" => WatOpcode::I8x16Add,
+                 b"i8x16.add_sat_s" => WatOpcode::I8x16AddSatS,
+                 b"i8x16.add_sat_u" => WatOpcode::I8x16AddSatU,
+                 b"i8x16.all_true" => WatOpcode::I8x16AllTrue,
+                 b"i8x16.avgr_u" => WatOpcode::I8x16AvgrU,
+                 b"i8x16.bitmask" => WatOpcode::I8x16Bitmask,
+                 b"i8x16.eq" => WatOpcode::I8x16Eq,
+                 b"i8x16.extract_lane_s" => WatOpcode::I8x16ExtractLaneS,
+                 b"i8x16.extract_lane_u" => WatOpcode::I8x16ExtractLaneU,
+                 b"i8x16.ge_s" => WatOpcode::I8x16GeS,
+                 b"i8x16.ge_u" => WatOpcode::I8x16GeU,
+                 b"i8x16.gt_s" => WatOpcode::I8x16GtS,
+                 b"i8x16.gt_u" => WatOpcode::I8x16GtU,
+                 b"i8x16.le_s" => WatOpcode::I8x16LeS,
+                 b"i8x16.le_u" => WatOpcode::I8x16LeU,
+                 b"i8x16.lt_s" => WatOpcode::I8x16LtS,
+                 b"i8x16.lt_u" => WatOpcode::I8x16LtU,
+                 b"i8x16.max_s" => WatOpcode::I8x16MaxS,
+                 b"i8x16.max_u" => WatOpcode::I8x16MaxU,
+                 b"i8x16.min_s" => WatOpcode::I8x16MinS,
+                 b"i8x16.min_u" => WatOpcode::I8x16MinU,
+                 b"i8x16.narrow_i16x8_s" => WatOpcode::I8x16NarrowI16x8S,
+                 b"i8x16.narrow_i16x8_u" => WatOpcode::I8x16NarrowI16x8U,
+                 b"i8x16.ne" => WatOpcode::I8x16Ne,
+                 b"i8x16.neg" => WatOpcode::I8x16Neg,
+                 b"i8x16.popcnt" => WatOpcode::I8x16Popcnt,
+                 b"i8x16.replace_lane" => WatOpcode::I8x16ReplaceLane,
+                 b"i8x16.shl" => WatOpcode::I8x16Shl,
+                 b"i8x16.shr_s" => WatOpcode::I8x16ShrS,
+                 b"i8x16.shr_u" => WatOpcode::I8x16ShrU,
+                 b"i8x16.shuffle" => WatOpcode::I8x16Shuffle,
+                 b"i8x16.splat" => WatOpcode::I8x16Splat,
+                 b"i8x16.sub" => WatOpcode::I8x16Sub,
+                 b"i8x16.sub_sat_s" => WatOpcode::I8x16SubSatS,
+                 b"i8x16.sub_sat_u" => WatOpcode::I8x16SubSatU,
+                 b"i8x16.swizzle" => WatOpcode::I8x16Swizzle,
+                 b"if" => WatOpcode::If,
+                 b"local.get" | b"get_local" => WatOpcode::LocalGet,
+                 b"local.set" | b"set_local" => WatOpcode::LocalSet,
+                 b"local.tee" | b"tee_local" => WatOpcode::LocalTee,
+                 b"loop" => WatOpcode::Loop,
+                 b"memory.atomic.notify" |
+                 b"atomic.notify" |
+                 b"atomic.wake" |
+                 b"wake" => WatOpcode::MemoryAtomicNotify,
+                 b"memory.atomic.wait32" |
+                 b"i32.atomic.wait" |
+                 b"i32.wait" => WatOpcode::MemoryAtomicWait32,
+                 b"memory.atomic.wait64" |
+                 b"i64.atomic.wait" |
+                 b"i64.wait" => WatOpcode::MemoryAtomicWait64,
+                 b"memory.copy" => WatOpcode::MemoryCopy,
+                 b"memory.fill" => WatOpcode::MemoryFill,
+                 b"memory.grow" | b"grow_memory" => WatOpcode::MemoryGrow,
+                 b"memory.init" => WatOpcode::MemoryInit,
+                 b"memory.size" | b"current_memory" => WatOpcode::MemorySize,
+                 b"nop" => WatOpcode::Nop,
+                 b"ref.func" => WatOpcode::RefFunc,
+                 b"ref.is_null" => WatOpcode::RefIsNull,
+                 b"ref.null" => WatOpcode::RefNull,
+                 b"return" => WatOpcode::Return,
+                 b"return_call" => WatOpcode::ReturnCall,
+                 b"return_call_indirect" => WatOpcode::ReturnCallIndirect,
+                 b"select" => WatOpcode::Select,
+                 b"table.copy" => WatOpcode::TableCopy,
+                 b"table.fill" => WatOpcode::TableFill,
+                 b"table.get" => WatOpcode::TableGet,
+                 b"table.grow" => WatOpcode::TableGrow,
+                 b"table.init" => WatOpcode::TableInit,
+                 b"table.set" => WatOpcode::TableSet,
+                 b"table.size" => WatOpcode::TableSize,
+                 b"unreachable" => WatOpcode::Unreachable,
+                 b"v128.and" => WatOpcode::V128And,
+                 b"v128.andnot" => WatOpcode::V128Andnot,
+                 b"v128.any_true" => WatOpcode::V128AnyTrue,
+                 b"v128.bitselect" => WatOpcode::V128Bitselect,
+                 b"v128.const" => WatOpcode::V128Const,
+                 b"v128.load" => WatOpcode::V128Load,
+                 b"v128.load16_lane" => WatOpcode::V128Load16Lane,
+                 b"v128.load16_splat" => WatOpcode::V128Load16Splat,
+                 b"v128.load16x4_s" => WatOpcode::V128Load16x4S,
+                 b"v128.load16x4_u" => WatOpcode::V128Load16x4U,
+                 b"v128.load32_lane" => WatOpcode::V128Load32Lane,
+                 b"v128.load32_splat" => WatOpcode::V128Load32Splat,
+                 b"v128.load32_zero" => WatOpcode::V128Load32Zero,
+                 b"v128.load32x2_s" => WatOpcode::V128Load32x2S,
+                 b"v128.load32x2_u" => WatOpcode::V128Load32x2U,
+                 b"v128.load64_lane" => WatOpcode::V128Load64Lane,
+                 b"v128.load64_splat" => WatOpcode::V128Load64Splat,
+                 b"v128.load64_zero" => WatOpcode::V128Load64Zero,
+                 b"v128.load8_lane" => WatOpcode::V128Load8Lane,
+                 b"v128.load8_splat" => WatOpcode::V128Load8Splat,
+                 b"v128.load8x8_s" => WatOpcode::V128Load8x8S,
+                 b"v128.load8x8_u" => WatOpcode::V128Load8x8U,
+                 b"v128.not" => WatOpcode::V128Not,
+                 b"v128.or" => WatOpcode::V128Or,
+                 b"v128.store" => WatOpcode::V128Store,
+                 b"v128.store16_lane" => WatOpcode::V128Store16Lane,
+                 b"v128.store32_lane" => WatOpcode::V128Store32Lane,
+                 b"v128.store64_lane" => WatOpcode::V128Store64Lane,
+                 b"v128.store8_lane" => WatOpcode::V128Store8Lane,
+                 b"v128.xor" => WatOpcode::V128Xor,
+                 _ => return None,
+             })
+    }
+
+    /// The standard spelling of the instruction, or the unknown name.
+    pub fn name(&self) -> &[u8] {
+        match *self {
+            WatOpcode::AtomicFence => b"atomic.fence",
+            WatOpcode::Block => b"block",
+            WatOpcode::Br => b"br",
+            WatOpcode::BrIf => b"br_if",
+            WatOpcode::BrTable => b"br_table",
+            WatOpcode::Call => b"call",
+            WatOpcode::CallIndirect => b"call_indirect",
+            WatOpcode::DataDrop => b"data.drop",
+            WatOpcode::Drop => b"drop",
+            WatOpcode::ElemDrop => b"elem.drop",
+            WatOpcode::Else => b"else",
+            WatOpcode::End => b"end",
+            WatOpcode::F32Abs => b"f32.abs",
+            WatOpcode::F32Add => b"f32.add",
+            WatOpcode::F32Ceil => b"f32.ceil",
+            WatOpcode::F32Const => b"f32.const",
+            WatOpcode::F32ConvertI32S => b"f32.convert_i32_s",
+            WatOpcode::F32ConvertI32U => b"f32.convert_i32_u",
+            WatOpcode::F32ConvertI64S => b"f32.convert_i64_s",
+            WatOpcode::F32ConvertI64U => b"f32.convert_i64_u",
+            WatOpcode::F32Copysign => b"f32.copysign",
+            WatOpcode::F32DemoteF64 => b"f32.demote_f64",
+            WatOpcode::F32Div => b"f32.div",
+            WatOpcode::F32Eq => b"f32.eq",
+            WatOpcode::F32Floor => b"f32.floor",
+            WatOpcode::F32Ge => b"f32.ge",
+            WatOpcode::F32Gt => b"f32.gt",
+            WatOpcode::F32Le => b"f32.le",
+            WatOpcode::F32Load => b"f32.load",
+            WatOpcode::F32Lt => b"f32.lt",
+            WatOpcode::F32Max => b"f32.max",
+            WatOpcode::F32Min => b"f32.min",
+            WatOpcode::F32Mul => b"f32.mul",
+            WatOpcode::F32Ne => b"f32.ne",
+            WatOpcode::F32Nearest => b"f32.nearest",
+            WatOpcode::F32Neg => b"f32.neg",
+            WatOpcode::F32ReinterpretI32 => b"f32.reinterpret_i32",
+            WatOpcode::F32Sqrt => b"f32.sqrt",
+            WatOpcode::F32Store => b"f32.store",
+            WatOpcode::F32Sub => b"f32.sub",
+            WatOpcode::F32Trunc => b"f32.trunc",
+            WatOpcode::F32x4Abs => b"f32x4.abs",
+            WatOpcode::F32x4Add => b"f32x4.add",
+            WatOpcode::F32x4Ceil => b"f32x4.ceil",
+            WatOpcode::F32x4ConvertI32x4S => b"f32x4.convert_i32x4_s",
+            WatOpcode::F32x4ConvertI32x4U => b"f32x4.convert_i32x4_u",
+            WatOpcode::F32x4DemoteF64x2Zero => b"f32x4.demote_f64x2_zero",
+            WatOpcode::F32x4Div => b"f32x4.div",
+            WatOpcode::F32x4Eq => b"f32x4.eq",
+            WatOpcode::F32x4ExtractLane => b"f32x4.extract_lane",
+            WatOpcode::F32x4Floor => b"f32x4.floor",
+            WatOpcode::F32x4Ge => b"f32x4.ge",
+            WatOpcode::F32x4Gt => b"f32x4.gt",
+            WatOpcode::F32x4Le => b"f32x4.le",
+            WatOpcode::F32x4Lt => b"f32x4.lt",
+            WatOpcode::F32x4Max => b"f32x4.max",
+            WatOpcode::F32x4Min => b"f32x4.min",
+            WatOpcode::F32x4Mul => b"f32x4.mul",
+            WatOpcode::F32x4Ne => b"f32x4.ne",
+            WatOpcode::F32x4Nearest => b"f32x4.nearest",
+            WatOpcode::F32x4Neg => b"f32x4.neg",
+            WatOpcode::F32x4Pmax => b"f32x4.pmax",
+            WatOpcode::F32x4Pmin => b"f32x4.pmin",
+            WatOpcode::F32x4ReplaceLane => b"f32x4.replace_lane",
+            WatOpcode::F32x4Splat => b"f32x4.splat",
+            WatOpcode::F32x4Sqrt => b"f32x4.sqrt",
+            WatOpcode::F32x4Sub => b"f32x4.sub",
+            WatOpcode::F32x4Trunc => b"f32x4.trunc",
+            WatOpcode::F64Abs => b"f64.abs",
+            WatOpcode::F64Add => b"f64.add",
+            WatOpcode::F64Ceil => b"f64.ceil",
+            WatOpcode::F64Const => b"f64.const",
+            WatOpcode::F64ConvertI32S => b"f64.convert_i32_s",
+            WatOpcode::F64ConvertI32U => b"f64.convert_i32_u",
+            WatOpcode::F64ConvertI64S => b"f64.convert_i64_s",
+            WatOpcode::F64ConvertI64U => b"f64.convert_i64_u",
+            WatOpcode::F64Copysign => b"f64.copysign",
+            WatOpcode::F64Div => b"f64.div",
+            WatOpcode::F64Eq => b"f64.eq",
+            WatOpcode::F64Floor => b"f64.floor",
+            WatOpcode::F64Ge => b"f64.ge",
+            WatOpcode::F64Gt => b"f64.gt",
+            WatOpcode::F64Le => b"f64.le",
+            WatOpcode::F64Load => b"f64.load",
+            WatOpcode::F64Lt => b"f64.lt",
+            WatOpcode::F64Max => b"f64.max",
+            WatOpcode::F64Min => b"f64.min",
+            WatOpcode::F64Mul => b"f64.mul",
+            WatOpcode::F64Ne => b"f64.ne",
+            WatOpcode::F64Nearest => b"f64.nearest",
+            WatOpcode::F64Neg => b"f64.neg",
+            WatOpcode::F64PromoteF32 => b"f64.promote_f32",
+            WatOpcode::F64ReinterpretI64 => b"f64.reinterpret_i64",
+            WatOpcode::F64Sqrt => b"f64.sqrt",
+            WatOpcode::F64Store => b"f64.store",
+            WatOpcode::F64Sub => b"f64.sub",
+            WatOpcode::F64Trunc => b"f64.trunc",
+            WatOpcode::F64x2Abs => b"f64x2.abs",
+            WatOpcode::F64x2Add => b"f64x2.add",
+            WatOpcode::F64x2Ceil => b"f64x2.ceil",
+            WatOpcode::F64x2ConvertLowI32x4S => b"f64x2.convert_low_i32x4_s",
+            WatOpcode::F64x2ConvertLowI32x4U => b"f64x2.convert_low_i32x4_u",
+            WatOpcode::F64x2Div => b"f64x2.div",
+            WatOpcode::F64x2Eq => b"f64x2.eq",
+            WatOpcode::F64x2ExtractLane => b"f64x2.extract_lane",
+            WatOpcode::F64x2Floor => b"f64x2.floor",
+            WatOpcode::F64x2Ge => b"f64x2.ge",
+            WatOpcode::F64x2Gt => b"f64x2.gt",
+            WatOpcode::F64x2Le => b"f64x2.le",
+            WatOpcode::F64x2Lt => b"f64x2.lt",
+            WatOpcode::F64x2Max => b"f64x2.max",
+            WatOpcode::F64x2Min => b"f64x2.min",
+            WatOpcode::F64x2Mul => b"f64x2.mul",
+            WatOpcode::F64x2Ne => b"f64x2.ne",
+            WatOpcode::F64x2Nearest => b"f64x2.nearest",
+            WatOpcode::F64x2Neg => b"f64x2.neg",
+            WatOpcode::F64x2Pmax => b"f64x2.pmax",
+            WatOpcode::F64x2Pmin => b"f64x2.pmin",
+            WatOpcode::F64x2PromoteLowF32x4 => b"f64x2.promote_low_f32x4",
+            WatOpcode::F64x2ReplaceLane => b"f64x2.replace_lane",
+            WatOpcode::F64x2Splat => b"f64x2.splat",
+            WatOpcode::F64x2Sqrt => b"f64x2.sqrt",
+            WatOpcode::F64x2Sub => b"f64x2.sub",
+            WatOpcode::F64x2Trunc => b"f64x2.trunc",
+            WatOpcode::GlobalGet => b"global.get",
+            WatOpcode::GlobalSet => b"global.set",
+            WatOpcode::I16x8Abs => b"i16x8.abs",
+            WatOpcode::I16x8Add => b"i16x8.add",
+            WatOpcode::I16x8AddSatS => b"i16x8.add_sat_s",
+            WatOpcode::I16x8AddSatU => b"i16x8.add_sat_u",
+            WatOpcode::I16x8AllTrue => b"i16x8.all_true",
+            WatOpcode::I16x8AvgrU => b"i16x8.avgr_u",
+            WatOpcode::I16x8Bitmask => b"i16x8.bitmask",
+            WatOpcode::I16x8Eq => b"i16x8.eq",
+            WatOpcode::I16x8ExtaddPairwiseI8x16S => b"i16x8.extadd_pairwise_i8x16_s",
+            WatOpcode::I16x8ExtaddPairwiseI8x16U => b"i16x8.extadd_pairwise_i8x16_u",
+            WatOpcode::I16x8ExtendHighI8x16S => b"i16x8.extend_high_i8x16_s",
+            WatOpcode::I16x8ExtendHighI8x16U => b"i16x8.extend_high_i8x16_u",
+            WatOpcode::I16x8ExtendLowI8x16S => b"i16x8.extend_low_i8x16_s",
+            WatOpcode::I16x8ExtendLowI8x16U => b"i16x8.extend_low_i8x16_u",
+            WatOpcode::I16x8ExtmulHighI8x16S => b"i16x8.extmul_high_i8x16_s",
+            WatOpcode::I16x8ExtmulHighI8x16U => b"i16x8.extmul_high_i8x16_u",
+            WatOpcode::I16x8ExtmulLowI8x16S => b"i16x8.extmul_low_i8x16_s",
+            WatOpcode::I16x8ExtmulLowI8x16U => b"i16x8.extmul_low_i8x16_u",
+            WatOpcode::I16x8ExtractLaneS => b"i16x8.extract_lane_s",
+            WatOpcode::I16x8ExtractLaneU => b"i16x8.extract_lane_u",
+            WatOpcode::I16x8GeS => b"i16x8.ge_s",
+            WatOpcode::I16x8GeU => b"i16x8.ge_u",
+            WatOpcode::I16x8GtS => b"i16x8.gt_s",
+            WatOpcode::I16x8GtU => b"i16x8.gt_u",
+            WatOpcode::I16x8LeS => b"i16x8.le_s",
+            WatOpcode::I16x8LeU => b"i16x8.le_u",
+            WatOpcode::I16x8LtS => b"i16x8.lt_s",
+            WatOpcode::I16x8LtU => b"i16x8.lt_u",
+            WatOpcode::I16x8MaxS => b"i16x8.max_s",
+            WatOpcode::I16x8MaxU => b"i16x8.max_u",
+            WatOpcode::I16x8MinS => b"i16x8.min_s",
+            WatOpcode::I16x8MinU => b"i16x8.min_u",
+            WatOpcode::I16x8Mul => b"i16x8.mul",
+            WatOpcode::I16x8NarrowI32x4S => b"i16x8.narrow_i32x4_s",
+            WatOpcode::I16x8NarrowI32x4U => b"i16x8.narrow_i32x4_u",
+            WatOpcode::I16x8Ne => b"i16x8.ne",
+            WatOpcode::I16x8Neg => b"i16x8.neg",
+            WatOpcode::I16x8Q15mulrSatS => b"i16x8.q15mulr_sat_s",
+            WatOpcode::I16x8ReplaceLane => b"i16x8.replace_lane",
+            WatOpcode::I16x8Shl => b"i16x8.shl",
+            WatOpcode::I16x8ShrS => b"i16x8.shr_s",
+            WatOpcode::I16x8ShrU => b"i16x8.shr_u",
+            WatOpcode::I16x8Splat => b"i16x8.splat",
+            WatOpcode::I16x8Sub => b"i16x8.sub",
+            WatOpcode::I16x8SubSatS => b"i16x8.sub_sat_s",
+            WatOpcode::I16x8SubSatU => b"i16x8.sub_sat_u",
+            WatOpcode::I32Add => b"i32.add",
+            WatOpcode::I32And => b"i32.and",
+            WatOpcode::I32AtomicLoad => b"i32.atomic.load",
+            WatOpcode::I32AtomicLoad16U => b"i32.atomic.load16_u",
+            WatOpcode::I32AtomicLoad8U => b"i32.atomic.load8_u",
+            WatOpcode::I32AtomicRmwAdd => b"i32.atomic.rmw.add",
+            WatOpcode::I32AtomicRmwAnd => b"i32.atomic.rmw.and",
+            WatOpcode::I32AtomicRmwCmpxchg => b"i32.atomic.rmw.cmpxchg",
+            WatOpcode::I32AtomicRmwOr => b"i32.atomic.rmw.or",
+            WatOpcode::I32AtomicRmwSub => b"i32.atomic.rmw.sub",
+            WatOpcode::I32AtomicRmwXchg => b"i32.atomic.rmw.xchg",
+            WatOpcode::I32AtomicRmwXor => b"i32.atomic.rmw.xor",
+            WatOpcode::I32AtomicRmw16AddU => b"i32.atomic.rmw16.add_u",
+            WatOpcode::I32AtomicRmw16AndU => b"i32.atomic.rmw16.and_u",
+            WatOpcode::I32AtomicRmw16CmpxchgU => b"i32.atomic.rmw16.cmpxchg_u",
+            WatOpcode::I32AtomicRmw16OrU => b"i32.atomic.rmw16.or_u",
+            WatOpcode::I32AtomicRmw16SubU => b"i32.atomic.rmw16.sub_u",
+            WatOpcode::I32AtomicRmw16XchgU => b"i32.atomic.rmw16.xchg_u",
+            WatOpcode::I32AtomicRmw16XorU => b"i32.atomic.rmw16.xor_u",
+            WatOpcode::I32AtomicRmw8AddU => b"i32.atomic.rmw8.add_u",
+            WatOpcode::I32AtomicRmw8AndU => b"i32.atomic.rmw8.and_u",
+            WatOpcode::I32AtomicRmw8CmpxchgU => b"i32.atomic.rmw8.cmpxchg_u",
+            WatOpcode::I32AtomicRmw8OrU => b"i32.atomic.rmw8.or_u",
+            WatOpcode::I32AtomicRmw8SubU => b"i32.atomic.rmw8.sub_u",
+            WatOpcode::I32AtomicRmw8XchgU => b"i32.atomic.rmw8.xchg_u",
+            WatOpcode::I32AtomicRmw8XorU => b"i32.atomic.rmw8.xor_u",
+            WatOpcode::I32AtomicStore => b"i32.atomic.store",
+            WatOpcode::I32AtomicStore16 => b"i32.atomic.store16",
+            WatOpcode::I32AtomicStore8 => b"i32.atomic.store8",
+            WatOpcode::I32Clz => b"i32.clz",
+            WatOpcode::I32Const => b"i32.const",
+            WatOpcode::I32Ctz => b"i32.ctz",
+            WatOpcode::I32DivS => b"i32.div_s",
+            WatOpcode::I32DivU => b"i32.div_u",
+            WatOpcode::I32Eq => b"i32.eq",
+            WatOpcode::I32Eqz => b"i32.eqz",
+            WatOpcode::I32Extend16S => b"i32.extend16_s",
+            WatOpcode::I32Extend8S => b"i32.extend8_s",
+            WatOpcode::I32GeS => b"i32.ge_s",
+            WatOpcode::I32GeU => b"i32.ge_u",
+            WatOpcode::I32GtS => b"i32.gt_s",
+            WatOpcode::I32GtU => b"i32.gt_u",
+            WatOpcode::I32LeS => b"i32.le_s",
+            WatOpcode::I32LeU => b"i32.le_u",
+            WatOpcode::I32Load => b"i32.load",
+            WatOpcode::I32Load16S => b"i32.load16_s",
+            WatOpcode::I32Load16U => b"i32.load16_u",
+            WatOpcode::I32Load8S => b"i32.load8_s",
+            WatOpcode::I32Load8U => b"i32.load8_u",
+            WatOpcode::I32LtS => b"i32.lt_s",
+            WatOpcode::I32LtU => b"i32.lt_u",
+            WatOpcode::I32Mul => b"i32.mul",
+            WatOpcode::I32Ne => b"i32.ne",
+            WatOpcode::I32Or => b"i32.or",
+            WatOpcode::I32Popcnt => b"i32.popcnt",
+            WatOpcode::I32ReinterpretF32 => b"i32.reinterpret_f32",
+            WatOpcode::I32RemS => b"i32.rem_s",
+            WatOpcode::I32RemU => b"i32.rem_u",
+            WatOpcode::I32Rotl => b"i32.rotl",
+            WatOpcode::I32Rotr => b"i32.rotr",
+            WatOpcode::I32Shl => b"i32.shl",
+            WatOpcode::I32ShrS => b"i32.shr_s",
+            WatOpcode::I32ShrU => b"i32.shr_u",
+            WatOpcode::I32Store => b"i32.store",
+            WatOpcode::I32Store16 => b"i32.store16",
+            WatOpcode::I32Store8 => b"i32.store8",
+            WatOpcode::I32Sub => b"i32.sub",
+            WatOpcode::I32TruncF32S => b"i32.trunc_f32_s",
+            WatOpcode::I32TruncF32U => b"i32.trunc_f32_u",
+            WatOpcode::I32TruncF64S => b"i32.trunc_f64_s",
+            WatOpcode::I32TruncF64U => b"i32.trunc_f64_u",
+            WatOpcode::I32TruncSatF32S => b"i32.trunc_sat_f32_s",
+            WatOpcode::I32TruncSatF32U => b"i32.trunc_sat_f32_u",
+            WatOpcode::I32TruncSatF64S => b"i32.trunc_sat_f64_s",
+            WatOpcode::I32TruncSatF64U => b"i32.trunc_sat_f64_u",
+            WatOpcode::I32WrapI64 => b"i32.wrap_i64",
+            WatOpcode::I32Xor => b"i32.xor",
+            WatOpcode::I32x4Abs => b"i32x4.abs",
+            WatOpcode::I32x4Add => b"i32x4.add",
+            WatOpcode::I32x4AllTrue => b"i32x4.all_true",
+            WatOpcode::I32x4Bitmask => b"i32x4.bitmask",
+            WatOpcode::I32x4DotI16x8S => b"i32x4.dot_i16x8_s",
+            WatOpcode::I32x4Eq => b"i32x4.eq",
+            WatOpcode::I32x4ExtaddPairwiseI16x8S => b"i32x4.extadd_pairwise_i16x8_s",
+            WatOpcode::I32x4ExtaddPairwiseI16x8U => b"i32x4.extadd_pairwise_i16x8_u",
+            WatOpcode::I32x4ExtendHighI16x8S => b"i32x4.extend_high_i16x8_s",
+            WatOpcode::I32x4ExtendHighI16x8U => b"i32x4.extend_high_i16x8_u",
+            WatOpcode::I32x4ExtendLowI16x8S => b"i32x4.extend_low_i16x8_s",
+            WatOpcode::I32x4ExtendLowI16x8U => b"i32x4.extend_low_i16x8_u",
+            WatOpcode::I32x4ExtmulHighI16x8S => b"i32x4.extmul_high_i16x8_s",
+            WatOpcode::I32x4ExtmulHighI16x8U => b"i32x4.extmul_high_i16x8_u",
+            WatOpcode::I32x4ExtmulLowI16x8S => b"i32x4.extmul_low_i16x8_s",
+            WatOpcode::I32x4ExtmulLowI16x8U => b"i32x4.extmul_low_i16x8_u",
+            WatOpcode::I32x4ExtractLane => b"i32x4.extract_lane",
+            WatOpcode::I32x4GeS => b"i32x4.ge_s",
+            WatOpcode::I32x4GeU => b"i32x4.ge_u",
+            WatOpcode::I32x4GtS => b"i32x4.gt_s",
+            WatOpcode::I32x4GtU => b"i32x4.gt_u",
+            WatOpcode::I32x4LeS => b"i32x4.le_s",
+            WatOpcode::I32x4LeU => b"i32x4.le_u",
+            WatOpcode::I32x4LtS => b"i32x4.lt_s",
+            WatOpcode::I32x4LtU => b"i32x4.lt_u",
+            WatOpcode::I32x4MaxS => b"i32x4.max_s",
+            WatOpcode::I32x4MaxU => b"i32x4.max_u",
+            WatOpcode::I32x4MinS => b"i32x4.min_s",
+            WatOpcode::I32x4MinU => b"i32x4.min_u",
+            WatOpcode::I32x4Mul => b"i32x4.mul",
+            WatOpcode::I32x4Ne => b"i32x4.ne",
+            WatOpcode::I32x4Neg => b"i32x4.neg",
+            WatOpcode::I32x4ReplaceLane => b"i32x4.replace_lane",
+            WatOpcode::I32x4Shl => b"i32x4.shl",
+            WatOpcode::I32x4ShrS => b"i32x4.shr_s",
+            WatOpcode::I32x4ShrU => b"i32x4.shr_u",
+            WatOpcode::I32x4Splat => b"i32x4.splat",
+            WatOpcode::I32x4Sub => b"i32x4.sub",
+            WatOpcode::I32x4TruncSatF32x4S => b"i32x4.trunc_sat_f32x4_s",
+            WatOpcode::I32x4TruncSatF32x4U => b"i32x4.trunc_sat_f32x4_u",
+            WatOpcode::I32x4TruncSatF64x2SZero => b"i32x4.trunc_sat_f64x2_s_zero",
+            WatOpcode::I32x4TruncSatF64x2UZero => b"i32x4.trunc_sat_f64x2_u_zero",
+            WatOpcode::I64Add => b"i64.add",
+            WatOpcode::I64And => b"i64.and",
+            WatOpcode::I64AtomicLoad => b"i64.atomic.load",
+            WatOpcode::I64AtomicLoad16U => b"i64.atomic.load16_u",
+            WatOpcode::I64AtomicLoad32U => b"i64.atomic.load32_u",
+            WatOpcode::I64AtomicLoad8U => b"i64.atomic.load8_u",
+            WatOpcode::I64AtomicRmwAdd => b"i64.atomic.rmw.add",
+            WatOpcode::I64AtomicRmwAnd => b"i64.atomic.rmw.and",
+            WatOpcode::I64AtomicRmwCmpxchg => b"i64.atomic.rmw.cmpxchg",
+            WatOpcode::I64AtomicRmwOr => b"i64.atomic.rmw.or",
+            WatOpcode::I64AtomicRmwSub => b"i64.atomic.rmw.sub",
+            WatOpcode::I64AtomicRmwXchg => b"i64.atomic.rmw.xchg",
+            WatOpcode::I64AtomicRmwXor => b"i64.atomic.rmw.xor",
+            WatOpcode::I64AtomicRmw16AddU => b"i64.atomic.rmw16.add_u",
+            WatOpcode::I64AtomicRmw16AndU => b"i64.atomic.rmw16.and_u",
+            WatOpcode::I64AtomicRmw16CmpxchgU => b"i64.atomic.rmw16.cmpxchg_u",
+            WatOpcode::I64AtomicRmw16OrU => b"i64.atomic.rmw16.or_u",
+            WatOpcode::I64AtomicRmw16SubU => b"i64.atomic.rmw16.sub_u",
+            WatOpcode::I64AtomicRmw16XchgU => b"i64.atomic.rmw16.xchg_u",
+            WatOpcode::I64AtomicRmw16XorU => b"i64.atomic.rmw16.xor_u",
+            WatOpcode::I64AtomicRmw32AddU => b"i64.atomic.rmw32.add_u",
+            WatOpcode::I64AtomicRmw32AndU => b"i64.atomic.rmw32.and_u",
+            WatOpcode::I64AtomicRmw32CmpxchgU => b"i64.atomic.rmw32.cmpxchg_u",
+            WatOpcode::I64AtomicRmw32OrU => b"i64.atomic.rmw32.or_u",
+            WatOpcode::I64AtomicRmw32SubU => b"i64.atomic.rmw32.sub_u",
+            WatOpcode::I64AtomicRmw32XchgU => b"i64.atomic.rmw32.xchg_u",
+            WatOpcode::I64AtomicRmw32XorU => b"i64.atomic.rmw32.xor_u",
+            WatOpcode::I64AtomicRmw8AddU => b"i64.atomic.rmw8.add_u",
+            WatOpcode::I64AtomicRmw8AndU => b"i64.atomic.rmw8.and_u",
+            WatOpcode::I64AtomicRmw8CmpxchgU => b"i64.atomic.rmw8.cmpxchg_u",
+            WatOpcode::I64AtomicRmw8OrU => b"i64.atomic.rmw8.or_u",
+            WatOpcode::I64AtomicRmw8SubU => b"i64.atomic.rmw8.sub_u",
+            WatOpcode::I64AtomicRmw8XchgU => b"i64.atomic.rmw8.xchg_u",
+            WatOpcode::I64AtomicRmw8XorU => b"i64.atomic.rmw8.xor_u",
+            WatOpcode::I64AtomicStore => b"i64.atomic.store",
+            WatOpcode::I64AtomicStore16 => b"i64.atomic.store16",
+            WatOpcode::I64AtomicStore32 => b"i64.atomic.store32",
+            WatOpcode::I64AtomicStore8 => b"i64.atomic.store8",
+            WatOpcode::I64Clz => b"i64.clz",
+            WatOpcode::I64Const => b"i64.const",
+            WatOpcode::I64Ctz => b"i64.ctz",
+            WatOpcode::I64DivS => b"i64.div_s",
+            WatOpcode::I64DivU => b"i64.div_u",
+            WatOpcode::I64Eq => b"i64.eq",
+            WatOpcode::I64Eqz => b"i64.eqz",
+            WatOpcode::I64Extend16S => b"i64.extend16_s",
+            WatOpcode::I64Extend32S => b"i64.extend32_s",
+            WatOpcode::I64Extend8S => b"i64.extend8_s",
+            WatOpcode::I64ExtendI32S => b"i64.extend_i32_s",
+            WatOpcode::I64ExtendI32U => b"i64.extend_i32_u",
+            WatOpcode::I64GeS => b"i64.ge_s",
+            WatOpcode::I64GeU => b"i64.ge_u",
+            WatOpcode::I64GtS => b"i64.gt_s",
+            WatOpcode::I64GtU => b"i64.gt_u",
+            WatOpcode::I64LeS => b"i64.le_s",
+            WatOpcode::I64LeU => b"i64.le_u",
+            WatOpcode::I64Load => b"i64.load",
+            WatOpcode::I64Load16S => b"i64.load16_s",
+            WatOpcode::I64Load16U => b"i64.load16_u",
+            WatOpcode::I64Load32S => b"i64.load32_s",
+            WatOpcode::I64Load32U => b"i64.load32_u",
+            WatOpcode::I64Load8S => b"i64.load8_s",
+            WatOpcode::I64Load8U => b"i64.load8_u",
+            WatOpcode::I64LtS => b"i64.lt_s",
+            WatOpcode::I64LtU => b"i64.lt_u",
+            WatOpcode::I64Mul => b"i64.mul",
+            WatOpcode::I64Ne => b"i64.ne",
+            WatOpcode::I64Or => b"i64.or",
+            WatOpcode::I64Popcnt => b"i64.popcnt",
+            WatOpcode::I64ReinterpretF64 => b"i64.reinterpret_f64",
+            WatOpcode::I64RemS => b"i64.rem_s",
+            WatOpcode::I64RemU => b"i64.rem_u",
+            WatOpcode::I64Rotl => b"i64.rotl",
+            WatOpcode::I64Rotr => b"i64.rotr",
+            WatOpcode::I64Shl => b"i64.shl",
+            WatOpcode::I64ShrS => b"i64.shr_s",
+            WatOpcode::I64ShrU => b"i64.shr_u",
+            WatOpcode::I64Store => b"i64.store",
+            WatOpcode::I64Store16 => b"i64.store16",
+            WatOpcode::I64Store32 => b"i64.store32",
+            WatOpcode::I64Store8 => b"i64.store8",
+            WatOpcode::I64Sub => b"i64.sub",
+            WatOpcode::I64TruncF32S => b"i64.trunc_f32_s",
+            WatOpcode::I64TruncF32U => b"i64.trunc_f32_u",
+            WatOpcode::I64TruncF64S => b"i64.trunc_f64_s",
+            WatOpcode::I64TruncF64U => b"i64.trunc_f64_u",
+            WatOpcode::I64TruncSatF32S => b"i64.trunc_sat_f32_s",
+            WatOpcode::I64TruncSatF32U => b"i64.trunc_sat_f32_u",
+            WatOpcode::I64TruncSatF64S => b"i64.trunc_sat_f64_s",
+            WatOpcode::I64TruncSatF64U => b"i64.trunc_sat_f64_u",
+            WatOpcode::I64Xor => b"i64.xor",
+            WatOpcode::I64x2Abs => b"i64x2.abs",
+            WatOpcode::I64x2Add => b"i64x2.add",
+            WatOpcode::I64x2AllTrue => b"i64x2.all_true",
+            WatOpcode::I64x2Bitmask => b"i64x2.bitmask",
+            WatOpcode::I64x2Eq => b"i64x2.eq",
+            WatOpcode::I64x2ExtendHighI32x4S => b"i64x2.extend_high_i32x4_s",
+            WatOpcode::I64x2ExtendHighI32x4U => b"i64x2.extend_high_i32x4_u",
+            WatOpcode::I64x2ExtendLowI32x4S => b"i64x2.extend_low_i32x4_s",
+            WatOpcode::I64x2ExtendLowI32x4U => b"i64x2.extend_low_i32x4_u",
+            WatOpcode::I64x2ExtmulHighI32x4S => b"i64x2.extmul_high_i32x4_s",
+            WatOpcode::I64x2ExtmulHighI32x4U => b"i64x2.extmul_high_i32x4_u",
+            WatOpcode::I64x2ExtmulLowI32x4S => b"i64x2.extmul_low_i32x4_s",
+            WatOpcode::I64x2ExtmulLowI32x4U => b"i64x2.extmul_low_i32x4_u",
+            WatOpcode::I64x2ExtractLane => b"i64x2.extract_lane",
+            WatOpcode::I64x2GeS => b"i64x2.ge_s",
+            WatOpcode::I64x2GtS => b"i64x2.gt_s",
+            WatOpcode::I64x2LeS => b"i64x2.le_s",
+            WatOpcode::I64x2LtS => b"i64x2.lt_s",
+            WatOpcode::I64x2Mul => b"i64x2.mul",
+            WatOpcode::I64x2Ne => b"i64x2.ne",
+            WatOpcode::I64x2Neg => b"i64x2.neg",
+            WatOpcode::I64x2ReplaceLane => b"i64x2.replace_lane",
+            WatOpcode::I64x2Shl => b"i64x2.shl",
+            WatOpcode::I64x2ShrS => b"i64x2.shr_s",
+            WatOpcode::I64x2ShrU => b"i64x2.shr_u",
+            WatOpcode::I64x2Splat => b"i64x2.splat",
+            WatOpcode::I64x2Sub => b"i64x2.sub",
+            WatOpcode::I8x16Abs => b"i8x16.abs",
+            WatOpcode::I8x16Add => b"i8x16.add",
+            WatOpcode::I8x16AddSatS => b"i8x16.add_sat_s",
+            WatOpcode::I8x16AddSatU => b"i8x16.add_sat_u",
+            WatOpcode::I8x16AllTrue => b"i8x16.all_true",
+            WatOpcode::I8x16AvgrU => b"i8x16.avgr_u",
+            WatOpcode::I8x16Bitmask => b"i8x16.bitmask",
+            WatOpcode::I8x16Eq => b"i8x16.eq",
+            WatOpcode::I8x16ExtractLaneS => b"i8x16.extract_lane_s",
+            WatOpcode::I8x16ExtractLaneU => b"i8x16.extract_lane_u",
+            WatOpcode::I8x16GeS => b"i8x16.ge_s",
+            WatOpcode::I8x16GeU => b"i8x16.ge_u",
+            WatOpcode::I8x16GtS => b"i8x16.gt_s",
+            WatOpcode::I8x16GtU => b"i8x16.gt_u",
+            WatOpcode::I8x16LeS => b"i8x16.le_s",
+            WatOpcode::I8x16LeU => b"i8x16.le_u",
+            WatOpcode::I8x16LtS => b"i8x16.lt_s",
+            WatOpcode::I8x16LtU => b"i8x16.lt_u",
+            WatOpcode::I8x16MaxS => b"i8x16.max_s",
+            WatOpcode::I8x16MaxU => b"i8x16.max_u",
+            WatOpcode::I8x16MinS => b"i8x16.min_s",
+            WatOpcode::I8x16MinU => b"i8x16.min_u",
+            WatOpcode::I8x16NarrowI16x8S => b"i8x16.narrow_i16x8_s",
+            WatOpcode::I8x16NarrowI16x8U => b"i8x16.narrow_i16x8_u",
+            WatOpcode::I8x16Ne => b"i8x16.ne",
+            WatOpcode::I8x16Neg => b"i8x16.neg",
+            WatOpcode::I8x16Popcnt => b"i8x16.popcnt",
+            WatOpcode::I8x16ReplaceLane => b"i8x16.replace_lane",
+            WatOpcode::I8x16Shl => b"i8x16.shl",
+            WatOpcode::I8x16ShrS => b"i8x16.shr_s",
+            WatOpcode::I8x16ShrU => b"i8x16.shr_u",
+            WatOpcode::I8x16Shuffle => b"i8x16.shuffle",
+            WatOpcode::I8x16Splat => b"i8x16.splat",
+            WatOpcode::I8x16Sub => b"i8x16.sub",
+            WatOpcode::I8x16SubSatS => b"i8x16.sub_sat_s",
+            WatOpcode::I8x16SubSatU => b"i8x16.sub_sat_u",
+            WatOpcode::I8x16Swizzle => b"i8x16.swizzle",
+            WatOpcode::If => b"if",
+            WatOpcode::LocalGet => b"local.get",
+            WatOpcode::LocalSet => b"local.set",
+            WatOpcode::LocalTee => b"local.tee",
+            WatOpcode::Loop => b"loop",
+            WatOpcode::MemoryAtomicNotify => b"memory.atomic.notify",
+            WatOpcode::MemoryAtomicWait32 => b"memory.atomic.wait32",
+            WatOpcode::MemoryAtomicWait64 => b"memory.atomic.wait64",
+            WatOpcode::MemoryCopy => b"memory.copy",
+            WatOpcode::MemoryFill => b"memory.fill",
+            WatOpcode::MemoryGrow => b"memory.grow",
+            WatOpcode::MemoryInit => b"memory.init",
+            WatOpcode::MemorySize => b"memory.size",
+            WatOpcode::Nop => b"nop",
+            WatOpcode::RefFunc => b"ref.func",
+            WatOpcode::RefIsNull => b"ref.is_null",
+            WatOpcode::RefNull => b"ref.null",
+            WatOpcode::Return => b"return",
+            WatOpcode::ReturnCall => b"return_call",
+            WatOpcode::ReturnCallIndirect => b"return_call_indirect",
+            WatOpcode::Select => b"select",
+            WatOpcode::TableCopy => b"table.copy",
+            WatOpcode::TableFill => b"table.fill",
+            WatOpcode::TableGet => b"table.get",
+            WatOpcode::TableGrow => b"table.grow",
+            WatOpcode::TableInit => b"table.init",
+            WatOpcode::TableSet => b"table.set",
+            WatOpcode::TableSize => b"table.size",
+            WatOpcode::Unreachable => b"unreachable",
+            WatOpcode::V128And => b"v128.and",
+            WatOpcode::V128Andnot => b"v128.andnot",
+            WatOpcode::V128AnyTrue => b"v128.any_true",
+            WatOpcode::V128Bitselect => b"v128.bitselect",
+            WatOpcode::V128Const => b"v128.const",
+            WatOpcode::V128Load => b"v128.load",
+            WatOpcode::V128Load16Lane => b"v128.load16_lane",
+            WatOpcode::V128Load16Splat => b"v128.load16_splat",
+            WatOpcode::V128Load16x4S => b"v128.load16x4_s",
+            WatOpcode::V128Load16x4U => b"v128.load16x4_u",
+            WatOpcode::V128Load32Lane => b"v128.load32_lane",
+            WatOpcode::V128Load32Splat => b"v128.load32_splat",
+            WatOpcode::V128Load32Zero => b"v128.load32_zero",
+            WatOpcode::V128Load32x2S => b"v128.load32x2_s",
+            WatOpcode::V128Load32x2U => b"v128.load32x2_u",
+            WatOpcode::V128Load64Lane => b"v128.load64_lane",
+            WatOpcode::V128Load64Splat => b"v128.load64_splat",
+            WatOpcode::V128Load64Zero => b"v128.load64_zero",
+            WatOpcode::V128Load8Lane => b"v128.load8_lane",
+            WatOpcode::V128Load8Splat => b"v128.load8_splat",
+            WatOpcode::V128Load8x8S => b"v128.load8x8_s",
+            WatOpcode::V128Load8x8U => b"v128.load8x8_u",
+            WatOpcode::V128Not => b"v128.not",
+            WatOpcode::V128Or => b"v128.or",
+            WatOpcode::V128Store => b"v128.store",
+            WatOpcode::V128Store16Lane => b"v128.store16_lane",
+            WatOpcode::V128Store32Lane => b"v128.store32_lane",
+            WatOpcode::V128Store64Lane => b"v128.store64_lane",
+            WatOpcode::V128Store8Lane => b"v128.store8_lane",
+            WatOpcode::V128Xor => b"v128.xor",
+            WatOpcode::Unknown(ref name) => name,
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(*self, WatOpcode::Unknown(_))
+    }
+
+    /// Whether the instruction may appear in a constant expression:
+    /// a global initializer, or an element or data segment offset.
+    pub fn is_constant(&self) -> bool {
+        matches!(*self,
+                 WatOpcode::I32Const | WatOpcode::I64Const | WatOpcode::F32Const |
+                 WatOpcode::F64Const | WatOpcode::V128Const | WatOpcode::GlobalGet |
+                 WatOpcode::RefNull | WatOpcode::RefFunc)
+    }
+}
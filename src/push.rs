@@ -0,0 +1,235 @@
+use core::mem;
+use alloc::vec::Vec;
+use error::{ErrorKind, Result, WatParserError};
+use lexer::{KnownKeyword, WatLexer, WatPosition, WatTokenType};
+use wat::{WatParser, WatParserOptions, WatParserState, START_POSITION};
+
+/// Parser that is fed the input in chunks, such as reads from a network
+/// stream, and passes every event to `sink`.
+///
+/// The events are the ones `WatParser` returns for the whole input, up
+/// to and including the final `End` or `Error`, except that text which
+/// is not UTF-8 is reported after the events before it. Each module field is
+/// parsed once the chunks holding all of it have been fed, and the rest
+/// waits for `finish()`, which marks the end of the input. The input is
+/// kept until the parser is dropped, so positions and ids refer to it as
+/// a whole.
+///
+/// ```
+/// use wasmtextparser::{PushParser, WatParserState};
+///
+/// let mut events = Vec::new();
+/// PushParser::new(|event| events.push(event))
+///     .feed(b"(module (func) (fu").unwrap()
+///     .feed(b"nc))").unwrap()
+///     .finish().unwrap();
+/// assert_eq!(events.len(), 7);
+/// assert_eq!(events.last(), Some(&WatParserState::End));
+/// ```
+pub struct PushParser<F: FnMut(WatParserState)> {
+    sink: F,
+    input: Vec<u8>,
+    // Set aside with no source between calls.
+    parser: WatParser<'static>,
+    // How far the input has been split into complete tokens, the paren
+    // depth there, and the depth module fields close to: 1 inside
+    // `(module`, 0 without it.
+    scanned: WatPosition,
+    depth: usize,
+    field_depth: Option<usize>,
+    // The end of the last field known to be followed by a complete
+    // token, and the end of a field that is not yet.
+    field_end: usize,
+    next_field_end: Option<usize>,
+    // The input before this has been parsed.
+    parsed: usize,
+    // Set once `End` or a final `Error` has been passed to the sink.
+    result: Option<Result<()>>,
+}
+
+impl<F: FnMut(WatParserState)> PushParser<F> {
+    pub fn new(sink: F) -> PushParser<F> {
+        PushParser::with_options(sink, WatParserOptions::default())
+    }
+
+    pub fn with_options(sink: F, options: WatParserOptions) -> PushParser<F> {
+        PushParser {
+            sink,
+            input: Vec::new(),
+            parser: WatParser::with_options(&[], options),
+            scanned: START_POSITION,
+            depth: 0,
+            field_depth: None,
+            field_end: 0,
+            next_field_end: None,
+            parsed: 0,
+            result: None,
+        }
+    }
+
+    /// Appends `chunk` to the input and passes on the events of the
+    /// module fields it completes. An error that stops parsing is
+    /// returned, by this call and every later one. Once `finish()` has
+    /// passed on `End`, the chunk is refused with an error at the end of
+    /// the input.
+    ///
+    /// ```
+    /// use wasmtextparser::PushParser;
+    ///
+    /// let mut parser = PushParser::new(|_| {});
+    /// parser.feed(b"(module)").unwrap().finish().unwrap();
+    /// let err = parser.feed(b"(func)").err().unwrap();
+    /// assert_eq!(format!("{}", err), "1:9: input fed after finish()");
+    /// assert_eq!(parser.finish(), Ok(()));
+    /// ```
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<&mut Self> {
+        match self.result {
+            Some(Ok(())) => {
+                let end = self.parser.stats().end.unwrap_or(self.scanned);
+                return Err(WatParserError {
+                               kind: ErrorKind::UnexpectedToken,
+                               message: "input fed after finish()".into(),
+                               start: end,
+                               end,
+                           });
+            }
+            Some(Err(ref err)) => return Err(err.clone()),
+            None => {}
+        }
+        self.input.extend_from_slice(chunk);
+        self.scan();
+        if self.field_end > self.parsed {
+            let end = self.field_end;
+            self.run(end, true)?;
+        }
+        Ok(self)
+    }
+
+    /// Ends the input and passes on the remaining events, `End`
+    /// included. Errors are returned as by `feed()`; with error recovery
+    /// on, the ones parsing got past only reach the sink.
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(ref result) = self.result {
+            return result.clone();
+        }
+        let end = self.input.len();
+        self.run(end, false)
+    }
+
+    pub fn into_sink(self) -> F {
+        self.sink
+    }
+
+    // Finds where the fed input can be cut: after a ')' that closes a
+    // module field and is followed by a complete token, which shows the
+    // token after the field lexes the same way once more input arrives.
+    fn scan(&mut self) {
+        let mut lexer = WatLexer::starting_at(&self.input, self.scanned);
+        // A lexical error may be a token cut short, so scanning waits
+        // for more input there.
+        while let Ok(token) = lexer.next() {
+            // A token running up to the end of the input may go on in
+            // the next chunk, even a '(' which may start a `(;` comment.
+            let complete = match token.ty {
+                WatTokenType::End => false,
+                WatTokenType::CloseParen => true,
+                _ => token.end.position < self.input.len(),
+            };
+            if !complete {
+                break;
+            }
+            if let Some(end) = self.next_field_end.take() {
+                self.field_end = end;
+            }
+            match token.ty {
+                WatTokenType::OpenParen => self.depth += 1,
+                WatTokenType::CloseParen => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth <= self.field_depth.unwrap_or(0) {
+                        self.next_field_end = Some(token.end.position);
+                    }
+                }
+                // A stray token outside of any field, which the parser
+                // reports.
+                _ if self.depth == 0 => self.next_field_end = Some(token.end.position),
+                _ if self.field_depth.is_none() => {
+                    let explicit = token.keyword == Some(KnownKeyword::Module);
+                    self.field_depth = Some(if explicit { 1 } else { 0 });
+                }
+                _ => {}
+            }
+            self.scanned = token.end;
+        }
+    }
+
+    // Parses the input up to `end`, a field end or, unless `partial`,
+    // the end of the input.
+    fn run(&mut self, end: usize, partial: bool) -> Result<()> {
+        let input = &self.input[..end];
+        // Field ends are at char boundaries, so only the new text needs
+        // checking. Unlike with the whole input at once, the events
+        // before it have been passed on already.
+        if let Err(err) = WatLexer::new(input).validate_utf8_from(self.parsed) {
+            (self.sink)(WatParserState::Error(err.clone()));
+            self.result = Some(Err(err.clone()));
+            return Err(err);
+        }
+        self.parsed = end;
+        let placeholder = WatParser::new(&[]);
+        let mut parser = mem::replace(&mut self.parser, placeholder).rebind(input, partial);
+        parser.resume_input();
+        loop {
+            match parser.parse() {
+                WatParserState::Initial if partial => break,
+                WatParserState::End => {
+                    (self.sink)(WatParserState::End);
+                    self.result = Some(Ok(()));
+                    break;
+                }
+                WatParserState::Error(err) => {
+                    (self.sink)(WatParserState::Error(err.clone()));
+                    if parser.stopped() {
+                        self.result = Some(Err(err));
+                        break;
+                    }
+                }
+                event => (self.sink)(event),
+            }
+        }
+        self.parser = parser.rebind(&[], true);
+        match self.result {
+            Some(Err(ref err)) => Err(err.clone()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use super::*;
+
+    #[test]
+    fn feed_after_finish_is_refused() {
+        let mut events = Vec::new();
+        {
+            let mut parser = PushParser::new(|event| events.push(event));
+            parser.feed(b"(module)").unwrap().finish().unwrap();
+            let err = parser.feed(b" (func)").err().unwrap();
+            assert_eq!(err.kind, ErrorKind::UnexpectedToken);
+            assert_eq!(&*err.message, "input fed after finish()");
+            assert_eq!(err.start.position, 8);
+            assert_eq!(parser.finish(), Ok(()));
+        }
+        assert_eq!(events.len(), 3);
+        assert_eq!(events.last(), Some(&WatParserState::End));
+    }
+
+    #[test]
+    fn feed_after_error_returns_the_error() {
+        let mut parser = PushParser::new(|_| {});
+        let err = parser.feed(b"(module (bogus)) ").err().unwrap();
+        assert_eq!(parser.feed(b"(func)").err(), Some(err.clone()));
+        assert_eq!(parser.finish(), Err(err));
+    }
+}
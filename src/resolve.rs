@@ -0,0 +1,312 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lexer::WatPosition;
+use wat::{ErrorKind, ID, OptionalID, Result, WatImport, WatIndex, WatInstructionArg,
+          WatParserError, WatParserState, WatTypeuse};
+
+/// The module-level index spaces that `$id`s can name.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash)]
+#[non_exhaustive]
+pub enum IndexSpace {
+    Type,
+    Func,
+    Table,
+    Memory,
+    Global,
+    Elem,
+    Data,
+}
+
+#[derive(Default)]
+struct Space {
+    count: u32,
+    ids: BTreeMap<ID, u32>,
+}
+
+/// Assigns indices to module items in declaration order and maps their
+/// `$id`s back to numbers.
+///
+/// Feed it every event with `add_event`; since references may point
+/// forward, resolve them once the whole module has been seen.
+///
+/// ```
+/// use wasmtextparser::{IndexResolver, IndexSpace, WatParser, WatParserState};
+/// use wasmtextparser::wat::WatInstructionArg;
+///
+/// let mut parser = WatParser::new(b"(module (func $g) (func $f) (func call $f))");
+/// let events = parser.events().collect::<Result<Vec<_>, _>>().unwrap();
+/// let mut resolver = IndexResolver::new();
+/// for event in &events {
+///     resolver.add_event(event);
+/// }
+/// let callee = events.iter().filter_map(|event| match *event {
+///     WatParserState::CodeOperator { ref args, .. } => match args[0] {
+///         WatInstructionArg::ID(ref id) => resolver.lookup(IndexSpace::Func, id),
+///         _ => None,
+///     },
+///     _ => None,
+/// }).next();
+/// assert_eq!(callee, Some(1));
+/// assert_eq!(resolver.count(IndexSpace::Func), 3);
+/// ```
+#[derive(Default)]
+pub struct IndexResolver {
+    spaces: BTreeMap<IndexSpace, Space>,
+    // The signature of each type, by index, and the line of its field.
+    types: Vec<(WatTypeuse, usize)>,
+}
+
+impl IndexResolver {
+    pub fn new() -> IndexResolver {
+        IndexResolver::default()
+    }
+
+    /// Records the item declared by `event`, if any. Imports take the
+    /// next index of their kind, like definitions.
+    pub fn add_event(&mut self, event: &WatParserState) {
+        match *event {
+            WatParserState::Import { ref import, .. } => {
+                match *import {
+                    WatImport::Func { ref id, .. } => self.declare(IndexSpace::Func, id),
+                    WatImport::Table { ref id, .. } => self.declare(IndexSpace::Table, id),
+                    WatImport::Memory { ref id, .. } => self.declare(IndexSpace::Memory, id),
+                    WatImport::Global { ref id, .. } => self.declare(IndexSpace::Global, id),
+                }
+            }
+            WatParserState::Type { ref id, ref functype, position } => {
+                self.declare(IndexSpace::Type, id);
+                self.types.push((functype.clone(), position.line));
+            }
+            WatParserState::StartFunc { ref id, .. } => self.declare(IndexSpace::Func, id),
+            WatParserState::Table { ref id, .. } => self.declare(IndexSpace::Table, id),
+            WatParserState::Memory { ref id, .. } => self.declare(IndexSpace::Memory, id),
+            WatParserState::StartGlobal { ref id, .. } => self.declare(IndexSpace::Global, id),
+            WatParserState::Elem { ref id, .. } => self.declare(IndexSpace::Elem, id),
+            WatParserState::Data { ref id, .. } => self.declare(IndexSpace::Data, id),
+            _ => {}
+        }
+    }
+
+    // A repeated id keeps the index of its first declaration.
+    fn declare(&mut self, space: IndexSpace, id: &OptionalID) {
+        let space = self.spaces.entry(space).or_default();
+        if let Some(ref id) = *id {
+            space.ids.entry(id.clone()).or_insert(space.count);
+        }
+        space.count += 1;
+    }
+
+    /// Number of items declared so far in `space`.
+    pub fn count(&self, space: IndexSpace) -> u32 {
+        self.spaces.get(&space).map_or(0, |space| space.count)
+    }
+
+    /// The index of the item named `id`, including its `$`.
+    pub fn lookup(&self, space: IndexSpace, id: &[u8]) -> Option<u32> {
+        self.spaces.get(&space).and_then(|space| space.ids.get(id).cloned())
+    }
+
+    /// Turns `index` into a number. Numeric indices are returned as is,
+    /// even when out of range; unknown ids give `None`, and so do
+    /// `Symbol`s, which need the parser's `WatInterner` first.
+    pub fn resolve(&self, space: IndexSpace, index: &WatIndex) -> Option<u32> {
+        match *index {
+            WatIndex::Num(num) => Some(num),
+            WatIndex::Id(ref id) => self.lookup(space, id),
+            WatIndex::Symbol(_) => None,
+        }
+    }
+
+    /// Checks that the `(type $t)` references of `event` name declared
+    /// types, and that inline params and results next to one match the
+    /// type. Types may be declared after their uses, so call this only
+    /// after every event has gone through `add_event`.
+    ///
+    /// ```
+    /// use wasmtextparser::{IndexResolver, WatParser};
+    ///
+    /// fn check(source: &[u8]) -> Result<(), String> {
+    ///     let events = WatParser::new(source).events().collect::<Result<Vec<_>, _>>().unwrap();
+    ///     let mut resolver = IndexResolver::new();
+    ///     for event in &events {
+    ///         resolver.add_event(event);
+    ///     }
+    ///     events.iter().try_for_each(|event| resolver.check_typeuses(event))
+    ///         .map_err(|err| format!("{} at {}", err.message, err.start.line))
+    /// }
+    ///
+    /// assert_eq!(check(b"(module
+    ///   (func (call_indirect (type $t) (i32.const 0)))
+    ///   (func (type $t) (param $x i32) (result i64) (i64.const 0))
+    ///   (type $t (func (param i32) (result i64))))"), Ok(()));
+    /// assert_eq!(check(b"(module
+    ///   (func (type $t) (param i32) (result i32) (i32.const 0))
+    ///   (type $t (func (param i32) (result i64))))"),
+    ///   Err("(type $t) is (func (param i32) (result i64)), declared at line 3, but the \
+    ///        inline signature is (func (param i32) (result i32)) at 2".into()));
+    /// ```
+    pub fn check_typeuses(&self, event: &WatParserState) -> Result<()> {
+        match *event {
+            WatParserState::Import {
+                import: WatImport::Func { ref typeuse, .. },
+                position,
+                ..
+            } |
+            WatParserState::StartFunc {
+                ref typeuse,
+                position,
+                ..
+            } => self.check_typeuse(typeuse, position),
+            WatParserState::CodeOperator { ref args, position, .. } => {
+                for arg in args {
+                    if let WatInstructionArg::Type(ref typeuse) = *arg {
+                        self.check_typeuse(typeuse, position)?;
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Typeuses carry no span of their own, so the error points at the
+    // event that holds them.
+    fn check_typeuse(&self, typeuse: &WatTypeuse, position: WatPosition) -> Result<()> {
+        let (index, text) = match (typeuse.id.as_ref(), typeuse.index) {
+            (Some(id), _) => (self.lookup(IndexSpace::Type, id), String::from_utf8_lossy(id)),
+            (None, Some(index)) => (Some(index), format!("{}", index).into()),
+            (None, None) => return Ok(()),
+        };
+        let index = match index {
+            Some(index) if (index as usize) < self.types.len() => index,
+            _ => {
+                let message = format!("unknown type {}", text);
+                return Err(WatParserError {
+                               kind: ErrorKind::Validation,
+                               message: message.into_boxed_str(),
+                               start: position,
+                               end: position,
+                           });
+            }
+        };
+        if typeuse.params.is_empty() && typeuse.results.is_empty() {
+            return Ok(());
+        }
+        match self.types.get(index as usize) {
+            Some((declared, line)) if !typeuse.same_signature(declared) => {
+                Err(typeuse.mismatch_error(declared, *line, position, position))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use super::*;
+    use wat::WatParser;
+
+    // The events of `source`, which must parse, and a resolver fed them.
+    fn resolve(source: &[u8]) -> (Vec<WatParserState>, IndexResolver) {
+        let events = WatParser::new(source).events().collect::<Result<Vec<_>>>().unwrap();
+        let mut resolver = IndexResolver::new();
+        for event in &events {
+            resolver.add_event(event);
+        }
+        (events, resolver)
+    }
+
+    // The index of the first argument of each `call`.
+    fn callees(events: &[WatParserState], resolver: &IndexResolver) -> Vec<Option<u32>> {
+        events.iter()
+            .filter_map(|event| match *event {
+                WatParserState::CodeOperator { ref instruction, ref args, .. }
+                    if &instruction[..] == b"call" => Some(args),
+                _ => None,
+            })
+            .map(|args| match args[0] {
+                WatInstructionArg::ID(ref id) => resolver.lookup(IndexSpace::Func, id),
+                WatInstructionArg::Function(ref index) => {
+                    resolver.resolve(IndexSpace::Func, index)
+                }
+                ref arg => panic!("unexpected {:?}", arg),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn call_resolves_to_the_declaration_order() {
+        let (events, resolver) = resolve(b"(module (func $g) (func $f) (func call $f call $g))");
+        assert_eq!(callees(&events, &resolver), [Some(1), Some(0)]);
+        assert_eq!(resolver.count(IndexSpace::Func), 3);
+        assert_eq!(resolver.lookup(IndexSpace::Func, b"$h"), None);
+        assert_eq!(resolver.resolve(IndexSpace::Func, &WatIndex::Num(7)), Some(7));
+    }
+
+    #[test]
+    fn imports_take_the_first_indices() {
+        let (events, resolver) = resolve(b"(module
+            (import \"m\" \"a\" (func $a))
+            (import \"m\" \"g\" (global $ga i32))
+            (func $b (import \"m\" \"b\"))
+            (memory $m (import \"m\" \"mem\") 1)
+            (func $f call $f call $b call $a)
+            (global $gb i32 (i32.const 0)))");
+        assert_eq!(callees(&events, &resolver), [Some(2), Some(1), Some(0)]);
+        assert_eq!(resolver.lookup(IndexSpace::Global, b"$ga"), Some(0));
+        assert_eq!(resolver.lookup(IndexSpace::Global, b"$gb"), Some(1));
+        assert_eq!(resolver.lookup(IndexSpace::Memory, b"$m"), Some(0));
+        assert_eq!(resolver.count(IndexSpace::Func), 3);
+    }
+
+    // The first typeuse `check_typeuses` rejects in `source`, as
+    // "message at line:column".
+    fn check(source: &[u8]) -> Option<String> {
+        let (events, resolver) = resolve(source);
+        events.iter()
+            .find_map(|event| resolver.check_typeuses(event).err())
+            .map(|err| format!("{} at {}:{}", err.message, err.start.line, err.start.column))
+    }
+
+    #[test]
+    fn forward_declared_call_indirect_type() {
+        assert_eq!(check(b"(module (table 1 funcref)
+                             (func (call_indirect (type $t) (param i32)
+                                     (i32.const 1) (i32.const 0)))
+                             (type $t (func (param i32))))"),
+                   None);
+        assert_eq!(check(b"(module (table 1 funcref)
+                             (func (call_indirect (type $t) (result i32) (i32.const 0)) drop)
+                             (type $t (func (param i32))))"),
+                   Some(String::from("(type $t) is (func (param i32)), declared at line 3, but \
+                                      the inline signature is (func (result i32)) at 2:36")));
+        assert_eq!(check(b"(module (func (call_indirect (type $u) (i32.const 0))))"),
+                   Some(String::from("unknown type $u at 1:15")));
+    }
+
+    #[test]
+    fn inline_signatures_against_their_type() {
+        assert_eq!(check(b"(module (type $t (func (param i32) (result i64)))
+                             (import \"m\" \"f\" (func (type $t) (param i32) (result i64)))
+                             (func (type $t) (param $x i32) (result i64) (i64.const 0))
+                             (func (type $t)))"),
+                   None);
+        assert_eq!(check(b"(module (type $t (func (param i32)))
+                             (func (type $t) (param i64)))"),
+                   Some(String::from("(type $t) is (func (param i32)), declared at line 1, but \
+                                      the inline signature is (func (param i64)) at 2:29")));
+        assert_eq!(check(b"(module (type $t (func (param i32)))
+                             (import \"m\" \"f\" (func (type $t) (result i32))))"),
+                   Some(String::from("(type $t) is (func (param i32)), declared at line 1, but \
+                                      the inline signature is (func (result i32)) at 2:29")));
+        // Numeric typeuses are checked the same way.
+        assert_eq!(check(b"(module (type (func (param i32))) (func (type 0) (param i32)))"), None);
+        assert_eq!(check(b"(module (type (func (param i32))) (func (type 0) (param f32)))"),
+                   Some(String::from("(type 0) is (func (param i32)), declared at line 1, but \
+                                      the inline signature is (func (param f32)) at 1:34")));
+        assert_eq!(check(b"(module (func (type 1)))"), Some(String::from("unknown type 1 at 1:8")));
+    }
+}
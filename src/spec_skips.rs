@@ -0,0 +1,31 @@
+// Spec test scripts skipped, by file name, for proposals the parser does
+// not support yet, and why. Included by src/main.rs and tests/spec.rs.
+// The names are those of the upstream testsuite's proposal scripts; any
+// other script of it that fails is a parser bug, not a missing entry.
+const SPEC_SKIPS: &[(&str, &str)] = &[
+    ("exceptions.wast", "exception handling is not supported"),
+    ("tag.wast", "exception handling is not supported"),
+    ("throw.wast", "exception handling is not supported"),
+    ("throw_ref.wast", "exception handling is not supported"),
+    ("try_table.wast", "exception handling is not supported"),
+    ("address64.wast", "64-bit memories are not supported"),
+    ("align64.wast", "64-bit memories are not supported"),
+    ("load64.wast", "64-bit memories are not supported"),
+    ("memory64.wast", "64-bit memories are not supported"),
+    ("memory_grow64.wast", "64-bit memories are not supported"),
+    ("br_on_non_null.wast", "typed function references are not supported"),
+    ("br_on_null.wast", "typed function references are not supported"),
+    ("call_ref.wast", "typed function references are not supported"),
+    ("ref_as_non_null.wast", "typed function references are not supported"),
+    ("return_call_ref.wast", "typed function references are not supported"),
+    ("array.wast", "gc instructions are not supported"),
+    ("br_on_cast.wast", "gc instructions are not supported"),
+    ("br_on_cast_fail.wast", "gc instructions are not supported"),
+    ("extern.wast", "gc instructions are not supported"),
+    ("i31.wast", "gc instructions are not supported"),
+    ("ref_cast.wast", "gc instructions are not supported"),
+    ("ref_test.wast", "gc instructions are not supported"),
+    ("struct.wast", "gc instructions are not supported"),
+    ("type-rec.wast", "recursive types are not supported"),
+    ("type-subtyping.wast", "recursive types are not supported"),
+];
@@ -0,0 +1,346 @@
+//! A readable trace of the parser events, one line per event.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io;
+use std::io::prelude::*;
+use emit::{id_text, local_clause, quote_name, typeuse_clauses, write_args, write_const_expr,
+           write_elem};
+use wat::{Name, WatDataKind, WatParser, WatParserState};
+
+/// Writes each event on a line of its own: its name, then what it holds
+/// in WAT notation. Module fields are indented under `StartModule`, and
+/// the instructions of a body under its `StartFunc` or `StartGlobal`, one
+/// more level per open folded group. A folded instruction starts with
+/// `(`, and its `CodeOperatorEnd` is a `)` line.
+///
+/// Unlike `WatWriter`, this shows the events as they come, errors
+/// included, rather than text that parses back to them.
+pub struct WatTracer<W: Write> {
+    out: W,
+    // Indent of the module fields, and of the top of the current body.
+    field_depth: usize,
+    body_depth: usize,
+}
+
+impl<W: Write> WatTracer<W> {
+    pub fn new(out: W) -> WatTracer<W> {
+        WatTracer {
+            out,
+            field_depth: 0,
+            body_depth: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn start_line(&mut self, depth: usize, name: &str) -> io::Result<()> {
+        for _ in 0..depth {
+            self.out.write_all(b"  ")?;
+        }
+        self.out.write_all(name.as_bytes())
+    }
+
+    fn write_exports(&mut self, names: &[Name]) -> io::Result<()> {
+        for name in names {
+            write!(self.out, " (export {})", quote_name(name))?;
+        }
+        Ok(())
+    }
+
+    fn write_clauses(&mut self, clauses: &[String]) -> io::Result<()> {
+        for clause in clauses {
+            write!(self.out, " {}", clause)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the line for one event. `Initial` writes nothing.
+    pub fn write(&mut self, event: &WatParserState) -> io::Result<()> {
+        let depth = self.field_depth;
+        match *event {
+            WatParserState::Initial => return Ok(()),
+            WatParserState::End => self.start_line(0, "End")?,
+            WatParserState::Error(ref err) => write!(self.out, "Error {}", err)?,
+            WatParserState::StartModule { ref id, .. } => {
+                self.start_line(depth, "StartModule")?;
+                write!(self.out, "{}", id_text(id))?;
+                self.field_depth += 1;
+                self.body_depth = self.field_depth;
+            }
+            WatParserState::EndModule => {
+                self.field_depth = self.field_depth.saturating_sub(1);
+                self.body_depth = self.field_depth;
+                self.start_line(self.field_depth, "EndModule")?;
+            }
+            WatParserState::Type { ref id, ref functype, .. } => {
+                self.start_line(depth, "Type")?;
+                write!(self.out, "{} (func", id_text(id))?;
+                self.write_clauses(&typeuse_clauses(functype))?;
+                self.out.write_all(b")")?;
+            }
+            WatParserState::Import {
+                ref modname,
+                ref fieldname,
+                ref import,
+                ref export_names,
+                ..
+            } => {
+                self.start_line(depth, "Import")?;
+                write!(self.out, " {} {} {}", quote_name(modname), quote_name(fieldname), import)?;
+                self.write_exports(export_names)?;
+            }
+            WatParserState::StartFunc {
+                ref id,
+                ref export_names,
+                ref typeuse,
+                ref locals,
+                ..
+            } => {
+                self.start_line(depth, "StartFunc")?;
+                write!(self.out, "{}", id_text(id))?;
+                self.write_exports(export_names)?;
+                self.write_clauses(&typeuse_clauses(typeuse))?;
+                let locals: Vec<String> = locals.iter().map(local_clause).collect();
+                self.write_clauses(&locals)?;
+                self.body_depth = depth + 1;
+            }
+            WatParserState::StartGlobal {
+                ref id,
+                ref export_names,
+                ref globaltype,
+                ..
+            } => {
+                self.start_line(depth, "StartGlobal")?;
+                write!(self.out, "{}", id_text(id))?;
+                self.write_exports(export_names)?;
+                write!(self.out, " {}", globaltype)?;
+                self.body_depth = depth + 1;
+            }
+            WatParserState::EndFunc { .. } | WatParserState::EndGlobal { .. } => {
+                let name = match *event {
+                    WatParserState::EndFunc { .. } => "EndFunc",
+                    _ => "EndGlobal",
+                };
+                self.start_line(depth, name)?;
+                self.body_depth = depth;
+            }
+            WatParserState::Table {
+                ref id,
+                ref export_names,
+                ref tabletype,
+                ..
+            } => {
+                self.start_line(depth, "Table")?;
+                write!(self.out, "{}", id_text(id))?;
+                self.write_exports(export_names)?;
+                write!(self.out, " {}", tabletype)?;
+            }
+            WatParserState::Memory {
+                ref id,
+                ref export_names,
+                ref memtype,
+                ..
+            } => {
+                self.start_line(depth, "Memory")?;
+                write!(self.out, "{}", id_text(id))?;
+                self.write_exports(export_names)?;
+                write!(self.out, " {}", memtype)?;
+            }
+            WatParserState::Export {
+                ref name,
+                kind,
+                ref index,
+                ..
+            } => {
+                self.start_line(depth, "Export")?;
+                write!(self.out, " {} ({} {})", quote_name(name), kind, index)?;
+            }
+            WatParserState::Start { ref func, .. } => {
+                self.start_line(depth, "Start")?;
+                write!(self.out, " {}", func)?;
+            }
+            WatParserState::Elem {
+                ref id,
+                ref kind,
+                ref elems,
+                ..
+            } => {
+                self.start_line(depth, "Elem")?;
+                write!(self.out, "{}", id_text(id))?;
+                write_elem(&mut self.out, kind, elems)?;
+            }
+            WatParserState::Data {
+                ref id,
+                ref kind,
+                ref data,
+                ..
+            } => {
+                self.start_line(depth, "Data")?;
+                write!(self.out, "{}", id_text(id))?;
+                if let WatDataKind::Active { ref memory, ref offset } = *kind {
+                    write!(self.out, " (memory {}) (offset ", memory)?;
+                    write_const_expr(&mut self.out, offset)?;
+                    self.out.write_all(b")")?;
+                }
+                write!(self.out, " {} bytes", data.len())?;
+            }
+            WatParserState::CustomSection { ref name, ref data, .. } => {
+                self.start_line(depth, "CustomSection")?;
+                write!(self.out, " {} {} bytes", quote_name(name), data.len())?;
+            }
+            WatParserState::CodeOperator {
+                ref instruction,
+                ref args,
+                group,
+                depth,
+                ..
+            } => {
+                self.start_line(self.body_depth + depth as usize, if group { "(" } else { "" })?;
+                self.out.write_all(instruction)?;
+                write_args(&mut self.out, args)?;
+            }
+            WatParserState::CodeOperatorEnd { depth } => {
+                self.start_line(self.body_depth + depth as usize, ")")?;
+            }
+        }
+        self.out.write_all(b"\n")
+    }
+}
+
+/// Parses `source` and writes the trace of its events to `out`, up to
+/// `End` or the first error, which gets an `Error` line like the other
+/// events. Only writing can fail.
+///
+/// ```
+/// use wasmtextparser::print_events;
+///
+/// let source = br#"(module
+///   (import "env" "log" (func $log (param i32)))
+///   (memory 1)
+///   (func $f (export "f") (param $x i32) (result i32) (local i64)
+///     (i32.add (local.get $x) (i32.const 1)))
+///   (data (i32.const 8) "hello"))"#;
+/// let mut out = Vec::new();
+/// print_events(source, &mut out).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "\
+/// StartModule
+///   Import \"env\" \"log\" (func $log (param i32))
+///   Memory 1
+///   StartFunc $f (export \"f\") (param $x i32) (result i32) (local i64)
+///     (i32.add
+///       (local.get $x
+///       )
+///       (i32.const 1
+///       )
+///     )
+///   EndFunc
+///   Data (memory 0) (offset i32.const 8) 5 bytes
+/// EndModule
+/// End
+/// ");
+///
+/// let mut out = Vec::new();
+/// print_events(b"(module (func (nop))", &mut out).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "\
+/// StartModule
+///   StartFunc
+///     (nop
+///     )
+///   EndFunc
+/// Error 1:1: expected ')', found end of input
+/// ");
+/// ```
+pub fn print_events<W: Write>(source: &[u8], out: W) -> io::Result<()> {
+    let mut parser = WatParser::new(source);
+    let mut tracer = WatTracer::new(out);
+    loop {
+        let event = parser.parse();
+        tracer.write(&event)?;
+        if let WatParserState::End | WatParserState::Error(_) = event {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &[u8] = b"(module $m (@custom \"c\" \"\\01\\02\")
+        (type $t (func (param i32) (result i32)))
+        (import \"env\" \"t\" (table 1 funcref))
+        (table $tab (export \"tab\") 2 10 funcref)
+        (memory (export \"mem\") 1 2)
+        (global $g (export \"g\") (mut i32) (i32.const 0))
+        (func $f (type $t)
+          local.get 0
+          if (result i32)
+            (i32.add (local.get 0) (global.get $g))
+          else
+            i32.const 1
+          end)
+        (export \"f\" (func $f)) (start 1) (func)
+        (elem (table $tab) (i32.const 0) func $f)
+        (data (memory 0) (i32.const 8) \"hi\"))";
+
+    #[test]
+    fn traces_a_module() {
+        let mut out = Vec::new();
+        print_events(SOURCE, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "\
+StartModule $m
+  CustomSection \"c\" 2 bytes
+  Type $t (func (param i32) (result i32))
+  Import \"env\" \"t\" (table 1 funcref)
+  Table $tab (export \"tab\") 2 10 funcref
+  Memory (export \"mem\") 1 2
+  StartGlobal $g (export \"g\") (mut i32)
+    (i32.const 0
+    )
+  EndGlobal
+  StartFunc $f (type $t)
+    local.get 0
+    if (result i32)
+      (i32.add
+        (local.get 0
+        )
+        (global.get $g
+        )
+      )
+    else
+      i32.const 1
+    end
+  EndFunc
+  Export \"f\" (func $f)
+  Start 1
+  StartFunc
+  EndFunc
+  Elem (table $tab) (offset i32.const 0) func $f
+  Data (memory 0) (offset i32.const 8) 2 bytes
+EndModule
+End
+");
+    }
+
+    #[test]
+    fn traces_a_bare_body_and_its_error() {
+        // `Initial` writes nothing, and the error line ends the trace. The
+        // `nop` is lost: the lexer fails on the token after it.
+        let mut parser = WatParser::func_body(b"(drop (i32.const 1)) nop\n  \"x");
+        let mut tracer = WatTracer::new(Vec::new());
+        tracer.write(&WatParserState::Initial).unwrap();
+        loop {
+            let event = parser.parse();
+            tracer.write(&event).unwrap();
+            if let WatParserState::Error(_) = event {
+                break;
+            }
+        }
+        assert_eq!(String::from_utf8(tracer.into_inner()).unwrap(),
+                   "(drop\n  (i32.const 1\n  )\n)\nError 2:5: Unexpected eos\n");
+    }
+}
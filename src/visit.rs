@@ -0,0 +1,113 @@
+use lexer::WatPosition;
+use wat::{Data, Name, OptionalID, WatDataKind, WatElemKind, WatElemList, WatExportKind,
+          WatGlobalType, WatImport, WatIndex, WatInstructionArg, WatLocal, WatMemoryType,
+          WatTableType, WatTypeuse};
+
+/// Callbacks for `WatParser::run`, one per event kind. Every method
+/// does nothing by default, so implementors only write the ones they
+/// need.
+#[allow(unused_variables)]
+pub trait WatVisitor {
+    fn start_module(&mut self, id: &OptionalID, position: WatPosition) {}
+    fn end_module(&mut self) {}
+    fn func_type(&mut self, id: &OptionalID, functype: &WatTypeuse, position: WatPosition) {}
+    fn import(&mut self,
+              modname: &Name,
+              fieldname: &Name,
+              import: &WatImport,
+              export_names: &[Name],
+              position: WatPosition) {
+    }
+    fn start_func(&mut self,
+                  id: &OptionalID,
+                  export_names: &[Name],
+                  typeuse: &WatTypeuse,
+                  locals: &[WatLocal],
+                  position: WatPosition) {
+    }
+    fn end_func(&mut self, position: WatPosition) {}
+    fn start_global(&mut self,
+                    id: &OptionalID,
+                    export_names: &[Name],
+                    globaltype: &WatGlobalType,
+                    position: WatPosition) {
+    }
+    fn end_global(&mut self, position: WatPosition) {}
+    fn table(&mut self,
+             id: &OptionalID,
+             export_names: &[Name],
+             tabletype: &WatTableType,
+             position: WatPosition) {
+    }
+    fn memory(&mut self,
+              id: &OptionalID,
+              export_names: &[Name],
+              memtype: &WatMemoryType,
+              position: WatPosition) {
+    }
+    fn export(&mut self,
+              name: &Name,
+              kind: WatExportKind,
+              index: &WatIndex,
+              position: WatPosition) {
+    }
+    fn start(&mut self, func: &WatIndex, position: WatPosition) {}
+    fn elem(&mut self, id: &OptionalID, kind: &WatElemKind, elems: &WatElemList, position: WatPosition) {}
+    fn data(&mut self, id: &OptionalID, kind: &WatDataKind, data: &Data, position: WatPosition) {}
+    fn custom_section(&mut self, name: &Name, data: &Data, position: WatPosition) {}
+    /// An instruction in a function body or global initializer. For a
+    /// folded one (`group`), the instructions nested in it follow and
+    /// `end_instruction` is called at its ')'.
+    fn instruction(&mut self,
+                   instruction: &[u8],
+                   args: &[WatInstructionArg],
+                   group: bool,
+                   position: WatPosition) {
+    }
+    fn end_instruction(&mut self) {}
+}
+
+/// Counts the instructions of every function body and global
+/// initializer.
+///
+/// ```
+/// use wasmtextparser::{InstructionCounter, WatParser};
+///
+/// let mut counter = InstructionCounter::default();
+/// WatParser::new(b"(module (func (drop (i32.const 1))) (func nop))").run(&mut counter).unwrap();
+/// assert_eq!(counter.count, 3);
+/// ```
+#[derive(Debug,Clone,Default)]
+pub struct InstructionCounter {
+    pub count: usize,
+}
+
+impl WatVisitor for InstructionCounter {
+    fn instruction(&mut self, _: &[u8], _: &[WatInstructionArg], _: bool, _: WatPosition) {
+        self.count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wat::WatParser;
+
+    #[test]
+    fn instruction_counter() {
+        let mut counter = InstructionCounter::default();
+        WatParser::new(b"(module (global i32 (i32.const 7))
+                            (func (local.get 0) (i32.add (i32.const 1) (i32.const 2)) drop)
+                            (func))")
+            .run(&mut counter)
+            .unwrap();
+        assert_eq!(counter.count, 6);
+
+        // The count stops at the first error, which `run` returns.
+        let mut counter = InstructionCounter::default();
+        assert!(WatParser::new(b"(module (func nop nop) (func (i32.const \"7\")))")
+            .run(&mut counter)
+            .is_err());
+        assert_eq!(counter.count, 2);
+    }
+}
@@ -0,0 +1,494 @@
+use alloc::vec::Vec;
+use error::{Result, WatParserError};
+use lexer::{WatPosition, WatTokenType};
+use opcode::WatOpcode;
+use wat::{Data, Name, OptionalID, WatInstruction, WatParser, WatParserState, WatValType};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use wat::{serialize_hex, serialize_optional_text, serialize_text};
+
+/// A value passed to or expected from an action.
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WastValue {
+    /// A constant instruction, such as `(i32.const 1)` or `(ref.null func)`.
+    Const(WatInstruction),
+    /// `(ref.extern n)`, a host reference. Without `n`, only allowed in
+    /// results, it stands for any non-null one.
+    RefExtern(Option<u32>),
+    /// `(ref.func)` in results: any non-null function reference.
+    RefFunc,
+    /// `(f32.const nan:canonical)` or its f64 form, in results.
+    CanonicalNan(WatValType),
+    /// `(f32.const nan:arithmetic)` or its f64 form, in results.
+    ArithmeticNan(WatValType),
+}
+
+/// What a script does with an instantiated module: `(invoke ...)` calls
+/// an exported function and `(get ...)` reads an exported global.
+/// `module` names the module; without it the last one is meant.
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WastAction {
+    Invoke {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        module: OptionalID,
+        name: Name,
+        args: Vec<WastValue>,
+    },
+    Get {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        module: OptionalID,
+        name: Name,
+    },
+}
+
+/// The `(module ...)` of an assertion. It is not parsed along with the
+/// script, since most such modules are there to fail.
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WastModule {
+    /// A module written out, from its '(' to past its ')';
+    /// `WastParser::source_text` returns its text.
+    Text { start: WatPosition, end: WatPosition },
+    /// `(module quote "...")`: the text of the module, its strings put
+    /// together.
+    Quote(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] Data),
+    /// `(module binary "...")`: the bytes of a binary module.
+    Binary(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))] Data),
+}
+
+/// Events of a `.wast` script. Each `position` is that of the command's
+/// '('.
+#[derive(Debug,Clone,PartialEq,Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WastParserState {
+    #[default]
+    Initial,
+    End,
+    Error(WatParserError),
+    /// An event of a `(module ...)` command: its `StartModule`, then the
+    /// module's events up to its `EndModule`.
+    Module(WatParserState),
+    /// `(module $id? quote "...")`: a module given as strings, which put
+    /// together are its text. They are left to the caller to parse, so
+    /// positions in the module are relative to `source`.
+    ///
+    /// ```
+    /// use wasmtextparser::{WastParser, WastParserState, WatParser};
+    ///
+    /// let mut parser = WastParser::new(b"(module quote \"(func\" \" (local i33))\")");
+    /// let source = match parser.parse() {
+    ///     WastParserState::QuotedModule { source, .. } => source,
+    ///     event => panic!("unexpected {:?}", event),
+    /// };
+    /// assert_eq!(source, b"(func (local i33))");
+    /// let err = WatParser::new(&source).events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(err.start.column, 13);
+    /// ```
+    QuotedModule {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))]
+        source: Data,
+        position: WatPosition,
+    },
+    /// `(module $id? binary "...")`: a binary module, its strings put
+    /// together byte for byte. It is not decoded.
+    ///
+    /// ```
+    /// use wasmtextparser::{WastParser, WastParserState};
+    ///
+    /// let mut parser = WastParser::new(b"(module binary \"\\00asm\" \"\\01\\00\\00\\00\" \"\\de\\ad\\be\\ef\")");
+    /// let bytes = match parser.parse() {
+    ///     WastParserState::BinaryModule { bytes, .. } => bytes,
+    ///     event => panic!("unexpected {:?}", event),
+    /// };
+    /// assert_eq!(bytes, b"\0asm\x01\0\0\0\xde\xad\xbe\xef");
+    /// ```
+    BinaryModule {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+        bytes: Data,
+        position: WatPosition,
+    },
+    /// `(register "name" $module?)` makes a module's exports importable
+    /// under `name`.
+    Register {
+        name: Name,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        module: OptionalID,
+        position: WatPosition,
+    },
+    /// An action run for its effects.
+    Action {
+        action: WastAction,
+        position: WatPosition,
+    },
+    AssertReturn {
+        action: WastAction,
+        results: Vec<WastValue>,
+        position: WatPosition,
+    },
+    AssertTrap {
+        action: WastAction,
+        message: Name,
+        position: WatPosition,
+    },
+    AssertExhaustion {
+        action: WastAction,
+        message: Name,
+        position: WatPosition,
+    },
+    /// `(assert_trap (module ...) "message")`: instantiation traps.
+    AssertUninstantiable {
+        module: WastModule,
+        message: Name,
+        position: WatPosition,
+    },
+    AssertInvalid {
+        module: WastModule,
+        message: Name,
+        position: WatPosition,
+    },
+    AssertMalformed {
+        module: WastModule,
+        message: Name,
+        position: WatPosition,
+    },
+    AssertUnlinkable {
+        module: WastModule,
+        message: Name,
+        position: WatPosition,
+    },
+}
+
+/// Pull parser for `.wast` scripts, the format of the WebAssembly spec
+/// tests: a sequence of modules, actions and assertions about them.
+///
+/// Modules are read by a `WatParser` and their events returned wrapped
+/// in `WastParserState::Module`. The first error ends the script.
+///
+/// ```
+/// use wasmtextparser::{WastAction, WastParser, WastParserState};
+///
+/// let mut parser = WastParser::new(b"(module (func (export \"f\") (param i32) (result i32) \
+///                                      local.get 0)) \
+///                                     (assert_return (invoke \"f\" (i32.const 1)) (i32.const 1))");
+/// let mut commands = 0;
+/// loop {
+///     match parser.parse() {
+///         WastParserState::Module(_) => {}
+///         WastParserState::AssertReturn { action: WastAction::Invoke { name, .. }, results, .. } => {
+///             assert_eq!((name.as_str(), results.len()), ("f", 1));
+///             commands += 1;
+///         }
+///         WastParserState::End => break,
+///         event => panic!("unexpected {:?}", event),
+///     }
+/// }
+/// assert_eq!(commands, 1);
+/// ```
+pub struct WastParser<'a> {
+    parser: WatParser<'a>,
+    started: bool,
+    // Set from a module's `StartModule` to its `EndModule`.
+    in_module: bool,
+    // The `End` or `Error` the script stopped at.
+    last: Option<WastParserState>,
+}
+
+impl<'a> WastParser<'a> {
+    pub fn new(source: &[u8]) -> WastParser<'_> {
+        WastParser {
+            parser: WatParser::new(source),
+            started: false,
+            in_module: false,
+            last: None,
+        }
+    }
+
+    /// Advances the parser by one event and returns it. Once `End` or
+    /// `Error` is reached, it is returned again by every call.
+    pub fn parse(&mut self) -> WastParserState {
+        if let Some(ref last) = self.last {
+            return last.clone();
+        }
+        if self.in_module {
+            return match self.parser.parse() {
+                WatParserState::Error(err) => self.stop(WastParserState::Error(err)),
+                event => {
+                    self.in_module = event != WatParserState::EndModule;
+                    WastParserState::Module(event)
+                }
+            };
+        }
+        match self.read_command() {
+            Ok(WastParserState::End) => self.stop(WastParserState::End),
+            Ok(event) => event,
+            Err(err) => self.stop(WastParserState::Error(err)),
+        }
+    }
+
+    /// The source between two positions, such as the text of a
+    /// `WastModule`.
+    pub fn source_text(&self, start: WatPosition, end: WatPosition) -> &'a [u8] {
+        self.parser.source_text(start, end)
+    }
+
+    fn stop(&mut self, event: WastParserState) -> WastParserState {
+        self.last = Some(event.clone());
+        event
+    }
+
+    fn keyword_is(&self, keyword: &[u8]) -> bool {
+        self.parser.is_keyword() && self.parser.current_token_content() == keyword
+    }
+
+    fn is_constant(&self) -> bool {
+        self.parser.is_keyword() &&
+        WatOpcode::lookup(self.parser.current_token_content()).is_some_and(|opcode| opcode.is_constant())
+    }
+
+    fn read_command(&mut self) -> Result<WastParserState> {
+        if !self.started {
+            self.started = true;
+            self.parser.start_script()?;
+        }
+        if let WatTokenType::End = *self.parser.current_token_type() {
+            return Ok(WastParserState::End);
+        }
+        self.parser.expect_open_paren()?;
+        let position = self.parser.field_position();
+        if self.keyword_is(b"module") {
+            self.parser.advance()?;
+            let id = self.parser.maybe_id()?;
+            if self.keyword_is(b"quote") {
+                self.parser.advance()?;
+                let source = self.parser.read_data_strings()?;
+                self.parser.expect_close_paren()?;
+                return Ok(WastParserState::QuotedModule { id, source, position });
+            }
+            if self.keyword_is(b"binary") {
+                self.parser.advance()?;
+                let bytes = self.parser.read_data_strings()?;
+                self.parser.expect_close_paren()?;
+                return Ok(WastParserState::BinaryModule { id, bytes, position });
+            }
+            self.in_module = true;
+            return Ok(WastParserState::Module(self.parser.start_script_module(id)));
+        }
+        if self.keyword_is(b"invoke") || self.keyword_is(b"get") {
+            let action = self.read_action_after_open_paren()?;
+            return Ok(WastParserState::Action { action, position });
+        }
+        if !self.parser.is_keyword() {
+            return Err(self.parser.expected_error("a script command"));
+        }
+        let command = Vec::from(self.parser.current_token_content());
+        let event = match &command[..] {
+            b"register" => {
+                self.parser.advance()?;
+                let name = self.parser.read_name()?;
+                let module = self.parser.maybe_id()?;
+                WastParserState::Register { name, module, position }
+            }
+            b"assert_return" => {
+                self.parser.advance()?;
+                let action = self.read_action()?;
+                let mut results = Vec::new();
+                while let WatTokenType::OpenParen = *self.parser.current_token_type() {
+                    results.push(self.read_value(true)?);
+                }
+                WastParserState::AssertReturn { action, results, position }
+            }
+            b"assert_trap" => {
+                self.parser.advance()?;
+                self.parser.expect_open_paren()?;
+                if self.keyword_is(b"module") {
+                    let module = self.read_module_after_open_paren()?;
+                    let message = self.parser.read_name()?;
+                    WastParserState::AssertUninstantiable { module, message, position }
+                } else {
+                    let action = self.read_action_after_open_paren()?;
+                    let message = self.parser.read_name()?;
+                    WastParserState::AssertTrap { action, message, position }
+                }
+            }
+            b"assert_exhaustion" => {
+                self.parser.advance()?;
+                let action = self.read_action()?;
+                let message = self.parser.read_name()?;
+                WastParserState::AssertExhaustion { action, message, position }
+            }
+            b"assert_invalid" | b"assert_malformed" | b"assert_unlinkable" => {
+                self.parser.advance()?;
+                self.parser.expect_open_paren()?;
+                if !self.keyword_is(b"module") {
+                    return Err(self.parser.expected_error("'module'"));
+                }
+                let module = self.read_module_after_open_paren()?;
+                let message = self.parser.read_name()?;
+                match &command[..] {
+                    b"assert_invalid" => WastParserState::AssertInvalid { module, message, position },
+                    b"assert_malformed" => {
+                        WastParserState::AssertMalformed { module, message, position }
+                    }
+                    _ => WastParserState::AssertUnlinkable { module, message, position },
+                }
+            }
+            _ => return Err(self.parser.expected_error("a script command")),
+        };
+        self.parser.expect_close_paren()?;
+        Ok(event)
+    }
+
+    fn read_action(&mut self) -> Result<WastAction> {
+        self.parser.expect_open_paren()?;
+        self.read_action_after_open_paren()
+    }
+
+    fn read_action_after_open_paren(&mut self) -> Result<WastAction> {
+        let invoke = self.keyword_is(b"invoke");
+        if !invoke && !self.keyword_is(b"get") {
+            return Err(self.parser.expected_error("'invoke' or 'get'"));
+        }
+        self.parser.advance()?;
+        let module = self.parser.maybe_id()?;
+        let name = self.parser.read_name()?;
+        let action = if invoke {
+            let mut args = Vec::new();
+            while let WatTokenType::OpenParen = *self.parser.current_token_type() {
+                args.push(self.read_value(false)?);
+            }
+            WastAction::Invoke { module, name, args }
+        } else {
+            WastAction::Get { module, name }
+        };
+        self.parser.expect_close_paren()?;
+        Ok(action)
+    }
+
+    // Reads a `(const)` argument, or with `result` one of the patterns
+    // only results can have.
+    fn read_value(&mut self, result: bool) -> Result<WastValue> {
+        self.parser.expect_open_paren()?;
+        let value = if self.keyword_is(b"ref.extern") || self.keyword_is(b"ref.host") {
+            self.parser.advance()?;
+            match *self.parser.current_token_type() {
+                WatTokenType::CloseParen if result => WastValue::RefExtern(None),
+                _ => WastValue::RefExtern(Some(self.parser.read_u32()?)),
+            }
+        } else {
+            if !self.is_constant() {
+                return Err(self.parser.expected_error("a constant"));
+            }
+            let instruction = self.parser.read_instruction_keyword()?;
+            let nan_type = match &instruction[..] {
+                b"f32.const" => Some(WatValType::F32),
+                b"f64.const" => Some(WatValType::F64),
+                _ => None,
+            };
+            match nan_type {
+                Some(ty) if result && self.keyword_is(b"nan:canonical") => {
+                    self.parser.advance()?;
+                    WastValue::CanonicalNan(ty)
+                }
+                Some(ty) if result && self.keyword_is(b"nan:arithmetic") => {
+                    self.parser.advance()?;
+                    WastValue::ArithmeticNan(ty)
+                }
+                _ if result && &instruction[..] == b"ref.func" &&
+                     *self.parser.current_token_type() == WatTokenType::CloseParen => {
+                    WastValue::RefFunc
+                }
+                _ => {
+                    let args = self.parser.read_instruction_args(&instruction)?;
+                    WastValue::Const(WatInstruction { instruction, args })
+                }
+            }
+        };
+        self.parser.expect_close_paren()?;
+        Ok(value)
+    }
+
+    // Reads a quoted or binary module, or skips over one written out, whose '(' has
+    // been read, to past its ')'.
+    fn read_module_after_open_paren(&mut self) -> Result<WastModule> {
+        let start = self.parser.field_position();
+        self.parser.advance()?;
+        self.parser.maybe_id()?;
+        if self.keyword_is(b"quote") {
+            self.parser.advance()?;
+            let source = self.parser.read_data_strings()?;
+            self.parser.expect_close_paren()?;
+            return Ok(WastModule::Quote(source));
+        }
+        if self.keyword_is(b"binary") {
+            self.parser.advance()?;
+            let bytes = self.parser.read_data_strings()?;
+            self.parser.expect_close_paren()?;
+            return Ok(WastModule::Binary(bytes));
+        }
+        let mut depth = 0;
+        loop {
+            match *self.parser.current_token_type() {
+                WatTokenType::OpenParen => depth += 1,
+                WatTokenType::CloseParen if depth == 0 => break,
+                WatTokenType::CloseParen => depth -= 1,
+                WatTokenType::End => break,
+                _ => {}
+            }
+            self.parser.advance()?;
+        }
+        self.parser.expect_close_paren()?;
+        Ok(WastModule::Text {
+               start,
+               end: self.parser.event_end(),
+           })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use super::*;
+
+    fn commands(source: &[u8]) -> Vec<WastParserState> {
+        let mut parser = WastParser::new(source);
+        let mut events = Vec::new();
+        loop {
+            match parser.parse() {
+                WastParserState::End => return events,
+                WastParserState::Error(err) => panic!("{}", err),
+                event => events.push(event),
+            }
+        }
+    }
+
+    #[test]
+    fn binary_module_bytes() {
+        let events = commands(b"(module $m binary \"\\de\\ad\" \"\" \"\\be\\ef\")\n\
+                                (assert_malformed (module binary \"\\de\\ad\\be\\ef\") \"magic\")");
+        match events[0] {
+            WastParserState::BinaryModule { ref id, ref bytes, .. } => {
+                assert_eq!(id.as_ref().map(|id| &id[..]), Some(&b"$m"[..]));
+                assert_eq!(*bytes, [0xde, 0xad, 0xbe, 0xef]);
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match events[1] {
+            WastParserState::AssertMalformed { module: WastModule::Binary(ref bytes), .. } => {
+                assert_eq!(*bytes, [0xde, 0xad, 0xbe, 0xef]);
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(events.len(), 2);
+    }
+}
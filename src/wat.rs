@@ -1,16 +1,20 @@
-use std::str;
-use std::char;
-use std::result;
-use lexer::{WatLexer, WatToken, WatTokenType, WatPosition};
-
-#[derive(Debug,Copy,Clone)]
-pub struct WatParserError {
-    pub message: &'static str,
-    pub line: usize,
-    pub column: usize,
-}
-
-pub type Result<T> = result::Result<T, WatParserError>;
+use core::str;
+use core::char;
+use core::fmt;
+use core::mem;
+use core::result;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use lexer::{unicode_escape, KnownKeyword, WatLexer, WatToken, WatTokenType, WatPosition};
+use intern::{Symbol, WatInterner};
+use visit::WatVisitor;
+use instructions::{instruction_proposal, Proposal};
+use opcode::WatOpcode;
+use resolve::IndexSpace;
+pub use error::{ErrorKind, Result, WatParserError};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
 
 pub type Keyword = Vec<u8>;
 pub type Data = Vec<u8>;
@@ -18,74 +22,409 @@ pub type ID = Vec<u8>;
 pub type OptionalID = Option<ID>;
 pub type Name = String;
 
-#[derive(Debug)]
+// IDs and keywords are made of ASCII idchars, so they serialize as
+// strings; other byte vectors (Data) as hex strings.
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_text<S: Serializer>(text: &[u8],
+                                           serializer: S)
+                                           -> result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&String::from_utf8_lossy(text))
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_optional_text<S: Serializer>(text: &Option<Vec<u8>>,
+                                                    serializer: S)
+                                                    -> result::Result<S::Ok, S::Error> {
+    match *text {
+        Some(ref text) => serializer.serialize_some(&*String::from_utf8_lossy(text)),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_hex<S: Serializer>(data: &[u8],
+                                          serializer: S)
+                                          -> result::Result<S::Ok, S::Error> {
+    let mut hex = String::with_capacity(data.len() * 2);
+    for byte in data {
+        hex.push(char::from_digit(u32::from(byte >> 4), 16).unwrap());
+        hex.push(char::from_digit(u32::from(byte & 0xF), 16).unwrap());
+    }
+    serializer.serialize_str(&hex)
+}
+
+#[cfg(feature = "serde")]
+fn serialize_optional_hex<S: Serializer>(data: &Option<Data>,
+                                         serializer: S)
+                                         -> result::Result<S::Ok, S::Error> {
+    match *data {
+        Some(ref data) => serialize_hex(data, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatLimits {
     pub min: u32,
     pub max: Option<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatMemoryType {
     pub limits: WatLimits,
     pub shared: bool,
 }
 
-#[derive(Debug)]
-pub enum WatTableType {
+/// A reference type, by its shorthand keyword or as `(ref ...)`. The
+/// nullable form of an abstract heap type is always the shorthand.
+///
+/// ```
+/// use wasmtextparser::{WatParser, WatParserState};
+/// use wasmtextparser::wat::{WatHeapType, WatIndex, WatRefType, WatValType};
+///
+/// let source = b"(module (type $t (func)) (func (param (ref null $t)) (local anyref (ref null any))))";
+/// let mut parser = WatParser::new(source);
+/// let (typeuse, locals) = loop {
+///     if let WatParserState::StartFunc { typeuse, locals, .. } = parser.parse() {
+///         break (typeuse, locals);
+///     }
+/// };
+/// let to_t = WatRefType::Ref { nullable: true, heaptype: WatHeapType::Index(WatIndex::Id(b"$t".to_vec())) };
+/// assert_eq!(typeuse.params[0].valtype, WatValType::Ref(to_t));
+/// assert_eq!(locals[0].valtype, WatValType::Ref(WatRefType::AnyRef));
+/// assert_eq!(locals[1].valtype, locals[0].valtype);
+/// ```
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatRefType {
+    FuncRef,
+    ExternRef,
+    AnyRef,
+    EqRef,
+    I31Ref,
+    StructRef,
+    ArrayRef,
+    NullRef,
+    /// A `(ref null? heaptype)` that has no shorthand: a non-null
+    /// reference, or a nullable one to a type of the module.
+    Ref { nullable: bool, heaptype: WatHeapType },
+}
+
+/// What a reference points to: an abstract heap type, or a type of the
+/// module by index.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatHeapType {
+    Func,
+    Extern,
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    None,
+    Index(WatIndex),
+}
+
+impl WatRefType {
+    /// The nullable reference to `heaptype`, by its shorthand if it has
+    /// one: `(ref null func)` is `funcref`.
+    pub fn nullable(heaptype: WatHeapType) -> WatRefType {
+        match heaptype {
+            WatHeapType::Func => WatRefType::FuncRef,
+            WatHeapType::Extern => WatRefType::ExternRef,
+            WatHeapType::Any => WatRefType::AnyRef,
+            WatHeapType::Eq => WatRefType::EqRef,
+            WatHeapType::I31 => WatRefType::I31Ref,
+            WatHeapType::Struct => WatRefType::StructRef,
+            WatHeapType::Array => WatRefType::ArrayRef,
+            WatHeapType::None => WatRefType::NullRef,
+            heaptype => {
+                WatRefType::Ref {
+                    nullable: true,
+                    heaptype,
+                }
+            }
+        }
+    }
+
+    pub fn heaptype(&self) -> WatHeapType {
+        match *self {
+            WatRefType::FuncRef => WatHeapType::Func,
+            WatRefType::ExternRef => WatHeapType::Extern,
+            WatRefType::AnyRef => WatHeapType::Any,
+            WatRefType::EqRef => WatHeapType::Eq,
+            WatRefType::I31Ref => WatHeapType::I31,
+            WatRefType::StructRef => WatHeapType::Struct,
+            WatRefType::ArrayRef => WatHeapType::Array,
+            WatRefType::NullRef => WatHeapType::None,
+            WatRefType::Ref { ref heaptype, .. } => heaptype.clone(),
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatTableType {
+    pub limits: WatLimits,
+    pub reftype: WatRefType,
+}
 
+/// A reference to a module item, by number or by `$id`.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatIndex {
+    Num(u32),
+    Id(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] ID),
+    /// An `$id` reference, with `WatParserOptions::intern_ids` on.
+    Symbol(Symbol),
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
 pub enum WatValType {
     I32,
     I64,
     F32,
     F64,
+    V128,
+    Ref(WatRefType),
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatParam {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
     pub id: OptionalID,
     pub valtype: WatValType,
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatResult {
     pub valtype: WatValType,
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatLocal {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
     pub id: OptionalID,
     pub valtype: WatValType,
 }
 
-#[derive(Debug,Clone,Copy)]
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
 pub enum WatSign {
     Positive,
     Negative,
 }
 
-#[derive(Debug,Clone)]
+/// A float literal. A `Number` is exact: the little-endian mantissa times
+/// ten to the power.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
 pub enum WatFloat {
-    Number(WatSign, Data, i32),
-    NaN(WatSign, Option<Data>),
+    Number(WatSign,
+           #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))] Data,
+           i32),
+    NaN(WatSign,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_hex"))]
+        Option<Data>),
     Inf(WatSign),
 }
 
-#[derive(Debug)]
+/// An instruction immediate.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
 pub enum WatInstructionArg {
-    ID(ID),
-    Unsigned(Data),
-    Signed(WatSign, Data),
+    ID(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] ID),
+    /// An `ID` argument, with `WatParserOptions::intern_ids` on.
+    Symbol(Symbol),
+    /// `value` is the decoded little-endian number, `raw` the literal as
+    /// written in the source. Underscores between digits are dropped when
+    /// decoding, here and wherever a `u32` is expected.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    /// use wasmtextparser::wat::WatInstructionArg;
+    ///
+    /// let mut parser = WatParser::new(b"(memory 1_0) (func i32.const 1_000_000 i32.const 0xFF_FF)");
+    /// let mut values = Vec::new();
+    /// loop {
+    ///     match parser.parse() {
+    ///         WatParserState::Memory { memtype, .. } => assert_eq!(memtype.limits.min, 10),
+    ///         WatParserState::CodeOperator { args, .. } => match args[0] {
+    ///             WatInstructionArg::Unsigned { ref value, .. } => values.push(value.clone()),
+    ///             _ => unreachable!(),
+    ///         },
+    ///         WatParserState::End => break,
+    ///         WatParserState::Error(err) => panic!("{}", err),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// assert_eq!(values, [vec![0x40, 0x42, 0x0F], vec![0xFF, 0xFF]]);
+    /// ```
+    Unsigned {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+        value: Data,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))]
+        raw: Data,
+    },
+    Signed {
+        sign: WatSign,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+        value: Data,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))]
+        raw: Data,
+    },
     Float(WatFloat),
-    Flags(Keyword, u32),
+    /// A memory argument such as `offset=8`, as written, and its value.
+    Flags(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] Keyword,
+          u32),
+    /// The shape of a `v128.const`, such as `i32x4`. Its lanes follow as
+    /// `Unsigned`, `Signed` or `Float` arguments.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    /// use wasmtextparser::wat::WatInstructionArg;
+    ///
+    /// let mut parser = WatParser::func_body(b"v128.const f32x4 1 -2 0.5 inf");
+    /// match parser.parse() {
+    ///     WatParserState::CodeOperator { args, .. } => {
+    ///         assert_eq!(args.len(), 5);
+    ///         assert_eq!(args[0], WatInstructionArg::Shape(b"f32x4".to_vec()));
+    ///     }
+    ///     event => panic!("unexpected {:?}", event),
+    /// }
+    ///
+    /// for &(source, message) in &[(&b"v128.const i32x4 1 2 3"[..],
+    ///                              "expected 4 lanes for i32x4 while parsing instruction, found end of input"),
+    ///                             (b"v128.const i8x16 256", "integer literal out of range for i8: '256'")] {
+    ///     match WatParser::func_body(source).parse() {
+    ///         WatParserState::Error(err) => assert_eq!(err.message.as_ref(), message),
+    ///         event => panic!("unexpected {:?}", event),
+    ///     }
+    /// }
+    /// ```
+    Shape(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] Keyword),
+    /// The memory of a memory instruction, by number, `$id` or in a
+    /// `(memory ...)`. It is left out for memory 0 when not written.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    /// use wasmtextparser::wat::{WatIndex, WatInstructionArg};
+    ///
+    /// let source = b"(module (memory $m 1) (memory 1) (func \
+    ///                memory.size memory.size 1 (memory.grow $m (i32.const 1)) \
+    ///                (memory.grow (memory 1) (i32.const 1)) drop drop drop drop))";
+    /// let args: Vec<_> = WatParser::new(source)
+    ///     .events()
+    ///     .filter_map(|event| match event.unwrap() {
+    ///         WatParserState::CodeOperator { instruction, args, .. }
+    ///             if instruction.starts_with(b"memory.") => Some(args),
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    /// assert_eq!(args, [vec![],
+    ///                   vec![WatInstructionArg::Memory(WatIndex::Num(1))],
+    ///                   vec![WatInstructionArg::Memory(WatIndex::Id(b"$m".to_vec()))],
+    ///                   vec![WatInstructionArg::Memory(WatIndex::Num(1))]]);
+    /// ```
+    Memory(WatIndex),
+    Table(WatIndex),
+    Function(WatIndex),
+    /// A data segment, as used by `memory.init` and `data.drop`.
+    Data(WatIndex),
+    /// An element segment, as used by `table.init` and `elem.drop`.
+    Elem(WatIndex),
+    /// The type of the null reference `ref.null` makes, written as its
+    /// heap type: `func` for `funcref`.
+    HeapType(WatRefType),
+    Result(WatValType),
+    Type(WatTypeuse),
 }
 
-#[derive(Debug)]
+/// An instruction inside a constant expression.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatInstruction {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))]
+    pub instruction: Keyword,
+    pub args: Vec<WatInstructionArg>,
+}
+
+pub type ConstExpr = Vec<WatInstruction>;
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatElemKind {
+    Active { table: Option<WatIndex>, offset: ConstExpr },
+    Passive,
+    Declarative,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatElemList {
+    Functions(Vec<WatIndex>),
+    Expressions(WatRefType, Vec<ConstExpr>),
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatDataKind {
+    Active { memory: WatIndex, offset: ConstExpr },
+    Passive,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct WatTypeuse {
+    /// The `$t` of a `(type $t)`; `index` holds the `n` of a `(type n)`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
     pub id: OptionalID,
+    pub index: Option<u32>,
     pub params: Vec<WatParam>,
+    /// All the results, in order, however they are split among
+    /// `(result ...)`s. There can be any number of them.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    /// use wasmtextparser::wat::{WatInstructionArg, WatValType};
+    ///
+    /// let source = b"(module (func (result i32 i64) unreachable) \
+    ///                (func (param i32) (result i32) (result) (result i64) unreachable) \
+    ///                (func (block (result f32 f64) unreachable) unreachable))";
+    /// let results: Vec<Vec<WatValType>> = WatParser::new(source)
+    ///     .events()
+    ///     .filter_map(|event| match event.unwrap() {
+    ///         WatParserState::StartFunc { typeuse, .. } => Some(typeuse.results),
+    ///         WatParserState::CodeOperator { args, .. } => args.into_iter().find_map(|arg| match arg {
+    ///             WatInstructionArg::Type(typeuse) => Some(typeuse.results),
+    ///             _ => None,
+    ///         }),
+    ///         _ => None,
+    ///     })
+    ///     .map(|results| results.into_iter().map(|result| result.valtype).collect())
+    ///     .collect();
+    /// assert_eq!(results, [vec![WatValType::I32, WatValType::I64],
+    ///                      vec![WatValType::I32, WatValType::I64],
+    ///                      vec![],
+    ///                      vec![WatValType::F32, WatValType::F64]]);
+    /// ```
     pub results: Vec<WatResult>,
 }
 
@@ -93,103 +432,626 @@ impl WatTypeuse {
     fn empty() -> WatTypeuse {
         WatTypeuse {
             id: None,
+            index: None,
             params: vec![],
             results: vec![],
         }
     }
+
+    fn is_empty(&self) -> bool {
+        self.id.is_none() && self.index.is_none() && self.params.is_empty() &&
+        self.results.is_empty()
+    }
+
+    // The `$t` or `n` of its `(type ...)`, if any.
+    fn type_text(&self) -> Option<String> {
+        match (self.id.as_ref(), self.index) {
+            (Some(id), _) => Some(String::from_utf8_lossy(id).into_owned()),
+            (None, Some(index)) => Some(index.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether both have the same params and results, ids aside.
+    pub fn same_signature(&self, other: &WatTypeuse) -> bool {
+        self.params.len() == other.params.len() &&
+        self.params.iter().zip(&other.params).all(|(a, b)| a.valtype == b.valtype) &&
+        self.results == other.results
+    }
+
+    // The error for an inline signature that differs from that of its
+    // `(type $t)`, declared at `line`.
+    pub(crate) fn mismatch_error(&self,
+                                 declared: &WatTypeuse,
+                                 line: usize,
+                                 start: WatPosition,
+                                 end: WatPosition)
+                                 -> WatParserError {
+        let message = format!("(type {}) is {}, declared at line {}, but the inline signature \
+                               is {}",
+                              self.type_text().unwrap_or_default(),
+                              declared.functype_text(),
+                              line,
+                              self.functype_text());
+        WatParserError {
+            kind: ErrorKind::Validation,
+            message: message.into_boxed_str(),
+            start,
+            end,
+        }
+    }
+
+    // The params and results as a `(func ...)`, without ids.
+    fn functype_text(&self) -> String {
+        let params = self.params
+            .iter()
+            .map(|param| {
+                     WatParam {
+                         id: None,
+                         valtype: param.valtype.clone(),
+                     }
+                 })
+            .collect();
+        let signature = WatTypeuse {
+            id: None,
+            index: None,
+            params,
+            results: self.results.clone(),
+        };
+        if signature.is_empty() {
+            return "(func)".to_string();
+        }
+        format!("(func {})", signature)
+    }
 }
 
-#[derive(Debug)]
-pub enum WatGlobalType {
+impl Default for WatTypeuse {
+    fn default() -> WatTypeuse {
+        WatTypeuse::empty()
+    }
+}
 
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatGlobalType {
+    pub valtype: WatValType,
+    pub mutable: bool,
 }
 
-#[derive(Debug)]
+/// The descriptor of an imported item.
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
 pub enum WatImport {
-    Func { id: OptionalID, typeuse: WatTypeuse },
+    Func {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        typeuse: WatTypeuse,
+    },
     Table {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
         id: OptionalID,
         tabletype: WatTableType,
     },
     Memory {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
         id: OptionalID,
         memtype: WatMemoryType,
     },
     Global {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
         id: OptionalID,
         globaltype: WatGlobalType,
     },
 }
 
-fn parse_hexnum_u32(bytes: &[u8]) -> Option<u32> {
-    // FIXME '_'?
-    let num = str::from_utf8(bytes);
-    if num.is_err() {
-        return None;
+/// The kind of item an `(export ...)` field names.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[non_exhaustive]
+pub enum WatExportKind {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+// Little-endian bytes, as produced by convert_u64_to_data.
+pub(crate) fn data_to_u64(data: &[u8]) -> u64 {
+    let mut num = 0;
+    for (i, byte) in data.iter().enumerate().take(8) {
+        num |= u64::from(*byte) << (i * 8);
     }
-    u32::from_str_radix(num.unwrap(), 16).ok()
+    num
 }
 
-fn parse_u32(bytes: &[u8]) -> Option<u32> {
-    if bytes.len() > 2 && bytes[0] == b'0' && bytes[0] == b'x' {
-        return parse_hexnum_u32(&bytes[2..]);
+// Any number of little-endian bytes, in decimal.
+fn data_to_decimal(data: &[u8]) -> String {
+    if data.len() <= 8 {
+        return data_to_u64(data).to_string();
     }
-    let num = str::from_utf8(bytes);
-    if num.is_err() {
-        return None;
+    let mut data = data.to_vec();
+    let mut digits = Vec::new();
+    while data.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0;
+        for byte in data.iter_mut().rev() {
+            let value = remainder << 8 | u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
     }
-    num.unwrap().parse::<u32>().ok()
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
 }
 
-fn convert_u32_to_data(maybe_num: Option<u32>) -> Option<Data> {
-    if maybe_num.is_none() {
-        return None;
+impl fmt::Display for WatValType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+                        WatValType::I32 => "i32",
+                        WatValType::I64 => "i64",
+                        WatValType::F32 => "f32",
+                        WatValType::F64 => "f64",
+                        WatValType::V128 => "v128",
+                        WatValType::Ref(ref reftype) => return reftype.fmt(f),
+                    })
+    }
+}
+
+impl fmt::Display for WatRefType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+                        WatRefType::FuncRef => "funcref",
+                        WatRefType::ExternRef => "externref",
+                        WatRefType::AnyRef => "anyref",
+                        WatRefType::EqRef => "eqref",
+                        WatRefType::I31Ref => "i31ref",
+                        WatRefType::StructRef => "structref",
+                        WatRefType::ArrayRef => "arrayref",
+                        WatRefType::NullRef => "nullref",
+                        WatRefType::Ref { nullable: true, ref heaptype } => {
+                            return write!(f, "(ref null {})", heaptype);
+                        }
+                        WatRefType::Ref { nullable: false, ref heaptype } => {
+                            return write!(f, "(ref {})", heaptype);
+                        }
+                    })
+    }
+}
+
+impl fmt::Display for WatHeapType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+                        WatHeapType::Func => "func",
+                        WatHeapType::Extern => "extern",
+                        WatHeapType::Any => "any",
+                        WatHeapType::Eq => "eq",
+                        WatHeapType::I31 => "i31",
+                        WatHeapType::Struct => "struct",
+                        WatHeapType::Array => "array",
+                        WatHeapType::None => "none",
+                        WatHeapType::Index(ref index) => return index.fmt(f),
+                    })
+    }
+}
+
+impl fmt::Display for WatSign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+                        WatSign::Positive => "+",
+                        WatSign::Negative => "-",
+                    })
+    }
+}
+
+impl fmt::Display for WatIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WatIndex::Num(num) => write!(f, "{}", num),
+            WatIndex::Id(ref id) => f.write_str(&String::from_utf8_lossy(id)),
+            WatIndex::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+impl fmt::Display for WatLimits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.min)?;
+        if let Some(max) = self.max {
+            write!(f, " {}", max)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WatMemoryType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.shared {
+            return write!(f, "(shared {})", self.limits);
+        }
+        write!(f, "{}", self.limits)
+    }
+}
+
+impl fmt::Display for WatTableType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.limits, self.reftype)
+    }
+}
+
+impl fmt::Display for WatGlobalType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.mutable {
+            return write!(f, "(mut {})", self.valtype);
+        }
+        write!(f, "{}", self.valtype)
+    }
+}
+
+// Writes ` $id` when there is one.
+fn fmt_optional_id(f: &mut fmt::Formatter, id: &OptionalID) -> fmt::Result {
+    match *id {
+        Some(ref id) => write!(f, " {}", String::from_utf8_lossy(id)),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for WatTypeuse {
+    // Consecutive unnamed params share one `(param ...)` group; named
+    // params each get their own. Renders nothing for an empty typeuse.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut separator = "";
+        if let Some(text) = self.type_text() {
+            write!(f, "(type {})", text)?;
+            separator = " ";
+        }
+        let mut in_group = false;
+        for param in &self.params {
+            match param.id {
+                Some(ref id) => {
+                    if in_group {
+                        f.write_str(")")?;
+                        in_group = false;
+                    }
+                    write!(f,
+                           "{}(param {} {})",
+                           separator,
+                           String::from_utf8_lossy(id),
+                           param.valtype)?;
+                }
+                None if in_group => write!(f, " {}", param.valtype)?,
+                None => {
+                    write!(f, "{}(param {}", separator, param.valtype)?;
+                    in_group = true;
+                }
+            }
+            separator = " ";
+        }
+        if in_group {
+            f.write_str(")")?;
+        }
+        if !self.results.is_empty() {
+            write!(f, "{}(result", separator)?;
+            for result in &self.results {
+                write!(f, " {}", result.valtype)?;
+            }
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WatImport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WatImport::Func { ref id, ref typeuse } => {
+                f.write_str("(func")?;
+                fmt_optional_id(f, id)?;
+                let typeuse = typeuse.to_string();
+                if !typeuse.is_empty() {
+                    write!(f, " {}", typeuse)?;
+                }
+            }
+            WatImport::Table { ref id, ref tabletype } => {
+                f.write_str("(table")?;
+                fmt_optional_id(f, id)?;
+                write!(f, " {}", tabletype)?;
+            }
+            WatImport::Memory { ref id, ref memtype } => {
+                f.write_str("(memory")?;
+                fmt_optional_id(f, id)?;
+                write!(f, " {}", memtype)?;
+            }
+            WatImport::Global { ref id, ref globaltype } => {
+                f.write_str("(global")?;
+                fmt_optional_id(f, id)?;
+                write!(f, " {}", globaltype)?;
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for WatExportKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+                        WatExportKind::Func => "func",
+                        WatExportKind::Table => "table",
+                        WatExportKind::Memory => "memory",
+                        WatExportKind::Global => "global",
+                    })
+    }
+}
+
+impl fmt::Display for WatFloat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WatFloat::Number(sign, ref data, power) => {
+                write!(f, "{}{}e{}", sign, data_to_decimal(data), power)
+            }
+            WatFloat::NaN(sign, None) => write!(f, "{}nan", sign),
+            WatFloat::NaN(sign, Some(ref payload)) => {
+                write!(f, "{}nan:0x{:x}", sign, data_to_u64(payload))
+            }
+            WatFloat::Inf(sign) => write!(f, "{}inf", sign),
+        }
+    }
+}
+
+enum NumberError {
+    Invalid,
+    Overflow,
+}
+
+// Skips the '_' separators, which the lexer only lets through between
+// digits, so `1_000` and `0xFF_FF` decode like `1000` and `0xFFFF`.
+fn parse_u64(bytes: &[u8]) -> result::Result<u64, NumberError> {
+    let (digits, radix) = if bytes.len() > 2 && bytes[0] == b'0' && bytes[1] == b'x' {
+        (&bytes[2..], 16)
+    } else {
+        (bytes, 10)
+    };
+    if digits.is_empty() {
+        return Err(NumberError::Invalid);
+    }
+    let mut num: u64 = 0;
+    for ch in digits.iter() {
+        if *ch == b'_' {
+            continue;
+        }
+        let digit = match (*ch as char).to_digit(radix) {
+            Some(digit) => u64::from(digit),
+            None => return Err(NumberError::Invalid),
+        };
+        num = match num.checked_mul(u64::from(radix)).and_then(|n| n.checked_add(digit)) {
+            Some(num) => num,
+            None => return Err(NumberError::Overflow),
+        };
+    }
+    Ok(num)
+}
+
+fn parse_u32(bytes: &[u8]) -> Option<u32> {
+    match parse_u64(bytes) {
+        Ok(num) if num <= u64::from(u32::MAX) => Some(num as u32),
+        _ => None,
     }
+}
+
+fn convert_u64_to_data(mut num: u64) -> Data {
     let mut result = Vec::new();
-    let mut num = maybe_num.unwrap();
     result.push((num & 0xFF) as u8);
     while num >= 0x100 {
         num >>= 8;
         result.push((num & 0xFF) as u8);
     }
-    Some(result)
+    result
+}
+
+// `i32.const 0`, the offset of the segments inline in a memory or table.
+fn zero_offset() -> ConstExpr {
+    vec![WatInstruction {
+             instruction: b"i32.const".to_vec(),
+             args: vec![WatInstructionArg::Unsigned {
+                            value: convert_u64_to_data(0),
+                            raw: b"0".to_vec(),
+                        }],
+         }]
+}
+
+fn int_const_width(instruction: &[u8]) -> Option<u32> {
+    match instruction {
+        b"i32.const" => Some(32),
+        b"i64.const" => Some(64),
+        _ => None,
+    }
+}
+
+// Instructions that accept a memory index ahead of their other immediates.
+// How many leading memory indices an instruction accepts; all of them
+// are optional and default to memory 0.
+fn memory_index_count(instruction: &[u8]) -> usize {
+    let name = match str::from_utf8(instruction) {
+        Ok(name) => name,
+        Err(_) => return 0,
+    };
+    if name == "memory.copy" {
+        return 2;
+    }
+    if name.contains(".load") || name.contains(".store") || name.contains(".atomic.") ||
+       name.ends_with(".wait") || name.ends_with(".notify") || name == "wake" ||
+       name == "memory.size" || name == "memory.grow" || name == "memory.fill" {
+        return 1;
+    }
+    0
+}
+
+// How many leading table indices an instruction accepts; all of them
+// are optional and default to table 0.
+fn table_index_count(instruction: &[u8]) -> usize {
+    match instruction {
+        b"table.get" | b"table.set" | b"table.size" | b"table.grow" | b"table.fill" |
+        b"call_indirect" | b"return_call_indirect" => 1,
+        b"table.copy" => 2,
+        _ => 0,
+    }
+}
+
+// `extract_lane`, `replace_lane`, `load8_lane` and the like take a lane
+// index, and `i8x16.shuffle` sixteen of them.
+fn takes_lanes(instruction: &[u8]) -> bool {
+    instruction.ends_with(b"_lane") || instruction.ends_with(b"_lane_s") ||
+    instruction.ends_with(b"_lane_u") || instruction == b"i8x16.shuffle"
+}
+
+fn is_memarg_keyword(content: &[u8]) -> bool {
+    content.len() > 7 && &content[..7] == b"offset=" ||
+    content.len() > 6 && &content[..6] == b"align=" ||
+    content.len() > 6 && &content[..6] == b"flags="
+}
+
+fn takes_typeuse(instruction: &[u8]) -> bool {
+    instruction == b"call_indirect" || instruction == b"return_call_indirect"
+}
+
+// Instructions that open a block, which take an optional label and a
+// block type.
+fn takes_blocktype(instruction: &[u8]) -> bool {
+    instruction == b"block" || instruction == b"loop" || instruction == b"if"
+}
+
+// The label of a `block`, `loop` or `if`, or after an `else` or `end`.
+fn label_arg(args: &[WatInstructionArg]) -> Option<&WatInstructionArg> {
+    match args.first() {
+        Some(arg @ &WatInstructionArg::ID(_)) | Some(arg @ &WatInstructionArg::Symbol(_)) => Some(arg),
+        _ => None,
+    }
+}
+
+fn is_float_const(instruction: &[u8]) -> bool {
+    instruction == b"f32.const" || instruction == b"f64.const"
+}
+
+// Multiplies the little-endian number in `data` by `mul` and adds `add`.
+fn data_mul_add(data: &mut Data, mul: u32, add: u32) {
+    let mut carry = u64::from(add);
+    for byte in data.iter_mut() {
+        carry += u64::from(*byte) * u64::from(mul);
+        *byte = carry as u8;
+        carry >>= 8;
+    }
+    while carry != 0 {
+        data.push(carry as u8);
+        carry >>= 8;
+    }
 }
 
-fn parse_hexnum(bytes: &[u8]) -> Option<Data> {
-    assert!(bytes.len() > 0);
-    if bytes.len() <= 8 {
-        return convert_u32_to_data(parse_hexnum_u32(bytes));
+// Appends digits in `radix` to the number in `data`, skipping the '_'
+// separators the lexer has already checked. Returns how many there were.
+fn parse_digits(bytes: &[u8], radix: u32, data: &mut Data) -> Option<usize> {
+    let mut count = 0;
+    for ch in bytes {
+        if *ch == b'_' {
+            continue;
+        }
+        data_mul_add(data, radix, (*ch as char).to_digit(radix)?);
+        count += 1;
     }
-    unimplemented!(); // FIXME
+    Some(count)
 }
 
-fn parse_num(bytes: &[u8]) -> Option<Data> {
-    if bytes.len() > 2 && bytes[0] == b'0' && bytes[0] == b'x' {
-        return parse_hexnum(&bytes[2..]);
+// Exponents are clamped far beyond where every float overflows or
+// underflows, so huge ones cannot blow up the mantissa.
+const MAX_FLOAT_EXPONENT: i64 = 100_000;
+
+fn parse_exponent(bytes: &[u8]) -> Option<i64> {
+    let (negative, digits) = match bytes.first() {
+        Some(&b'-') => (true, &bytes[1..]),
+        Some(&b'+') => (false, &bytes[1..]),
+        _ => (false, bytes),
+    };
+    let mut exponent: i64 = 0;
+    for ch in digits {
+        if *ch == b'_' {
+            continue;
+        }
+        let digit = i64::from((*ch as char).to_digit(10)?);
+        exponent = (exponent * 10 + digit).min(MAX_FLOAT_EXPONENT);
     }
-    assert!(bytes.len() > 0);
-    if bytes.len() <= 9 {
-        return convert_u32_to_data(parse_u32(bytes));
+    Some(if negative { -exponent } else { exponent })
+}
+
+// Splits `mantissa[.fraction][exponent marker + exponent]`.
+fn split_float<'b>(bytes: &'b [u8], markers: &[u8]) -> (&'b [u8], &'b [u8], &'b [u8]) {
+    let end = bytes.iter().position(|ch| markers.contains(ch)).unwrap_or(bytes.len());
+    let (number, exponent) = bytes.split_at(end);
+    let exponent = if exponent.is_empty() { exponent } else { &exponent[1..] };
+    match number.iter().position(|&ch| ch == b'.') {
+        Some(dot) => (&number[..dot], &number[dot + 1..], exponent),
+        None => (number, &[], exponent),
     }
-    unimplemented!(); // FIXME
 }
 
-fn parse_float(bytes: &[u8]) -> Option<(WatSign, Data, i32)> {
-    Some((WatSign::Positive, vec![], 0)) // FIXME
+// Floats are kept exact, as a mantissa times a power of ten. Hex floats
+// are converted to that form: m * 2^-k is m * 5^k * 10^-k.
+fn parse_float(bytes: &[u8]) -> Option<WatFloat> {
+    let (sign, bytes) = match bytes.first() {
+        Some(&b'-') => (WatSign::Negative, &bytes[1..]),
+        Some(&b'+') => (WatSign::Positive, &bytes[1..]),
+        _ => (WatSign::Positive, bytes),
+    };
+    match bytes {
+        b"inf" => return Some(WatFloat::Inf(sign)),
+        b"nan" => return Some(WatFloat::NaN(sign, None)),
+        _ => {}
+    }
+    let mut data = Vec::new();
+    if bytes.starts_with(b"nan:0x") {
+        parse_digits(&bytes[6..], 16, &mut data)?;
+        return Some(WatFloat::NaN(sign, Some(data)));
+    }
+    if bytes.starts_with(b"0x") {
+        let (whole, fraction, exponent) = split_float(&bytes[2..], b"pP");
+        parse_digits(whole, 16, &mut data)?;
+        let digits = parse_digits(fraction, 16, &mut data)? as i64;
+        // Past these bounds every float has overflowed, or underflowed
+        // to zero.
+        let bits = data.len() as i64 * 8;
+        let exponent = (parse_exponent(exponent)? - digits * 4).clamp(-1100 - bits, 1100);
+        let power = if exponent < 0 { exponent } else { 0 };
+        for _ in 0..exponent.abs() {
+            data_mul_add(&mut data, if exponent < 0 { 5 } else { 2 }, 0);
+        }
+        return Some(WatFloat::Number(sign, data, power as i32));
+    }
+    let (whole, fraction, exponent) = split_float(bytes, b"eE");
+    parse_digits(whole, 10, &mut data)?;
+    let digits = parse_digits(fraction, 10, &mut data)? as i64;
+    let power = (parse_exponent(exponent)? - digits).max(i64::from(i32::MIN));
+    Some(WatFloat::Number(sign, data, power as i32))
 }
 
-fn parse_string(bytes: &[u8]) -> String {
-    assert!(bytes.len() >= 2 && bytes[0] == b'\"' && bytes[bytes.len() - 1] == b'\"');
+fn parse_data_string(bytes: &[u8]) -> Option<Data> {
+    if bytes.len() < 2 || bytes[0] != b'\"' || bytes[bytes.len() - 1] != b'\"' {
+        return None;
+    }
     let mut i = 1;
     let last = bytes.len() - 1;
     let mut result = Vec::new();
     while i < last {
         let ch = bytes[i];
         i += 1;
+        if ch < 0x20 || ch == 0x7F {
+            // As in the lexer, these must be escaped.
+            return None;
+        }
         if ch != b'\\' {
             result.push(ch);
             continue;
         }
+        if i >= last {
+            return None;
+        }
         let escape = bytes[i];
         i += 1;
         match escape {
@@ -200,185 +1062,1223 @@ fn parse_string(bytes: &[u8]) -> String {
             b'\'' => result.push(b'\''),
             b'\\' => result.push(b'\\'),
             b'u' => {
-                if bytes[i] != b'{' {
-                    panic!();
+                if i >= last || bytes[i] != b'{' {
+                    return None;
                 }
                 i += 1;
                 let j = i;
-                while bytes[i] != b'}' {
+                while i < last && bytes[i] != b'}' {
                     i += 1;
                 }
-                let hexnum = parse_hexnum_u32(&bytes[j..i]).unwrap(); // FIXME
-                let code = char::from_u32(hexnum).unwrap(); // FIXME
-                let mut buffer = [0; 5];
+                if i >= last {
+                    return None;
+                }
+                let code = unicode_escape(&bytes[j..i])?;
+                let mut buffer = [0; 4];
                 let code_bytes = code.encode_utf8(&mut buffer).as_bytes();
-                result.extend_from_slice(&code_bytes);
-                assert!(i < last);
+                result.extend_from_slice(code_bytes);
+                i += 1;
+            }
+            _ => {
+                if i >= last {
+                    return None;
+                }
+                let high = (escape as char).to_digit(16)?;
+                let low = (bytes[i] as char).to_digit(16)?;
+                result.push((high * 16 + low) as u8);
                 i += 1;
             }
-            _ => panic!(),
         }
     }
-    String::from_utf8(result).unwrap()
+    Some(result)
+}
+
+fn duplicate_id_error(id: &[u8],
+                      previous: WatPosition,
+                      start: WatPosition,
+                      end: WatPosition)
+                      -> WatParserError {
+    let message = format!("duplicate identifier {}, previously defined at line {}",
+                          String::from_utf8_lossy(id),
+                          previous.line);
+    WatParserError {
+        kind: ErrorKind::Validation,
+        message: message.into_boxed_str(),
+        start,
+        end,
+    }
 }
 
-#[derive(Debug)]
+/// Events produced by `WatParser::parse`.
+///
+/// New events are added as the parser grows, so outside this crate a
+/// `match` needs a wildcard arm:
+///
+/// ```compile_fail
+/// use wasmtextparser::WatParserState;
+///
+/// fn is_done(event: &WatParserState) -> bool {
+///     match *event {
+///         WatParserState::Initial |
+///         WatParserState::StartModule { .. } |
+///         WatParserState::EndModule |
+///         WatParserState::Type { .. } |
+///         WatParserState::Import { .. } |
+///         WatParserState::StartFunc { .. } |
+///         WatParserState::EndFunc { .. } |
+///         WatParserState::StartGlobal { .. } |
+///         WatParserState::EndGlobal { .. } |
+///         WatParserState::Table { .. } |
+///         WatParserState::Memory { .. } |
+///         WatParserState::Export { .. } |
+///         WatParserState::Start { .. } |
+///         WatParserState::Elem { .. } |
+///         WatParserState::Data { .. } |
+///         WatParserState::CustomSection { .. } |
+///         WatParserState::CodeOperator { .. } |
+///         WatParserState::CodeOperatorEnd { .. } => false,
+///         WatParserState::End | WatParserState::Error(_) => true,
+///     }
+/// }
+/// ```
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event"))]
+#[non_exhaustive]
 pub enum WatParserState {
+    #[default]
     Initial,
     End,
     Error(WatParserError),
-    StartModule { id: OptionalID },
+    StartModule {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        position: WatPosition,
+    },
     EndModule,
+    /// A `(type $t (func ...))` field. `functype` has no `id` of its own.
+    Type {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        functype: WatTypeuse,
+        position: WatPosition,
+    },
+    /// Names have their escapes decoded, so a newline can be written as
+    /// `\n`, `\0a` or `\u{a}`. Once decoded, they must be valid UTF-8:
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    ///
+    /// let source = br#"(module (import "a\nb\0ac\u{a}" "\u{e9}\"\\caf\c3\a9" (func)))"#;
+    /// let event = WatParser::new(source).events().nth(1).unwrap().unwrap();
+    /// match event {
+    ///     WatParserState::Import { modname, fieldname, .. } => {
+    ///         assert_eq!(modname, "a\nb\nc\n");
+    ///         assert_eq!(fieldname, "\u{e9}\"\\caf\u{e9}");
+    ///     }
+    ///     event => panic!("unexpected {:?}", event),
+    /// }
+    ///
+    /// fn error(name: &str) -> String {
+    ///     let source = format!("(module (import \"{}\" \"f\" (func)))", name);
+    ///     let err = WatParser::new(source.as_bytes()).events().find_map(|event| event.err());
+    ///     let err = err.unwrap();
+    ///     format!("{} at {}-{}", err.message, err.start.column, err.end.column)
+    /// }
+    /// // Overlong encoding of '\0'.
+    /// assert_eq!(error(r"\c0\80"),
+    ///            r#"name '"\c0\80"' is not valid UTF-8 while parsing import at 16-24"#);
+    /// // A surrogate, as bytes and as an escape.
+    /// assert_eq!(error(r"\ed\a0\80"),
+    ///            r#"name '"\ed\a0\80"' is not valid UTF-8 while parsing import at 16-27"#);
+    /// assert_eq!(error(r"\u{d800}"), "Invalid unicode escape at 20-24");
+    /// ```
+    /// An `(import ...)` field, or a func, table, memory or global with
+    /// an inline `(import ...)`, whose `(export ...)`s are in
+    /// `export_names`.
     Import {
         modname: Name,
         fieldname: Name,
         import: WatImport,
+        export_names: Vec<Name>,
+        position: WatPosition,
     },
+    /// `export_names` holds the names of inline `(export ...)`s.
     StartFunc {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
         id: OptionalID,
-        export_name: Option<Name>,
+        export_names: Vec<Name>,
         typeuse: WatTypeuse,
         locals: Vec<WatLocal>,
+        position: WatPosition,
+    },
+    /// `position` is that of the closing ')'.
+    EndFunc {
+        position: WatPosition,
+    },
+    /// `export_names` holds the names of inline `(export ...)`s.
+    StartGlobal {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        export_names: Vec<Name>,
+        globaltype: WatGlobalType,
+        position: WatPosition,
+    },
+    EndGlobal {
+        position: WatPosition,
+    },
+    /// A table with an inline `(elem ...)` is sized to fit it exactly,
+    /// and followed by the `Elem` event that fills it.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    ///
+    /// fn limits(source: &[u8]) -> Vec<(u32, Option<u32>)> {
+    ///     WatParser::new(source)
+    ///         .events()
+    ///         .filter_map(|event| match event.unwrap() {
+    ///             WatParserState::Table { tabletype, .. } => {
+    ///                 Some((tabletype.limits.min, tabletype.limits.max))
+    ///             }
+    ///             _ => None,
+    ///         })
+    ///         .collect()
+    /// }
+    ///
+    /// assert_eq!(limits(b"(module (table 1 10 funcref) (table 1 funcref))"),
+    ///            [(1, Some(10)), (1, None)]);
+    /// assert_eq!(limits(b"(module (func $f) (table funcref (elem $f $f $f)))"),
+    ///            [(3, Some(3))]);
+    ///
+    /// let err = WatParser::new(b"(module (table 1 10))").events().last().unwrap().unwrap_err();
+    /// assert_eq!(err.message.as_ref(),
+    ///            "expected a reference type while parsing table type, found ')'");
+    /// ```
+    Table {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        export_names: Vec<Name>,
+        tabletype: WatTableType,
+        position: WatPosition,
+    },
+    Memory {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        export_names: Vec<Name>,
+        memtype: WatMemoryType,
+        position: WatPosition,
+    },
+    /// An `(export "name" (func $f))` field.
+    Export {
+        name: Name,
+        kind: WatExportKind,
+        index: WatIndex,
+        position: WatPosition,
+    },
+    /// A `(start $f)` field.
+    Start {
+        func: WatIndex,
+        position: WatPosition,
+    },
+    Elem {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        kind: WatElemKind,
+        elems: WatElemList,
+        position: WatPosition,
+    },
+    Data {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_text"))]
+        id: OptionalID,
+        kind: WatDataKind,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+        data: Data,
+        position: WatPosition,
     },
-    EndFunc,
+    /// A `(@custom "name" "data")` annotation among the module fields.
+    /// Other annotations there are skipped.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    ///
+    /// let source = b"(module (@custom \"producers\" \"\\00\") (@other (x)) (func))";
+    /// let events = WatParser::new(source).events().collect::<Result<Vec<_>, _>>().unwrap();
+    /// match events[1] {
+    ///     WatParserState::CustomSection { ref name, ref data, .. } => {
+    ///         assert_eq!((name.as_str(), &data[..]), ("producers", &[0][..]));
+    ///     }
+    ///     ref event => panic!("unexpected {:?}", event),
+    /// }
+    /// match events[2] {
+    ///     WatParserState::StartFunc { .. } => {}
+    ///     ref event => panic!("unexpected {:?}", event),
+    /// }
+    /// ```
+    CustomSection {
+        name: Name,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+        data: Data,
+        position: WatPosition,
+    },
+    /// `instruction` is the name as written, `opcode` its decoded form.
+    /// `depth` counts the folded groups and `block`/`loop`/`if`s open
+    /// around it, 0 at the top of a body; an `else` or `end` is at the
+    /// depth of the `if` or block it belongs to.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    ///
+    /// let mut parser = WatParser::new(b"(module (func (block (nop)) loop nop end))");
+    /// let mut depths = vec![];
+    /// for event in parser.events() {
+    ///     match event.unwrap() {
+    ///         WatParserState::CodeOperator { depth, .. } |
+    ///         WatParserState::CodeOperatorEnd { depth } => depths.push(depth),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// assert_eq!(depths, [0, 1, 1, 0, 0, 1, 0]);
+    /// ```
     CodeOperator {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))]
         instruction: Keyword,
+        opcode: WatOpcode,
         args: Vec<WatInstructionArg>,
         group: bool,
+        depth: u32,
         position: WatPosition,
     },
-    CodeOperatorEnd,
+    /// Closes a folded group, at the `depth` of its `CodeOperator`.
+    CodeOperatorEnd { depth: u32 },
 }
 
-enum KnownKeyword {
-    Func,
-    Import,
-    Memory,
-    Shared,
-}
+// Deeper nesting is almost certainly an attack on code that walks the
+// instruction trees recursively, such as dropping a `WatModule`.
+const DEFAULT_MAX_NESTING: u32 = 1000;
 
-pub struct WatParser<'a> {
-    lexer: WatLexer<'a>,
-    state: WatParserState,
-    func_depth: Option<u32>,
-}
+// The most 64 KiB pages a 32-bit memory can have.
+const MAX_PAGES: u32 = 65536;
 
-impl<'a> WatParser<'a> {
-    pub fn new(source: &[u8]) -> WatParser {
-        return WatParser {
-                   lexer: WatLexer::new(source),
-                   state: WatParserState::Initial,
-                   func_depth: None,
-               };
-    }
+pub(crate) const START_POSITION: WatPosition = WatPosition {
+    line: 1,
+    column: 0,
+    position: 0,
+};
 
-    fn current_token(&self) -> &WatToken {
-        self.lexer.current_token()
-    }
+// What the last event leaves the parser expecting.
+#[derive(Clone,Copy)]
+enum ParserTag {
+    Initial,
+    ModuleField,
+    Code,
+    EndModule,
+    End,
+    Error,
+}
+
+impl ParserTag {
+    // No wildcard arm, so that a new event can't be added without
+    // deciding what follows it.
+    fn of(event: &WatParserState) -> ParserTag {
+        match *event {
+            WatParserState::Initial => ParserTag::Initial,
+            WatParserState::End => ParserTag::End,
+            WatParserState::Error(_) => ParserTag::Error,
+            WatParserState::EndModule => ParserTag::EndModule,
+            WatParserState::StartModule { .. } |
+            WatParserState::EndFunc { .. } |
+            WatParserState::EndGlobal { .. } |
+            WatParserState::Table { .. } |
+            WatParserState::Memory { .. } |
+            WatParserState::Elem { .. } |
+            WatParserState::Data { .. } |
+            WatParserState::Type { .. } |
+            WatParserState::Import { .. } |
+            WatParserState::Export { .. } |
+            WatParserState::Start { .. } |
+            WatParserState::CustomSection { .. } => ParserTag::ModuleField,
+            WatParserState::StartFunc { .. } |
+            WatParserState::StartGlobal { .. } |
+            WatParserState::CodeOperator { .. } |
+            WatParserState::CodeOperatorEnd { .. } => ParserTag::Code,
+        }
+    }
+}
+
+#[derive(Clone,Copy)]
+enum SegmentKind {
+    Data,
+    Elem,
+}
+
+impl SegmentKind {
+    fn arg(self, index: WatIndex) -> WatInstructionArg {
+        match self {
+            SegmentKind::Data => WatInstructionArg::Data(index),
+            SegmentKind::Elem => WatInstructionArg::Elem(index),
+        }
+    }
+}
+
+enum CodeBlock {
+    Func,
+    Global,
+}
+
+#[derive(Clone,Copy)]
+enum BlockKind {
+    Block,
+    Loop,
+    If,
+    // An `if` past its `else`.
+    Else,
+}
+
+// An unfolded `block`, `loop` or `if` waiting for its `end`.
+struct OpenBlock {
+    kind: BlockKind,
+    // The `ID` or `Symbol` argument naming it.
+    label: Option<WatInstructionArg>,
+    // Folded groups open around it.
+    groups: u32,
+    start: WatPosition,
+    end: WatPosition,
+}
+
+/// The post-MVP proposals the parser accepts. The default enables all of
+/// them; `mvp()` enables none.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct WatFeatures {
+    /// Shared memories and atomic instructions.
+    pub threads: bool,
+    /// The `v128` type and vector instructions.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatFeatures, WatOpcode, WatParserOptions, WatParserState};
+    ///
+    /// let source = b"(module (memory 1) (func \
+    ///                (v128.store (i32.const 0) \
+    ///                  (i32x4.add (v128.load (i32.const 16)) (i32x4.splat (i32.const 1))))))";
+    /// let opcodes: Vec<_> = WatParserOptions::new()
+    ///     .build(source)
+    ///     .events()
+    ///     .filter_map(|event| match event.unwrap() {
+    ///         WatParserState::CodeOperator { opcode, .. } => Some(opcode),
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    /// assert_eq!(opcodes, [WatOpcode::V128Store, WatOpcode::I32Const, WatOpcode::I32x4Add,
+    ///                      WatOpcode::V128Load, WatOpcode::I32Const, WatOpcode::I32x4Splat,
+    ///                      WatOpcode::I32Const]);
+    ///
+    /// let mut parser = WatParserOptions::new().features(WatFeatures::mvp()).build(source);
+    /// let err = parser.events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(err.message.as_ref(), "instruction 'v128.store' requires the simd feature");
+    /// ```
+    pub simd: bool,
+    /// `externref`, `ref.*` and `table.*` instructions, typed `select`
+    /// and declarative element segments.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatFeatures, WatParserOptions, WatParserState};
+    /// use wasmtextparser::wat::WatRefType;
+    ///
+    /// let source = b"(module (table $t 1 externref) \
+    ///                (global (mut externref) (ref.null extern)) \
+    ///                (func $f (param $x externref) (result i32) \
+    ///                  (table.set $t (i32.const 0) (local.get $x)) \
+    ///                  (ref.is_null (table.get $t (i32.const 0)))))";
+    /// let mut tables = Vec::new();
+    /// for event in WatParserOptions::new().build(source).events() {
+    ///     if let WatParserState::Table { tabletype, .. } = event.unwrap() {
+    ///         tables.push(tabletype.reftype);
+    ///     }
+    /// }
+    /// assert_eq!(tables, [WatRefType::ExternRef]);
+    ///
+    /// let mvp = |source: &[u8]| {
+    ///     let mut parser = WatParserOptions::new().features(WatFeatures::mvp()).build(source);
+    ///     let err = parser.events().find_map(|event| event.err()).unwrap();
+    ///     err.message.into_string()
+    /// };
+    /// assert_eq!(mvp(source), "externref requires the reference types feature");
+    /// assert_eq!(mvp(b"(module (func (param funcref)))"),
+    ///            "funcref values require the reference types feature");
+    /// assert_eq!(mvp(b"(module (table 1 funcref) (func (drop (table.get 0 (i32.const 0)))))"),
+    ///            "instruction 'table.get' requires the reference types feature");
+    /// ```
+    pub reference_types: bool,
+    /// `memory.copy`/`fill`/`init`, `table.copy`/`init`, `data.drop`,
+    /// `elem.drop` and passive segments.
+    ///
+    /// ```
+    /// use wasmtextparser::{encode_module, WatFeatures, WatParserOptions};
+    ///
+    /// let source = b"(module (memory 1) (data (i32.const 16) \"active\") (data $d \"passive\") \
+    ///                (func $init \
+    ///                  (memory.init $d (i32.const 0) (i32.const 0) (i32.const 7)) \
+    ///                  (data.drop $d)))";
+    /// let wasm = encode_module(WatParserOptions::new().build(source).events()).unwrap();
+    /// // A data count of 2 ahead of the code, where memory.init takes the
+    /// // passive segment $d, index 1, then memory 0.
+    /// let has = |bytes: &[u8]| wasm.windows(bytes.len()).any(|window| window == bytes);
+    /// assert!(has(b"\x0c\x01\x02"));
+    /// assert!(has(b"\xfc\x08\x01\x00\xfc\x09\x01\x0b"));
+    ///
+    /// let mut parser = WatParserOptions::new().features(WatFeatures::mvp()).build(source);
+    /// let err = parser.events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(err.message.as_ref(), "passive data segments require the bulk memory feature");
+    /// ```
+    pub bulk_memory: bool,
+    pub sign_extension: bool,
+    pub saturating_float_to_int: bool,
+    pub tail_call: bool,
+    /// The `anyref`, `eqref`, `i31ref`, `structref`, `arrayref` and
+    /// `nullref` types, and `(ref null? heaptype)`.
+    pub gc: bool,
+}
+
+impl WatFeatures {
+    pub fn all() -> WatFeatures {
+        WatFeatures {
+            threads: true,
+            simd: true,
+            reference_types: true,
+            bulk_memory: true,
+            sign_extension: true,
+            saturating_float_to_int: true,
+            tail_call: true,
+            gc: true,
+        }
+    }
+
+    pub fn mvp() -> WatFeatures {
+        WatFeatures {
+            threads: false,
+            simd: false,
+            reference_types: false,
+            bulk_memory: false,
+            sign_extension: false,
+            saturating_float_to_int: false,
+            tail_call: false,
+            gc: false,
+        }
+    }
+
+    fn enabled(&self, proposal: Proposal) -> bool {
+        match proposal {
+            Proposal::Threads => self.threads,
+            Proposal::Simd => self.simd,
+            Proposal::ReferenceTypes => self.reference_types,
+            Proposal::BulkMemory => self.bulk_memory,
+            Proposal::SignExtension => self.sign_extension,
+            Proposal::SaturatingFloatToInt => self.saturating_float_to_int,
+            Proposal::TailCall => self.tail_call,
+            Proposal::Gc => self.gc,
+        }
+    }
+}
+
+impl Default for WatFeatures {
+    fn default() -> WatFeatures {
+        WatFeatures::all()
+    }
+}
+
+/// Parser settings, built with `WatParser::builder()`. The defaults
+/// match `WatParser::new`.
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct WatParserOptions {
+    error_recovery: bool,
+    features: WatFeatures,
+    max_nesting: Option<u32>,
+    reject_unknown_instructions: bool,
+    intern_ids: bool,
+    strict_const_exprs: bool,
+    strict_typeuses: bool,
+    strict_limits: bool,
+    unique_ids: bool,
+    imports_first: bool,
+    func_body: bool,
+    // Kept inverted so the derived default emits them.
+    skip_annotations: bool,
+}
+
+impl WatParserOptions {
+    pub fn new() -> WatParserOptions {
+        WatParserOptions::default()
+    }
+
+    /// See `WatParser::set_error_recovery`. Off by default.
+    pub fn error_recovery(mut self, enabled: bool) -> WatParserOptions {
+        self.error_recovery = enabled;
+        self
+    }
+
+    /// Sets the accepted proposals. All are accepted by default; use
+    /// `WatFeatures::mvp()` to reject anything past the MVP.
+    pub fn features(mut self, features: WatFeatures) -> WatParserOptions {
+        self.features = features;
+        self
+    }
+
+    /// Shorthand for toggling `WatFeatures::threads`.
+    pub fn enable_threads(mut self, enabled: bool) -> WatParserOptions {
+        self.features.threads = enabled;
+        self
+    }
+
+    /// Limits how many parentheses may be open at once, counting those
+    /// of `(module` and of the field. Beyond it, parsing fails with an
+    /// `OutOfRange` error rather than building ever deeper trees. 1000
+    /// by default.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParser;
+    ///
+    /// let depth = 100_000;
+    /// let wat = format!("(module (func {}{}))", "(nop ".repeat(depth), ")".repeat(depth));
+    /// let err = WatParser::new(wat.as_bytes()).events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(&*err.message, "maximum nesting depth of 1000 exceeded");
+    /// assert_eq!(err.start.column, 14 + 5 * 998);
+    /// ```
+    pub fn max_nesting(mut self, depth: u32) -> WatParserOptions {
+        self.max_nesting = Some(depth);
+        self
+    }
+
+    /// Rejects instruction names the parser does not know, such as the
+    /// typo `i32.addd`, instead of passing them through. Off by default.
+    pub fn reject_unknown_instructions(mut self, enabled: bool) -> WatParserOptions {
+        self.reject_unknown_instructions = enabled;
+        self
+    }
+
+    /// Replaces `$id` references in instruction immediates and segments
+    /// with `Symbol`s from the parser's `WatInterner`, so each distinct
+    /// id is stored once. Declared ids and `(type $t)` uses stay bytes.
+    /// Off by default.
+    pub fn intern_ids(mut self, enabled: bool) -> WatParserOptions {
+        self.intern_ids = enabled;
+        self
+    }
+
+    /// Rejects instructions other than `*.const`, `global.get`,
+    /// `ref.null` and `ref.func` in global initializers and segment
+    /// offsets and items. Off by default.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParserOptions;
+    ///
+    /// let options = WatParserOptions::new().strict_const_exprs(true);
+    /// let valid = b"(module (import \"env\" \"base\" (global $base i32))
+    ///                       (global i32 (global.get $base)))";
+    /// assert!(options.clone().build(valid).events().all(|event| event.is_ok()));
+    /// let invalid = b"(module (global i32 (i32.add (i32.const 1) (i32.const 2))))";
+    /// let err = options.build(invalid).events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(&*err.message, "non-constant instruction 'i32.add' in initializer");
+    /// ```
+    pub fn strict_const_exprs(mut self, enabled: bool) -> WatParserOptions {
+        self.strict_const_exprs = enabled;
+        self
+    }
+
+    /// Rejects a typeuse with both `(type $t)` and inline params or
+    /// results that differ from those of `$t`. Types declared after the
+    /// typeuse are not checked here; see `IndexResolver::check_typeuses`.
+    /// Off by default.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParserOptions, WatParserState};
+    ///
+    /// let options = WatParserOptions::new().strict_typeuses(true);
+    /// let valid = b"(module (type $t (func (param i32) (result i32)))
+    ///                       (func (type $t) (param $x i32) (result i32) local.get $x))";
+    /// let events = options.clone().build(valid).events().collect::<Result<Vec<_>, _>>().unwrap();
+    /// match events[2] {
+    ///     WatParserState::StartFunc { ref typeuse, .. } => {
+    ///         assert_eq!(typeuse.id, Some(b"$t".to_vec()));
+    ///         assert_eq!(typeuse.params.len(), 1);
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// let invalid = b"(module (type $t (func (param i32)))
+    ///                         (func (type $t) (param i32 i32)))";
+    /// let err = options.build(invalid).events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(&*err.message,
+    ///            "(type $t) is (func (param i32)), declared at line 1, but the inline \
+    ///             signature is (func (param i32 i32))");
+    /// let invalid = b"(module (type $t (func (param i32)))
+    ///                         (func (type $t) (param f32)))";
+    /// let err = WatParserOptions::new().strict_typeuses(true).build(invalid)
+    ///     .events().find_map(|event| event.err()).unwrap();
+    /// assert_eq!(&*err.message,
+    ///            "(type $t) is (func (param i32)), declared at line 1, but the inline \
+    ///             signature is (func (param f32))");
+    /// ```
+    pub fn strict_typeuses(mut self, enabled: bool) -> WatParserOptions {
+        self.strict_typeuses = enabled;
+        self
+    }
+
+    /// Rejects limits whose maximum is below their minimum, and memory
+    /// limits beyond 65536 pages. Table limits only need to fit a u32,
+    /// which is always checked. The error spans the offending literal.
+    /// Off by default.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParserOptions;
+    ///
+    /// fn check(source: &[u8]) -> Result<(), String> {
+    ///     let mut parser = WatParserOptions::new().strict_limits(true).build(source);
+    ///     match parser.events().find_map(|event| event.err()) {
+    ///         Some(err) => Err(format!("{} at {}", err.message, err.start.column)),
+    ///         None => Ok(()),
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(check(b"(module (memory 2 5) (memory 65536 65536) (table 5 5 funcref))"), Ok(()));
+    /// assert_eq!(check(b"(module (memory 5 2))"),
+    ///            Err("maximum 2 is below the minimum 5 at 18".into()));
+    /// assert_eq!(check(b"(module (table 5 2 funcref))"),
+    ///            Err("maximum 2 is below the minimum 5 at 17".into()));
+    /// assert_eq!(check(b"(module (memory 100000))"),
+    ///            Err("memory of 100000 pages is above the limit of 65536 at 16".into()));
+    /// assert_eq!(check(b"(module (memory 1 65537))"),
+    ///            Err("memory of 65537 pages is above the limit of 65536 at 18".into()));
+    /// assert!(check(b"(module (table 4294967296 funcref))").is_err());
+    /// ```
+    pub fn strict_limits(mut self, enabled: bool) -> WatParserOptions {
+        self.strict_limits = enabled;
+        self
+    }
+
+    /// Rejects a `$id` bound twice in one index space: by two functions,
+    /// two types and so on, or within a function by two params or locals,
+    /// which share a space. The error spans the second `$id` and names
+    /// the line of the first. Off by default.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParserOptions;
+    ///
+    /// fn check(source: &[u8]) -> Result<(), String> {
+    ///     let mut parser = WatParserOptions::new().unique_ids(true).build(source);
+    ///     match parser.events().find_map(|event| event.err()) {
+    ///         Some(err) => Err(format!("{} at {}:{}", err.message, err.start.line, err.start.column)),
+    ///         None => Ok(()),
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(check(b"(module (func $x (param $x i32) (local $y i32))\n\
+    ///                            (global $x i32 (i32.const 0))\n\
+    ///                            (func (param $y i32) (local $x i32)))"), Ok(()));
+    /// assert_eq!(check(b"(module (func $f)\n(import \"m\" \"f\" (func $f)))"),
+    ///            Err("duplicate identifier $f, previously defined at line 1 at 2:22".into()));
+    /// assert_eq!(check(b"(module (func (param $x i32) (local $x i32)))"),
+    ///            Err("duplicate identifier $x, previously defined at line 1 at 1:36".into()));
+    /// assert_eq!(check(b"(module (func (param $x i32) (param $x f32)))"),
+    ///            Err("duplicate identifier $x, previously defined at line 1 at 1:36".into()));
+    /// ```
+    pub fn unique_ids(mut self, enabled: bool) -> WatParserOptions {
+        self.unique_ids = enabled;
+        self
+    }
+
+    /// Rejects an import, including a `(func (import ...))`, that follows
+    /// a func, table, memory or global definition, which would take the
+    /// indices imports come first in. Module-less sources are always
+    /// checked. The error spans the `import` keyword and names the line
+    /// of the first definition. Off by default.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParserOptions;
+    ///
+    /// fn check(source: &[u8]) -> Result<(), String> {
+    ///     let mut parser = WatParserOptions::new().imports_first(true).build(source);
+    ///     match parser.events().find_map(|event| event.err()) {
+    ///         Some(err) => Err(format!("{} at {}:{}", err.message, err.start.line, err.start.column)),
+    ///         None => Ok(()),
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(check(b"(module (type (func)) (import \"m\" \"f\" (func))\n\
+    ///                            (func (import \"m\" \"g\")) (memory 1) (func))"), Ok(()));
+    /// assert_eq!(check(b"(module (memory 1)\n(import \"m\" \"f\" (func)))"),
+    ///            Err("import follows definition at line 1 at 2:1".into()));
+    /// assert_eq!(check(b"(module (global i32 (i32.const 0))\n(func (import \"m\" \"f\")))"),
+    ///            Err("import follows definition at line 1 at 2:7".into()));
+    /// ```
+    pub fn imports_first(mut self, enabled: bool) -> WatParserOptions {
+        self.imports_first = enabled;
+        self
+    }
+
+    /// Parses the source as the instructions of a function body rather
+    /// than a module; see `WatParser::func_body`. Off by default.
+    pub fn func_body(mut self, enabled: bool) -> WatParserOptions {
+        self.func_body = enabled;
+        self
+    }
+
+    /// Returns each `(@custom ...)` annotation as a `CustomSection`
+    /// event. With it off, they are skipped like other annotations. On
+    /// by default.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParser;
+    ///
+    /// let source = b"(module (@custom \"name\" \"\") (func))";
+    /// assert_eq!(WatParser::builder().build(source).events().count(), 5);
+    /// assert_eq!(WatParser::builder().emit_annotations(false).build(source).events().count(), 4);
+    /// ```
+    pub fn emit_annotations(mut self, enabled: bool) -> WatParserOptions {
+        self.skip_annotations = !enabled;
+        self
+    }
+
+    pub fn build(self, source: &[u8]) -> WatParser<'_> {
+        WatParser::with_options(source, self)
+    }
+}
+
+/// Counts gathered while parsing, see `WatParser::stats`.
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WatParseStats {
+    /// Defined functions; imported ones count as imports.
+    pub funcs: usize,
+    pub imports: usize,
+    /// `CodeOperator` events, from function bodies and global initializers.
+    pub instructions: usize,
+    /// Where the input ended; `None` until `End` has been returned.
+    pub end: Option<WatPosition>,
+}
+
+/// Pull parser for a single WAT module.
+///
+/// The module may also be written as just its fields, without
+/// `(module ...)` around them. Imports must then come before any func,
+/// table, memory or global definition.
+pub struct WatParser<'a> {
+    lexer: WatLexer<'a>,
+    tag: ParserTag,
+    // The event being built; handed out by parse().
+    event: WatParserState,
+    // A second event from the same field, returned by the next parse().
+    pending_event: Option<WatParserState>,
+    // Memories declared so far in the current module, imports included.
+    memories: u32,
+    // Tables declared so far in the current module, imports included.
+    tables: u32,
+    interner: Option<WatInterner>,
+    func_depth: Option<u32>,
+    // Unfolded `block`/`loop`/`if`s open in the current body.
+    blocks: Vec<OpenBlock>,
+    code_block: CodeBlock,
+    context: Vec<&'static str>,
+    open_parens: Vec<WatPosition>,
+    // Where the text of the last event starts, the end of the last
+    // consumed token, and the end of the one before it, which
+    // rewind_token goes back to.
+    event_start: WatPosition,
+    last_end: WatPosition,
+    past_end: WatPosition,
+    fields_parsed: usize,
+    stats: WatParseStats,
+    in_module: bool,
+    // Set when the module is written as just its fields, without
+    // `(module ...)` around them.
+    implicit_module: bool,
+    // The first func, table, memory or global defined in the module.
+    first_definition: Option<WatPosition>,
+    // The types declared so far and the lines of their fields, kept
+    // with `strict_typeuses`.
+    type_signatures: BTreeMap<ID, (WatTypeuse, usize)>,
+    // Where the `$id`s of the module and of the current function's
+    // params and locals were bound, kept with `unique_ids`.
+    bound_ids: BTreeMap<(IndexSpace, ID), WatPosition>,
+    local_ids: BTreeMap<ID, WatPosition>,
+    // Set while the source is a prefix of the input ending after a
+    // module field, see `PushParser`.
+    partial: bool,
+    options: WatParserOptions,
+    errors: Vec<WatParserError>,
+}
+
+impl<'a> WatParser<'a> {
+    /// Creates a parser for `source`. Input that is not valid UTF-8 is
+    /// reported by the first `parse()` call.
+    pub fn new(source: &[u8]) -> WatParser<'_> {
+        WatParser::with_options(source, WatParserOptions::default())
+    }
+
+    /// Creates a parser for a bare instruction sequence, as found in a
+    /// function body. `parse()` returns its `CodeOperator` and
+    /// `CodeOperatorEnd` events, then `End` once the input is used up;
+    /// there are no module or function events.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatOpcode, WatParser, WatParserState};
+    ///
+    /// let mut parser = WatParser::func_body(b"i32.const 1 i32.const 2 i32.add");
+    /// let mut opcodes = vec![];
+    /// loop {
+    ///     match parser.parse() {
+    ///         WatParserState::CodeOperator { opcode, .. } => opcodes.push(opcode),
+    ///         WatParserState::End => break,
+    ///         event => panic!("unexpected {:?}", event),
+    ///     }
+    /// }
+    /// assert_eq!(opcodes, [WatOpcode::I32Const, WatOpcode::I32Const, WatOpcode::I32Add]);
+    /// ```
+    pub fn func_body(source: &[u8]) -> WatParser<'_> {
+        WatParser::with_options(source, WatParserOptions::default().func_body(true))
+    }
+
+    /// Creates a parser for `source` with non-default settings.
+    pub fn with_options(source: &[u8], options: WatParserOptions) -> WatParser<'_> {
+        return WatParser {
+                   lexer: WatLexer::new(source),
+                   tag: ParserTag::Initial,
+                   event: WatParserState::Initial,
+                   pending_event: None,
+                   memories: 0,
+                   tables: 0,
+                   interner: if options.intern_ids { Some(WatInterner::new()) } else { None },
+                   func_depth: None,
+                   blocks: Vec::new(),
+                   code_block: CodeBlock::Func,
+                   context: Vec::new(),
+                   open_parens: Vec::new(),
+                   event_start: START_POSITION,
+                   last_end: START_POSITION,
+                   past_end: START_POSITION,
+                   fields_parsed: 0,
+                   stats: WatParseStats::default(),
+                   in_module: false,
+                   implicit_module: false,
+                   first_definition: None,
+                   type_signatures: BTreeMap::new(),
+                   bound_ids: BTreeMap::new(),
+                   local_ids: BTreeMap::new(),
+                   partial: false,
+                   options,
+                   errors: Vec::new(),
+               };
+    }
+
+    /// Points the parser at new source and returns it to its initial
+    /// state. Options are kept, and so are the buffers used for
+    /// bookkeeping, which makes reuse cheaper than creating a new parser.
+    pub fn reset(&mut self, source: &'a [u8]) {
+        self.lexer = WatLexer::new(source);
+        self.tag = ParserTag::Initial;
+        self.event = WatParserState::Initial;
+        self.pending_event = None;
+        self.memories = 0;
+        self.tables = 0;
+        if let Some(ref mut interner) = self.interner {
+            interner.clear();
+        }
+        self.func_depth = None;
+        self.blocks.clear();
+        self.code_block = CodeBlock::Func;
+        self.context.clear();
+        self.open_parens.clear();
+        self.event_start = START_POSITION;
+        self.last_end = START_POSITION;
+        self.past_end = START_POSITION;
+        self.fields_parsed = 0;
+        self.stats = WatParseStats::default();
+        self.in_module = false;
+        self.implicit_module = false;
+        self.first_definition = None;
+        self.type_signatures.clear();
+        self.bound_ids.clear();
+        self.local_ids.clear();
+        self.partial = false;
+        self.errors.clear();
+    }
+
+    // Moves the parser over to `source`, which holds the same text up to
+    // the current token. With `partial`, the input may go on past the
+    // end of `source`, and parse() returns `Initial` where it would
+    // need to look beyond it.
+    pub(crate) fn rebind(self, source: &[u8], partial: bool) -> WatParser<'_> {
+        return WatParser {
+                   lexer: self.lexer.rebind(source),
+                   tag: self.tag,
+                   event: self.event,
+                   pending_event: self.pending_event,
+                   memories: self.memories,
+                   tables: self.tables,
+                   interner: self.interner,
+                   func_depth: self.func_depth,
+                   blocks: self.blocks,
+                   code_block: self.code_block,
+                   context: self.context,
+                   open_parens: self.open_parens,
+                   event_start: self.event_start,
+                   last_end: self.last_end,
+                   past_end: self.past_end,
+                   fields_parsed: self.fields_parsed,
+                   stats: self.stats,
+                   in_module: self.in_module,
+                   implicit_module: self.implicit_module,
+                   first_definition: self.first_definition,
+                   type_signatures: self.type_signatures,
+                   bound_ids: self.bound_ids,
+                   local_ids: self.local_ids,
+                   partial,
+                   options: self.options,
+                   errors: self.errors,
+               };
+    }
+
+    // After a rebind to longer source, replaces an `End` token found at
+    // the end of the old source by what follows it.
+    pub(crate) fn resume_input(&mut self) {
+        if let ParserTag::Initial = self.tag {
+            return;
+        }
+        if *self.current_token_type() != WatTokenType::End {
+            return;
+        }
+        if let Err(err) = self.lexer.rescan() {
+            self.errors.push(err);
+            self.tag = ParserTag::Error;
+        }
+    }
+
+    // Whether parse() will only repeat the last `End` or `Error`.
+    pub(crate) fn stopped(&self) -> bool {
+        match self.tag {
+            ParserTag::End => true,
+            ParserTag::Error => !self.can_recover(),
+            _ => false,
+        }
+    }
+
+    /// Starts a `WatParserOptions` builder.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParser;
+    ///
+    /// let mut parser = WatParser::builder().max_nesting(1000).build(b"(module)");
+    /// assert!(parser.events().all(|event| event.is_ok()));
+    /// ```
+    pub fn builder() -> WatParserOptions {
+        WatParserOptions::default()
+    }
+
+    /// Creates a parser for WAT text that is already a `str`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &str) -> WatParser<'_> {
+        WatParser::new(source.as_bytes())
+    }
+
+    pub(crate) fn current_token(&self) -> &WatToken {
+        self.lexer.token()
+    }
 
-    fn current_token_type(&self) -> &WatTokenType {
-        &self.lexer.current_token().ty
+    pub(crate) fn current_token_type(&self) -> &WatTokenType {
+        &self.lexer.token().ty
     }
 
-    fn current_token_content(&self) -> &[u8] {
+    pub(crate) fn current_token_content(&self) -> &[u8] {
         self.lexer.current_token_content()
     }
 
-    fn create_error(&self, message: &'static str) -> WatParserError {
-        let ref position = self.current_token().start;
+    pub(crate) fn create_error(&self, kind: ErrorKind, message: String) -> WatParserError {
+        let token = self.current_token();
         WatParserError {
-            message,
-            line: position.line,
-            column: position.column,
+            kind,
+            message: message.into_boxed_str(),
+            start: token.start,
+            end: token.end,
+        }
+    }
+
+    // Spans from the innermost unclosed paren to the current token.
+    fn unclosed_paren_error(&self, err: WatParserError) -> WatParserError {
+        match self.open_parens.last() {
+            Some(open) => WatParserError { start: *open, ..err },
+            None => err,
+        }
+    }
+
+    fn describe_current_token(&self) -> String {
+        if let WatTokenType::End = *self.current_token_type() {
+            return String::from("end of input");
+        }
+        let content = self.current_token_content();
+        if content == b"+" || content == b"-" {
+            return format!("'{}' (a sign without a number)", content[0] as char);
+        }
+        if content.len() > 32 {
+            return format!("'{}...'", String::from_utf8_lossy(&content[..32]));
         }
+        format!("'{}'", String::from_utf8_lossy(content))
     }
 
-    fn advance(&mut self) -> Result<()> {
-        let result = self.lexer.next();
-        if result.is_ok() {
+    fn context_suffix(&self) -> String {
+        match self.context.last() {
+            Some(context) => format!(" while parsing {}", context),
+            None => String::new(),
+        }
+    }
+
+    pub(crate) fn expected_error(&self, expected: &str) -> WatParserError {
+        let message = format!("expected {}{}, found {}",
+                              expected,
+                              self.context_suffix(),
+                              self.describe_current_token());
+        self.create_error(ErrorKind::UnexpectedToken, message)
+    }
+
+    // Fails with "<what> the <proposal> feature" at the current token
+    // unless the proposal is enabled.
+    fn require_feature(&self, proposal: Proposal, what: &str) -> Result<()> {
+        if self.options.features.enabled(proposal) {
             return Ok(());
         }
-        let err = result.unwrap_err();
-        Err(WatParserError {
-                message: err.message,
-                line: err.line,
-                column: err.column,
-            })
+        let message = format!("{} the {} feature", what, proposal.name());
+        Err(self.create_error(ErrorKind::Unsupported, message))
+    }
+
+    fn invalid_literal_error(&self, literal: &str) -> WatParserError {
+        let message = format!("invalid {} literal {}{}",
+                              literal,
+                              self.describe_current_token(),
+                              self.context_suffix());
+        self.create_error(ErrorKind::InvalidLiteral, message)
+    }
+
+    fn in_context<T, F>(&mut self, context: &'static str, f: F) -> Result<T>
+        where F: FnOnce(&mut Self) -> Result<T>
+    {
+        self.context.push(context);
+        let result = f(self);
+        self.context.pop();
+        result
+    }
+
+    pub(crate) fn advance(&mut self) -> Result<()> {
+        // The lexer stops scanning right after the token being consumed.
+        self.past_end = mem::replace(&mut self.last_end, self.lexer.current_position());
+        self.lexer.next()?;
+        Ok(())
     }
 
+    // Only used to step back over a just-consumed '(', so the paren is
+    // dropped from the open parens as well.
     fn rewind_token(&mut self) {
         self.lexer.rewind();
+        self.last_end = self.past_end;
+        self.open_parens.pop();
     }
 
-    fn maybe_open_paren(&mut self) -> Result<bool> {
+    pub(crate) fn maybe_open_paren(&mut self) -> Result<bool> {
         if let WatTokenType::OpenParen = *self.current_token_type() {
+            let max = self.options.max_nesting.unwrap_or(DEFAULT_MAX_NESTING);
+            if self.open_parens.len() >= max as usize {
+                let message = format!("maximum nesting depth of {} exceeded", max);
+                return Err(self.create_error(ErrorKind::OutOfRange, message));
+            }
+            let start = self.current_token().start;
             self.advance()?;
+            self.open_parens.push(start);
             return Ok(true);
         }
         Ok(false)
     }
 
-    fn expect_open_paren(&mut self) -> Result<()> {
+    pub(crate) fn expect_open_paren(&mut self) -> Result<()> {
         if self.maybe_open_paren()? {
             return Ok(());
         }
-        Err(self.create_error("( is expected"))
+        Err(self.expected_error("'('"))
+    }
+
+    // The '(' that opened the field being read.
+    pub(crate) fn field_position(&self) -> WatPosition {
+        *self.open_parens.last().unwrap()
     }
 
     fn maybe_close_paren(&mut self) -> Result<bool> {
         if let WatTokenType::CloseParen = *self.current_token_type() {
             self.advance()?;
+            self.open_parens.pop();
             return Ok(true);
         }
         Ok(false)
     }
 
-    fn expect_close_paren(&mut self) -> Result<()> {
+    pub(crate) fn expect_close_paren(&mut self) -> Result<()> {
         if self.maybe_close_paren()? {
             return Ok(());
         }
-        Err(self.create_error(") is expected"))
+        Err(self.unclosed_paren_error(self.expected_error("')'")))
     }
 
-    fn maybe_exact_keyword(&mut self, keyword: &[u8]) -> Result<bool> {
-        if let WatTokenType::Keyword = *self.current_token_type() {
-            if self.current_token_content() == keyword {
-                self.advance()?;
-                return Ok(true);
-            }
+    fn known_keyword(&self) -> Option<KnownKeyword> {
+        self.current_token().keyword
+    }
+
+    fn maybe_exact_keyword(&mut self, keyword: KnownKeyword) -> Result<bool> {
+        if self.known_keyword() == Some(keyword) {
+            self.advance()?;
+            return Ok(true);
         }
         Ok(false)
     }
 
-    fn expect_exact_keyword(&mut self, keyword: &[u8]) -> Result<()> {
+    fn expect_exact_keyword(&mut self, keyword: KnownKeyword) -> Result<()> {
         if self.maybe_exact_keyword(keyword)? {
             return Ok(());
         }
-        Err(self.create_error("?? keyword is expected"))
+        let expected = format!("'{}'", keyword.as_str());
+        Err(self.expected_error(&expected))
     }
 
-    fn is_keyword(&self) -> bool {
-        if let WatTokenType::Keyword = *self.current_token_type() {
-            true
-        } else {
-            false
+    // Consumes `(keyword`, or nothing if the group starts differently.
+    fn maybe_open_paren_keyword(&mut self, keyword: KnownKeyword) -> Result<bool> {
+        if self.maybe_open_paren()? {
+            if self.maybe_exact_keyword(keyword)? {
+                return Ok(true);
+            }
+            self.rewind_token();
         }
+        Ok(false)
+    }
+
+    pub(crate) fn is_keyword(&self) -> bool {
+        matches!(*self.current_token_type(), WatTokenType::Keyword)
     }
 
     fn get_keyword(&self) -> Result<&[u8]> {
         if self.is_keyword() {
             return Ok(self.current_token_content());
         }
-        Err(self.create_error("a keyword is expected"))
+        Err(self.expected_error("a keyword"))
     }
 
     fn is_memarg_flag(&self) -> Result<bool> {
-        let content = self.get_keyword()?;
-        Ok(content.len() > 7 && &content[..7] == b"offset=" ||
-           content.len() > 6 && &content[..6] == b"flags=")
+        Ok(is_memarg_keyword(self.get_keyword()?))
+    }
+
+    // Whether the token after the current one is a number or a memory
+    // argument, looking ahead without moving the lexer.
+    fn memarg_or_number_follows(&self) -> bool {
+        let mut lexer = WatLexer::starting_at(self.lexer.source(), self.current_token().end);
+        match lexer.next() {
+            Ok(token) if token.ty == WatTokenType::Unsigned => true,
+            Ok(token) if token.ty == WatTokenType::Keyword => is_memarg_keyword(lexer.current_token_content()),
+            _ => false,
+        }
     }
 
-    fn maybe_id(&mut self) -> Result<OptionalID> {
+    pub(crate) fn maybe_id(&mut self) -> Result<OptionalID> {
         if let WatTokenType::ID = *self.current_token_type() {
             let id = Vec::from(self.current_token_content());
             self.advance()?;
@@ -387,36 +2287,85 @@ impl<'a> WatParser<'a> {
         Ok(None)
     }
 
-    fn read_id(&mut self) -> Result<ID> {
+    // Reads an optional `$id` that binds an item of `space`. With
+    // `unique_ids`, fails if the id is bound already.
+    fn maybe_bound_id(&mut self, space: IndexSpace) -> Result<OptionalID> {
+        let (start, end) = (self.lexer.token().start, self.lexer.token().end);
+        let id = self.maybe_id()?;
+        if let (true, Some(id)) = (self.options.unique_ids, id.as_ref()) {
+            let previous = *self.bound_ids.entry((space, id.clone())).or_insert(start);
+            if previous != start {
+                return Err(duplicate_id_error(id, previous, start, end));
+            }
+        }
+        Ok(id)
+    }
+
+    // Reads an optional `$id` of a param or local, which share an index
+    // space within their function.
+    fn maybe_local_id(&mut self) -> Result<OptionalID> {
+        let (start, end) = (self.lexer.token().start, self.lexer.token().end);
         let id = self.maybe_id()?;
-        if id.is_some() {
-            return Ok(id.unwrap());
+        if let (true, Some(id)) = (self.options.unique_ids, id.as_ref()) {
+            let previous = *self.local_ids.entry(id.clone()).or_insert(start);
+            if previous != start {
+                return Err(duplicate_id_error(id, previous, start, end));
+            }
+        }
+        Ok(id)
+    }
+
+    // Interns the current token if it is an `$id` and interning is on.
+    fn maybe_symbol(&mut self) -> Result<Option<Symbol>> {
+        let symbol = match self.interner {
+            Some(ref mut interner) if self.lexer.token().ty == WatTokenType::ID => {
+                interner.intern(self.lexer.current_token_content())
+            }
+            _ => return Ok(None),
+        };
+        self.advance()?;
+        Ok(Some(symbol))
+    }
+
+    fn read_id(&mut self) -> Result<ID> {
+        if let Some(id) = self.maybe_id()? {
+            return Ok(id);
         }
-        Err(self.create_error("id is expected"))
+        Err(self.expected_error("an identifier"))
     }
 
-    fn read_u32(&mut self) -> Result<u32> {
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
         if let WatTokenType::Unsigned = *self.current_token_type() {
-            let result = {
-                let result = parse_u32(self.current_token_content());
-                if result.is_none() {
-                    return Err(self.create_error("unable to read u32"));
-                }
-                result.unwrap()
+            let result = match parse_u32(self.current_token_content()) {
+                Some(result) => result,
+                None => return Err(self.invalid_literal_error("u32")),
             };
             self.advance()?;
             return Ok(result);
         }
-        unreachable!();
+        Err(self.expected_error("an unsigned integer"))
     }
 
-    fn read_name(&mut self) -> Result<Name> {
+    // Reads a name, which unlike a data string must be valid UTF-8.
+    pub(crate) fn read_name(&mut self) -> Result<Name> {
         if let WatTokenType::String = *self.current_token_type() {
-            let name = parse_string(self.current_token_content());
+            let bytes = match parse_data_string(self.current_token_content()) {
+                Some(bytes) => bytes,
+                None => return Err(self.invalid_literal_error("string")),
+            };
+            let name = match String::from_utf8(bytes) {
+                Ok(name) => name,
+                Err(_) => {
+                    let message = format!("name {} is not valid UTF-8{}",
+                                          self.describe_current_token(),
+                                          self.context_suffix());
+                    return Err(self.create_error(ErrorKind::InvalidLiteral, message));
+                }
+            };
             self.advance()?;
             return Ok(name);
         }
-        unreachable!();
+        Err(self.expected_error("a string literal"))
     }
 
     fn read_keyword(&mut self) -> Result<Keyword> {
@@ -425,30 +2374,68 @@ impl<'a> WatParser<'a> {
             self.advance()?;
             return Ok(keyword);
         }
-        unreachable!();
+        Err(self.expected_error("a keyword"))
     }
 
-    fn read_limits(&mut self) -> Result<WatLimits> {
+    // Reads the limits of a memory, in pages, or of a table. With
+    // `strict_limits`, the maximum may not be below the minimum, and
+    // memories may not be larger than `MAX_PAGES`.
+    fn read_limits(&mut self, memory: bool) -> Result<WatLimits> {
+        let min_token = self.current_token().clone();
         let min = self.read_u32()?;
-        let max = if let WatTokenType::Unsigned = *self.current_token_type() {
-            Some(self.read_u32()?)
-        } else {
-            None
-        };
-        Ok(WatLimits { min, max })
+        self.check_pages(memory, min, &min_token)?;
+        if *self.current_token_type() != WatTokenType::Unsigned {
+            return Ok(WatLimits { min, max: None });
+        }
+        let max_token = self.current_token().clone();
+        let max = self.read_u32()?;
+        self.check_pages(memory, max, &max_token)?;
+        if self.options.strict_limits && max < min {
+            let message = format!("maximum {} is below the minimum {}", max, min);
+            return Err(WatParserError {
+                           kind: ErrorKind::Validation,
+                           message: message.into_boxed_str(),
+                           start: max_token.start,
+                           end: max_token.end,
+                       });
+        }
+        Ok(WatLimits { min, max: Some(max) })
+    }
+
+    fn check_pages(&self, memory: bool, pages: u32, token: &WatToken) -> Result<()> {
+        if !self.options.strict_limits || !memory || pages <= MAX_PAGES {
+            return Ok(());
+        }
+        let message = format!("memory of {} pages is above the limit of {}", pages, MAX_PAGES);
+        Err(WatParserError {
+                kind: ErrorKind::Validation,
+                message: message.into_boxed_str(),
+                start: token.start,
+                end: token.end,
+            })
     }
 
     fn read_memtype(&mut self) -> Result<WatMemoryType> {
+        // `shared` is only accepted in the parenthesized `(shared min max)` form.
         if self.maybe_open_paren()? {
-            self.expect_exact_keyword(b"shared")?;
-            let limits = self.read_limits()?;
+            let start = self.current_token().start;
+            if self.known_keyword() == Some(KnownKeyword::Shared) {
+                self.require_feature(Proposal::Threads, "shared memories require")?;
+            }
+            self.expect_exact_keyword(KnownKeyword::Shared)?;
+            let limits = self.read_limits(true)?;
+            if limits.max.is_none() {
+                let message = String::from("shared memory requires a maximum");
+                let err = self.create_error(ErrorKind::Validation, message);
+                return Err(WatParserError { start, ..err });
+            }
             self.expect_close_paren()?;
             return Ok(WatMemoryType {
                           limits,
                           shared: true,
                       });
         }
-        let limits = self.read_limits()?;
+        let limits = self.read_limits(true)?;
         Ok(WatMemoryType {
                limits,
                shared: false,
@@ -456,84 +2443,390 @@ impl<'a> WatParser<'a> {
     }
 
     fn read_start_module(&mut self) -> Result<()> {
+        self.lexer.validate_utf8()?;
         self.advance()?;
         self.expect_open_paren()?;
-        self.expect_exact_keyword(b"module")?;
+        let position = self.field_position();
+        self.event_start = position;
+        if self.is_field_keyword() || self.is_annotation() {
+            // The fields are the module. Its '(' stays open until the end
+            // of the input, standing in for the one of `(module`.
+            self.rewind_token();
+            self.open_parens.push(position);
+            self.implicit_module = true;
+            self.last_end = position;
+            self.event = WatParserState::StartModule { id: None, position };
+            return Ok(());
+        }
+        self.expect_exact_keyword(KnownKeyword::Module)?;
         let id = self.maybe_id()?;
-        self.state = WatParserState::StartModule { id };
+        self.event = WatParserState::StartModule { id, position };
         Ok(())
     }
 
+    // Checks the input and reads its first token, for a script, which
+    // reads its commands itself and hands over at each `(module`.
+    pub(crate) fn start_script(&mut self) -> Result<()> {
+        self.lexer.validate_utf8()?;
+        self.advance()
+    }
+
+    // Starts a script's module command, whose `(module $id?` has been
+    // read. parse() then returns the module's events as for a whole
+    // input, up to its `EndModule`.
+    pub(crate) fn start_script_module(&mut self, id: OptionalID) -> WatParserState {
+        self.memories = 0;
+        self.tables = 0;
+        self.first_definition = None;
+        self.type_signatures.clear();
+        self.bound_ids.clear();
+        let position = self.field_position();
+        self.event_start = position;
+        let event = WatParserState::StartModule { id, position };
+        self.tag = ParserTag::of(&event);
+        self.count_event(&event);
+        event
+    }
+
     fn read_memory_import(&mut self) -> Result<WatImport> {
         self.advance()?;
-        let id = self.maybe_id()?;
-        let memtype = self.read_memtype()?;
+        let id = self.maybe_bound_id(IndexSpace::Memory)?;
+        let memtype = self.in_context("memory limits", Self::read_memtype)?;
         Ok(WatImport::Memory { id, memtype })
     }
 
+    fn read_func_import(&mut self) -> Result<WatImport> {
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Func)?;
+        let typeuse = self.read_typeuse()?;
+        Ok(WatImport::Func { id, typeuse })
+    }
+
+    fn read_table_import(&mut self) -> Result<WatImport> {
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Table)?;
+        let tabletype = self.in_context("table type", Self::read_tabletype)?;
+        Ok(WatImport::Table { id, tabletype })
+    }
+
+    fn read_global_import(&mut self) -> Result<WatImport> {
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Global)?;
+        let globaltype = self.in_context("global type", Self::read_globaltype)?;
+        Ok(WatImport::Global { id, globaltype })
+    }
+
+    // Imports take the first indices, which only the explicit
+    // `(module ...)` form without `imports_first` leaves to the reader
+    // to get right. Called on the `import` keyword.
+    fn check_import_order(&self) -> Result<()> {
+        match self.first_definition {
+            Some(definition) if self.implicit_module || self.options.imports_first => {
+                let message = format!("import follows definition at line {}", definition.line);
+                Err(self.create_error(ErrorKind::Validation, message))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn read_import(&mut self) -> Result<()> {
+        self.check_import_order()?;
+        let position = self.field_position();
         self.advance()?;
         let modname = self.read_name()?;
         let fieldname = self.read_name()?;
         self.expect_open_paren()?;
-        let keyword = match self.get_keyword()? {
-            b"memory" => KnownKeyword::Memory,
-            _ => unimplemented!("nyi"),
-        };
-        let import = match keyword {
-            KnownKeyword::Memory => self.read_memory_import()?,
-            _ => panic!(),
+        let read_descriptor: fn(&mut Self) -> Result<WatImport> = match self.known_keyword() {
+            Some(KnownKeyword::Func) => Self::read_func_import,
+            Some(KnownKeyword::Memory) => Self::read_memory_import,
+            Some(KnownKeyword::Global) => Self::read_global_import,
+            Some(KnownKeyword::Table) => Self::read_table_import,
+            _ => return Err(self.expected_error("an import descriptor")),
         };
+        let import = self.in_context("import descriptor", read_descriptor)?;
         self.expect_close_paren()?;
 
-        self.state = WatParserState::Import {
+        self.event = WatParserState::Import {
             modname,
             fieldname,
             import,
+            export_names: vec![],
+            position,
+        };
+        self.expect_close_paren()?;
+        Ok(())
+    }
+
+    // Reads the `(import "module" "name")` of a func, table, memory or
+    // global defined by import, if there is one.
+    fn maybe_inline_import(&mut self) -> Result<Option<(Name, Name)>> {
+        if !self.maybe_open_paren()? {
+            return Ok(None);
+        }
+        if self.known_keyword() != Some(KnownKeyword::Import) {
+            self.rewind_token();
+            return Ok(None);
+        }
+        self.check_import_order()?;
+        self.advance()?;
+        let modname = self.read_name()?;
+        let fieldname = self.read_name()?;
+        self.expect_close_paren()?;
+        Ok(Some((modname, fieldname)))
+    }
+
+    fn read_export(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let name = self.read_name()?;
+        self.expect_open_paren()?;
+        let kind = match self.known_keyword() {
+            Some(KnownKeyword::Func) => WatExportKind::Func,
+            Some(KnownKeyword::Table) => WatExportKind::Table,
+            Some(KnownKeyword::Memory) => WatExportKind::Memory,
+            Some(KnownKeyword::Global) => WatExportKind::Global,
+            _ => return Err(self.expected_error("an export descriptor")),
+        };
+        self.advance()?;
+        let index = self.read_index()?;
+        self.expect_close_paren()?;
+        self.expect_close_paren()?;
+        self.event = WatParserState::Export {
+            name,
+            kind,
+            index,
+            position,
         };
+        Ok(())
+    }
+
+    fn read_start(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let func = self.read_index()?;
         self.expect_close_paren()?;
+        self.event = WatParserState::Start { func, position };
         Ok(())
     }
 
     fn read_valtype(&mut self) -> Result<WatValType> {
+        if self.is_reftype() || *self.current_token_type() == WatTokenType::OpenParen {
+            // The MVP has funcref in tables only.
+            if self.known_keyword() == Some(KnownKeyword::Funcref) {
+                self.require_feature(Proposal::ReferenceTypes, "funcref values require")?;
+            }
+            return Ok(WatValType::Ref(self.read_reftype()?));
+        }
+        if !self.is_keyword() {
+            return Err(self.expected_error("a value type"));
+        }
         let valtype = match self.get_keyword()? {
             b"i32" => WatValType::I32,
-            b"f64" => WatValType::I64,
+            b"i64" => WatValType::I64,
             b"f32" => WatValType::F32,
             b"f64" => WatValType::F64,
-            _ => unimplemented!("nyi"),
+            b"v128" => {
+                self.require_feature(Proposal::Simd, "v128 values require")?;
+                WatValType::V128
+            }
+            _ => return Err(self.expected_error("a value type")),
         };
         self.advance()?;
         Ok(valtype)
     }
 
-    fn read_typeuse_after_open_paren(&mut self) -> Result<(WatTypeuse, bool)> {
-        let mut id = None;
-        if self.maybe_exact_keyword(b"type")? {
-            id = self.maybe_id()?;
-            if id.is_none() {
-                return Err(self.create_error("id is expected for typeuse"));
-            }
+    fn read_reftype(&mut self) -> Result<WatRefType> {
+        if self.maybe_open_paren_keyword(KnownKeyword::Ref)? {
+            self.require_feature(Proposal::Gc, "(ref ...) types require")?;
+            let nullable = self.maybe_exact_keyword(KnownKeyword::Null)?;
+            let heaptype = self.read_heaptype()?;
             self.expect_close_paren()?;
-            if !self.maybe_open_paren()? {
-                return Ok((WatTypeuse {
-                               id,
-                               params: vec![],
-                               results: vec![],
-                           },
-                           false));
+            if nullable {
+                return Ok(WatRefType::nullable(heaptype));
             }
+            return Ok(WatRefType::Ref {
+                          nullable,
+                          heaptype,
+                      });
         }
-        let mut params = Vec::new();
-        while self.maybe_exact_keyword(b"param")? {
-            let param_id = self.maybe_id()?;
-            let no_id = param_id.is_none();
-            let valtype = self.read_valtype()?;
-            params.push(WatParam {
-                            id: param_id,
-                            valtype,
-                        });
-            while no_id && self.is_keyword() {
+        let reftype = match self.get_keyword() {
+            Ok(b"funcref") => WatRefType::FuncRef,
+            Ok(b"externref") => WatRefType::ExternRef,
+            Ok(b"anyref") => WatRefType::AnyRef,
+            Ok(b"eqref") => WatRefType::EqRef,
+            Ok(b"i31ref") => WatRefType::I31Ref,
+            Ok(b"structref") => WatRefType::StructRef,
+            Ok(b"arrayref") => WatRefType::ArrayRef,
+            Ok(b"nullref") => WatRefType::NullRef,
+            _ => return Err(self.expected_error("a reference type")),
+        };
+        match reftype {
+            WatRefType::FuncRef => {}
+            WatRefType::ExternRef => {
+                self.require_feature(Proposal::ReferenceTypes, "externref requires")?
+            }
+            _ => {
+                let what = format!("{} requires", self.describe_current_token());
+                self.require_feature(Proposal::Gc, &what)?
+            }
+        }
+        self.advance()?;
+        Ok(reftype)
+    }
+
+    fn read_heaptype(&mut self) -> Result<WatHeapType> {
+        if self.is_index() {
+            self.require_feature(Proposal::Gc, "type references require")?;
+            return Ok(WatHeapType::Index(self.read_index()?));
+        }
+        let heaptype = match self.get_keyword() {
+            Ok(b"func") => WatHeapType::Func,
+            Ok(b"extern") => WatHeapType::Extern,
+            Ok(b"any") => WatHeapType::Any,
+            Ok(b"eq") => WatHeapType::Eq,
+            Ok(b"i31") => WatHeapType::I31,
+            Ok(b"struct") => WatHeapType::Struct,
+            Ok(b"array") => WatHeapType::Array,
+            Ok(b"none") => WatHeapType::None,
+            _ => return Err(self.expected_error("a heap type")),
+        };
+        if let WatHeapType::Func | WatHeapType::Extern = heaptype {
+        } else {
+            let what = format!("heap type {} requires", self.describe_current_token());
+            self.require_feature(Proposal::Gc, &what)?;
+        }
+        self.advance()?;
+        Ok(heaptype)
+    }
+
+    // Whether a reference type keyword is next. `(ref ...)` is not
+    // looked for, as it takes a token of lookahead past the '('.
+    fn is_reftype(&self) -> bool {
+        matches!(self.known_keyword(),
+                 Some(KnownKeyword::Funcref) | Some(KnownKeyword::Externref)) ||
+        self.is_keyword() &&
+        matches!(self.current_token_content(),
+                 b"anyref" | b"eqref" | b"i31ref" | b"structref" | b"arrayref" | b"nullref")
+    }
+
+    // Whether a value type may be next, in a list of them that ends
+    // at ')'.
+    fn is_valtype(&self) -> bool {
+        self.is_keyword() || *self.current_token_type() == WatTokenType::OpenParen
+    }
+
+    fn read_tabletype(&mut self) -> Result<WatTableType> {
+        let limits = self.read_limits(false)?;
+        let reftype = self.read_reftype()?;
+        Ok(WatTableType { limits, reftype })
+    }
+
+    fn read_index(&mut self) -> Result<WatIndex> {
+        match *self.current_token_type() {
+            WatTokenType::Unsigned => Ok(WatIndex::Num(self.read_u32()?)),
+            WatTokenType::ID => {
+                match self.maybe_symbol()? {
+                    Some(symbol) => Ok(WatIndex::Symbol(symbol)),
+                    None => Ok(WatIndex::Id(self.read_id()?)),
+                }
+            }
+            _ => Err(self.expected_error("an index")),
+        }
+    }
+
+    fn is_index(&self) -> bool {
+        matches!(*self.current_token_type(), WatTokenType::Unsigned | WatTokenType::ID)
+    }
+
+    fn read_globaltype(&mut self) -> Result<WatGlobalType> {
+        if self.maybe_open_paren_keyword(KnownKeyword::Mut)? {
+            let valtype = self.read_valtype()?;
+            self.expect_close_paren()?;
+            return Ok(WatGlobalType {
+                          valtype,
+                          mutable: true,
+                      });
+        }
+        let valtype = self.read_valtype()?;
+        Ok(WatGlobalType {
+               valtype,
+               mutable: false,
+           })
+    }
+
+    fn read_typeuse_after_open_paren(&mut self) -> Result<(WatTypeuse, bool)> {
+        let mut id = None;
+        let mut index = None;
+        let mut type_span = None;
+        if self.maybe_exact_keyword(KnownKeyword::Type)? {
+            let start = *self.open_parens.last().unwrap();
+            if let WatTokenType::Unsigned = *self.current_token_type() {
+                index = Some(self.read_u32()?);
+            } else {
+                id = self.maybe_id()?;
+                if id.is_none() {
+                    return Err(self.expected_error("a type index"));
+                }
+            }
+            self.expect_close_paren()?;
+            type_span = Some((start, self.last_end));
+            if !self.maybe_open_paren()? {
+                return Ok((WatTypeuse {
+                               id,
+                               index,
+                               params: vec![],
+                               results: vec![],
+                           },
+                           false));
+            }
+        }
+        let result = self.read_signature_after_open_paren(id, index)?;
+        if let Some((start, end)) = type_span {
+            self.check_typeuse_signature(&result.0, start, end)?;
+        }
+        Ok(result)
+    }
+
+    // With `strict_typeuses`, fails if the inline params and results of
+    // `typeuse` differ from those of its already declared type.
+    fn check_typeuse_signature(&self,
+                               typeuse: &WatTypeuse,
+                               start: WatPosition,
+                               end: WatPosition)
+                               -> Result<()> {
+        if !self.options.strict_typeuses || typeuse.params.is_empty() && typeuse.results.is_empty() {
+            return Ok(());
+        }
+        // Declared types are only kept by id.
+        let id = match typeuse.id {
+            Some(ref id) => id,
+            None => return Ok(()),
+        };
+        match self.type_signatures.get(id) {
+            Some((declared, line)) if !typeuse.same_signature(declared) => {
+                Err(typeuse.mismatch_error(declared, *line, start, end))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Reads the params and results of a typeuse after its `(type $t)`,
+    // if any.
+    fn read_signature_after_open_paren(&mut self,
+                                       id: OptionalID,
+                                       index: Option<u32>)
+                                       -> Result<(WatTypeuse, bool)> {
+        let mut params = Vec::new();
+        self.local_ids.clear();
+        while self.maybe_exact_keyword(KnownKeyword::Param)? {
+            let param_id = self.maybe_local_id()?;
+            let no_id = param_id.is_none();
+            let valtype = self.read_valtype()?;
+            params.push(WatParam {
+                            id: param_id,
+                            valtype,
+                        });
+            while no_id && self.is_valtype() {
                 let valtype = self.read_valtype()?;
                 params.push(WatParam { id: None, valtype });
             }
@@ -541,6 +2834,7 @@ impl<'a> WatParser<'a> {
             if !self.maybe_open_paren()? {
                 return Ok((WatTypeuse {
                                id,
+                               index,
                                params,
                                results: vec![],
                            },
@@ -548,10 +2842,9 @@ impl<'a> WatParser<'a> {
             }
         }
         let mut results = Vec::new();
-        while self.maybe_exact_keyword(b"result")? {
-            let valtype = self.read_valtype()?;
-            results.push(WatResult { valtype });
-            while self.is_keyword() {
+        // Results have no ids, and `(result)` is allowed, if useless.
+        while self.maybe_exact_keyword(KnownKeyword::Result)? {
+            while self.is_valtype() {
                 let valtype = self.read_valtype()?;
                 results.push(WatResult { valtype });
             }
@@ -559,6 +2852,7 @@ impl<'a> WatParser<'a> {
             if !self.maybe_open_paren()? {
                 return Ok((WatTypeuse {
                                id,
+                               index,
                                params,
                                results,
                            },
@@ -567,6 +2861,7 @@ impl<'a> WatParser<'a> {
         }
         Ok((WatTypeuse {
                 id,
+                index,
                 params,
                 results,
             },
@@ -586,12 +2881,12 @@ impl<'a> WatParser<'a> {
 
     fn read_locals_after_open_paren(&mut self) -> Result<(Vec<WatLocal>, bool)> {
         let mut locals = Vec::new();
-        while self.maybe_exact_keyword(b"local")? {
-            let id = self.maybe_id()?;
+        while self.maybe_exact_keyword(KnownKeyword::Local)? {
+            let id = self.maybe_local_id()?;
             let no_id = id.is_none();
             let valtype = self.read_valtype()?;
             locals.push(WatLocal { id, valtype });
-            while no_id && self.is_keyword() {
+            while no_id && self.is_valtype() {
                 let valtype = self.read_valtype()?;
                 locals.push(WatLocal { id: None, valtype });
             }
@@ -604,214 +2899,3118 @@ impl<'a> WatParser<'a> {
     }
 
     fn read_func(&mut self) -> Result<()> {
+        let position = self.field_position();
         self.advance()?;
-        let id = self.maybe_id()?;
-        let (export_name, typeuse, locals) = if self.maybe_open_paren()? {
-            if self.maybe_exact_keyword(b"import")? {
-                let modname = self.read_name()?;
-                let fieldname = self.read_name()?;
-                self.expect_close_paren()?;
-                let typeuse = self.read_typeuse()?;
-                self.expect_close_paren()?;
-                let import = WatImport::Func { id, typeuse };
-                self.state = WatParserState::Import {
-                    modname,
-                    fieldname,
-                    import,
-                };
-                return Ok(());
-            }
-            let export_name = if self.maybe_exact_keyword(b"export")? {
-                let name = Some(self.read_name()?);
-                self.expect_close_paren()?;
-                if !self.maybe_open_paren()? {
-                    self.state = WatParserState::StartFunc {
-                        id,
-                        export_name: name,
-                        typeuse: WatTypeuse::empty(),
-                        locals: vec![],
-                    };
-                    return Ok(());
-                }
-                name
-            } else {
-                None
+        let id = self.maybe_bound_id(IndexSpace::Func)?;
+        let export_names = self.read_inline_exports()?;
+        if let Some((modname, fieldname)) = self.maybe_inline_import()? {
+            let typeuse = self.read_typeuse()?;
+            self.expect_close_paren()?;
+            self.event = WatParserState::Import {
+                modname,
+                fieldname,
+                import: WatImport::Func { id, typeuse },
+                export_names,
+                position,
             };
+            return Ok(());
+        }
+        let (typeuse, locals) = if self.maybe_open_paren()? {
             let (typeuse, keyword_expected) = self.read_typeuse_after_open_paren()?;
             if keyword_expected {
                 let (locals, keyword_expected) = self.read_locals_after_open_paren()?;
                 if keyword_expected {
                     self.rewind_token();
                 }
-                (export_name, typeuse, locals)
+                (typeuse, locals)
             } else {
-                (export_name, typeuse, vec![])
+                (typeuse, vec![])
             }
         } else {
-            (None, WatTypeuse::empty(), vec![])
+            (WatTypeuse::empty(), vec![])
         };
-        self.state = WatParserState::StartFunc {
+        self.event = WatParserState::StartFunc {
             id,
-            export_name,
+            export_names,
             typeuse,
             locals,
+            position,
         };
         self.func_depth = Some(0);
+        self.blocks.clear();
+        self.code_block = CodeBlock::Func;
         Ok(())
     }
 
-    fn read_memarg_flag(&mut self) -> Result<WatInstructionArg> {
-        let keyword = self.read_keyword()?;
-        Ok(WatInstructionArg::Flags(keyword, 0)) // FIXME
-    }
-
-    fn read_arg_id(&mut self) -> Result<WatInstructionArg> {
-        let id = self.read_id()?;
-        Ok(WatInstructionArg::ID(id))
+    fn read_type(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Type)?;
+        self.expect_open_paren()?;
+        self.expect_exact_keyword(KnownKeyword::Func)?;
+        let functype = if self.maybe_open_paren()? {
+            if self.known_keyword() == Some(KnownKeyword::Type) {
+                return Err(self.expected_error("'param' or 'result'"));
+            }
+            let (functype, keyword_expected) = self.read_typeuse_after_open_paren()?;
+            if keyword_expected {
+                return Err(self.expected_error("'param' or 'result'"));
+            }
+            functype
+        } else {
+            WatTypeuse::empty()
+        };
+        self.expect_close_paren()?;
+        self.expect_close_paren()?;
+        if let (true, Some(id)) = (self.options.strict_typeuses, id.as_ref()) {
+            self.type_signatures.entry(id.clone()).or_insert((functype.clone(), position.line));
+        }
+        self.event = WatParserState::Type {
+            id,
+            functype,
+            position,
+        };
+        Ok(())
     }
 
-    fn read_arg_signed(&mut self) -> Result<WatInstructionArg> {
-        let (sign, data) = {
-            let signed = self.current_token_content();
-            assert!(signed[0] == b'-' || signed[0] == b'+');
-            let sign = match signed[0] {
-                b'-' => WatSign::Negative,
-                b'+' => WatSign::Positive,
-                _ => unreachable!(),
+    fn read_global(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Global)?;
+        let export_names = self.read_inline_exports()?;
+        if let Some((modname, fieldname)) = self.maybe_inline_import()? {
+            let globaltype = self.in_context("global type", Self::read_globaltype)?;
+            self.expect_close_paren()?;
+            self.event = WatParserState::Import {
+                modname,
+                fieldname,
+                import: WatImport::Global { id, globaltype },
+                export_names,
+                position,
             };
-            let data = parse_num(&signed[1..]);
-            (sign, data)
-        };
-        if data.is_none() {
-            return Err(self.create_error("Unable to parse signed"));
+            return Ok(());
         }
-        self.advance()?;
-        Ok(WatInstructionArg::Signed(sign, data.unwrap()))
+        let globaltype = self.in_context("global type", Self::read_globaltype)?;
+        self.event = WatParserState::StartGlobal {
+            id,
+            export_names,
+            globaltype,
+            position,
+        };
+        self.func_depth = Some(0);
+        self.blocks.clear();
+        self.code_block = CodeBlock::Global;
+        Ok(())
     }
 
-    fn read_arg_unsigned(&mut self) -> Result<WatInstructionArg> {
-        let data = parse_num(self.current_token_content());
-        if data.is_none() {
-            return Err(self.create_error("Unable to parse unsigned"));
+    // Reads any number of `(export "name")`s.
+    fn read_inline_exports(&mut self) -> Result<Vec<Name>> {
+        let mut names = Vec::new();
+        while self.maybe_open_paren_keyword(KnownKeyword::Export)? {
+            names.push(self.read_name()?);
+            self.expect_close_paren()?;
         }
+        Ok(names)
+    }
+
+    fn read_memory(&mut self) -> Result<()> {
+        let position = self.field_position();
         self.advance()?;
-        Ok(WatInstructionArg::Unsigned(data.unwrap()))
+        let id = self.maybe_bound_id(IndexSpace::Memory)?;
+        let export_names = self.read_inline_exports()?;
+        if let Some((modname, fieldname)) = self.maybe_inline_import()? {
+            let memtype = self.in_context("memory limits", Self::read_memtype)?;
+            self.expect_close_paren()?;
+            self.event = WatParserState::Import {
+                modname,
+                fieldname,
+                import: WatImport::Memory { id, memtype },
+                export_names,
+                position,
+            };
+            return Ok(());
+        }
+        if self.maybe_open_paren_keyword(KnownKeyword::Data)? {
+            return self.read_memory_with_data(id, export_names, position);
+        }
+        let memtype = self.in_context("memory limits", Self::read_memtype)?;
+        self.expect_close_paren()?;
+        self.event = WatParserState::Memory {
+            id,
+            export_names,
+            memtype,
+            position,
+        };
+        Ok(())
     }
 
-    fn read_arg_float(&mut self) -> Result<WatInstructionArg> {
-        let result = parse_float(self.current_token_content());
-        if result.is_none() {
-            return Err(self.create_error("Unable to parse float"));
+    // `(memory $id? (data "..."))` is short for a memory just large
+    // enough for the data, with no room to grow, and an active segment
+    // at offset 0 of it.
+    fn read_memory_with_data(&mut self,
+                             id: OptionalID,
+                             export_names: Vec<Name>,
+                             position: WatPosition)
+                             -> Result<()> {
+        let data_position = self.field_position();
+        let data = self.read_data_strings()?;
+        self.expect_close_paren()?;
+        self.expect_close_paren()?;
+        let pages = (data.len() as u64).div_ceil(0x10000) as u32;
+        let memory = self.own_index(&id, self.memories);
+        let offset = zero_offset();
+        self.event = WatParserState::Memory {
+            id,
+            export_names,
+            memtype: WatMemoryType {
+                limits: WatLimits {
+                    min: pages,
+                    max: Some(pages),
+                },
+                shared: false,
+            },
+            position,
+        };
+        self.pending_event = Some(WatParserState::Data {
+                                      id: None,
+                                      kind: WatDataKind::Active { memory, offset },
+                                      data,
+                                      position: data_position,
+                                  });
+        Ok(())
+    }
+
+    // The index an inline data or elem segment refers to its memory or
+    // table by: the id if there is one, else the next index of that kind.
+    fn own_index(&mut self, id: &OptionalID, next: u32) -> WatIndex {
+        match (id.as_ref(), self.interner.as_mut()) {
+            (Some(id), Some(interner)) => WatIndex::Symbol(interner.intern(id)),
+            (Some(id), None) => WatIndex::Id(id.clone()),
+            (None, _) => WatIndex::Num(next),
         }
-        self.advance()?;
-        let (sign, data, power) = result.unwrap();
-        Ok(WatInstructionArg::Float(WatFloat::Number(sign, data, power)))
     }
 
-    fn read_func_body(&mut self) -> Result<()> {
-        if self.maybe_close_paren()? {
-            if self.func_depth.unwrap() == 0 {
-                self.state = WatParserState::EndFunc;
-                self.func_depth = None;
-                return Ok(());
-            }
-            self.state = WatParserState::CodeOperatorEnd;
-            self.func_depth = Some(self.func_depth.unwrap() - 1);
+    fn read_table(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Table)?;
+        let export_names = self.read_inline_exports()?;
+        if let Some((modname, fieldname)) = self.maybe_inline_import()? {
+            let tabletype = self.in_context("table type", Self::read_tabletype)?;
+            self.expect_close_paren()?;
+            self.event = WatParserState::Import {
+                modname,
+                fieldname,
+                import: WatImport::Table { id, tabletype },
+                export_names,
+                position,
+            };
             return Ok(());
         }
-        let group = if self.maybe_open_paren()? {
-            true
+        if self.is_reftype() {
+            return self.read_table_with_elem(id, export_names, position);
+        }
+        let tabletype = self.in_context("table type", Self::read_tabletype)?;
+        self.expect_close_paren()?;
+        self.event = WatParserState::Table {
+            id,
+            export_names,
+            tabletype,
+            position,
+        };
+        Ok(())
+    }
+
+    // `(table $id? reftype (elem ...))` is short for a table exactly as
+    // large as the list, and an active segment at offset 0 of it.
+    fn read_table_with_elem(&mut self,
+                            id: OptionalID,
+                            export_names: Vec<Name>,
+                            position: WatPosition)
+                            -> Result<()> {
+        let reftype = self.read_reftype()?;
+        let elem_position = self.current_token().start;
+        if !self.maybe_open_paren_keyword(KnownKeyword::Elem)? {
+            return Err(self.expected_error("'(elem'"));
+        }
+        let funcs = matches!(reftype, WatRefType::FuncRef) &&
+                    (self.is_index() || *self.current_token_type() == WatTokenType::CloseParen);
+        let elems = if funcs {
+            let mut funcs = Vec::new();
+            while self.is_index() {
+                funcs.push(self.read_index()?);
+            }
+            WatElemList::Functions(funcs)
         } else {
-            false
+            WatElemList::Expressions(reftype.clone(), self.read_elem_exprs()?)
         };
-        let position = self.current_token().start;
-        let instruction = self.read_keyword()?;
-        let mut args = Vec::new();
-        'main: loop {
+        self.expect_close_paren()?;
+        self.expect_close_paren()?;
+        let len = match elems {
+            WatElemList::Functions(ref funcs) => funcs.len(),
+            WatElemList::Expressions(_, ref exprs) => exprs.len(),
+        };
+        let table = self.own_index(&id, self.tables);
+        self.event = WatParserState::Table {
+            id,
+            export_names,
+            tabletype: WatTableType {
+                limits: WatLimits {
+                    min: len as u32,
+                    max: Some(len as u32),
+                },
+                reftype,
+            },
+            position,
+        };
+        self.pending_event = Some(WatParserState::Elem {
+                                      id: None,
+                                      kind: WatElemKind::Active {
+                                          table: Some(table),
+                                          offset: zero_offset(),
+                                      },
+                                      elems,
+                                      position: elem_position,
+                                  });
+        Ok(())
+    }
+
+    // Reads instructions up to (not including) the enclosing ')'; folded
+    // instructions are flattened with their operands first.
+    fn read_const_expr(&mut self) -> Result<ConstExpr> {
+        let mut expr = Vec::new();
+        loop {
             match *self.current_token_type() {
-                WatTokenType::End => break,
-                WatTokenType::Keyword => {
-                    if self.is_memarg_flag()? {
-                        args.push(self.read_memarg_flag()?);
-                        continue;
-                    }
-                    break 'main;
-                }
-                WatTokenType::OpenParen | WatTokenType::CloseParen => {
-                    break 'main;
-                }
-                WatTokenType::ID => {
-                    args.push(self.read_arg_id()?);
-                }
-                WatTokenType::Signed => {
-                    args.push(self.read_arg_signed()?);
-                }
-                WatTokenType::Unsigned => {
-                    args.push(self.read_arg_unsigned()?);
-                }
-                WatTokenType::Float => {
-                    args.push(self.read_arg_float()?);
+                WatTokenType::CloseParen => return Ok(expr),
+                WatTokenType::OpenParen => {
+                    self.expect_open_paren()?;
+                    self.read_folded_instruction_after_open_paren(&mut expr)?;
                 }
                 _ => {
-                    return Err(self.create_error("unexpected token in the instruction"));
+                    self.check_constant_instruction()?;
+                    let instruction = self.read_instruction_keyword()?;
+                    let args = self.read_instruction_args(&instruction)?;
+                    expr.push(WatInstruction { instruction, args });
                 }
             }
         }
-        if group {
-            self.func_depth = Some(self.func_depth.unwrap() + 1);
+    }
+
+    fn read_folded_instruction_after_open_paren(&mut self, expr: &mut ConstExpr) -> Result<()> {
+        self.check_constant_instruction()?;
+        let instruction = self.read_instruction_keyword()?;
+        let args = self.read_instruction_args(&instruction)?;
+        let mut operands = self.read_const_expr()?;
+        self.expect_close_paren()?;
+        expr.append(&mut operands);
+        expr.push(WatInstruction { instruction, args });
+        Ok(())
+    }
+
+    // Reads `(offset expr)` or the single folded instruction abbreviation.
+    fn read_offset(&mut self) -> Result<ConstExpr> {
+        self.expect_open_paren()?;
+        if self.maybe_exact_keyword(KnownKeyword::Offset)? {
+            let expr = self.read_const_expr()?;
+            self.expect_close_paren()?;
+            return Ok(expr);
         }
-        self.state = WatParserState::CodeOperator {
-            instruction,
-            args,
-            group,
+        let mut expr = Vec::new();
+        self.read_folded_instruction_after_open_paren(&mut expr)?;
+        Ok(expr)
+    }
+
+    fn read_elem_list(&mut self) -> Result<WatElemList> {
+        if self.maybe_exact_keyword(KnownKeyword::Func)? || !self.is_reftype() {
+            let mut funcs = Vec::new();
+            while self.is_index() {
+                funcs.push(self.read_index()?);
+            }
+            return Ok(WatElemList::Functions(funcs));
+        }
+        let reftype = self.read_reftype()?;
+        Ok(WatElemList::Expressions(reftype, self.read_elem_exprs()?))
+    }
+
+    // Reads `(item expr)`s and single folded instruction abbreviations.
+    fn read_elem_exprs(&mut self) -> Result<Vec<ConstExpr>> {
+        let mut exprs = Vec::new();
+        while self.maybe_open_paren()? {
+            if self.maybe_exact_keyword(KnownKeyword::Item)? {
+                exprs.push(self.read_const_expr()?);
+                self.expect_close_paren()?;
+            } else {
+                let mut expr = Vec::new();
+                self.read_folded_instruction_after_open_paren(&mut expr)?;
+                exprs.push(expr);
+            }
+        }
+        Ok(exprs)
+    }
+
+    fn read_elem(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Elem)?;
+        let kind = if self.known_keyword() == Some(KnownKeyword::Declare) {
+            self.require_feature(Proposal::ReferenceTypes, "declarative element segments require")?;
+            self.advance()?;
+            WatElemKind::Declarative
+        } else if self.is_keyword() {
+            self.require_feature(Proposal::BulkMemory, "passive element segments require")?;
+            WatElemKind::Passive
+        } else {
+            let table = if self.maybe_open_paren()? {
+                if self.maybe_exact_keyword(KnownKeyword::Table)? {
+                    let table = self.read_index()?;
+                    self.expect_close_paren()?;
+                    Some(table)
+                } else {
+                    self.rewind_token();
+                    None
+                }
+            } else {
+                None
+            };
+            let offset = self.read_offset()?;
+            WatElemKind::Active { table, offset }
+        };
+        let elems = self.read_elem_list()?;
+        self.expect_close_paren()?;
+        self.event = WatParserState::Elem {
+            id,
+            kind,
+            elems,
             position,
         };
         Ok(())
     }
 
-    fn read_module_field(&mut self) -> Result<()> {
-        if self.maybe_close_paren()? {
-            self.state = WatParserState::EndModule;
-            return Ok(());
+    pub(crate) fn read_data_strings(&mut self) -> Result<Data> {
+        let mut data = Vec::new();
+        while let WatTokenType::String = *self.current_token_type() {
+            match parse_data_string(self.current_token_content()) {
+                Some(mut bytes) => data.append(&mut bytes),
+                None => return Err(self.invalid_literal_error("string")),
+            }
+            self.advance()?;
         }
-        self.expect_open_paren()?;
-        let keyword = match self.get_keyword()? {
-            b"import" => KnownKeyword::Import,
-            b"func" => KnownKeyword::Func,
-            _ => unreachable!("nyi"),
+        Ok(data)
+    }
+
+    fn read_data(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let id = self.maybe_bound_id(IndexSpace::Data)?;
+        let kind = match *self.current_token_type() {
+            WatTokenType::String | WatTokenType::CloseParen => {
+                self.require_feature(Proposal::BulkMemory, "passive data segments require")?;
+                WatDataKind::Passive
+            }
+            _ => {
+                // The memory defaults to 0 when omitted.
+                let memory = self.maybe_memory_index()?.unwrap_or(WatIndex::Num(0));
+                let offset = self.read_offset()?;
+                WatDataKind::Active { memory, offset }
+            }
         };
-        match keyword {
-            KnownKeyword::Import => self.read_import(),
-            KnownKeyword::Func => self.read_func(),
-            _ => panic!(),
-        }
+        let data = self.read_data_strings()?;
+        self.expect_close_paren()?;
+        self.event = WatParserState::Data {
+            id,
+            kind,
+            data,
+            position,
+        };
+        Ok(())
     }
 
-    fn find_end(&mut self) -> Result<()> {
-        if let WatTokenType::End = *self.current_token_type() {
-            self.state = WatParserState::End;
-            return Ok(());
+    // Reads `offset=n`, `align=n` or `flags=n`, keeping the whole
+    // keyword next to the value.
+    fn read_memarg_flag(&mut self) -> Result<WatInstructionArg> {
+        let value = {
+            let keyword = self.get_keyword()?;
+            let digits = &keyword[keyword.iter().position(|&ch| ch == b'=').unwrap() + 1..];
+            match parse_u64(digits) {
+                Ok(value) if value <= u64::from(u32::MAX) => value as u32,
+                Ok(_) | Err(NumberError::Overflow) => return Err(self.out_of_range_error(32)),
+                Err(NumberError::Invalid) => return Err(self.invalid_literal_error("memory argument")),
+            }
+        };
+        let keyword = self.read_keyword()?;
+        Ok(WatInstructionArg::Flags(keyword, value))
+    }
+
+    // Reads a bare memory index or `(memory idx)`.
+    fn maybe_memory_index(&mut self) -> Result<Option<WatIndex>> {
+        if self.is_index() {
+            return Ok(Some(self.read_index()?));
+        }
+        if self.maybe_open_paren_keyword(KnownKeyword::Memory)? {
+            let index = self.read_index()?;
+            self.expect_close_paren()?;
+            return Ok(Some(index));
         }
-        Err(self.create_error("unexpected content after the module"))
+        Ok(None)
     }
 
-    pub fn parse(&mut self) -> &WatParserState {
-        let result = match self.state {
-            WatParserState::End => panic!("WatParser at the end of stream"),
-            WatParserState::Error(_) => panic!("WatParser in error state"),
-            WatParserState::EndModule => self.find_end(),
-            WatParserState::Initial => self.read_start_module(),
-            WatParserState::StartModule { .. } |
-            WatParserState::EndFunc |
-            WatParserState::Import { .. } => self.read_module_field(),
-            WatParserState::StartFunc { .. } |
-            WatParserState::CodeOperator { .. } |
-            WatParserState::CodeOperatorEnd => self.read_func_body(),
-            _ => panic!("nyi"),
-        };
-        if result.is_err() {
-            self.state = WatParserState::Error(result.unwrap_err());
+    // Reads the `(result t)` annotation of a typed select.
+    fn maybe_select_result(&mut self) -> Result<Option<WatValType>> {
+        if !self.maybe_open_paren_keyword(KnownKeyword::Result)? {
+            return Ok(None);
+        }
+        self.require_feature(Proposal::ReferenceTypes, "typed select requires")?;
+        let valtype = self.read_valtype()?;
+        self.expect_close_paren()?;
+        if self.maybe_open_paren_keyword(KnownKeyword::Result)? {
+            let message = format!("select takes a single result type{}", self.context_suffix());
+            return Err(self.create_error(ErrorKind::UnexpectedToken, message));
         }
-        &self.state
+        Ok(Some(valtype))
+    }
+
+    fn read_arg_id(&mut self) -> Result<WatInstructionArg> {
+        if let Some(symbol) = self.maybe_symbol()? {
+            return Ok(WatInstructionArg::Symbol(symbol));
+        }
+        let id = self.read_id()?;
+        Ok(WatInstructionArg::ID(id))
+    }
+
+    fn out_of_range_error(&self, width: u32) -> WatParserError {
+        let message = format!("integer literal out of range for i{}: {}",
+                              width,
+                              self.describe_current_token());
+        self.create_error(ErrorKind::OutOfRange, message)
+    }
+
+    fn read_arg_signed(&mut self, int_width: Option<u32>) -> Result<WatInstructionArg> {
+        let signed = self.current_token_content();
+        let sign = match signed.first() {
+            Some(&b'-') => WatSign::Negative,
+            Some(&b'+') => WatSign::Positive,
+            _ => return Err(self.invalid_literal_error("signed integer")),
+        };
+        let num = parse_u64(&signed[1..]);
+        let num = match (num, int_width) {
+            (Ok(num), Some(width)) => {
+                // Signed literals cover -2^(N-1) ..= 2^(N-1)-1.
+                let bound = 1u64 << (width - 1);
+                let in_range = match sign {
+                    WatSign::Negative => num <= bound,
+                    WatSign::Positive => num < bound,
+                };
+                if !in_range {
+                    return Err(self.out_of_range_error(width));
+                }
+                num
+            }
+            (Ok(num), None) => num,
+            (Err(NumberError::Overflow), Some(width)) => {
+                return Err(self.out_of_range_error(width));
+            }
+            (Err(_), _) => return Err(self.invalid_literal_error("signed integer")),
+        };
+        let raw = Vec::from(self.current_token_content());
+        self.advance()?;
+        Ok(WatInstructionArg::Signed {
+               sign,
+               value: convert_u64_to_data(num),
+               raw,
+           })
+    }
+
+    fn read_arg_unsigned(&mut self, int_width: Option<u32>) -> Result<WatInstructionArg> {
+        let num = match (parse_u64(self.current_token_content()), int_width) {
+            (Ok(num), Some(width)) if width < 64 && num >> width != 0 => {
+                return Err(self.out_of_range_error(width));
+            }
+            (Ok(num), _) => num,
+            (Err(NumberError::Overflow), Some(width)) => {
+                return Err(self.out_of_range_error(width));
+            }
+            (Err(_), _) => return Err(self.invalid_literal_error("unsigned integer")),
+        };
+        let raw = Vec::from(self.current_token_content());
+        self.advance()?;
+        Ok(WatInstructionArg::Unsigned {
+               value: convert_u64_to_data(num),
+               raw,
+           })
+    }
+
+    fn read_arg_float(&mut self) -> Result<WatInstructionArg> {
+        let float = match parse_float(self.current_token_content()) {
+            Some(float) => float,
+            None => return Err(self.invalid_literal_error("float")),
+        };
+        self.advance()?;
+        Ok(WatInstructionArg::Float(float))
+    }
+
+    pub(crate) fn read_instruction_keyword(&mut self) -> Result<Keyword> {
+        if self.is_keyword() {
+            let name = self.current_token_content();
+            if self.options.reject_unknown_instructions && WatOpcode::lookup(name).is_none() {
+                let message = format!("unknown instruction {}", self.describe_current_token());
+                return Err(self.create_error(ErrorKind::UnexpectedToken, message));
+            }
+            if let Some(proposal) = instruction_proposal(name) {
+                let what = format!("instruction {} requires", self.describe_current_token());
+                self.require_feature(proposal, &what)?;
+            }
+            return self.read_keyword();
+        }
+        Err(self.expected_error("an instruction"))
+    }
+
+    // With `strict_const_exprs`, fails unless the current token is an
+    // instruction allowed in a constant expression.
+    fn check_constant_instruction(&self) -> Result<()> {
+        if !self.options.strict_const_exprs || !self.is_keyword() {
+            return Ok(());
+        }
+        let name = self.current_token_content();
+        if WatOpcode::lookup(name).is_some_and(|opcode| opcode.is_constant()) {
+            return Ok(());
+        }
+        let message = format!("non-constant instruction {} in initializer", self.describe_current_token());
+        Err(self.create_error(ErrorKind::Validation, message))
+    }
+
+    // `memory.init` and `table.init` take an optional memory or table
+    // index before the required segment index.
+    fn read_segment_init(&mut self,
+                         args: &mut Vec<WatInstructionArg>,
+                         kind: SegmentKind)
+                         -> Result<()> {
+        let first = self.read_index()?;
+        if !self.is_index() {
+            args.push(kind.arg(first));
+            return Ok(());
+        }
+        let segment = self.read_index()?;
+        args.push(match kind {
+                      SegmentKind::Data => WatInstructionArg::Memory(first),
+                      SegmentKind::Elem => WatInstructionArg::Table(first),
+                  });
+        args.push(kind.arg(segment));
+        Ok(())
+    }
+
+    // The shape of a `v128.const` and its lanes, such as `i32x4 1 2 3 4`.
+    fn read_v128_const(&mut self, args: &mut Vec<WatInstructionArg>) -> Result<()> {
+        let (lanes, int_width) = match self.current_token_content() {
+            b"i8x16" => (16, Some(8)),
+            b"i16x8" => (8, Some(16)),
+            b"i32x4" => (4, Some(32)),
+            b"i64x2" => (2, Some(64)),
+            b"f32x4" => (4, None),
+            b"f64x2" => (2, None),
+            _ => return Err(self.expected_error("a v128 shape such as i32x4")),
+        };
+        let shape = self.read_keyword()?;
+        args.push(WatInstructionArg::Shape(shape.clone()));
+        for _ in 0..lanes {
+            let lane = match (*self.current_token_type(), int_width) {
+                (WatTokenType::Signed, Some(_)) => self.read_arg_signed(int_width)?,
+                (WatTokenType::Unsigned, Some(_)) => self.read_arg_unsigned(int_width)?,
+                (WatTokenType::Signed, None) |
+                (WatTokenType::Unsigned, None) |
+                (WatTokenType::Float, None) => self.read_arg_float()?,
+                _ => {
+                    let expected = format!("{} lanes for {}", lanes, String::from_utf8_lossy(&shape));
+                    return Err(self.expected_error(&expected));
+                }
+            };
+            args.push(lane);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_instruction_args(&mut self, instruction: &[u8]) -> Result<Vec<WatInstructionArg>> {
+        // Lane indices are single bytes.
+        let int_width = if takes_lanes(instruction) { Some(8) } else { int_const_width(instruction) };
+        let mut args = Vec::new();
+        for _ in 0..memory_index_count(instruction) {
+            // `v128.load8_lane 1` loads lane 1 from memory 0: a number only
+            // names the memory when the lane or a memory argument follows.
+            if takes_lanes(instruction) && *self.current_token_type() == WatTokenType::Unsigned &&
+               !self.memarg_or_number_follows() {
+                break;
+            }
+            match self.maybe_memory_index()? {
+                Some(memory) => args.push(WatInstructionArg::Memory(memory)),
+                None => break,
+            }
+        }
+        match instruction {
+            b"memory.init" => self.read_segment_init(&mut args, SegmentKind::Data)?,
+            b"table.init" => self.read_segment_init(&mut args, SegmentKind::Elem)?,
+            b"data.drop" => args.push(WatInstructionArg::Data(self.read_index()?)),
+            b"elem.drop" => args.push(WatInstructionArg::Elem(self.read_index()?)),
+            _ => {}
+        }
+        for _ in 0..table_index_count(instruction) {
+            if !self.is_index() {
+                break;
+            }
+            args.push(WatInstructionArg::Table(self.read_index()?));
+        }
+        if takes_typeuse(instruction) {
+            let typeuse = self.read_typeuse()?;
+            if !typeuse.is_empty() {
+                args.push(WatInstructionArg::Type(typeuse));
+            }
+        }
+        if takes_blocktype(instruction) {
+            if let WatTokenType::ID = *self.current_token_type() {
+                args.push(self.read_arg_id()?);
+            }
+            let typeuse = self.read_typeuse()?;
+            if !typeuse.is_empty() {
+                args.push(WatInstructionArg::Type(typeuse));
+            }
+        }
+        match instruction {
+            b"ref.null" => {
+                let heaptype = self.read_heaptype()?;
+                args.push(WatInstructionArg::HeapType(WatRefType::nullable(heaptype)))
+            }
+            b"ref.func" => args.push(WatInstructionArg::Function(self.read_index()?)),
+            _ => {}
+        }
+        if instruction == b"v128.const" {
+            self.read_v128_const(&mut args)?;
+        }
+        if instruction == b"select" {
+            if let Some(valtype) = self.maybe_select_result()? {
+                args.push(WatInstructionArg::Result(valtype));
+            }
+        }
+        loop {
+            match *self.current_token_type() {
+                WatTokenType::Keyword => {
+                    if self.is_memarg_flag()? {
+                        args.push(self.read_memarg_flag()?);
+                        continue;
+                    }
+                    break;
+                }
+                WatTokenType::End | WatTokenType::OpenParen | WatTokenType::CloseParen => break,
+                WatTokenType::ID => {
+                    args.push(self.read_arg_id()?);
+                }
+                // Integer literals are floats too, and keep their value
+                // when larger than 64 bits.
+                WatTokenType::Signed | WatTokenType::Unsigned if is_float_const(instruction) => {
+                    args.push(self.read_arg_float()?);
+                }
+                WatTokenType::Signed => {
+                    args.push(self.read_arg_signed(int_width)?);
+                }
+                WatTokenType::Unsigned => {
+                    args.push(self.read_arg_unsigned(int_width)?);
+                }
+                WatTokenType::Float => {
+                    args.push(self.read_arg_float()?);
+                }
+                _ => {
+                    let message = format!("unexpected {}{}",
+                                          self.describe_current_token(),
+                                          self.context_suffix());
+                    return Err(self.create_error(ErrorKind::UnexpectedToken, message));
+                }
+            }
+        }
+        Ok(args)
+    }
+
+    // Fails if a block opened inside the body or folded group just closed
+    // was left without its `end`.
+    fn check_blocks_closed(&self) -> Result<()> {
+        let groups = self.func_depth.unwrap();
+        let block = match self.blocks.last() {
+            Some(block) if block.groups == groups => block,
+            _ => return Ok(()),
+        };
+        let what = match (groups, &self.code_block) {
+            (0, _) if self.options.func_body => "input",
+            (0, &CodeBlock::Func) => "function",
+            (0, &CodeBlock::Global) => "global",
+            _ => "folded instruction",
+        };
+        let message = format!("unclosed block at end of {}", what);
+        Err(WatParserError {
+                start: block.start,
+                end: block.end,
+                ..self.create_error(ErrorKind::Validation, message)
+            })
+    }
+
+    // Matches an unfolded `else` or `end`, at `start`, with the innermost
+    // open block, which has to be in the same folded group.
+    fn match_block(&mut self, opcode: &WatOpcode, start: WatPosition) -> Result<()> {
+        let groups = self.func_depth.unwrap();
+        let block = match self.blocks.last_mut() {
+            Some(block) if block.groups == groups => Some(block),
+            _ => None,
+        };
+        let message = match (opcode, block) {
+            (&WatOpcode::Else, Some(block)) => {
+                if let BlockKind::If = block.kind {
+                    block.kind = BlockKind::Else;
+                    return Ok(());
+                }
+                "else without if"
+            }
+            (&WatOpcode::Else, None) => "else without if",
+            (_, Some(_)) => return Ok(()),
+            (_, None) => "end without block",
+        };
+        Err(WatParserError {
+                start,
+                end: self.last_end,
+                ..self.create_error(ErrorKind::Validation, String::from(message))
+            })
+    }
+
+    // A label after an unfolded `else` or `end` has to be that of the
+    // innermost open block, which `match_block` has checked is there.
+    fn check_block_label(&self, args: &[WatInstructionArg], label: &WatToken) -> Result<()> {
+        let found = match label_arg(args) {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+        if self.blocks.last().and_then(|block| block.label.as_ref()) == Some(found) {
+            return Ok(());
+        }
+        let message = format!("mismatched block label '{}'",
+                              String::from_utf8_lossy(label.content(self.lexer.source())));
+        Err(WatParserError {
+                start: label.start,
+                end: label.end,
+                ..self.create_error(ErrorKind::Validation, message)
+            })
+    }
+
+    // With `func_body`, the input is one body, open from the start.
+    fn read_func_body_start(&mut self) -> Result<()> {
+        self.lexer.validate_utf8()?;
+        self.advance()?;
+        self.event_start = self.current_token().start;
+        self.func_depth = Some(0);
+        self.code_block = CodeBlock::Func;
+        self.read_func_body()
+    }
+
+    fn read_func_body(&mut self) -> Result<()> {
+        if self.options.func_body && self.func_depth == Some(0) {
+            match *self.current_token_type() {
+                WatTokenType::End => {
+                    self.check_blocks_closed()?;
+                    self.event = WatParserState::End;
+                    return Ok(());
+                }
+                WatTokenType::CloseParen => {
+                    let message = format!("unexpected {}{}",
+                                          self.describe_current_token(),
+                                          self.context_suffix());
+                    return Err(self.create_error(ErrorKind::UnexpectedToken, message));
+                }
+                _ => {}
+            }
+        }
+        let position = self.current_token().start;
+        if self.maybe_close_paren()? {
+            self.check_blocks_closed()?;
+            if self.func_depth.unwrap() == 0 {
+                self.event = match self.code_block {
+                    CodeBlock::Func => WatParserState::EndFunc { position },
+                    CodeBlock::Global => WatParserState::EndGlobal { position },
+                };
+                self.func_depth = None;
+                return Ok(());
+            }
+            let depth = self.func_depth.unwrap() - 1;
+            self.func_depth = Some(depth);
+            self.event = WatParserState::CodeOperatorEnd { depth: depth + self.blocks.len() as u32 };
+            return Ok(());
+        }
+        if let WatTokenType::End = *self.current_token_type() {
+            return Err(self.unclosed_paren_error(self.expected_error("')'")));
+        }
+        let group = self.maybe_open_paren()?;
+        let position = self.current_token().start;
+        if let CodeBlock::Global = self.code_block {
+            self.check_constant_instruction()?;
+        }
+        let instruction = self.read_instruction_keyword()?;
+        let keyword_end = self.last_end;
+        let opcode = WatOpcode::from_bytes(&instruction);
+        let groups = self.func_depth.unwrap();
+        let mut depth = groups + self.blocks.len() as u32;
+        let closes_block = matches!((group, &opcode),
+                                    (false, WatOpcode::Else) | (false, WatOpcode::End));
+        if closes_block {
+            self.match_block(&opcode, position)?;
+            depth -= 1;
+        }
+        let label = self.current_token().clone();
+        let args = self.read_instruction_args(&instruction)?;
+        if closes_block {
+            self.check_block_label(&args, &label)?;
+        }
+        if group {
+            self.func_depth = Some(groups + 1);
+        } else {
+            let kind = match opcode {
+                WatOpcode::Block => Some(BlockKind::Block),
+                WatOpcode::Loop => Some(BlockKind::Loop),
+                WatOpcode::If => Some(BlockKind::If),
+                WatOpcode::End => {
+                    self.blocks.pop();
+                    None
+                }
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.blocks.push(OpenBlock {
+                                     kind,
+                                     label: label_arg(&args).cloned(),
+                                     groups,
+                                     start: position,
+                                     end: keyword_end,
+                                 });
+            }
+        }
+        self.event = WatParserState::CodeOperator {
+            instruction,
+            opcode,
+            args,
+            group,
+            depth,
+            position,
+        };
+        Ok(())
+    }
+
+    fn is_field_keyword(&self) -> bool {
+        matches!(self.known_keyword(),
+                 Some(KnownKeyword::Type) | Some(KnownKeyword::Import) | Some(KnownKeyword::Export) |
+                 Some(KnownKeyword::Func) | Some(KnownKeyword::Global) | Some(KnownKeyword::Table) |
+                 Some(KnownKeyword::Memory) | Some(KnownKeyword::Elem) | Some(KnownKeyword::Data) |
+                 Some(KnownKeyword::Start))
+    }
+
+    fn read_module_field(&mut self) -> Result<()> {
+        loop {
+            if self.implicit_module {
+                match *self.current_token_type() {
+                    WatTokenType::End => {
+                        self.open_parens.pop();
+                        self.event = WatParserState::EndModule;
+                        return Ok(());
+                    }
+                    WatTokenType::CloseParen => return Err(self.expected_error("a module field")),
+                    _ => {}
+                }
+            }
+            if self.maybe_close_paren()? {
+                self.event = WatParserState::EndModule;
+                return Ok(());
+            }
+            if let WatTokenType::End = *self.current_token_type() {
+                return Err(self.unclosed_paren_error(self.expected_error("')'")));
+            }
+            self.expect_open_paren()?;
+            if !self.is_annotation() {
+                break;
+            }
+            if self.current_token_content() == b"@custom" && !self.options.skip_annotations {
+                return self.in_context("custom section", Self::read_custom);
+            }
+            self.skip_annotation()?;
+            // Past the end of partial input, no event is returned yet.
+            if self.partial && *self.current_token_type() == WatTokenType::End {
+                return Ok(());
+            }
+            self.event_start = self.current_token().start;
+        }
+        match self.known_keyword() {
+            Some(KnownKeyword::Import) => self.in_context("import", Self::read_import),
+            Some(KnownKeyword::Func) => self.in_context("function", Self::read_func),
+            Some(KnownKeyword::Global) => self.in_context("global", Self::read_global),
+            Some(KnownKeyword::Table) => self.in_context("table", Self::read_table),
+            Some(KnownKeyword::Memory) => self.in_context("memory", Self::read_memory),
+            Some(KnownKeyword::Elem) => self.in_context("element segment", Self::read_elem),
+            Some(KnownKeyword::Data) => self.in_context("data segment", Self::read_data),
+            Some(KnownKeyword::Type) => self.in_context("type", Self::read_type),
+            Some(KnownKeyword::Export) => self.in_context("export", Self::read_export),
+            Some(KnownKeyword::Start) => self.in_context("start", Self::read_start),
+            _ => Err(self.expected_error("a module field")),
+        }
+    }
+
+    // Whether the token after a '(' starts an annotation.
+    fn is_annotation(&self) -> bool {
+        *self.current_token_type() == WatTokenType::Reserved &&
+        self.current_token_content().starts_with(b"@")
+    }
+
+    // Reads a `(@custom "name" "data"*)` annotation, whose '(' has been
+    // read.
+    fn read_custom(&mut self) -> Result<()> {
+        let position = self.field_position();
+        self.advance()?;
+        let name = self.read_name()?;
+        let data = self.read_data_strings()?;
+        self.expect_close_paren()?;
+        self.event = WatParserState::CustomSection {
+            name,
+            data,
+            position,
+        };
+        Ok(())
+    }
+
+    // Skips an annotation other than `@custom`, whose '(' has been read,
+    // to past its ')'.
+    fn skip_annotation(&mut self) -> Result<()> {
+        let depth = self.open_parens.len();
+        self.advance()?;
+        while self.open_parens.len() >= depth {
+            if let WatTokenType::End = *self.current_token_type() {
+                return Err(self.unclosed_paren_error(self.expected_error("')'")));
+            }
+            if !self.maybe_open_paren()? && !self.maybe_close_paren()? {
+                self.advance()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_end(&mut self) -> Result<()> {
+        if let WatTokenType::End = *self.current_token_type() {
+            self.event = WatParserState::End;
+            return Ok(());
+        }
+        let message = format!("unexpected {} after the module", self.describe_current_token());
+        Err(self.create_error(ErrorKind::UnexpectedToken, message))
+    }
+
+    /// Line of the lexer's scan position, 1-based.
+    pub fn current_line(&self) -> usize {
+        self.lexer.current_position().line
+    }
+
+    /// Column of the lexer's scan position, 0-based like `WatPosition`.
+    pub fn current_column(&self) -> usize {
+        self.lexer.current_position().column
+    }
+
+    /// Enables or disables error recovery. When enabled, a parse() call
+    /// following an `Error` inside a module skips the rest of the broken
+    /// field and continues with the next module field.
+    pub fn set_error_recovery(&mut self, enabled: bool) {
+        self.options.error_recovery = enabled;
+    }
+
+    /// All errors reported so far.
+    pub fn errors(&self) -> &[WatParserError] {
+        &self.errors
+    }
+
+    fn can_recover(&self) -> bool {
+        if !self.options.error_recovery || !self.in_module {
+            return false;
+        }
+        match self.errors.last() {
+            Some(err) if err.kind == ErrorKind::Lexical => true,
+            _ => *self.current_token_type() != WatTokenType::End,
+        }
+    }
+
+    // Skips what is left of the field an error was found in.
+    fn recover(&mut self) -> Result<()> {
+        let lexical = match self.errors.last() {
+            Some(err) => err.kind == ErrorKind::Lexical,
+            None => false,
+        };
+        self.func_depth = None;
+        if lexical {
+            // The current token is stale: resume lexing past the bad char.
+            self.lexer.skip_char();
+            self.advance()?;
+        } else if self.open_parens.len() == 1 {
+            // A stray token directly inside the module. Without
+            // `(module`, a ')' here has nothing to close either.
+            match *self.current_token_type() {
+                WatTokenType::OpenParen => {}
+                WatTokenType::CloseParen if !self.implicit_module => {}
+                _ => self.advance()?,
+            }
+        }
+        while self.open_parens.len() > 1 {
+            match *self.current_token_type() {
+                WatTokenType::End => break,
+                WatTokenType::OpenParen => {
+                    let start = self.current_token().start;
+                    self.open_parens.push(start);
+                }
+                WatTokenType::CloseParen => {
+                    self.open_parens.pop();
+                }
+                _ => {}
+            }
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Advances the parser by one event and returns it.
+    ///
+    /// Once `End` or `Error` is reached the state is final: further calls
+    /// keep returning `End` or a copy of the same error without consuming
+    /// any more input. With error recovery enabled, an error inside a
+    /// module is not final; see `set_error_recovery`.
+    pub fn parse(&mut self) -> WatParserState {
+        if let Some(event) = self.pending_event.take() {
+            self.tag = ParserTag::of(&event);
+            self.count_event(&event);
+            return event;
+        }
+        if let ParserTag::Error = self.tag {
+            if self.can_recover() {
+                // Once the rest of the broken field is skipped, the next
+                // one is read as usual.
+                if let Err(err) = self.recover() {
+                    self.errors.push(err.clone());
+                    return WatParserState::Error(err);
+                }
+                self.tag = ParserTag::ModuleField;
+            }
+        }
+        if let ParserTag::ModuleField | ParserTag::EndModule = self.tag {
+            if self.partial && *self.current_token_type() == WatTokenType::End {
+                return WatParserState::Initial;
+            }
+        }
+        if let ParserTag::EndModule | ParserTag::ModuleField | ParserTag::Code = self.tag {
+            self.event_start = self.current_token().start;
+        }
+        let result = match self.tag {
+            ParserTag::End => return WatParserState::End,
+            ParserTag::Error => return WatParserState::Error(self.errors.last().unwrap().clone()),
+            ParserTag::EndModule => self.find_end(),
+            ParserTag::Initial if self.options.func_body => {
+                self.in_context("instruction", Self::read_func_body_start)
+            }
+            ParserTag::Initial => self.in_context("module", Self::read_start_module),
+            ParserTag::ModuleField => self.read_module_field(),
+            ParserTag::Code => self.in_context("instruction", Self::read_func_body),
+        };
+        let event = match result {
+            Ok(()) => mem::replace(&mut self.event, WatParserState::Initial),
+            Err(err) => {
+                self.errors.push(err.clone());
+                WatParserState::Error(err)
+            }
+        };
+        // Annotations were skipped up to the end of partial input.
+        if let WatParserState::Initial = event {
+            return event;
+        }
+        self.tag = ParserTag::of(&event);
+        self.count_event(&event);
+        event
+    }
+
+    fn count_event(&mut self, event: &WatParserState) {
+        match *event {
+            WatParserState::StartFunc { .. } => self.stats.funcs += 1,
+            WatParserState::Import { .. } => self.stats.imports += 1,
+            WatParserState::CodeOperator { .. } => self.stats.instructions += 1,
+            WatParserState::End => self.stats.end = Some(self.current_token().start),
+            _ => {}
+        }
+        match *event {
+            WatParserState::Memory { .. } |
+            WatParserState::Import { import: WatImport::Memory { .. }, .. } => self.memories += 1,
+            WatParserState::Table { .. } |
+            WatParserState::Import { import: WatImport::Table { .. }, .. } => self.tables += 1,
+            _ => {}
+        }
+        match *event {
+            WatParserState::StartFunc { position, .. } |
+            WatParserState::Table { position, .. } |
+            WatParserState::Memory { position, .. } |
+            WatParserState::StartGlobal { position, .. } => {
+                self.first_definition.get_or_insert(position);
+            }
+            _ => {}
+        }
+        match *event {
+            WatParserState::StartModule { .. } => {
+                self.fields_parsed = 0;
+                self.memories = 0;
+                self.tables = 0;
+                self.first_definition = None;
+                self.in_module = true;
+            }
+            WatParserState::EndModule => self.in_module = false,
+            WatParserState::EndFunc { .. } |
+            WatParserState::EndGlobal { .. } |
+            WatParserState::Table { .. } |
+            WatParserState::Memory { .. } |
+            WatParserState::Elem { .. } |
+            WatParserState::Data { .. } |
+            WatParserState::Type { .. } |
+            WatParserState::Import { .. } |
+            WatParserState::Export { .. } |
+            WatParserState::Start { .. } => self.fields_parsed += 1,
+            _ => {}
+        }
+    }
+
+    /// Number of module fields completed so far in the current module.
+    pub fn fields_parsed(&self) -> usize {
+        self.fields_parsed
+    }
+
+    /// Functions, imports and instructions seen so far, and once `End`
+    /// is reached, the position where the input ended.
+    pub fn stats(&self) -> WatParseStats {
+        self.stats
+    }
+
+    /// The table for `Symbol`s in events, if `WatParserOptions::intern_ids`
+    /// is on. It covers every event returned so far.
+    pub fn interner(&self) -> Option<&WatInterner> {
+        self.interner.as_ref()
+    }
+
+    /// Start of the text read for the event last returned by `parse()`.
+    /// This is the event's `position`, except for a folded
+    /// `CodeOperator`, where it is the '(' before the instruction name.
+    pub fn event_start(&self) -> WatPosition {
+        self.event_start
+    }
+
+    /// End of the text read for the event last returned by `parse()`.
+    /// A folded `CodeOperator` ends at its matching `CodeOperatorEnd`,
+    /// where this returns the end of the ')'; take `event_start()` from
+    /// the first and `event_end()` from the second.
+    pub fn event_end(&self) -> WatPosition {
+        self.last_end
+    }
+
+    /// The unmodified source between two positions, such as
+    /// `event_start()` and `event_end()`.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    ///
+    /// let mut parser = WatParser::new(b"(module (func (f32.neg (f32.const 0x1p-1)) drop))");
+    /// let mut open = vec![];
+    /// let mut texts = vec![];
+    /// loop {
+    ///     match parser.parse() {
+    ///         WatParserState::CodeOperator { group: true, .. } => open.push(parser.event_start()),
+    ///         WatParserState::CodeOperator { .. } => {
+    ///             texts.push(parser.source_text(parser.event_start(), parser.event_end()))
+    ///         }
+    ///         WatParserState::CodeOperatorEnd { .. } => {
+    ///             let start = open.pop().unwrap();
+    ///             texts.push(parser.source_text(start, parser.event_end()))
+    ///         }
+    ///         WatParserState::End => break,
+    ///         WatParserState::Error(err) => panic!("{}", err),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// assert_eq!(texts, [&b"(f32.const 0x1p-1)"[..], b"(f32.neg (f32.const 0x1p-1))", b"drop"]);
+    /// ```
+    pub fn source_text(&self, start: WatPosition, end: WatPosition) -> &'a [u8] {
+        &self.lexer.source()[start.position..end.position]
+    }
+
+    /// Parses the rest of the input, calling `visitor` for each event.
+    /// Stops at the end of input or at the first error, which is
+    /// returned.
+    pub fn run<V: WatVisitor>(&mut self, visitor: &mut V) -> Result<()> {
+        loop {
+            match self.parse() {
+                WatParserState::Initial => {}
+                WatParserState::End => return Ok(()),
+                WatParserState::Error(err) => return Err(err),
+                WatParserState::StartModule { ref id, position } => visitor.start_module(id, position),
+                WatParserState::EndModule => visitor.end_module(),
+                WatParserState::Type {
+                    ref id,
+                    ref functype,
+                    position,
+                } => visitor.func_type(id, functype, position),
+                WatParserState::Import {
+                    ref modname,
+                    ref fieldname,
+                    ref import,
+                    ref export_names,
+                    position,
+                } => visitor.import(modname, fieldname, import, export_names, position),
+                WatParserState::StartFunc {
+                    ref id,
+                    ref export_names,
+                    ref typeuse,
+                    ref locals,
+                    position,
+                } => visitor.start_func(id, export_names, typeuse, locals, position),
+                WatParserState::EndFunc { position } => visitor.end_func(position),
+                WatParserState::StartGlobal {
+                    ref id,
+                    ref export_names,
+                    ref globaltype,
+                    position,
+                } => visitor.start_global(id, export_names, globaltype, position),
+                WatParserState::EndGlobal { position } => visitor.end_global(position),
+                WatParserState::Table {
+                    ref id,
+                    ref export_names,
+                    ref tabletype,
+                    position,
+                } => visitor.table(id, export_names, tabletype, position),
+                WatParserState::Memory {
+                    ref id,
+                    ref export_names,
+                    ref memtype,
+                    position,
+                } => visitor.memory(id, export_names, memtype, position),
+                WatParserState::Export {
+                    ref name,
+                    kind,
+                    ref index,
+                    position,
+                } => visitor.export(name, kind, index, position),
+                WatParserState::Start { ref func, position } => visitor.start(func, position),
+                WatParserState::Elem {
+                    ref id,
+                    ref kind,
+                    ref elems,
+                    position,
+                } => visitor.elem(id, kind, elems, position),
+                WatParserState::Data {
+                    ref id,
+                    ref kind,
+                    ref data,
+                    position,
+                } => visitor.data(id, kind, data, position),
+                WatParserState::CustomSection {
+                    ref name,
+                    ref data,
+                    position,
+                } => visitor.custom_section(name, data, position),
+                WatParserState::CodeOperator {
+                    ref instruction,
+                    ref args,
+                    group,
+                    position,
+                    ..
+                } => visitor.instruction(instruction, args, group, position),
+                WatParserState::CodeOperatorEnd { .. } => visitor.end_instruction(),
+            }
+        }
+    }
+
+    /// Returns an iterator over the remaining events.
+    ///
+    /// Each item is an owned event. The iterator ends after
+    /// `End`, which is not yielded, or after yielding the first error;
+    /// error recovery is not applied.
+    ///
+    /// ```
+    /// use wasmtextparser::{WatParser, WatParserState};
+    ///
+    /// let mut parser = WatParser::new(b"(module (memory 1) (func nop))");
+    /// let events = parser.events().collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(events.len(), 6);
+    /// match events[1] {
+    ///     WatParserState::Memory { .. } => {}
+    ///     ref event => panic!("unexpected {:?}", event),
+    /// }
+    /// ```
+    pub fn events<'b>(&'b mut self) -> WatEvents<'a, 'b> {
+        WatEvents {
+            parser: self,
+            done: false,
+        }
+    }
+
+    /// Appends up to `max` events to `buf`, stopping early at the end of
+    /// the input. Returns how many were appended and whether the end was
+    /// reached; `End` itself is not appended. An error is returned as
+    /// `Err`, after the events that preceded it.
+    ///
+    /// ```
+    /// use wasmtextparser::WatParser;
+    ///
+    /// let mut parser = WatParser::new(b"(module (memory 1) (func))");
+    /// let mut events = Vec::new();
+    /// assert_eq!(parser.parse_into(&mut events, 3).unwrap(), (3, false));
+    /// assert_eq!(parser.parse_into(&mut events, 3).unwrap(), (2, true));
+    /// assert_eq!(events.len(), 5);
+    /// ```
+    pub fn parse_into(&mut self, buf: &mut Vec<WatParserState>, max: usize) -> Result<(usize, bool)> {
+        for count in 0..max {
+            match self.parse() {
+                WatParserState::End => return Ok((count, true)),
+                WatParserState::Error(err) => return Err(err),
+                event => buf.push(event),
+            }
+        }
+        Ok((max, false))
+    }
+}
+
+/// Iterator over parser events, see `WatParser::events`.
+pub struct WatEvents<'a, 'b> {
+    parser: &'b mut WatParser<'a>,
+    done: bool,
+}
+
+impl<'a, 'b> Iterator for WatEvents<'a, 'b> {
+    type Item = Result<WatParserState>;
+
+    fn next(&mut self) -> Option<Result<WatParserState>> {
+        if self.done {
+            return None;
+        }
+        match self.parser.parse() {
+            WatParserState::End => {
+                self.done = true;
+                None
+            }
+            WatParserState::Error(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+            event => Some(Ok(event)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use encode::encode_module;
+    use super::*;
+
+    // Parses to `End` or the first `Error`, which is returned last.
+    fn events(source: &[u8]) -> Vec<WatParserState> {
+        let mut parser = WatParser::new(source);
+        let mut events = Vec::new();
+        loop {
+            let event = parser.parse();
+            let last = matches!(event, WatParserState::End | WatParserState::Error(_));
+            events.push(event);
+            if last {
+                return events;
+            }
+        }
+    }
+
+    #[test]
+    fn parse_keeps_returning_end() {
+        assert_eq!(events(b"(module (func))").last(), Some(&WatParserState::End));
+        let mut parser = WatParser::new(b"(module (func))");
+        while parser.parse() != WatParserState::End {}
+        for _ in 0..3 {
+            assert_eq!(parser.parse(), WatParserState::End);
+        }
+        assert_eq!(parser.fields_parsed(), 1);
+    }
+
+    #[test]
+    fn parse_keeps_returning_the_error() {
+        let mut parser = WatParser::new(b"(module (func) (bogus) (func))");
+        let err = loop {
+            if let WatParserState::Error(err) = parser.parse() {
+                break err;
+            }
+        };
+        assert_eq!(&*err.message, "expected a module field, found 'bogus'");
+        for _ in 0..3 {
+            assert_eq!(parser.parse(), WatParserState::Error(err.clone()));
+        }
+        assert_eq!(parser.errors(), [err]);
+    }
+
+    #[test]
+    fn global_keeps_mutability_and_streams_its_initializer() {
+        for &(source, mutable) in &[(&b"(module (global (mut i32) (i32.const 0)))"[..], true),
+                                    (b"(module (global i32 (i32.const 0)))", false)] {
+            let events = events(source);
+            assert_eq!(events.len(), 7, "{:?}", events);
+            match events[1] {
+                WatParserState::StartGlobal { ref globaltype, .. } => {
+                    assert_eq!(*globaltype,
+                               WatGlobalType {
+                                   valtype: WatValType::I32,
+                                   mutable,
+                               });
+                }
+                ref event => panic!("unexpected {:?}", event),
+            }
+            match events[2] {
+                WatParserState::CodeOperator { ref instruction, ref opcode, ref args, .. } => {
+                    assert_eq!(&instruction[..], b"i32.const");
+                    assert_eq!(*opcode, WatOpcode::I32Const);
+                    assert_eq!(*args,
+                               [WatInstructionArg::Unsigned {
+                                    value: vec![0],
+                                    raw: b"0".to_vec(),
+                                }]);
+                }
+                ref event => panic!("unexpected {:?}", event),
+            }
+            assert_eq!(events[3], WatParserState::CodeOperatorEnd { depth: 0 });
+            assert!(matches!(events[4], WatParserState::EndGlobal { .. }), "{:?}", events[4]);
+        }
+    }
+
+    #[test]
+    fn comments_around_module_field_keywords() {
+        let source = b"(module ((;c;)func (;c;) $f) ( ;; c\n memory (;c;) 1) \
+                       ((;c;)global(;c;)$g (;c;)i32 (i32.const 0)))";
+        let events = events(source);
+        match events[1] {
+            WatParserState::StartFunc { ref id, .. } => assert_eq!(*id, Some(b"$f".to_vec())),
+            ref event => panic!("unexpected {:?}", event),
+        }
+        assert!(matches!(events[2], WatParserState::EndFunc { .. }), "{:?}", events[2]);
+        match events[3] {
+            WatParserState::Memory { ref id, ref memtype, position, .. } => {
+                assert_eq!((id, memtype.limits.min), (&None, 1));
+                assert_eq!((position.line, position.column), (1, 29));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match events[4] {
+            WatParserState::StartGlobal { ref id, ref globaltype, .. } => {
+                assert_eq!((id, &globaltype.valtype), (&Some(b"$g".to_vec()), &WatValType::I32));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(events.last(), Some(&WatParserState::End));
+    }
+
+    #[test]
+    fn integer_literal_range() {
+        for source in &["i32.const 4294967295",
+                        "i32.const -2147483648",
+                        "i32.const 0xFFFF_FFFF",
+                        "i64.const 18446744073709551615",
+                        "i64.const -9223372036854775808"] {
+            match WatParser::func_body(source.as_bytes()).parse() {
+                WatParserState::CodeOperator { .. } => {}
+                event => panic!("{}: unexpected {:?}", source, event),
+            }
+        }
+        for &(source, message) in
+            &[("i32.const 4294967296", "integer literal out of range for i32: '4294967296'"),
+              ("i32.const -2147483649", "integer literal out of range for i32: '-2147483649'"),
+              ("i64.const 18446744073709551616",
+               "integer literal out of range for i64: '18446744073709551616'"),
+              ("i64.const -9223372036854775809",
+               "integer literal out of range for i64: '-9223372036854775809'")] {
+            match WatParser::func_body(source.as_bytes()).parse() {
+                WatParserState::Error(err) => {
+                    assert_eq!((err.kind, &*err.message), (ErrorKind::OutOfRange, message));
+                    assert_eq!((err.start.column, err.end.column), (10, source.len()));
+                }
+                event => panic!("{}: unexpected {:?}", source, event),
+            }
+        }
+    }
+
+    #[test]
+    fn fields_parsed_counts_completed_fields() {
+        let source = b"(module (type (func)) (import \"m\" \"f\" (func)) (memory 1) \
+                       (func nop) (global i32 (i32.const 0)) (data (i32.const 0) \"x\"))";
+        let mut parser = WatParser::new(source);
+        let mut counts = Vec::new();
+        loop {
+            match parser.parse() {
+                WatParserState::End => break,
+                WatParserState::Error(err) => panic!("{}", err),
+                _ => counts.push(parser.fields_parsed()),
+            }
+        }
+        assert_eq!(counts, [0, 1, 2, 3, 3, 3, 4, 4, 4, 4, 5, 6, 6]);
+        assert_eq!(parser.fields_parsed(), 6);
+
+        parser.reset(b"(module (func))");
+        assert_eq!(parser.fields_parsed(), 0);
+        while parser.parse() != WatParserState::End {}
+        assert_eq!(parser.fields_parsed(), 1);
+    }
+
+    #[test]
+    fn error_recovery_reports_each_broken_field() {
+        let source = b"(module\n\
+                       (memory 1)\n\
+                       (fnuc $a)\n\
+                       (func $b (param i33))\n\
+                       (global i32 (i32.const 0))\n\
+                       (memory x)\n\
+                       (func $c nop))";
+        let mut parser = WatParserOptions::new().error_recovery(true).build(source);
+        let mut fields = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match parser.parse() {
+                WatParserState::End => break,
+                WatParserState::Error(err) => errors.push(err),
+                WatParserState::Memory { position, .. } |
+                WatParserState::StartFunc { position, .. } |
+                WatParserState::StartGlobal { position, .. } => fields.push(position.line),
+                _ => {}
+            }
+        }
+        let errors: Vec<_> = errors.iter()
+            .map(|err| (&*err.message, err.start.line, err.start.column))
+            .collect();
+        assert_eq!(errors,
+                   [("expected a module field, found 'fnuc'", 3, 1),
+                    ("expected a value type while parsing function, found 'i33'", 4, 16),
+                    ("expected an unsigned integer while parsing memory limits, found 'x'",
+                     6,
+                     8)]);
+        assert_eq!(parser.errors().len(), 3);
+        assert_eq!(fields, [2, 5, 7]);
+    }
+
+    #[test]
+    fn table_and_elem_reftypes() {
+        let fields = events(b"(module (table 1 funcref) (table $e 2 3 externref) (func $f) \
+                               (elem func $f 0) (elem externref (ref.null extern)))");
+        let tabletypes: Vec<_> = fields.iter()
+            .filter_map(|event| match *event {
+                WatParserState::Table { ref tabletype, .. } => Some(tabletype.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tabletypes,
+                   [WatTableType {
+                        limits: WatLimits { min: 1, max: None },
+                        reftype: WatRefType::FuncRef,
+                    },
+                    WatTableType {
+                        limits: WatLimits { min: 2, max: Some(3) },
+                        reftype: WatRefType::ExternRef,
+                    }]);
+        let elems: Vec<_> = fields.iter()
+            .filter_map(|event| match *event {
+                WatParserState::Elem { ref elems, .. } => Some(elems.clone()),
+                _ => None,
+            })
+            .collect();
+        let null = WatInstruction {
+            instruction: b"ref.null".to_vec(),
+            args: vec![WatInstructionArg::HeapType(WatRefType::ExternRef)],
+        };
+        assert_eq!(elems,
+                   [WatElemList::Functions(vec![WatIndex::Id(b"$f".to_vec()), WatIndex::Num(0)]),
+                    WatElemList::Expressions(WatRefType::ExternRef, vec![vec![null]])]);
+
+        match *events(b"(module (table 1 i32))").last().unwrap() {
+            WatParserState::Error(ref err) => {
+                assert_eq!(&*err.message,
+                           "expected a reference type while parsing table type, found 'i32'");
+                assert_eq!((err.start.column, err.end.column), (17, 20));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn current_line_advances() {
+        let mut parser = WatParser::new(b"(module\n  (memory 1)\n  (func\n    nop)\n)");
+        assert_eq!((parser.current_line(), parser.current_column()), (1, 0));
+        let mut lines = Vec::new();
+        loop {
+            match parser.parse() {
+                WatParserState::End => break,
+                WatParserState::Error(err) => panic!("{}", err),
+                _ => lines.push(parser.current_line()),
+            }
+        }
+        // Each event leaves the lexer on the token after it.
+        assert_eq!(lines, [2, 3, 4, 4, 5, 5]);
+        assert_eq!(parser.current_column(), 1);
+    }
+
+    // The args of each instruction named `name`.
+    fn args_of(events: &[WatParserState], name: &[u8]) -> Vec<Vec<WatInstructionArg>> {
+        events.iter()
+            .filter_map(|event| match *event {
+                WatParserState::CodeOperator { ref instruction, ref args, .. }
+                    if &instruction[..] == name => Some(args.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn multiple_memories_and_memory_immediates() {
+        let fields = events(b"(module (memory $a 1) (memory $m 2 3) (func \
+                              (drop (i32.load (memory $m) offset=0 (i32.const 0))) \
+                              (drop (i32.load 1 offset=4 align=2 (i32.const 0))) \
+                              (drop (i32.load (i32.const 0)))))");
+        let memories: Vec<_> = fields.iter()
+            .filter_map(|event| match *event {
+                WatParserState::Memory { ref id, ref memtype, .. } => {
+                    Some((id.clone(), memtype.limits.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(memories,
+                   [(Some(b"$a".to_vec()), WatLimits { min: 1, max: None }),
+                    (Some(b"$m".to_vec()), WatLimits { min: 2, max: Some(3) })]);
+        assert_eq!(args_of(&fields, b"i32.load"),
+                   [vec![WatInstructionArg::Memory(WatIndex::Id(b"$m".to_vec())),
+                         WatInstructionArg::Flags(b"offset=0".to_vec(), 0)],
+                    vec![WatInstructionArg::Memory(WatIndex::Num(1)),
+                         WatInstructionArg::Flags(b"offset=4".to_vec(), 4),
+                         WatInstructionArg::Flags(b"align=2".to_vec(), 2)],
+                    vec![]]);
+    }
+
+    #[test]
+    fn memory_size_and_grow_immediates() {
+        let fields = events(b"(module (memory $a 1) (memory $m 1) (func \
+                              (drop (memory.size)) (drop (memory.size 1)) \
+                              (drop (memory.grow $m (i32.const 1)))))");
+        assert_eq!(args_of(&fields, b"memory.size"),
+                   [vec![], vec![WatInstructionArg::Memory(WatIndex::Num(1))]]);
+        assert_eq!(args_of(&fields, b"memory.grow"),
+                   [vec![WatInstructionArg::Memory(WatIndex::Id(b"$m".to_vec()))]]);
+        assert_eq!(*fields.last().unwrap(), WatParserState::End);
+    }
+
+    #[test]
+    fn select_with_and_without_result() {
+        let fields = events(b"(module (func select select (result i32) (select (result f64) \
+                              (f64.const 0) (f64.const 1) (i32.const 0))))");
+        assert_eq!(args_of(&fields, b"select"),
+                   [vec![],
+                    vec![WatInstructionArg::Result(WatValType::I32)],
+                    vec![WatInstructionArg::Result(WatValType::F64)]]);
+
+        // Typed select has exactly one result type.
+        for &(source, message, column) in
+            &[(&b"select (result)"[..],
+               "expected a value type while parsing instruction, found ')'",
+               14),
+              (b"select (result i32 i64)",
+               "expected ')' while parsing instruction, found 'i64'",
+               7)] {
+            match WatParser::func_body(source).parse() {
+                WatParserState::Error(err) => {
+                    assert_eq!((&*err.message, err.start.column), (message, column));
+                }
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn data_kinds() {
+        let fields = events(b"(module (memory 1) (data (i32.const 0) \"x\") (data $d \"x\"))");
+        let kinds: Vec<_> = fields.iter()
+            .filter_map(|event| match *event {
+                WatParserState::Data { ref kind, ref data, .. } => {
+                    assert_eq!(data, b"x");
+                    Some(kind.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        let offset = WatInstruction {
+            instruction: b"i32.const".to_vec(),
+            args: vec![WatInstructionArg::Unsigned {
+                           value: vec![0],
+                           raw: b"0".to_vec(),
+                       }],
+        };
+        assert_eq!(kinds,
+                   [WatDataKind::Active {
+                        memory: WatIndex::Num(0),
+                        offset: vec![offset],
+                    },
+                    WatDataKind::Passive]);
+    }
+
+    #[test]
+    fn shared_memory_needs_a_maximum() {
+        match events(b"(module (memory (shared 1 2)))")[1] {
+            WatParserState::Memory { ref memtype, .. } => {
+                assert_eq!(*memtype,
+                           WatMemoryType {
+                               limits: WatLimits { min: 1, max: Some(2) },
+                               shared: true,
+                           });
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match *events(b"(module (memory (shared 1)))").last().unwrap() {
+            WatParserState::Error(ref err) => {
+                assert_eq!((err.kind, &*err.message),
+                           (ErrorKind::Validation, "shared memory requires a maximum"));
+                assert_eq!(err.start.column, 17);
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        // `shared` only comes first inside its own parens.
+        assert!(matches!(events(b"(module (memory 1 2 shared))").last(),
+                         Some(&WatParserState::Error(_))));
+    }
+
+    fn import_of(source: &[u8]) -> WatImport {
+        match events(source)[1] {
+            WatParserState::Import { ref import, .. } => import.clone(),
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    fn float_of(source: &[u8]) -> WatFloat {
+        match WatParser::func_body(source).parse() {
+            WatParserState::CodeOperator { ref args, .. } => match args[0] {
+                WatInstructionArg::Float(ref float) => float.clone(),
+                ref arg => panic!("unexpected {:?}", arg),
+            },
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for &(descriptor, normalized) in
+            &[("(func $f (param $x i32) (param i64) (param f32) (result i32 i64))",
+               "(func $f (param $x i32) (param i64 f32) (result i32 i64))"),
+              ("(func (type $t))", "(func (type $t))"),
+              ("(func)", "(func)"),
+              ("(memory $m 1 2)", "(memory $m 1 2)"),
+              ("(memory (shared 1 2))", "(memory (shared 1 2))"),
+              ("(table 0x10 funcref)", "(table 16 funcref)"),
+              ("(global (mut f64))", "(global (mut f64))")] {
+            let source = format!("(module (import \"m\" \"f\" {}))", descriptor);
+            let import = import_of(source.as_bytes());
+            assert_eq!(import.to_string(), normalized);
+            let again = format!("(module (import \"m\" \"f\" {}))", import);
+            assert_eq!(import_of(again.as_bytes()), import, "{}", again);
+        }
+
+        for &(literal, normalized) in &[("1.5", "+15e-1"),
+                                        ("-0x10", "-16e0"),
+                                        ("inf", "+inf"),
+                                        ("-nan:0x200000", "-nan:0x200000")] {
+            let float = float_of(format!("f32.const {}", literal).as_bytes());
+            assert_eq!(float.to_string(), normalized);
+            let again = float_of(format!("f32.const {}", float).as_bytes());
+            assert_eq!(again.to_string(), normalized);
+        }
+    }
+
+    #[test]
+    fn integer_literals_keep_their_spelling() {
+        let fields = events(b"(module (func i32.const 0x10 i32.const 16 i32.const 1_6 \
+                              i64.const -0x10 i64.const -16 drop drop drop drop drop))");
+        let literals: Vec<_> = args_of(&fields, b"i32.const")
+            .into_iter()
+            .chain(args_of(&fields, b"i64.const"))
+            .map(|args| match args[0] {
+                WatInstructionArg::Unsigned { ref value, ref raw } => {
+                    (None, value.clone(), raw.clone())
+                }
+                WatInstructionArg::Signed { sign, ref value, ref raw } => {
+                    (Some(sign), value.clone(), raw.clone())
+                }
+                ref arg => panic!("unexpected {:?}", arg),
+            })
+            .collect();
+        assert_eq!(literals,
+                   [(None, vec![16], b"0x10".to_vec()),
+                    (None, vec![16], b"16".to_vec()),
+                    (None, vec![16], b"1_6".to_vec()),
+                    (Some(WatSign::Negative), vec![16], b"-0x10".to_vec()),
+                    (Some(WatSign::Negative), vec![16], b"-16".to_vec())]);
+    }
+
+    #[test]
+    fn underscores_in_numbers() {
+        let fields = events(b"(module (memory 1_0) (func i32.const 1_000_000 i32.const 1000000 \
+                              i32.const 0xFF_FF i32.const 65535 drop drop drop drop))");
+        match fields[1] {
+            WatParserState::Memory { ref memtype, .. } => assert_eq!(memtype.limits.min, 10),
+            ref event => panic!("unexpected {:?}", event),
+        }
+        let values: Vec<_> = args_of(&fields, b"i32.const")
+            .into_iter()
+            .map(|args| match args[0] {
+                WatInstructionArg::Unsigned { ref value, .. } => value.clone(),
+                ref arg => panic!("unexpected {:?}", arg),
+            })
+            .collect();
+        assert_eq!(values[0], values[1]);
+        assert_eq!(values[2], values[3]);
+
+        for &(source, message) in
+            &[("(module (func i32.const 1__000 drop))",
+               "unexpected '1__000' while parsing instruction"),
+              ("(module (func i32.const 0xFF__FF drop))",
+               "unexpected '0xFF__FF' while parsing instruction"),
+              ("(module (memory 1__0))",
+               "expected an unsigned integer while parsing memory limits, found '1__0'")] {
+            match *events(source.as_bytes()).last().unwrap() {
+                WatParserState::Error(ref err) => assert_eq!(&*err.message, message),
+                ref event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn table_index_immediates() {
+        let fields = events(b"(module (type $v (func)) (table $t 1 funcref) (table 1 funcref) \
+                              (func (drop (table.get 1 (i32.const 0))) \
+                              (drop (table.get $t (i32.const 0))) \
+                              (drop (table.size)) (drop (table.size 1)) \
+                              (call_indirect (type $v) (i32.const 0)) \
+                              (call_indirect $t (type $v) (i32.const 0))))");
+        let table_t = WatInstructionArg::Table(WatIndex::Id(b"$t".to_vec()));
+        assert_eq!(args_of(&fields, b"table.get"),
+                   [vec![WatInstructionArg::Table(WatIndex::Num(1))], vec![table_t.clone()]]);
+        assert_eq!(args_of(&fields, b"table.size"),
+                   [vec![], vec![WatInstructionArg::Table(WatIndex::Num(1))]]);
+        let type_v = WatInstructionArg::Type(WatTypeuse {
+                                                 id: Some(b"$v".to_vec()),
+                                                 ..WatTypeuse::default()
+                                             });
+        assert_eq!(args_of(&fields, b"call_indirect"),
+                   [vec![type_v.clone()], vec![table_t, type_v]]);
+    }
+
+    #[test]
+    fn defaults_are_the_empty_values() {
+        assert_eq!(WatTypeuse::default(), WatTypeuse::empty());
+        assert!(WatTypeuse::default().is_empty());
+        assert_eq!(WatLimits::default(), WatLimits { min: 0, max: None });
+        assert_eq!(WatParserState::default(), WatParserState::Initial);
+    }
+
+    #[test]
+    fn reference_instruction_operands() {
+        let fields = events(b"(module (func $f (drop (ref.null func)) (drop (ref.null extern)) \
+                              (drop (ref.func $f)) ref.func 0 drop))");
+        assert_eq!(args_of(&fields, b"ref.null"),
+                   [vec![WatInstructionArg::HeapType(WatRefType::FuncRef)],
+                    vec![WatInstructionArg::HeapType(WatRefType::ExternRef)]]);
+        assert_eq!(args_of(&fields, b"ref.func"),
+                   [vec![WatInstructionArg::Function(WatIndex::Id(b"$f".to_vec()))],
+                    vec![WatInstructionArg::Function(WatIndex::Num(0))]]);
+    }
+
+    #[test]
+    fn unknown_instructions() {
+        let source = b"(module (func (result i32) i32.const 1 i32.const 2 i32.addd))";
+        let strict = WatParserOptions::new().reject_unknown_instructions(true);
+        let err = strict.clone().build(source).events().find_map(|event| event.err()).unwrap();
+        assert_eq!(&*err.message, "unknown instruction 'i32.addd'");
+        assert_eq!((err.start.column, err.end.column), (51, 59));
+
+        let valid = b"(module (func (result i32) i32.const 1 i32.const 2 i32.add))";
+        assert!(strict.build(valid).events().all(|event| event.is_ok()));
+        // Permissive by default.
+        assert_eq!(events(source).last(), Some(&WatParserState::End));
+    }
+
+    #[test]
+    fn field_positions() {
+        let source = b"(module $m\n\
+                       \x20 (import \"env\" \"foo\" (func))\n\
+                       \x20 (memory 1)\n\
+                       \x20 (table 1 funcref)\n\
+                       \x20 (global i32 (i32.const 0))\n\
+                       \x20 (func $f\n\
+                       \x20   nop\n\
+                       \x20 )\n\
+                       )";
+        let positions: Vec<_> = events(source)
+            .iter()
+            .filter_map(|event| {
+                let (name, position) = match *event {
+                    WatParserState::StartModule { position, .. } => ("StartModule", position),
+                    WatParserState::Import { position, .. } => ("Import", position),
+                    WatParserState::Memory { position, .. } => ("Memory", position),
+                    WatParserState::Table { position, .. } => ("Table", position),
+                    WatParserState::StartGlobal { position, .. } => ("StartGlobal", position),
+                    WatParserState::EndGlobal { position } => ("EndGlobal", position),
+                    WatParserState::StartFunc { position, .. } => ("StartFunc", position),
+                    WatParserState::CodeOperator { position, .. } => ("CodeOperator", position),
+                    WatParserState::EndFunc { position } => ("EndFunc", position),
+                    _ => return None,
+                };
+                Some((name, position.line, position.column))
+            })
+            .collect();
+        assert_eq!(positions,
+                   [("StartModule", 1, 0),
+                    ("Import", 2, 2),
+                    ("Memory", 3, 2),
+                    ("Table", 4, 2),
+                    ("StartGlobal", 5, 2),
+                    ("CodeOperator", 5, 15),
+                    ("EndGlobal", 5, 27),
+                    ("StartFunc", 6, 2),
+                    ("CodeOperator", 7, 4),
+                    ("EndFunc", 8, 2)]);
+    }
+
+    #[test]
+    fn segment_index_immediates() {
+        let fields = events(b"(module (memory $m 1) (memory 1) (table $t 1 funcref) (elem $e func) \
+                              (data $d \"\") (func \
+                              memory.init 0 memory.init $m $d data.drop $d \
+                              table.init $t $e table.init $e elem.drop 0 \
+                              memory.copy memory.copy $m 1 memory.fill memory.fill 1))");
+        let id = |id: &[u8]| WatIndex::Id(id.to_vec());
+        assert_eq!(args_of(&fields, b"memory.init"),
+                   [vec![WatInstructionArg::Data(WatIndex::Num(0))],
+                    vec![WatInstructionArg::Memory(id(b"$m")),
+                         WatInstructionArg::Data(id(b"$d"))]]);
+        assert_eq!(args_of(&fields, b"data.drop"), [vec![WatInstructionArg::Data(id(b"$d"))]]);
+        assert_eq!(args_of(&fields, b"table.init"),
+                   [vec![WatInstructionArg::Table(id(b"$t")), WatInstructionArg::Elem(id(b"$e"))],
+                    vec![WatInstructionArg::Elem(id(b"$e"))]]);
+        assert_eq!(args_of(&fields, b"elem.drop"),
+                   [vec![WatInstructionArg::Elem(WatIndex::Num(0))]]);
+        assert_eq!(args_of(&fields, b"memory.copy"),
+                   [vec![],
+                    vec![WatInstructionArg::Memory(id(b"$m")),
+                         WatInstructionArg::Memory(WatIndex::Num(1))]]);
+        assert_eq!(args_of(&fields, b"memory.fill"),
+                   [vec![], vec![WatInstructionArg::Memory(WatIndex::Num(1))]]);
+    }
+
+    #[test]
+    fn memory_with_inline_data() {
+        let limits = |event: &WatParserState| match *event {
+            WatParserState::Memory { ref memtype, .. } => memtype.limits.clone(),
+            ref event => panic!("unexpected {:?}", event),
+        };
+        let fields = events(b"(module (memory $m (data \"hello\")) (memory (data)))");
+        assert_eq!(limits(&fields[1]), WatLimits { min: 1, max: Some(1) });
+        match fields[2] {
+            WatParserState::Data { ref kind, ref data, .. } => {
+                assert_eq!(data, b"hello");
+                match *kind {
+                    WatDataKind::Active { ref memory, ref offset } => {
+                        assert_eq!(*memory, WatIndex::Id(b"$m".to_vec()));
+                        assert_eq!(&offset[0].instruction[..], b"i32.const");
+                    }
+                    ref kind => panic!("unexpected {:?}", kind),
+                }
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        // Pages are rounded up, and no data needs none.
+        assert_eq!(limits(&fields[3]), WatLimits { min: 0, max: Some(0) });
+        let big = format!("(module (memory (data \"{}\")))", "a".repeat(65537));
+        assert_eq!(limits(&events(big.as_bytes())[1]), WatLimits { min: 2, max: Some(2) });
+    }
+
+    #[test]
+    fn import_descriptors() {
+        match events(b"(module (import \"foo\" \"bar\" (memory 1)))")[1] {
+            WatParserState::Import { ref modname, ref fieldname, ref import, .. } => {
+                assert_eq!((&modname[..], &fieldname[..]), ("foo", "bar"));
+                assert_eq!(*import,
+                           WatImport::Memory {
+                               id: None,
+                               memtype: WatMemoryType {
+                                   limits: WatLimits { min: 1, max: None },
+                                   shared: false,
+                               },
+                           });
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        for &(field, message, column) in
+            &[("(import \"foo\" \"bar\" (bogus 1))",
+               "expected an import descriptor while parsing import, found 'bogus'",
+               29),
+              ("(import foo \"bar\" (memory 1))",
+               "expected a string literal while parsing import, found 'foo'",
+               16),
+              ("(import \"foo\" \"bar\" memory)",
+               "expected '(' while parsing import, found 'memory'",
+               28),
+              ("(import \"foo\" \"bar\")", "expected '(' while parsing import, found ')'", 27)] {
+            let source = format!("(module {})", field);
+            match *events(source.as_bytes()).last().unwrap() {
+                WatParserState::Error(ref err) => {
+                    assert_eq!((&*err.message, err.start.column), (message, column));
+                }
+                ref event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn module_less_imports_come_first() {
+        let fields = events(b"(import \"m\" \"f\" (func))\n(memory 1)\n(func)");
+        assert!(matches!(fields[0], WatParserState::StartModule { .. }));
+        assert!(matches!(fields[1], WatParserState::Import { .. }));
+        assert_eq!(*fields.last().unwrap(), WatParserState::End);
+
+        for &(source, column) in &[(&b"(func)\n(import \"m\" \"f\" (func))"[..], 1),
+                                   (&b"(memory 1)\n(func (import \"m\" \"f\"))"[..], 7)] {
+            match *events(source).last().unwrap() {
+                WatParserState::Error(ref err) => {
+                    assert_eq!(err.kind, ErrorKind::Validation);
+                    assert_eq!(&*err.message, "import follows definition at line 1");
+                    assert_eq!((err.start.line, err.start.column), (2, column));
+                }
+                ref event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn imports_first_in_a_module() {
+        let imports_first = |source: &[u8]| {
+            let mut parser = WatParser::builder().imports_first(true).build(source);
+            parser.events().find_map(|event| event.err()).map(|err| {
+                (String::from(err.message), err.start.line, err.start.column)
+            })
+        };
+        // Types, annotations and exports may come before an import.
+        assert_eq!(imports_first(b"(module (type (func)) (@custom \"c\" \"\") \
+                                  (export \"f\" (func 0)) (import \"m\" \"f\" (func)) \
+                                  (table (import \"m\" \"t\") 1 funcref) (func))"),
+                   None);
+        // Each kind of definition, and each form of import after one.
+        for &(source, column) in
+            &[(&b"(module (func)\n(import \"m\" \"f\" (func)))"[..], 1),
+              (b"(module (table 1 funcref)\n(memory (import \"m\" \"m\") 1))", 9),
+              (b"(module (memory 1)\n(global (import \"m\" \"g\") i32))", 9),
+              (b"(module (global i32 (i32.const 0))\n(table (import \"m\" \"t\") 1 funcref))",
+               8)] {
+            assert_eq!(first_error(WatParser::new(source)), None);
+            assert_eq!(imports_first(source),
+                       Some(("import follows definition at line 1".into(), 2, column)));
+        }
+    }
+
+    #[test]
+    fn func_inline_exports() {
+        for &(source, params) in
+            &[(&b"(module (func $f (export \"a\") (export \"b\") (param i32)))"[..],
+               &[WatValType::I32][..]),
+              (&b"(module (func (export \"a\") (export \"b\")))"[..], &[][..])] {
+            match events(source)[1] {
+                WatParserState::StartFunc { ref export_names, ref typeuse, .. } => {
+                    assert_eq!(*export_names, ["a", "b"]);
+                    let valtypes: Vec<_> = typeuse.params.iter().map(|p| &p.valtype).collect();
+                    assert_eq!(valtypes, params.iter().collect::<Vec<_>>());
+                }
+                ref event => panic!("unexpected {:?}", event),
+            }
+        }
+        match events(b"(module (func))")[1] {
+            WatParserState::StartFunc { ref export_names, .. } => assert!(export_names.is_empty()),
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn export_and_start_fields() {
+        let fields = events(b"(module (func $f) (export \"f\" (func $f)) (export \"m\" (memory 0)) \
+                              (start $f))");
+        match fields[3] {
+            WatParserState::Export { ref name, kind, ref index, position } => {
+                assert_eq!((&name[..], kind), ("f", WatExportKind::Func));
+                assert_eq!(*index, WatIndex::Id(b"$f".to_vec()));
+                assert_eq!(position.column, 18);
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[4] {
+            WatParserState::Export { ref name, kind, ref index, .. } => {
+                assert_eq!((&name[..], kind), ("m", WatExportKind::Memory));
+                assert_eq!(*index, WatIndex::Num(0));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[5] {
+            WatParserState::Start { ref func, .. } => {
+                assert_eq!(*func, WatIndex::Id(b"$f".to_vec()))
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+
+        for &(field, message) in
+            &[("(export \"f\" (local 0))",
+               "expected an export descriptor while parsing export, found 'local'"),
+              ("(export \"f\" (func))", "expected an index while parsing export, found ')'"),
+              ("(start)", "expected an index while parsing start, found ')'")] {
+            let source = format!("(module {})", field);
+            match *events(source.as_bytes()).last().unwrap() {
+                WatParserState::Error(ref err) => assert_eq!(&*err.message, message),
+                ref event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn inline_imports_and_exports() {
+        let fields = events(b"(module \
+                              (memory $m (export \"a\") (export \"b\") (import \"x\" \"y\") 1) \
+                              (table (export \"t\") 1 funcref) \
+                              (global (import \"x\" \"g\") i32))");
+        match fields[1] {
+            WatParserState::Import {
+                ref modname,
+                ref fieldname,
+                ref import,
+                ref export_names,
+                ..
+            } => {
+                assert_eq!((&modname[..], &fieldname[..]), ("x", "y"));
+                assert_eq!(*export_names, ["a", "b"]);
+                assert!(matches!(*import,
+                                 WatImport::Memory { id: Some(ref id), .. } if id == b"$m"));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[2] {
+            WatParserState::Table { ref export_names, .. } => assert_eq!(*export_names, ["t"]),
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[3] {
+            WatParserState::Import { ref import, ref export_names, .. } => {
+                assert!(export_names.is_empty());
+                assert!(matches!(*import, WatImport::Global { id: None, .. }));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+
+        // The import comes after the exports, as in the spec's abbreviation.
+        match *events(b"(module (memory (import \"x\" \"y\") (export \"m\") 1))").last().unwrap() {
+            WatParserState::Error(ref err) => assert_eq!(err.start.column, 34),
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn numeric_typeuse() {
+        match events(b"(module (type (func)) (func (type 0) (param i32)))")[2] {
+            WatParserState::StartFunc { ref typeuse, .. } => {
+                assert_eq!((&typeuse.id, typeuse.index, typeuse.params.len()), (&None, Some(0), 1));
+                assert_eq!(typeuse.to_string(), "(type 0) (param i32)");
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match *events(b"(module (func (type)))").last().unwrap() {
+            WatParserState::Error(ref err) => {
+                assert_eq!(&*err.message,
+                           "expected a type index while parsing function, found ')'");
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    // None of these matches has a wildcard arm, so a new variant of a
+    // #[non_exhaustive] enum does not compile until it is listed here, and
+    // for `WatParserState`, in the `compile_fail` example on it, which
+    // must fail only for the missing wildcard. `WatOpcode` and
+    // `KnownKeyword` are matched in full by their own tables.
+    #[test]
+    fn non_exhaustive_enums_are_listed() {
+        use resolve::IndexSpace;
+        use wast::{WastAction, WastModule, WastParserState, WastValue};
+
+        fn is_done(event: &WatParserState) -> bool {
+            match *event {
+                WatParserState::Initial |
+                WatParserState::StartModule { .. } |
+                WatParserState::EndModule |
+                WatParserState::Type { .. } |
+                WatParserState::Import { .. } |
+                WatParserState::StartFunc { .. } |
+                WatParserState::EndFunc { .. } |
+                WatParserState::StartGlobal { .. } |
+                WatParserState::EndGlobal { .. } |
+                WatParserState::Table { .. } |
+                WatParserState::Memory { .. } |
+                WatParserState::Export { .. } |
+                WatParserState::Start { .. } |
+                WatParserState::Elem { .. } |
+                WatParserState::Data { .. } |
+                WatParserState::CustomSection { .. } |
+                WatParserState::CodeOperator { .. } |
+                WatParserState::CodeOperatorEnd { .. } => false,
+                WatParserState::End | WatParserState::Error(_) => true,
+            }
+        }
+        fn is_module(event: &WastParserState) -> bool {
+            match *event {
+                WastParserState::Initial |
+                WastParserState::End |
+                WastParserState::Error(_) |
+                WastParserState::Register { .. } |
+                WastParserState::Action { .. } |
+                WastParserState::AssertReturn { .. } |
+                WastParserState::AssertTrap { .. } |
+                WastParserState::AssertExhaustion { .. } |
+                WastParserState::AssertUninstantiable { .. } |
+                WastParserState::AssertInvalid { .. } |
+                WastParserState::AssertMalformed { .. } |
+                WastParserState::AssertUnlinkable { .. } => false,
+                WastParserState::Module(_) |
+                WastParserState::QuotedModule { .. } |
+                WastParserState::BinaryModule { .. } => true,
+            }
+        }
+        fn is_reference(valtype: &WatValType) -> bool {
+            match *valtype {
+                WatValType::I32 | WatValType::I64 | WatValType::F32 | WatValType::F64 |
+                WatValType::V128 => false,
+                WatValType::Ref(_) => true,
+            }
+        }
+        fn is_nullable(reftype: &WatRefType) -> bool {
+            match *reftype {
+                WatRefType::FuncRef |
+                WatRefType::ExternRef |
+                WatRefType::AnyRef |
+                WatRefType::EqRef |
+                WatRefType::I31Ref |
+                WatRefType::StructRef |
+                WatRefType::ArrayRef |
+                WatRefType::NullRef => true,
+                WatRefType::Ref { nullable, .. } => nullable,
+            }
+        }
+        fn is_abstract(heaptype: &WatHeapType) -> bool {
+            match *heaptype {
+                WatHeapType::Func |
+                WatHeapType::Extern |
+                WatHeapType::Any |
+                WatHeapType::Eq |
+                WatHeapType::I31 |
+                WatHeapType::Struct |
+                WatHeapType::Array |
+                WatHeapType::None => true,
+                WatHeapType::Index(_) => false,
+            }
+        }
+        fn is_number(arg: &WatInstructionArg) -> bool {
+            match *arg {
+                WatInstructionArg::Unsigned { .. } |
+                WatInstructionArg::Signed { .. } |
+                WatInstructionArg::Float(_) => true,
+                WatInstructionArg::ID(_) |
+                WatInstructionArg::Symbol(_) |
+                WatInstructionArg::Flags(..) |
+                WatInstructionArg::Shape(_) |
+                WatInstructionArg::Memory(_) |
+                WatInstructionArg::Table(_) |
+                WatInstructionArg::Function(_) |
+                WatInstructionArg::Data(_) |
+                WatInstructionArg::Elem(_) |
+                WatInstructionArg::HeapType(_) |
+                WatInstructionArg::Result(_) |
+                WatInstructionArg::Type(_) => false,
+            }
+        }
+        fn is_numeric(index: &WatIndex) -> bool {
+            match *index {
+                WatIndex::Num(_) => true,
+                WatIndex::Id(_) | WatIndex::Symbol(_) => false,
+            }
+        }
+        fn is_negative(sign: WatSign) -> bool {
+            match sign {
+                WatSign::Positive => false,
+                WatSign::Negative => true,
+            }
+        }
+        fn is_finite(float: &WatFloat) -> bool {
+            match *float {
+                WatFloat::Number(..) => true,
+                WatFloat::NaN(..) | WatFloat::Inf(_) => false,
+            }
+        }
+        fn is_active(elem: &WatElemKind, data: &WatDataKind) -> (bool, bool) {
+            let elem = match *elem {
+                WatElemKind::Active { .. } => true,
+                WatElemKind::Passive | WatElemKind::Declarative => false,
+            };
+            let data = match *data {
+                WatDataKind::Active { .. } => true,
+                WatDataKind::Passive => false,
+            };
+            (elem, data)
+        }
+        fn has_expressions(elems: &WatElemList) -> bool {
+            match *elems {
+                WatElemList::Functions(_) => false,
+                WatElemList::Expressions(..) => true,
+            }
+        }
+        fn import_kind(import: &WatImport) -> WatExportKind {
+            match *import {
+                WatImport::Func { .. } => WatExportKind::Func,
+                WatImport::Table { .. } => WatExportKind::Table,
+                WatImport::Memory { .. } => WatExportKind::Memory,
+                WatImport::Global { .. } => WatExportKind::Global,
+            }
+        }
+        fn space(kind: WatExportKind) -> IndexSpace {
+            match kind {
+                WatExportKind::Func => IndexSpace::Func,
+                WatExportKind::Table => IndexSpace::Table,
+                WatExportKind::Memory => IndexSpace::Memory,
+                WatExportKind::Global => IndexSpace::Global,
+            }
+        }
+        fn is_item(space: IndexSpace) -> bool {
+            match space {
+                IndexSpace::Func | IndexSpace::Table | IndexSpace::Memory | IndexSpace::Global => {
+                    true
+                }
+                IndexSpace::Type | IndexSpace::Elem | IndexSpace::Data => false,
+            }
+        }
+        fn is_syntax(kind: ErrorKind) -> bool {
+            match kind {
+                ErrorKind::Lexical |
+                ErrorKind::UnexpectedToken |
+                ErrorKind::InvalidLiteral |
+                ErrorKind::OutOfRange => true,
+                ErrorKind::Unsupported | ErrorKind::Validation => false,
+            }
+        }
+        fn is_atom(token: WatTokenType) -> bool {
+            match token {
+                WatTokenType::Keyword |
+                WatTokenType::Unsigned |
+                WatTokenType::Signed |
+                WatTokenType::Float |
+                WatTokenType::String |
+                WatTokenType::ID |
+                WatTokenType::Reserved => true,
+                WatTokenType::End | WatTokenType::OpenParen | WatTokenType::CloseParen => false,
+            }
+        }
+        fn is_nan(value: &WastValue) -> bool {
+            match *value {
+                WastValue::Const(_) | WastValue::RefExtern(_) | WastValue::RefFunc => false,
+                WastValue::CanonicalNan(_) | WastValue::ArithmeticNan(_) => true,
+            }
+        }
+        fn is_invoke(action: &WastAction) -> bool {
+            match *action {
+                WastAction::Invoke { .. } => true,
+                WastAction::Get { .. } => false,
+            }
+        }
+        fn is_text(module: &WastModule) -> bool {
+            match *module {
+                WastModule::Text { .. } => true,
+                WastModule::Quote(_) | WastModule::Binary(_) => false,
+            }
+        }
+
+        assert!(is_done(&WatParserState::End));
+        assert!(!is_module(&WastParserState::End));
+        assert!(!is_reference(&WatValType::I32));
+        assert!(is_nullable(&WatRefType::FuncRef));
+        assert!(is_abstract(&WatHeapType::Func));
+        assert!(!is_number(&WatInstructionArg::ID(b"$x".to_vec())));
+        assert!(is_numeric(&WatIndex::Num(0)));
+        assert!(is_negative(WatSign::Negative));
+        assert!(!is_finite(&WatFloat::Inf(WatSign::Positive)));
+        assert_eq!(is_active(&WatElemKind::Passive, &WatDataKind::Passive), (false, false));
+        assert!(!has_expressions(&WatElemList::Functions(vec![])));
+        assert_eq!(space(WatExportKind::Memory), IndexSpace::Memory);
+        assert!(is_item(IndexSpace::Func));
+        assert!(is_syntax(ErrorKind::Lexical));
+        assert!(!is_atom(WatTokenType::End));
+        assert!(!is_nan(&WastValue::RefFunc));
+        assert!(!is_invoke(&WastAction::Get { module: None, name: "g".into() }));
+        assert!(!is_text(&WastModule::Quote(vec![])));
+        assert_eq!(import_kind(&import_of(b"(module (import \"a\" \"b\" (global i32)))")),
+                   WatExportKind::Global);
+    }
+
+    // The message of the first error `parser` returns, if any.
+    fn first_error(mut parser: WatParser) -> Option<String> {
+        parser.events().find_map(|event| event.err()).map(|err| err.message.into())
+    }
+
+    #[test]
+    fn default_options_match_new() {
+        let source = b"(module (@custom \"c\" \"\") (memory 1) (import \"m\" \"f\" (func $f)) \
+                       (func $f (call $f)))";
+        let mut built = WatParserOptions::new().build(source);
+        let mut new = WatParser::new(source);
+        assert!(built.events().eq(new.events()));
+    }
+
+    #[test]
+    fn enable_threads_option() {
+        let source = b"(module (memory (shared 1 1)))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().enable_threads(false).build(source)).unwrap(),
+                   "shared memories require the threads feature");
+    }
+
+    #[test]
+    fn features_option() {
+        let source = b"(module (func i32.const 0 i32x4.splat drop))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().features(WatFeatures::mvp()).build(source))
+                       .unwrap(),
+                   "instruction 'i32x4.splat' requires the simd feature");
+    }
+
+    #[test]
+    fn max_nesting_option() {
+        let source = b"(module (func (block (nop))))";
+        assert_eq!(first_error(WatParser::builder().max_nesting(4).build(source)), None);
+        assert_eq!(first_error(WatParser::builder().max_nesting(3).build(source)).unwrap(),
+                   "maximum nesting depth of 3 exceeded");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        // Far past the default limit: fails at the 1000th '(' instead of
+        // overflowing the stack.
+        let depth = 100_000;
+        let mut wat = Vec::new();
+        wat.extend_from_slice(b"(module (func ");
+        for _ in 0..depth {
+            wat.extend_from_slice(b"(nop ");
+        }
+        wat.resize(wat.len() + depth + 2, b')');
+        let err = WatParser::new(&wat).events().find_map(|event| event.err()).unwrap();
+        assert_eq!((err.kind, &*err.message),
+                   (ErrorKind::OutOfRange, "maximum nesting depth of 1000 exceeded"));
+        assert_eq!((err.start.line, err.start.column), (1, 14 + 5 * 998));
+    }
+
+    #[test]
+    fn intern_ids_option() {
+        let source = b"(module (func $f (call $f)))";
+        let call = |mut parser: WatParser| {
+            let events: Vec<_> = parser.events().map(|event| event.unwrap()).collect();
+            args_of(&events, b"call").remove(0).remove(0)
+        };
+        assert_eq!(call(WatParser::builder().build(source)),
+                   WatInstructionArg::ID(b"$f".to_vec()));
+        assert!(matches!(call(WatParser::builder().intern_ids(true).build(source)),
+                         WatInstructionArg::Symbol(_)));
+    }
+
+    #[test]
+    fn strict_const_exprs_option() {
+        let source = b"(module (global i32 (i32.add (i32.const 1) (i32.const 2))))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().strict_const_exprs(true).build(source))
+                       .unwrap(),
+                   "non-constant instruction 'i32.add' in initializer");
+
+        // Constants and global.get stay valid initializers.
+        let source = b"(module (import \"m\" \"g\" (global $g i32)) (memory 1) \
+                       (global i32 (global.get $g)) (global i64 (i64.const -1)) \
+                       (data (global.get $g) \"\"))";
+        assert_eq!(first_error(WatParser::builder().strict_const_exprs(true).build(source)), None);
+    }
+
+    #[test]
+    fn strict_typeuses_option() {
+        let source = b"(module (type $t (func (param i32))) (func (type $t) (param f32)))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().strict_typeuses(true).build(source)).unwrap(),
+                   "(type $t) is (func (param i32)), declared at line 1, but the inline \
+                    signature is (func (param f32))");
+    }
+
+    #[test]
+    fn strict_limits_option() {
+        let source = b"(module (memory 5 2))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().strict_limits(true).build(source)).unwrap(),
+                   "maximum 2 is below the minimum 5");
+
+        // Each rule, just inside and just outside it. Without the option
+        // all of these parse.
+        let valid: &[&[u8]] = &[b"(module (memory 5 5))",
+                                b"(module (table 5 5 funcref))",
+                                b"(module (memory 65536))",
+                                b"(module (memory 0 65536))",
+                                b"(module (table 100000 4294967295 funcref))",
+                                b"(module (import \"m\" \"m\" (memory 1 2)))"];
+        for &source in valid {
+            assert_eq!(first_error(WatParser::builder().strict_limits(true).build(source)), None);
+        }
+        let invalid: &[(&[u8], &str)] =
+            &[(b"(module (memory 5 4))", "maximum 4 is below the minimum 5"),
+              (b"(module (table 5 4 funcref))", "maximum 4 is below the minimum 5"),
+              (b"(module (memory 65537))", "memory of 65537 pages is above the limit of 65536"),
+              (b"(module (memory 0 65537))", "memory of 65537 pages is above the limit of 65536"),
+              (b"(module (import \"m\" \"m\" (memory 2 1)))", "maximum 1 is below the minimum 2")];
+        for &(source, message) in invalid {
+            assert_eq!(first_error(WatParser::builder().build(source)), None);
+            assert_eq!(first_error(WatParser::builder().strict_limits(true).build(source)).unwrap(),
+                       message);
+        }
+    }
+
+    #[test]
+    fn unique_ids_option() {
+        let source = b"(module (func $f) (func $f))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().unique_ids(true).build(source)).unwrap(),
+                   "duplicate identifier $f, previously defined at line 1");
+
+        // Params and locals share one space per function, apart from the
+        // module's ids and from other functions.
+        let unique = |source: &[u8]| {
+            let mut parser = WatParser::builder().unique_ids(true).build(source);
+            parser.events().find_map(|event| event.err()).map(|err| {
+                (String::from(err.message), err.start.line, err.start.column)
+            })
+        };
+        assert_eq!(unique(b"(module (func $x (param $x i32) (local $y i32)) \
+                           (func (param $y i32) (local $x i32)))"),
+                   None);
+        let clash = |column| {
+            Some(("duplicate identifier $x, previously defined at line 1".into(), 1, column))
+        };
+        assert_eq!(unique(b"(module (func (param $x i32) (local $x i32)))"), clash(36));
+        assert_eq!(unique(b"(module (func (local $x i32)\n(local $x i64)))"),
+                   Some(("duplicate identifier $x, previously defined at line 1".into(), 2, 7)));
+        assert_eq!(unique(b"(module (func (param $x i32) (result i32) (local $x i32) \
+                           local.get $x))"),
+                   clash(49));
+    }
+
+    #[test]
+    fn imports_first_option() {
+        let source = b"(module (memory 1) (import \"m\" \"f\" (func)))";
+        assert_eq!(first_error(WatParser::builder().build(source)), None);
+        assert_eq!(first_error(WatParser::builder().imports_first(true).build(source)).unwrap(),
+                   "import follows definition at line 1");
+    }
+
+    #[test]
+    fn func_body_option() {
+        let source = b"i32.const 1 drop";
+        assert!(first_error(WatParser::builder().build(source)).is_some());
+        assert_eq!(first_error(WatParser::builder().func_body(true).build(source)), None);
+    }
+
+    #[test]
+    fn emit_annotations_option() {
+        let source = b"(module (@custom \"name\" \"data\") (func))";
+        let custom_sections = |mut parser: WatParser| {
+            parser.events()
+                .filter(|event| matches!(*event, Ok(WatParserState::CustomSection { .. })))
+                .count()
+        };
+        assert_eq!(custom_sections(WatParser::builder().build(source)), 1);
+        assert_eq!(custom_sections(WatParser::builder().emit_annotations(true).build(source)), 1);
+        assert_eq!(custom_sections(WatParser::builder().emit_annotations(false).build(source)), 0);
+        // Skipped, a malformed `@custom` is not checked.
+        let source = b"(module (@custom 1) (func))";
+        assert!(first_error(WatParser::builder().build(source)).is_some());
+        assert_eq!(first_error(WatParser::builder().emit_annotations(false).build(source)), None);
+    }
+
+    #[test]
+    fn sign_without_a_number() {
+        for &(source, message) in
+            &[("(module (func i32.const +))",
+               "unexpected '+' (a sign without a number) while parsing instruction"),
+              ("(module (func nop - drop))",
+               "unexpected '-' (a sign without a number) while parsing instruction"),
+              ("(module (memory +))",
+               "expected an unsigned integer while parsing memory limits, found '+' (a sign \
+                without a number)")] {
+            match WatParser::new(source.as_bytes()).events().find_map(|event| event.err()) {
+                Some(err) => assert_eq!(&*err.message, message, "{}", source),
+                None => panic!("{} parses", source),
+            }
+        }
+        match WatParser::func_body(b"i32.const +5").parse() {
+            WatParserState::CodeOperator { ref args, .. } => {
+                assert_eq!(*args,
+                           [WatInstructionArg::Signed {
+                                sign: WatSign::Positive,
+                                value: vec![5],
+                                raw: b"+5".to_vec(),
+                            }]);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn signed_literal_needs_a_sign() {
+        // Only `Signed` tokens reach read_arg_signed; any other is an
+        // invalid literal rather than a panic.
+        let mut parser = WatParser::func_body(b"5");
+        parser.advance().unwrap();
+        let err = parser.read_arg_signed(Some(32)).unwrap_err();
+        assert_eq!((err.kind, &*err.message),
+                   (ErrorKind::InvalidLiteral, "invalid signed integer literal '5'"));
+        let mut parser = WatParser::func_body(b"-5");
+        parser.advance().unwrap();
+        assert!(matches!(parser.read_arg_signed(Some(32)).unwrap(),
+                         WatInstructionArg::Signed { sign: WatSign::Negative, .. }));
+    }
+
+    #[test]
+    fn parse_into_batches() {
+        let source = b"(module (memory 1) (func))";
+        let mut parser = WatParser::new(source);
+        let mut batched = Vec::new();
+        assert_eq!(parser.parse_into(&mut batched, 3).unwrap(), (3, false));
+        assert_eq!(batched.len(), 3);
+        assert_eq!(parser.parse_into(&mut batched, 3).unwrap(), (2, true));
+        // End is not pushed, and the events are the same as one at a time.
+        let mut expected = events(source);
+        assert!(matches!(expected.pop(), Some(WatParserState::End)));
+        assert_eq!(format!("{:?}", batched), format!("{:?}", expected));
+
+        // An error is returned in place of the count; the events before it stay.
+        let mut parser = WatParser::new(b"(module (memory 1) bogus)");
+        let mut batched = Vec::new();
+        assert!(parser.parse_into(&mut batched, 3).is_err());
+        assert_eq!(batched.len(), 2);
+    }
+
+    #[test]
+    fn code_operator_depths() {
+        let source = b"(module (func (result i32)
+                         (if (result i32) (i32.const 1)
+                           (then (block (result i32) i32.const 2))
+                           (else block (result i32) (i32.const 3) end))))";
+        let mut depths = Vec::new();
+        for event in events(source) {
+            match event {
+                WatParserState::CodeOperator { instruction, group, depth, .. } => {
+                    depths.push((String::from_utf8(instruction.to_vec()).unwrap(), group, depth))
+                }
+                WatParserState::CodeOperatorEnd { depth } => {
+                    depths.push((")".into(), false, depth))
+                }
+                _ => {}
+            }
+        }
+        // Each ')' and `end` is at the depth of what it closes.
+        let expected = [("if", true, 0),
+                        ("i32.const", true, 1),
+                        (")", false, 1),
+                        ("then", true, 1),
+                        ("block", true, 2),
+                        ("i32.const", false, 3),
+                        (")", false, 2),
+                        (")", false, 1),
+                        ("else", true, 1),
+                        ("block", false, 2),
+                        ("i32.const", true, 3),
+                        (")", false, 3),
+                        ("end", false, 2),
+                        (")", false, 1),
+                        (")", false, 0)];
+        assert_eq!(depths.len(), expected.len());
+        for (actual, &(name, group, depth)) in depths.iter().zip(expected.iter()) {
+            assert_eq!((&*actual.0, actual.1, actual.2), (name, group, depth));
+        }
+    }
+
+    #[test]
+    fn read_table_forms() {
+        let fields = events(b"(module (table 1 10 funcref) (table $t 1 funcref) (func $f) \
+                               (table funcref (elem $f 0)))");
+        let tabletypes: Vec<_> = fields.iter()
+            .filter_map(|event| match *event {
+                WatParserState::Table { ref tabletype, .. } => Some(tabletype.clone()),
+                _ => None,
+            })
+            .collect();
+        let funcref = |min, max| WatTableType {
+            limits: WatLimits { min, max },
+            reftype: WatRefType::FuncRef,
+        };
+        // The inline form is as large as its list, and not growable.
+        assert_eq!(tabletypes, [funcref(1, Some(10)), funcref(1, None), funcref(2, Some(2))]);
+
+        // Its elements follow, as an active segment at offset 0 of table 2.
+        match fields[fields.len() - 3] {
+            WatParserState::Elem { id: None, ref kind, ref elems, .. } => {
+                assert_eq!(*kind,
+                           WatElemKind::Active {
+                               table: Some(WatIndex::Num(2)),
+                               offset: zero_offset(),
+                           });
+                assert_eq!(*elems,
+                           WatElemList::Functions(vec![WatIndex::Id(b"$f".to_vec()),
+                                                       WatIndex::Num(0)]));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        assert!(matches!(fields[fields.len() - 4], WatParserState::Table { .. }));
+
+        // The inline form needs its '(elem'.
+        match *events(b"(module (table funcref))").last().unwrap() {
+            WatParserState::Error(ref err) => {
+                assert_eq!(&*err.message, "expected '(elem' while parsing table, found ')'");
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn block_structure_errors() {
+        fn error(source: &[u8]) -> Option<(String, usize, usize)> {
+            WatParser::new(source).events().find_map(|event| event.err()).map(|err| {
+                (err.message.into(), err.start.column, err.end.column)
+            })
+        }
+        // An orphan `else`, at top level and after an `else`.
+        assert_eq!(error(b"(module (func nop else))"),
+                   Some(("else without if".into(), 18, 22)));
+        assert_eq!(error(b"(module (func i32.const 0 if else else end))"),
+                   Some(("else without if".into(), 34, 38)));
+        assert_eq!(error(b"(module (func block else end))"),
+                   Some(("else without if".into(), 20, 24)));
+        assert_eq!(error(b"(module (func nop end))"),
+                   Some(("end without block".into(), 18, 21)));
+        // An `if` left open is reported at the `if`, when its body or group ends.
+        assert_eq!(error(b"(module (func i32.const 0 if nop))"),
+                   Some(("unclosed block at end of function".into(), 26, 28)));
+        assert_eq!(error(b"(module (func (block i32.const 0 if nop)))"),
+                   Some(("unclosed block at end of folded instruction".into(), 33, 35)));
+
+        // An end label may be absent, or match the block's.
+        assert_eq!(error(b"(module (func block $b end loop $l end $l \
+                           i32.const 0 if $i else $i end $i))"),
+                   None);
+        assert_eq!(error(b"(module (func block $b end $c))"),
+                   Some(("mismatched block label '$c'".into(), 27, 29)));
+        assert_eq!(error(b"(module (func i32.const 0 if $i else $e end))"),
+                   Some(("mismatched block label '$e'".into(), 37, 39)));
+        // A block without a label can't be closed with one.
+        assert_eq!(error(b"(module (func block end $b))"),
+                   Some(("mismatched block label '$b'".into(), 24, 26)));
+    }
+
+    #[test]
+    fn func_body_fragment() {
+        let events: Vec<_> = WatParser::func_body(b"i32.const 1 i32.const 2 i32.add")
+            .events()
+            .collect::<Result<_>>()
+            .unwrap();
+        let opcodes: Vec<_> = events.iter()
+            .map(|event| match *event {
+                WatParserState::CodeOperator { ref opcode, group: false, depth: 0, .. } => {
+                    opcode.clone()
+                }
+                ref event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        assert_eq!(opcodes, [WatOpcode::I32Const, WatOpcode::I32Const, WatOpcode::I32Add]);
+
+        // A folded group closes with its CodeOperatorEnd, and nothing else follows.
+        let mut parser = WatParser::func_body(b"(drop (i32.const 1))");
+        assert_eq!(parser.events().count(), 4);
+        assert_eq!(parser.parse(), WatParserState::End);
+
+        assert_eq!(first_error(WatParser::func_body(b"block")).unwrap(),
+                   "unclosed block at end of input");
+        assert_eq!(first_error(WatParser::func_body(b"nop)")).unwrap(),
+                   "unexpected ')' while parsing instruction");
+    }
+
+    #[test]
+    fn reference_type_locals() {
+        let fields = events(b"(module (type $t (func)) \
+                               (func (local $a anyref) (local (ref null $t) (ref $t)) \
+                                     (local (ref null func) (ref null any) (ref extern))))");
+        let locals = match fields[2] {
+            WatParserState::StartFunc { ref locals, .. } => locals.clone(),
+            ref event => panic!("unexpected {:?}", event),
+        };
+        let to_t = |nullable| {
+            WatValType::Ref(WatRefType::Ref {
+                nullable,
+                heaptype: WatHeapType::Index(WatIndex::Id(b"$t".to_vec())),
+            })
+        };
+        let valtypes: Vec<_> = locals.iter().map(|local| local.valtype.clone()).collect();
+        // Nullable abstract heap types come back as their shorthand.
+        assert_eq!(valtypes,
+                   [WatValType::Ref(WatRefType::AnyRef),
+                    to_t(true),
+                    to_t(false),
+                    WatValType::Ref(WatRefType::FuncRef),
+                    WatValType::Ref(WatRefType::AnyRef),
+                    WatValType::Ref(WatRefType::Ref {
+                        nullable: false,
+                        heaptype: WatHeapType::Extern,
+                    })]);
+        assert_eq!(locals[0].id.as_ref().map(|id| &id[..]), Some(&b"$a"[..]));
+        let text: Vec<_> = valtypes.iter().map(|valtype| valtype.to_string()).collect();
+        assert_eq!(text,
+                   ["anyref", "(ref null $t)", "(ref $t)", "funcref", "anyref", "(ref extern)"]);
+
+        let mvp = |source: &[u8]| {
+            first_error(WatParser::builder().features(WatFeatures::mvp()).build(source))
+        };
+        assert_eq!(mvp(b"(module (func (local anyref)))").unwrap(),
+                   "'anyref' requires the gc feature");
+        assert_eq!(mvp(b"(module (type $t (func)) (func (local (ref null $t))))").unwrap(),
+                   "(ref ...) types require the gc feature");
+    }
+
+    #[test]
+    fn control_characters_in_strings() {
+        // The decoder takes the same strings as the lexer: each control
+        // character and DEL escaped, none written as itself.
+        for ch in (0..0x20).chain(Some(0x7F)) {
+            let escaped = format!("\"\\{:02x}\"", ch);
+            assert_eq!(parse_data_string(escaped.as_bytes()), Some(vec![ch]), "{:#x}", ch);
+            assert_eq!(parse_data_string(&[b'"', ch, b'"']), None, "{:#x}", ch);
+        }
+        assert_eq!(parse_data_string(b"\"\\t\\n\\r\\u{7f}\""), Some(vec![9, 10, 13, 0x7F]));
+
+        // A name can hold a newline only as an escape.
+        match events(b"(module (import \"a\\nb\" \"f\" (func)))")[1] {
+            WatParserState::Import { ref modname, .. } => assert_eq!(*modname, "a\nb"),
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match *events(b"(module (import \"a\nb\" \"f\" (func)))").last().unwrap() {
+            WatParserState::Error(ref err) => {
+                assert_eq!(&*err.message, "Unexpected character");
+                assert_eq!((err.start.line, err.start.column), (1, 18));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn custom_section_annotations() {
+        let fields = events(b"(module (@custom \"producers\" \"\\00\") (@other (x \"y\")) \
+                               (@custom \"c\" \"a\\ff\" \"\" \"\\u{e9}\") (func))");
+        let sections: Vec<_> = fields.iter()
+            .filter_map(|event| match *event {
+                WatParserState::CustomSection { ref name, ref data, .. } => {
+                    Some((name.clone(), data.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        // The data strings are decoded and joined; other annotations are skipped.
+        assert_eq!(sections,
+                   [("producers".into(), vec![0]), ("c".into(), vec![b'a', 0xFF, 0xC3, 0xA9])]);
+        assert!(matches!(fields[fields.len() - 4], WatParserState::StartFunc { .. }));
+
+        match *events(b"(module (@custom \"c\" 1))").last().unwrap() {
+            WatParserState::Error(ref err) => {
+                assert_eq!(&*err.message, "expected ')' while parsing custom section, found '1'");
+                assert_eq!((err.start.column, err.end.column), (8, 22));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn names_must_be_utf8() {
+        // A multi-byte name, written as itself and as escapes.
+        let fields = events("(module (import \"caf\u{e9}\" \"\\e2\\82\\ac\\u{1f600}\" (func)) \
+                              (func (export \"\u{4e2d}\")))"
+            .as_bytes());
+        match fields[1] {
+            WatParserState::Import { ref modname, ref fieldname, .. } => {
+                assert_eq!((&modname[..], &fieldname[..]), ("caf\u{e9}", "\u{20ac}\u{1f600}"));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[2] {
+            WatParserState::StartFunc { ref export_names, .. } => {
+                assert_eq!(*export_names, ["\u{4e2d}"]);
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+
+        fn error(source: &[u8]) -> (ErrorKind, String, usize, usize) {
+            let err = WatParser::new(source).events().find_map(|event| event.err()).unwrap();
+            (err.kind, err.message.into(), err.start.column, err.end.column)
+        }
+        // An overlong '/', and a surrogate encoded as bytes, span the string.
+        assert_eq!(error(br#"(module (export "\c0\af" (func 0)))"#),
+                   (ErrorKind::InvalidLiteral,
+                    r#"name '"\c0\af"' is not valid UTF-8 while parsing export"#.into(),
+                    16,
+                    24));
+        assert_eq!(error(br#"(module (func (export "\ed\b0\80")))"#),
+                   (ErrorKind::InvalidLiteral,
+                    r#"name '"\ed\b0\80"' is not valid UTF-8 while parsing function"#.into(),
+                    22,
+                    33));
+        // An escaped surrogate is not a scalar value, which the lexer rejects.
+        assert_eq!(error(br#"(module (import "\u{dc00}" "f" (func)))"#),
+                   (ErrorKind::Lexical, "Invalid unicode escape".into(), 20, 24));
+        // Data strings take any bytes.
+        match events(br#"(module (memory 1) (data (i32.const 0) "\c0\af\ed\b0\80"))"#)[2] {
+            WatParserState::Data { ref data, .. } => {
+                assert_eq!(*data, [0xC0, 0xAF, 0xED, 0xB0, 0x80])
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn simd_load_splat_add_store() {
+        let fields = events(b"(module (memory 1) (func (param i32) (local v128) \
+                               local.get 0 local.get 0 v128.load offset=16 \
+                               i32.const 7 i32x4.splat i32x4.add v128.store))");
+        match fields[2] {
+            WatParserState::StartFunc { ref locals, .. } => {
+                assert_eq!(locals[0].valtype, WatValType::V128)
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        let operators: Vec<_> = fields[3..fields.len() - 3]
+            .iter()
+            .map(|event| match *event {
+                WatParserState::CodeOperator { ref opcode, ref args, .. } => {
+                    (opcode.clone(), args.clone())
+                }
+                ref event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        let local = WatInstructionArg::Unsigned {
+            value: vec![0],
+            raw: b"0".to_vec(),
+        };
+        let seven = WatInstructionArg::Unsigned {
+            value: vec![7],
+            raw: b"7".to_vec(),
+        };
+        let offset = WatInstructionArg::Flags(b"offset=16".to_vec(), 16);
+        assert_eq!(operators,
+                   [(WatOpcode::LocalGet, vec![local.clone()]),
+                    (WatOpcode::LocalGet, vec![local]),
+                    (WatOpcode::V128Load, vec![offset]),
+                    (WatOpcode::I32Const, vec![seven]),
+                    (WatOpcode::I32x4Splat, vec![]),
+                    (WatOpcode::I32x4Add, vec![]),
+                    (WatOpcode::V128Store, vec![])]);
+
+        // The body as encoded, after its one v128 local.
+        let wasm = encode_module(fields.into_iter().map(Ok)).unwrap();
+        assert!(wasm.ends_with(&[0x01, 0x01, 0x7B, 0x20, 0x00, 0x20, 0x00, 0xFD, 0x00, 0x04, 0x10,
+                                 0x41, 0x07, 0xFD, 0x11, 0xFD, 0xAE, 0x01, 0xFD, 0x0B, 0x04, 0x00,
+                                 0x0B]),
+                "{:02x?}",
+                wasm);
+    }
+
+    #[test]
+    fn multi_value_results() {
+        let results = |source: &[u8]| {
+            events(source)
+                .into_iter()
+                .filter_map(|event| match event {
+                    WatParserState::Type { functype: typeuse, .. } |
+                    WatParserState::StartFunc { typeuse, .. } => Some(typeuse.results),
+                    WatParserState::Import { import: WatImport::Func { typeuse, .. }, .. } => {
+                        Some(typeuse.results)
+                    }
+                    _ => None,
+                })
+                .map(|results| results.into_iter().map(|result| result.valtype).collect())
+                .collect::<Vec<Vec<_>>>()
+        };
+        let two = vec![WatValType::I32, WatValType::I64];
+        assert_eq!(results(b"(module (func (result i32 i64) unreachable) \
+                                      (func (result i32) (result i64) unreachable) \
+                                      (func (result) (result i32) (result) (result i64) \
+                                        unreachable))"),
+                   [two.clone(), two.clone(), two.clone()]);
+        assert_eq!(results(b"(module (type (func (result i32 i64))) \
+                                      (import \"m\" \"f\" (func (result i32) (result i64))))"),
+                   [two.clone(), two]);
+        // `(result)` alone is no result at all.
+        assert_eq!(results(b"(module (func (result)) (func (param i32) (result) (result)))"),
+                   [vec![], vec![]]);
+
+        // Results have no ids.
+        assert_eq!(first_error(WatParser::new(b"(module (func (result $r i32)))")).unwrap(),
+                   "expected ')' while parsing function, found '$r'");
+    }
+
+    #[test]
+    fn reference_types_acceptance() {
+        let source = b"(module (table $t 2 externref) \
+                               (global $g (mut externref) (ref.null extern)) \
+                               (func $set (param $x externref) (result i32) \
+                                 (table.set $t (i32.const 0) (local.get $x)) \
+                                 (global.set $g (table.get $t (i32.const 0))) \
+                                 (ref.is_null (global.get $g))) \
+                               (elem declare func $set) \
+                               (func (drop (ref.func $set))))";
+        let fields = events(source);
+        assert_eq!(*fields.last().unwrap(), WatParserState::End);
+        let externref = WatValType::Ref(WatRefType::ExternRef);
+        match fields[1] {
+            WatParserState::Table { ref tabletype, .. } => {
+                assert_eq!(tabletype.reftype, WatRefType::ExternRef)
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[2] {
+            WatParserState::StartGlobal { ref globaltype, .. } => {
+                assert_eq!(globaltype.valtype, externref)
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match fields[3] {
+            WatParserState::CodeOperator { ref opcode, ref args, .. } => {
+                assert_eq!(*opcode, WatOpcode::RefNull);
+                assert_eq!(*args, [WatInstructionArg::HeapType(WatRefType::ExternRef)]);
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        let params = fields.iter()
+            .find_map(|event| match *event {
+                WatParserState::StartFunc { ref typeuse, .. } => Some(typeuse.params.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(params[0].valtype, externref);
+
+        let mvp = WatFeatures {
+            reference_types: false,
+            ..WatFeatures::default()
+        };
+        assert_eq!(first_error(WatParser::builder().features(mvp).build(source)).unwrap(),
+                   "externref requires the reference types feature");
     }
 }
@@ -0,0 +1,83 @@
+//! Bulk memory end to end: a module with passive and active data
+//! segments, initialized and dropped from its start function, parsed,
+//! resolved and encoded, then rejected with the feature off.
+
+extern crate wasmtextparser;
+
+use wasmtextparser::wat::{WatDataKind, WatInstructionArg};
+use wasmtextparser::{encode_module, IndexResolver, IndexSpace, WatFeatures, WatOpcode,
+                     WatParserOptions, WatParserState};
+
+const SOURCE: &[u8] = b"(module
+  (memory $m 1)
+  (data $active (i32.const 0) \"a\")
+  (data $d \"passive\")
+  (func $init
+    (memory.init $d (i32.const 16) (i32.const 0) (i32.const 7))
+    (data.drop $d))
+  (start $init))";
+
+#[test]
+fn passive_data_is_initialized_and_dropped() {
+    let events: Vec<WatParserState> = WatParserOptions::new()
+        .build(SOURCE)
+        .events()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut resolver = IndexResolver::new();
+    for event in &events {
+        resolver.add_event(event);
+    }
+    assert_eq!(resolver.count(IndexSpace::Data), 2);
+
+    let kinds: Vec<bool> = events.iter()
+        .filter_map(|event| match *event {
+            WatParserState::Data { ref kind, .. } => Some(*kind == WatDataKind::Passive),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(kinds, [false, true]);
+
+    // Both instructions name $d, the second segment.
+    let segments: Vec<(WatOpcode, u32)> = events.iter()
+        .filter_map(|event| match *event {
+            WatParserState::CodeOperator { ref opcode, ref args, .. } => {
+                let index = args.iter().find_map(|arg| match *arg {
+                    WatInstructionArg::Data(ref index) => resolver.resolve(IndexSpace::Data, index),
+                    _ => None,
+                });
+                index.map(|index| (opcode.clone(), index))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(segments, [(WatOpcode::MemoryInit, 1), (WatOpcode::DataDrop, 1)]);
+
+    // A data count of 2, memory.init 1 on memory 0, data.drop 1, and the
+    // passive segment after the active one.
+    let wasm = encode_module(events.into_iter().map(Ok)).unwrap();
+    let has = |bytes: &[u8]| wasm.windows(bytes.len()).any(|window| window == bytes);
+    assert!(has(b"\x0c\x01\x02"));
+    assert!(has(b"\xfc\x08\x01\x00\xfc\x09\x01\x0b"));
+    assert!(has(b"\x00\x41\x00\x0b\x01a\x01\x07passive"));
+}
+
+#[test]
+fn bulk_memory_off_rejects_passive_data() {
+    let error = |source: &[u8], features: WatFeatures| {
+        let mut parser = WatParserOptions::new().features(features).build(source);
+        parser.events().find_map(|event| event.err()).map(|err| String::from(err.message))
+    };
+    let without_bulk_memory = WatFeatures {
+        bulk_memory: false,
+        ..WatFeatures::default()
+    };
+    for &features in &[WatFeatures::mvp(), without_bulk_memory] {
+        assert_eq!(error(SOURCE, features).unwrap(),
+                   "passive data segments require the bulk memory feature");
+        assert_eq!(error(b"(module (memory 1) (func (data.drop 0)))", features).unwrap(),
+                   "instruction 'data.drop' requires the bulk memory feature");
+        // Active segments are MVP.
+        assert_eq!(error(b"(module (memory 1) (data (i32.const 0) \"a\"))", features), None);
+    }
+}
@@ -0,0 +1,79 @@
+//! Drives the built binary: how it takes its input paths from argv, and
+//! its modes.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// Runs the built binary with `args`, writing `stdin` to its standard input.
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wasmtextparser"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn missing_path_prints_usage() {
+    let output = run(&[], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("usage: "));
+}
+
+#[test]
+fn open_failure_names_the_path() {
+    let output = run(&["corpus/missing.wat"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: corpus/missing.wat: "));
+}
+
+#[test]
+fn dash_reads_stdin() {
+    let output = run(&["-"], b"(module)");
+    assert!(output.status.success());
+    // The events print as a trace, or in their Debug form without the std
+    // feature; both start and end like this.
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("StartModule"));
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("\nEndModule\nEnd\n"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr),
+               "<stdin>: 0 functions, 0 imports, 0 instructions, 8 bytes\n");
+
+    let output = run(&["--check", "-"], b"(module bogus)");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(" --> <stdin>:1:9\n"));
+}
+
+#[test]
+fn tokens_dump() {
+    let output = run(&["--tokens", "tests/cli/tokens.wat"], b"");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout),
+               "OpenParen 1:1-1:2 \"(\"\n\
+                Keyword 1:2-1:8 \"module\"\n\
+                ID 1:9-1:11 \"$m\"\n\
+                OpenParen 2:3-2:4 \"(\"\n\
+                Keyword 2:4-2:8 \"func\"\n\
+                OpenParen 2:9-2:10 \"(\"\n\
+                Keyword 2:10-2:16 \"export\"\n\
+                String 2:17-2:20 \"\\\"f\\\"\"\n\
+                CloseParen 2:20-2:21 \")\"\n\
+                Keyword 2:22-2:31 \"i32.const\"\n\
+                Unsigned 2:32-2:36 \"0x2a\"\n\
+                Keyword 2:37-2:41 \"drop\"\n\
+                CloseParen 2:41-2:42 \")\"\n\
+                CloseParen 2:42-2:43 \")\"\n\
+                End 3:1-3:1 \"\"\n");
+
+    // A lexer error ends the dump, with the parse error status.
+    let output = run(&["--tokens", "-"], b"(module \"unterminated\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout),
+               "OpenParen 1:1-1:2 \"(\"\n\
+                Keyword 1:2-1:8 \"module\"\n\
+                Error 1:22-1:22 \"Unexpected character\"\n");
+}
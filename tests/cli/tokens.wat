@@ -0,0 +1,2 @@
+(module $m
+  (func (export "f") i32.const 0x2a drop)) ;; end
@@ -0,0 +1,74 @@
+//! Replays the inputs in fuzz/corpus/parse, which once made the parser
+//! panic, through the same drivers as the fuzz targets.
+
+extern crate wasmtextparser;
+
+use std::fs;
+use std::path::Path;
+use wasmtextparser::{PushParser, WatParserOptions, WatParserState};
+
+// The events of the whole input, up to End or the error that stops
+// parsing, as in fuzz_targets/push.rs.
+fn parse_whole(data: &[u8], recover: bool) -> Vec<WatParserState> {
+    let mut parser = WatParserOptions::new().error_recovery(recover).build(data);
+    let mut events = Vec::new();
+    let mut last_error = None;
+    loop {
+        match parser.parse() {
+            WatParserState::End => {
+                events.push(WatParserState::End);
+                return events;
+            }
+            WatParserState::Error(err) => {
+                // Errors that recovery cannot get past are sticky.
+                if last_error.as_ref() == Some(&err) {
+                    return events;
+                }
+                events.push(WatParserState::Error(err.clone()));
+                if !recover {
+                    return events;
+                }
+                last_error = Some(err);
+            }
+            event => events.push(event),
+        }
+    }
+}
+
+fn parse_pushed(data: &[u8], recover: bool, size: usize) -> Vec<WatParserState> {
+    let mut events = Vec::new();
+    {
+        let options = WatParserOptions::new().error_recovery(recover);
+        let mut parser = PushParser::with_options(|event| events.push(event), options);
+        if data.chunks(size).all(|chunk| parser.feed(chunk).is_ok()) {
+            let _ = parser.finish();
+        }
+    }
+    events
+}
+
+#[test]
+fn fuzz_regressions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/parse");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let data = fs::read(&path).unwrap();
+        for &recover in &[false, true] {
+            let events = parse_whole(&data, recover);
+            match events.last() {
+                Some(&WatParserState::End) | Some(&WatParserState::Error(_)) => {}
+                event => panic!("{}: ended with {:?}", path.display(), event),
+            }
+            for &size in &[1, 7] {
+                assert_eq!(parse_pushed(&data, recover, size),
+                           events,
+                           "{} in chunks of {}",
+                           path.display(),
+                           size);
+            }
+        }
+        count += 1;
+    }
+    assert!(count >= 8, "only {} regression inputs", count);
+}
@@ -0,0 +1,35 @@
+//! Locks the JSON form of the events, as written by `--json`, against a
+//! snapshot. After an intended format change, regenerate it with
+//! `cargo run --features json -- --json corpus/fields.wat`.
+
+#![cfg(feature = "json")]
+
+extern crate serde_json;
+extern crate wasmtextparser;
+
+use std::fs;
+use std::path::Path;
+use wasmtextparser::{WatParser, WatParserState};
+
+#[test]
+fn fields_snapshot() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let wat = fs::read(root.join("corpus/fields.wat")).unwrap();
+    let expected = fs::read_to_string(root.join("tests/snapshots/fields.jsonl")).unwrap();
+    let mut actual = String::new();
+    let mut parser = WatParser::new(&wat);
+    loop {
+        let event = parser.parse();
+        actual.push_str(&serde_json::to_string(&event).unwrap());
+        actual.push('\n');
+        match event {
+            WatParserState::End => break,
+            WatParserState::Error(err) => panic!("{}", err),
+            _ => {}
+        }
+    }
+    for (number, (actual, expected)) in actual.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(actual, expected, "line {} of the snapshot", number + 1);
+    }
+    assert_eq!(actual.lines().count(), expected.lines().count());
+}
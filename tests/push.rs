@@ -0,0 +1,63 @@
+//! Feeds each corpus/*.wat to a `PushParser` split at every byte offset,
+//! and a byte at a time, and checks that the sink gets the events of a
+//! one-shot parse.
+
+extern crate wasmtextparser;
+
+use std::fs;
+use std::path::Path;
+use wasmtextparser::{PushParser, WatParserOptions, WatParserState};
+
+// The events of `WatParser::events()`, with the `End` it stops at.
+fn one_shot(source: &[u8], recover: bool) -> Vec<WatParserState> {
+    let mut parser = WatParserOptions::new().error_recovery(recover).build(source);
+    let mut events: Vec<WatParserState> = parser.events()
+        .map(|event| event.unwrap_or_else(WatParserState::Error))
+        .collect();
+    if !matches!(events.last(), Some(&WatParserState::Error(_))) {
+        events.push(WatParserState::End);
+    }
+    events
+}
+
+fn pushed(chunks: &[&[u8]], recover: bool) -> Vec<WatParserState> {
+    let mut events = Vec::new();
+    {
+        let options = WatParserOptions::new().error_recovery(recover);
+        let mut parser = PushParser::with_options(|event| events.push(event), options);
+        for chunk in chunks {
+            parser.feed(chunk).unwrap();
+        }
+        parser.finish().unwrap();
+    }
+    events
+}
+
+#[test]
+fn corpus_in_chunks() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "wat") {
+            continue;
+        }
+        let source = fs::read(&path).unwrap();
+        for &recover in &[false, true] {
+            let expected = one_shot(&source, recover);
+            assert_eq!(expected.last(), Some(&WatParserState::End), "{}", path.display());
+            for offset in 0..source.len() + 1 {
+                let (head, tail) = source.split_at(offset);
+                assert_eq!(pushed(&[head, tail], recover),
+                           expected,
+                           "{} split at {}",
+                           path.display(),
+                           offset);
+            }
+            let bytes: Vec<&[u8]> = source.chunks(1).collect();
+            assert_eq!(pushed(&bytes, recover), expected, "{} byte by byte", path.display());
+        }
+        count += 1;
+    }
+    assert!(count >= 8, "only {} corpus files", count);
+}
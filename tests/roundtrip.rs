@@ -0,0 +1,61 @@
+//! Formats each corpus/*.wat and checks that the text parses back to the
+//! same events, as `--roundtrip` does, and that formatting it again gives
+//! the same text.
+
+#![cfg(feature = "std")]
+
+extern crate wasmtextparser;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use wasmtextparser::{format, roundtrip, WatParser, WatParserState};
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wat"))
+        .collect();
+    assert!(paths.len() >= 8, "only {} corpus files", paths.len());
+    paths
+}
+
+#[test]
+fn corpus_roundtrips() {
+    for path in corpus() {
+        let wat = fs::read(&path).unwrap();
+        match roundtrip(&wat) {
+            Ok(None) => {}
+            Ok(Some(mismatch)) => {
+                panic!("{}: {:?} formats as {:?}\n{}",
+                       path.display(),
+                       mismatch.original,
+                       mismatch.formatted,
+                       mismatch.text)
+            }
+            Err(err) => panic!("{}: {}", path.display(), err),
+        }
+    }
+}
+
+#[test]
+fn format_is_idempotent() {
+    for path in corpus() {
+        let wat = fs::read(&path).unwrap();
+        let once = format(WatParser::new(&wat).events())
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        let twice = format(WatParser::new(once.as_bytes()).events())
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assert!(once == twice, "{}: formats as\n{}\nthen as\n{}", path.display(), once, twice);
+    }
+}
+
+#[test]
+fn format_returns_error_events() {
+    let mut events: Vec<_> = WatParser::new(b"(module bogus)").events().collect();
+    let err = events.pop().unwrap().unwrap_err();
+    // An `Error` passed as `Ok` is returned the same way, not panicked on.
+    events.push(Ok(WatParserState::Error(err.clone())));
+    assert_eq!(format(events), Err(err));
+}
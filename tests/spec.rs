@@ -0,0 +1,92 @@
+//! Checks the spec test scripts in tests/spec, and those of an upstream
+//! testsuite checkout if WASM_SPEC_TESTSUITE names its directory, the way
+//! `--check` does: their modules must parse, and the quoted modules of
+//! assert_malformed must not. Scripts for proposals not supported yet are
+//! on the skip list, SPEC_SKIPS. Run with --nocapture for the counts.
+//!
+//! tests/spec holds fac.wast from the testsuite and shorter hand-written
+//! scripts modelled on its comments, int_literals, select, memory, binary,
+//! exports and tag tests, the last of which is skipped. They are not
+//! copies of the upstream files; only a WASM_SPEC_TESTSUITE run checks the
+//! parser against those.
+
+extern crate wasmtextparser;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use wasmtextparser::{WastModule, WastParser, WastParserState, WatParser, WatParserState};
+
+include!("../src/spec_skips.rs");
+
+// The scripts under tests/spec, all of which are expected to pass or be
+// skipped.
+const VENDORED: usize = 8;
+
+fn parses(wat: &[u8]) -> bool {
+    let mut parser = WatParser::new(wat);
+    loop {
+        match parser.parse() {
+            WatParserState::End => return true,
+            WatParserState::Error(_) => return false,
+            _ => {}
+        }
+    }
+}
+
+// The first thing wrong with the script, if anything.
+fn check(wast: &[u8]) -> Option<String> {
+    let mut parser = WastParser::new(wast);
+    loop {
+        match parser.parse() {
+            WastParserState::End => return None,
+            WastParserState::Error(err) => return Some(err.to_string()),
+            WastParserState::QuotedModule { ref source, position, .. } if !parses(source) => {
+                return Some(format!("the quoted module at {} does not parse", position.line));
+            }
+            WastParserState::AssertMalformed { module: WastModule::Quote(ref source),
+                                               position,
+                                               .. } if parses(source) => {
+                return Some(format!("the malformed module at {} parses", position.line));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wast"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn spec_scripts() {
+    let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec")];
+    dirs.extend(env::var_os("WASM_SPEC_TESTSUITE").map(PathBuf::from));
+    let (mut passed, mut skipped) = (0, 0);
+    let mut failures = Vec::new();
+    for dir in &dirs {
+        for path in scripts(dir) {
+            let file_name = path.file_name().unwrap();
+            if SPEC_SKIPS.iter().any(|skip| file_name == skip.0) {
+                skipped += 1;
+                continue;
+            }
+            match check(&fs::read(&path).unwrap()) {
+                None => passed += 1,
+                Some(failure) => failures.push(format!("{}: {}", path.display(), failure)),
+            }
+        }
+    }
+    println!("spec: {} passed, {} failed, {} skipped",
+             passed,
+             failures.len(),
+             skipped);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    assert!(passed + skipped >= VENDORED, "only {} scripts", passed + skipped);
+}
@@ -0,0 +1,20 @@
+(module binary "\00asm" "\01\00\00\00")
+(module binary "\00asm\01\00\00\00")
+(module $M1 binary "\00asm\01\00\00\00")
+(module $M2 binary "\00asm" "\01\00\00\00")
+
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\01") "unexpected end")
+(assert_malformed (module binary "\00as") "unexpected end")
+(assert_malformed (module binary "asm\00") "magic header not detected")
+(assert_malformed (module binary "\00asm\00\00\00\01") "unknown binary version")
+
+;; The type section, written out byte for byte.
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\05\01"                           ;; type section, 1 type
+  "\60\00\01\7f"                        ;; (func (result i32))
+  "\03\02\01\00"                        ;; function section
+  "\0a\06\01"                           ;; code section
+  "\04\00\41\2a\0b"                     ;; (i32.const 42)
+)
@@ -0,0 +1,68 @@
+;; Test comment syntax
+
+;;comment
+
+;;;;;;;;;;;
+
+    ;;comment
+
+( ;;comment
+module;;comment
+);;comment
+
+;;)
+;;;)
+;; ;)
+;; (;
+
+(;;)
+
+(;comment;)
+
+(;;comment;)
+
+(;;;comment;)
+
+(;;;;;;;;;;;;;;)
+
+(;(((((((((( ;)
+
+(;)))))))))));)
+
+(;comment";)
+
+(;comment"";)
+
+(;comment""";)
+
+;; Nested block comments
+
+(;(;;);)
+
+(;(;comment;);)
+
+(;(;comment
+;);)
+
+(;
+  (;
+    (;
+      (;
+      ;)
+    ;)
+  ;)
+;)
+
+(module;;comment
+(;comment;);;comment
+(;(;comment;);)
+)
+
+(assert_malformed
+  (module quote "(func (;comment)")
+  "unclosed comment"
+)
+(assert_malformed
+  (module quote "(func (;(;comment;))")
+  "unclosed comment"
+)
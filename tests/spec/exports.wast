@@ -0,0 +1,45 @@
+;; Export, start and import fields in their top-level and inline forms
+
+(module
+  (type (func (param i32) (result i32)))
+  (import "spectest" "print_i32" (func (type 0)))
+  (func $f (type 0) (local.get 0))
+  (func $init)
+  (export "f" (func $f))
+  (export "g" (func 0))
+  (start $init)
+)
+
+(module
+  (memory (export "m") 1)
+  (table (export "t") 1 funcref)
+  (global (export "g") i32 (i32.const 0))
+  (export "m2" (memory 0))
+  (export "t2" (table 0))
+  (export "g2" (global 0))
+)
+
+(module (memory (import "spectest" "memory") 1 2))
+(module (table (import "spectest" "table") 10 20 funcref))
+(module (global (import "spectest" "global_i32") i32))
+(module (global (export "a") (export "b") (import "spectest" "global_i32") i32))
+(module (func (export "p") (import "spectest" "print")))
+(module (func $f (export "p") (import "spectest" "print_i32") (param i32)))
+(module (start 0) (func))
+
+(assert_malformed
+  (module quote "(export \"f\" (func))")
+  "unexpected token"
+)
+(assert_malformed
+  (module quote "(export \"f\" (local 0))")
+  "unexpected token"
+)
+(assert_malformed
+  (module quote "(start)")
+  "unexpected token"
+)
+(assert_malformed
+  (module quote "(memory (import \"a\" \"b\") (export \"m\") 1)")
+  "unexpected token"
+)
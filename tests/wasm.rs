@@ -0,0 +1,32 @@
+//! Encodes each tests/wasm/*.wat and compares the bytes with the .wasm
+//! next to it. Those were assembled by hand from the binary format spec,
+//! section by section, so they do not come from `encode_module` itself.
+
+extern crate wasmtextparser;
+
+use std::fs;
+use std::path::Path;
+use wasmtextparser::{encode_module, WatParser};
+
+#[test]
+fn wasm_fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/wasm");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "wat") {
+            continue;
+        }
+        let wat = fs::read(&path).unwrap();
+        let expected = fs::read(path.with_extension("wasm")).unwrap();
+        let wasm = encode_module(WatParser::new(&wat).events())
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assert!(wasm == expected,
+                "{}: encoded as {:02x?}\nexpected {:02x?}",
+                path.display(),
+                wasm,
+                expected);
+        count += 1;
+    }
+    assert!(count >= 3, "only {} fixtures", count);
+}
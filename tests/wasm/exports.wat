@@ -0,0 +1,7 @@
+(module
+  (type (func))
+  (memory (export "mem") (import "a" "m") 1)
+  (func $f (type 0))
+  (table (export "t") 1 funcref)
+  (export "f" (func $f))
+  (start $f))
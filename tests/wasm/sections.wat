@@ -0,0 +1,24 @@
+(module
+  (type $log (func (param i32)))
+  (import "env" "log" (func $log (type $log)))
+  (import "env" "mem" (memory 1))
+  (global $count (mut i32) (i32.const 0))
+  (global (export "limit") i32 (i32.const 100))
+  (table 2 funcref)
+  (elem (i32.const 0) $tick $log)
+  (func $tick (local i32 i32 i64)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (call $log (global.get $count)))
+  (func (export "run") (param $n i32)
+    (block $done
+      (loop $again
+        (br_if $done (i32.eqz (local.get $n)))
+        (call $tick)
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $again))))
+  (func (export "init")
+    (memory.init 1 (i32.const 16) (i32.const 0) (i32.const 4))
+    (data.drop 1)
+    (i32.store offset=8 (i32.const 0) (i32.const -1)))
+  (data (i32.const 0) "hi")
+  (data "\de\ad\be\ef"))
@@ -0,0 +1,10 @@
+(module
+  (memory 1)
+  (func $bump (param $p i32)
+    local.get $p
+    local.get $p
+    v128.load
+    i32.const 7
+    i32x4.splat
+    i32x4.add
+    v128.store))